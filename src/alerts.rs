@@ -0,0 +1,146 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use rodio::{
+    source::{SineWave, Source},
+    OutputStream, OutputStreamHandle,
+};
+use stereokit::{ButtonState, Handed, SkDraw, StereoKitMultiThread};
+
+use crate::{
+    audio,
+    config::GeneralConfig,
+    monado::Monado,
+    toast::{self, NotifyLevel},
+    AppState,
+};
+
+const BATTERY_INTERVAL: Duration = Duration::from_secs(30);
+
+// Short dropouts are normal, e.g. when a hand passes behind the other
+const TRACKING_GRACE: Duration = Duration::from_secs(2);
+
+// A warned battery must recover by this much before it's warned about again
+const BATTERY_HYSTERESIS: f32 = 0.05;
+
+const HANDS: [(Handed, &str); 2] = [(Handed::Left, "Left"), (Handed::Right, "Right")];
+
+// Watches device batteries and controller tracking, warning next to the watch
+pub struct Alerts {
+    next_battery_check: Instant,
+    low_batteries: HashSet<String>,
+    lost_since: [Option<Instant>; 2],
+    tracking_warned: [bool; 2],
+    // a controller that was never turned on isn't lost
+    ever_tracked: [bool; 2],
+    // device the stream was opened on, retried when that changes
+    audio: Option<(OutputStream, OutputStreamHandle)>,
+    audio_device: Option<String>,
+}
+
+impl Alerts {
+    pub fn new() -> Self {
+        Self {
+            next_battery_check: Instant::now() + BATTERY_INTERVAL,
+            low_batteries: HashSet::new(),
+            lost_since: [None; 2],
+            tracking_warned: [false; 2],
+            ever_tracked: [false; 2],
+            audio: None,
+            audio_device: None,
+        }
+    }
+}
+
+pub fn update(sk: &SkDraw, app: &mut AppState) {
+    let mut messages = Vec::new();
+    let config = &app.session.config;
+    let alerts = &mut app.alerts;
+
+    if config.tracking_alerts {
+        alerts.check_tracking(sk, &mut messages);
+    }
+    // 0.0 turns battery warnings off
+    if config.low_battery_threshold > 0. && alerts.next_battery_check < Instant::now() {
+        alerts.next_battery_check = Instant::now() + BATTERY_INTERVAL;
+        if let Some(monado) = app.monado.as_ref() {
+            alerts.check_batteries(monado, config, &mut messages);
+        }
+    }
+
+    if messages.is_empty() {
+        return;
+    }
+    if config.alert_sound {
+        alerts.beep(&config.audio_device);
+    }
+    for msg in messages {
+        toast::notify(NotifyLevel::Warn, &msg);
+    }
+}
+
+impl Alerts {
+    fn check_tracking(&mut self, sk: &SkDraw, messages: &mut Vec<String>) {
+        for (i, (hand, name)) in HANDS.iter().enumerate() {
+            let tracked = sk
+                .input_controller(*hand)
+                .tracked
+                .contains(ButtonState::ACTIVE);
+            if tracked {
+                if self.tracking_warned[i] {
+                    messages.push(format!("{} controller is tracked again", name));
+                }
+                self.lost_since[i] = None;
+                self.tracking_warned[i] = false;
+                self.ever_tracked[i] = true;
+                continue;
+            }
+            if !self.ever_tracked[i] {
+                continue;
+            }
+
+            let lost_since = *self.lost_since[i].get_or_insert_with(Instant::now);
+            if !self.tracking_warned[i] && lost_since.elapsed() > TRACKING_GRACE {
+                self.tracking_warned[i] = true;
+                messages.push(format!("{} controller lost tracking", name));
+            }
+        }
+    }
+
+    fn check_batteries(
+        &mut self,
+        monado: &Monado,
+        config: &GeneralConfig,
+        messages: &mut Vec<String>,
+    ) {
+        let threshold = config.low_battery_threshold;
+        for battery in monado.batteries() {
+            if battery.charging || battery.charge > threshold + BATTERY_HYSTERESIS {
+                self.low_batteries.remove(&battery.device);
+                continue;
+            }
+            if battery.charge <= threshold && self.low_batteries.insert(battery.device.clone()) {
+                messages.push(format!(
+                    "{} battery low: {:.0}%",
+                    battery.device,
+                    battery.charge * 100.
+                ));
+            }
+        }
+    }
+
+    fn beep(&mut self, device: &str) {
+        if self.audio_device.as_deref() != Some(device) {
+            self.audio_device = Some(device.to_string());
+            self.audio = audio::open_output(device);
+        }
+        if let Some((_, handle)) = self.audio.as_ref() {
+            let tone = SineWave::new(880.)
+                .take_duration(Duration::from_millis(150))
+                .amplify(0.2);
+            let _ = handle.play_raw(tone);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+
+use glam::Vec3;
+use log::{error, info};
+use rodio::{cpal::traits::HostTrait, DeviceTrait, OutputStream, OutputStreamHandle};
+
+use crate::{
+    config::report_error,
+    config_io,
+    gui::{color_parse, Canvas},
+    overlay::{OverlayData, RelativeTo},
+    AppSession,
+};
+
+const WIDTH: f32 = 400.;
+const HEIGHT: f32 = 110.;
+
+// Names of the devices UI sounds can be played on
+pub fn output_devices() -> Vec<String> {
+    let host = rodio::cpal::default_host();
+    match host.output_devices() {
+        Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+        Err(e) => {
+            error!("Failed to list audio devices: {}", e);
+            vec![]
+        }
+    }
+}
+
+// Opens the named device, falling back to the default one if it's empty or gone
+pub fn open_output(device: &str) -> Option<(OutputStream, OutputStreamHandle)> {
+    if !device.is_empty() {
+        let host = rodio::cpal::default_host();
+        let found = host
+            .output_devices()
+            .ok()
+            .and_then(|mut devices| devices.find(|d| d.name().is_ok_and(|n| n == device)));
+        match found.map(|d| OutputStream::try_from_device(&d)) {
+            Some(Ok(output)) => return Some(output),
+            Some(Err(e)) => error!("Failed to open audio device {}: {}", device, e),
+            None => info!("Audio device {} not found, using the default", device),
+        }
+    }
+    match OutputStream::try_default() {
+        Ok(output) => Some(output),
+        Err(e) => {
+            error!("Failed to open audio stream: {}", e);
+            None
+        }
+    }
+}
+
+fn get_audio_path() -> PathBuf {
+    let mut path = config_io::get_conf_d_path();
+    path.push("audio.yaml");
+    path
+}
+
+// The config watcher picks the file up and applies it
+fn save_device(device: &str) {
+    let mut audio = serde_yaml::Mapping::new();
+    audio.insert("audio_device".into(), device.into());
+    let result = serde_yaml::to_string(&audio)
+        .map_err(|e| e.to_string())
+        .and_then(|yaml| std::fs::write(get_audio_path(), yaml).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        report_error(&format!("Failed to save audio device: {}", e));
+    }
+}
+
+struct AudioData {
+    current: String,
+    devices: Vec<String>,
+}
+
+// Picks the device for key clicks and alerts; listed on first show, since that can be slow
+pub fn create_audio_panel(session: &AppSession) -> OverlayData {
+    let data = AudioData {
+        current: session.config.audio_device.clone(),
+        devices: vec![],
+    };
+    let mut canvas: Canvas<AudioData, bool> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        data,
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.font_size = theme.font_size;
+    canvas.fg_color = color_parse(&theme.text_color);
+    canvas.label(10., 30., WIDTH - 20., 30., "Play UI sounds on".into());
+
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+    let dropdown = canvas.dropdown(10., 50., WIDTH - 20., 40., vec![]);
+    canvas.controls[dropdown].on_update = Some(|control, data| {
+        if control.state.is_some() {
+            return;
+        }
+        data.devices = output_devices();
+        let items = std::iter::once("Default")
+            .chain(data.devices.iter().map(|d| d.as_str()))
+            .map(|name| name.into())
+            .collect();
+        control.set_items(items);
+        // a device that's gone shows as nothing picked
+        let current = match data.current.as_str() {
+            "" => Some(0),
+            current => data
+                .devices
+                .iter()
+                .position(|d| d == current)
+                .map(|i| i + 1),
+        };
+        control.set_selected(current);
+        control.state = Some(true);
+    });
+    canvas.controls[dropdown].on_select = Some(|_control, row, data| {
+        let device = match row {
+            0 => "",
+            _ => data.devices.get(row - 1).map(|d| d.as_str()).unwrap_or(""),
+        };
+        data.current = device.to_string();
+        save_device(device);
+    });
+
+    OverlayData {
+        name: "Audio".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.3 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: Vec3::NEG_Z * 0.6,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+use glam::{Affine3A, Quat};
+
+use crate::{
+    config::BedModeConfig,
+    overlay::{OverlayData, RelativeTo},
+    toast::{self, NotifyLevel},
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Head rotation when bed mode was turned on, and the frame the overlays were last moved to
+struct BedFrame {
+    anchor: Quat,
+    frame: Affine3A,
+}
+
+static FRAME: Mutex<Option<BedFrame>> = Mutex::new(None);
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn toggle() {
+    let enabled = !ENABLED.fetch_xor(true, Ordering::Relaxed);
+    let text = if enabled {
+        "Bed mode on: overlays follow your head"
+    } else {
+        "Bed mode off"
+    };
+    toast::notify(NotifyLevel::Info, text);
+}
+
+// Once per frame: carries the overlays placed in the world along with the head, turning
+// them by only a fraction of the head's rotation since bed mode was turned on
+pub fn update(
+    config: &BedModeConfig,
+    hmd: &Affine3A,
+    overlays: &mut [OverlayData],
+    grabbed: Option<usize>,
+) {
+    let Ok(mut state) = FRAME.lock() else {
+        return;
+    };
+    if !is_enabled() {
+        // overlays stay where they are, a new anchor is taken next time
+        *state = None;
+        return;
+    }
+
+    let (_, head_rot, head_pos) = hmd.to_scale_rotation_translation();
+    let anchor = state.as_ref().map(|s| s.anchor).unwrap_or(head_rot);
+    let previous = state
+        .as_ref()
+        .map(|s| s.frame)
+        .unwrap_or_else(|| Affine3A::from_rotation_translation(anchor, head_pos));
+
+    let pitch = Quat::from_rotation_x(config.pitch_offset.clamp(-90., 90.).to_radians());
+    let rotation = anchor.slerp(head_rot, config.sensitivity) * pitch;
+    let frame = Affine3A::from_rotation_translation(rotation, head_pos);
+
+    let delta = frame * previous.inverse();
+    for (idx, overlay) in overlays.iter_mut().enumerate() {
+        // the grabbed one follows the pointer instead
+        if overlay.visible
+            && matches!(overlay.relative_to, RelativeTo::None)
+            && grabbed != Some(idx)
+        {
+            overlay.transform = delta * overlay.transform;
+        }
+    }
+    *state = Some(BedFrame { anchor, frame });
+}
@@ -0,0 +1,96 @@
+// Sends a command to a running wlx-overlay-x over its unix socket
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    process::ExitCode,
+};
+
+#[path = "../ipc/proto.rs"]
+mod proto;
+
+use proto::{socket_path, Command, Response};
+
+const USAGE: &str = "Usage: wlx-ctl <command>
+
+Commands:
+    show <overlay>      Show an overlay, e.g. a screen such as DP-3, Kbd or Watch
+    hide <overlay>      Hide an overlay
+    toggle <overlay>    Show or hide an overlay
+    list                List overlays and whether they are visible
+    screenshot [name]   Save the named overlay, or all visible screens, to ~/Pictures
+    quit                Save the session and exit";
+
+fn parse_args() -> Option<Command> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let command = match args.as_slice() {
+        ["show", overlay] => Command::Show {
+            overlay: overlay.to_string(),
+        },
+        ["hide", overlay] => Command::Hide {
+            overlay: overlay.to_string(),
+        },
+        ["toggle", overlay] => Command::Toggle {
+            overlay: overlay.to_string(),
+        },
+        ["list"] => Command::List,
+        ["screenshot"] => Command::Screenshot { overlay: None },
+        ["screenshot", overlay] => Command::Screenshot {
+            overlay: Some(overlay.to_string()),
+        },
+        ["quit"] => Command::Quit,
+        _ => return None,
+    };
+    Some(command)
+}
+
+fn send(command: &Command) -> Result<Response, String> {
+    let path = socket_path();
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| format!("Could not connect to {}: {}", path.to_string_lossy(), e))?;
+
+    let mut json = serde_json::to_string(command).map_err(|e| e.to_string())?;
+    json.push('\n');
+    stream
+        .write_all(json.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&line).map_err(|e| format!("Invalid response: {}", e))
+}
+
+fn main() -> ExitCode {
+    let Some(command) = parse_args() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+
+    match send(&command) {
+        Ok(Response::Ok) => ExitCode::SUCCESS,
+        Ok(Response::Overlays { overlays }) => {
+            for overlay in overlays {
+                let state = if overlay.visible { "shown" } else { "hidden" };
+                println!("{}\t{}", overlay.name, state);
+            }
+            ExitCode::SUCCESS
+        }
+        Ok(Response::Files { files }) => {
+            for file in files {
+                println!("{}", file);
+            }
+            ExitCode::SUCCESS
+        }
+        Ok(Response::Error { message }) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
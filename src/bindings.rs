@@ -0,0 +1,78 @@
+use glam::Vec3;
+use stereokit::SkDraw;
+
+use crate::{
+    config::{self, BindingButton, BindingHand, ButtonAction},
+    keyboard::summon_keyboard,
+    overlay::OverlayData,
+    radial::recenter_overlays,
+    screenshot::screenshot_overlays,
+    AppSession, AppState, TASKS,
+};
+
+// Bits of the controller buttons that can be bound, as kept in PointerState
+pub const BUTTON_X1: u8 = 1;
+pub const BUTTON_X2: u8 = 2;
+pub const BUTTON_STICK_CLICK: u8 = 4;
+
+pub fn button_bit(button: BindingButton) -> u8 {
+    match button {
+        BindingButton::X1 => BUTTON_X1,
+        BindingButton::X2 => BUTTON_X2,
+        BindingButton::StickClick => BUTTON_STICK_CLICK,
+    }
+}
+
+// Queues the actions bound to the buttons that were just pressed on a controller
+pub fn dispatch(session: &AppSession, hand: usize, pressed: u8, position: Vec3) {
+    for binding in session.config.button_bindings.iter() {
+        let bound_hand = match binding.hand {
+            BindingHand::Left => 0,
+            BindingHand::Right => 1,
+        };
+        if bound_hand != hand || pressed & button_bit(binding.button) == 0 {
+            continue;
+        }
+        let action = binding.action;
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(move |sk, app, o| {
+                run(action, position, sk, app, o);
+            }));
+        }
+    }
+}
+
+fn run(
+    action: ButtonAction,
+    position: Vec3,
+    sk: &SkDraw,
+    app: &mut AppState,
+    overlays: &mut [OverlayData],
+) {
+    match action {
+        ButtonAction::ToggleKeyboard => {
+            for overlay in overlays.iter_mut() {
+                if &*overlay.name == "Kbd" {
+                    overlay.want_visible = !overlay.want_visible;
+                }
+            }
+        }
+        ButtonAction::SummonKeyboard => summon_keyboard(overlays, &app.input.hmd, position),
+        ButtonAction::ToggleNearest => {
+            let nearest = overlays.iter_mut().filter(|o| o.show_hide).min_by(|a, b| {
+                let da = position.distance_squared(a.transform.translation.into());
+                let db = position.distance_squared(b.transform.translation.into());
+                da.total_cmp(&db)
+            });
+            if let Some(overlay) = nearest {
+                overlay.want_visible = !overlay.want_visible;
+            }
+        }
+        ButtonAction::RecenterOverlays => recenter_overlays(app, overlays),
+        ButtonAction::Screenshot => {
+            if let Err(e) = screenshot_overlays(sk, app, overlays, None) {
+                config::report_error(&e);
+            }
+        }
+    }
+}
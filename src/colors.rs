@@ -0,0 +1,214 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use glam::Vec3;
+use serde_yaml::Value;
+
+use crate::{
+    config::{report_error, PointerColors},
+    config_io,
+    gui::{color_parse, color_to_html, Canvas},
+    overlay::{OverlayData, RelativeTo},
+    AppSession, TASKS,
+};
+
+const WIDTH: f32 = 400.;
+const HEIGHT: f32 = 420.;
+
+#[derive(Clone, Copy)]
+enum Target {
+    // pointer mode: norm, shift, alt
+    Pointer(usize),
+    // field of the theme
+    Theme(&'static str),
+}
+
+const TARGETS: [(&str, Target); 13] = [
+    ("Pointer", Target::Pointer(0)),
+    ("Pointer, shift mode", Target::Pointer(1)),
+    ("Pointer, alt mode", Target::Pointer(2)),
+    ("Text", Target::Theme("text_color")),
+    ("Panel", Target::Theme("watch_bg_color")),
+    ("Button", Target::Theme("button_color")),
+    ("Button text", Target::Theme("button_text_color")),
+    ("Keyboard button", Target::Theme("keyboard_button_color")),
+    ("Screen button", Target::Theme("screen_button_color")),
+    ("Toggle text", Target::Theme("toggle_text_color")),
+    ("Keyboard background", Target::Theme("keyboard_bg_color")),
+    ("Key", Target::Theme("key_color")),
+    ("Key text", Target::Theme("key_text_color")),
+];
+
+// Kept across rebuilds, which follow every save
+static TARGET: AtomicUsize = AtomicUsize::new(0);
+
+fn get_colors_path() -> PathBuf {
+    let mut path = config_io::get_conf_d_path();
+    path.push("colors.yaml");
+    path
+}
+
+struct ColorsData {
+    pointer: PointerColors,
+    // overrides, as saved
+    theme_colors: HashMap<String, String>,
+    // every color of the theme, overrides included
+    theme: HashMap<String, String>,
+}
+
+impl ColorsData {
+    fn pointer_mut(&mut self, mode: usize) -> &mut String {
+        match mode {
+            0 => &mut self.pointer.norm,
+            1 => &mut self.pointer.shift,
+            _ => &mut self.pointer.alt,
+        }
+    }
+
+    fn get(&mut self, target: Target) -> Option<String> {
+        match target {
+            Target::Pointer(mode) => Some(self.pointer_mut(mode).clone()),
+            Target::Theme(key) => self.theme.get(key).cloned(),
+        }
+    }
+
+    fn set(&mut self, target: Target, color: String) {
+        match target {
+            Target::Pointer(mode) => *self.pointer_mut(mode) = color,
+            Target::Theme(key) => {
+                self.theme.insert(key.to_string(), color.clone());
+                self.theme_colors.insert(key.to_string(), color);
+            }
+        }
+    }
+
+    // The config watcher picks the file up and applies it, rebuilding the UI in the new colors
+    fn save(&self) {
+        let mut colors = serde_yaml::Mapping::new();
+        let result = serde_yaml::to_value(&self.pointer)
+            .and_then(|pointer| {
+                colors.insert("pointer_colors".into(), pointer);
+                serde_yaml::to_value(&self.theme_colors)
+            })
+            .and_then(|theme| {
+                colors.insert("theme_colors".into(), theme);
+                serde_yaml::to_string(&colors)
+            })
+            .map_err(|e| e.to_string())
+            .and_then(|yaml| std::fs::write(get_colors_path(), yaml).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            report_error(&format!("Failed to save colors: {}", e));
+        }
+    }
+}
+
+fn current_target() -> Target {
+    let idx = TARGET.load(Ordering::Relaxed).min(TARGETS.len() - 1);
+    TARGETS[idx].1
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ColorsControl {
+    // index into TARGETS being edited
+    Target(usize),
+}
+
+// Edits pointer and theme colors; pointers change while dragging, the theme once let go
+pub fn create_colors_panel(session: &AppSession) -> OverlayData {
+    let theme = match serde_yaml::to_value(&session.theme) {
+        Ok(Value::Mapping(fields)) => fields
+            .into_iter()
+            .filter_map(|(k, v)| Some((k.as_str()?.to_string(), v.as_str()?.to_string())))
+            .collect(),
+        _ => HashMap::new(),
+    };
+    let data = ColorsData {
+        pointer: session.config.pointer_colors.clone(),
+        theme_colors: session.config.theme_colors.clone(),
+        theme,
+    };
+    let mut canvas: Canvas<ColorsData, ColorsControl> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        data,
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.font_size = theme.font_size;
+    canvas.fg_color = color_parse(&theme.text_color);
+    canvas.label(10., 30., WIDTH - 20., 30., "Color of".into());
+
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+    let items = TARGETS.iter().map(|(label, _)| (*label).into()).collect();
+    let dropdown = canvas.dropdown(10., 45., WIDTH - 20., 40., items);
+    canvas.controls[dropdown].set_selected(Some(TARGET.load(Ordering::Relaxed)));
+    canvas.controls[dropdown].on_select = Some(|_control, row, _data| {
+        TARGET.store(row, Ordering::Relaxed);
+    });
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    let picker = canvas.color_picker(10., 95., WIDTH - 20., 270., Vec3::ONE);
+    canvas.controls[picker].on_update = Some(|control, data| {
+        let idx = TARGET.load(Ordering::Relaxed);
+        let state = Some(ColorsControl::Target(idx));
+        if control.state == state {
+            return;
+        }
+        if let Some(color) = data.get(current_target()) {
+            control.set_color(color_parse(&color));
+        }
+        control.state = state;
+    });
+    canvas.controls[picker].on_change = Some(|control, data| {
+        let target = current_target();
+        data.set(target, color_to_html(control.get_color()));
+        if let Target::Pointer(_) = target {
+            let pointer = data.pointer.clone();
+            if let Ok(mut tasks) = TASKS.lock() {
+                tasks.push_back(Box::new(move |_sk, app, _o| {
+                    app.session.config.pointer_colors = pointer;
+                    app.session.load_pointer_colors();
+                }));
+            }
+        }
+    });
+    canvas.controls[picker].on_release = Some(|_control, data| {
+        data.save();
+    });
+
+    canvas.bg_color = color_parse(&theme.keyboard_button_color);
+    let reset = canvas.button(10., HEIGHT - 45., WIDTH - 20., 35., "Default".into());
+    canvas.controls[reset].set_tooltip("Go back to the theme's or built-in color");
+    canvas.controls[reset].on_press = Some(|_control, _session, data| {
+        match current_target() {
+            Target::Pointer(mode) => {
+                let defaults = PointerColors::default();
+                let default = [defaults.norm, defaults.shift, defaults.alt];
+                *data.pointer_mut(mode) = default[mode].clone();
+            }
+            Target::Theme(key) => {
+                data.theme_colors.remove(key);
+            }
+        }
+        data.save();
+    });
+
+    OverlayData {
+        name: "Colors".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.3 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: Vec3::NEG_Z * 0.6,
+        ..Default::default()
+    }
+}
@@ -1,11 +1,30 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
 use crate::config_io;
 use crate::config_io::get_conf_d_path;
 use crate::desktop::def_pw_tokens;
-use crate::keyboard;
+use crate::gui::{self, theme::Theme};
+use crate::input::{MOUSE_BACK, MOUSE_FORWARD, MOUSE_LEFT, MOUSE_MIDDLE, MOUSE_RIGHT};
+use crate::keyboard::{self, ExecCommand};
 use crate::load_with_fallback;
-use log::error;
+use crate::osc::OscSender;
+use crate::overlay::{OverlayData, RelativeTo};
+use crate::toast::{self, NotifyLevel};
+use crate::watch::{mirror_watch, rebuild_ui};
+use crate::AppState;
+use crate::TASKS;
+use glam::{vec2, Affine3A, Vec2};
+use inotify::{Event, Inotify, WatchDescriptor, WatchMask};
+use log::{error, info};
+use regex::Regex;
 use serde::Deserialize;
 use serde::Serialize;
+use serde_yaml::Value;
 
 fn def_grab_threshold() -> f32 {
     0.6
@@ -19,6 +38,10 @@ fn def_click_freeze_time_ms() -> u32 {
     300
 }
 
+fn def_recenter_hold_ms() -> u32 {
+    1000
+}
+
 fn def_true() -> bool {
     true
 }
@@ -31,6 +54,195 @@ fn def_one() -> f32 {
     1.0
 }
 
+fn def_screen_anisotropy() -> f32 {
+    8.0
+}
+
+fn def_canvas_supersample() -> f32 {
+    1.5
+}
+
+// The downsample is a single bilinear tap, which only averages all the texels up to 2x;
+// past that it skips some and text shimmers instead of getting sharper
+const MAX_CANVAS_SUPERSAMPLE: f32 = 2.0;
+
+fn def_power_save_fps() -> f32 {
+    10.0
+}
+
+fn def_power_save_idle_secs() -> u32 {
+    60
+}
+
+// Only the text grows, not the controls around it; past this labels no longer fit their
+// buttons. ui_scale enlarges everything.
+const MAX_FONT_SCALE: f32 = 1.5;
+
+fn def_font_scale() -> f32 {
+    1.3
+}
+
+fn def_laser_scale() -> f32 {
+    2.5
+}
+
+fn def_osc_listen_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn def_shortcut_trigger() -> String {
+    "CTRL+ALT+O".to_string()
+}
+
+fn def_shortcut_action() -> ShortcutAction {
+    ShortcutAction::ToggleOverlays
+}
+
+fn def_button_norm() -> MouseButton {
+    MouseButton::Left
+}
+
+fn def_button_shift() -> MouseButton {
+    MouseButton::Right
+}
+
+fn def_button_alt() -> MouseButton {
+    MouseButton::Middle
+}
+
+fn def_webcam_width() -> u32 {
+    640
+}
+
+fn def_webcam_height() -> u32 {
+    480
+}
+
+fn def_webcam_fps() -> u32 {
+    30
+}
+
+fn def_bed_pitch_offset() -> f32 {
+    20.
+}
+
+fn def_bed_sensitivity() -> f32 {
+    0.3
+}
+
+fn def_power_lock() -> Vec<String> {
+    vec!["loginctl".to_string(), "lock-session".to_string()]
+}
+
+fn def_power_suspend() -> Vec<String> {
+    vec!["systemctl".to_string(), "suspend".to_string()]
+}
+
+fn def_power_logout() -> Vec<String> {
+    vec![
+        "loginctl".to_string(),
+        "terminate-session".to_string(),
+        "self".to_string(),
+    ]
+}
+
+fn def_power_shutdown() -> Vec<String> {
+    vec!["systemctl".to_string(), "poweroff".to_string()]
+}
+
+fn def_power_hold_ms() -> u32 {
+    1500
+}
+
+fn def_low_battery_threshold() -> f32 {
+    0.2
+}
+
+fn def_magnifier_zoom() -> f32 {
+    3.0
+}
+
+fn def_font_family() -> String {
+    "LiberationSans".to_string()
+}
+
+fn def_font_style() -> String {
+    "bold".to_string()
+}
+
+fn def_symbol_font() -> String {
+    "DejaVu Sans".to_string()
+}
+
+fn def_terminal_command() -> Vec<String> {
+    vec!["xterm".to_string(), "-e".to_string()]
+}
+
+fn def_metric_label() -> String {
+    "♥".to_string()
+}
+
+fn def_metric_low_color() -> String {
+    "#60a0ff".to_string()
+}
+
+fn def_metric_high_color() -> String {
+    "#ff5050".to_string()
+}
+
+fn def_weather_provider_url() -> String {
+    "https://api.open-meteo.com/v1/forecast".to_string()
+}
+
+fn def_weather_interval_mins() -> u32 {
+    15
+}
+
+fn def_pointer_color_norm() -> String {
+    "#00ffff".to_string()
+}
+
+fn def_pointer_color_shift() -> String {
+    "#ffff00".to_string()
+}
+
+fn def_pointer_color_alt() -> String {
+    "#ff00ff".to_string()
+}
+
+fn def_theme() -> String {
+    "dark".to_string()
+}
+
+fn def_radial_menu_button() -> String {
+    "stick_click".to_string()
+}
+
+fn def_nav_activate_button() -> String {
+    "x1".to_string()
+}
+
+fn def_watch_hand() -> String {
+    "left".to_string()
+}
+
+fn def_show_screens() -> Vec<String> {
+    vec!["DP-3".to_string()]
+}
+
+fn def_capture_method() -> String {
+    "auto".to_string()
+}
+
+// WLXO_<KEY> overrides the config key of the same name, lowercased
+const ENV_PREFIX: &str = "WLXO_";
+
+// Read by the logger instead of being merged into the config
+pub const ENV_LOG_LEVEL: &str = "WLXO_LOG_LEVEL";
+
+// Name of the profiles/<name>.yaml merged over the config, if any
+static PROFILE: Mutex<Option<String>> = Mutex::new(None);
+
 #[derive(Deserialize, Serialize)]
 pub struct GeneralConfig {
     #[serde(default = "def_grab_threshold")]
@@ -45,6 +257,18 @@ pub struct GeneralConfig {
     #[serde(default = "def_true")]
     pub keyboard_sound_enabled: bool,
 
+    #[serde(default)]
+    pub audio_device: String,
+
+    #[serde(default)]
+    pub sticky_keys: StickyKeys,
+
+    #[serde(default = "def_true")]
+    pub mirror_physical_modifiers: bool,
+
+    #[serde(default = "def_terminal_command")]
+    pub terminal_command: Vec<String>,
+
     #[serde(default = "def_one")]
     pub keyboard_scale: f32,
 
@@ -54,69 +278,1299 @@ pub struct GeneralConfig {
     #[serde(default = "def_one")]
     pub watch_scale: f32,
 
+    #[serde(default = "def_true")]
+    pub screen_mipmaps: bool,
+
+    #[serde(default = "def_screen_anisotropy")]
+    pub screen_anisotropy: f32,
+
+    #[serde(default)]
+    pub wlr_color_space: ColorSpace,
+
+    #[serde(default)]
+    pub pipewire_color_space: ColorSpace,
+
+    // Lists screens the portal has no token for in VR, instead of opening its dialog for each
+    #[serde(default = "def_true")]
+    pub pw_picker: bool,
+
+    // Draws reference gradients over the screens, to check the color space settings
+    #[serde(default = "def_false")]
+    pub color_test: bool,
+
+    #[serde(default)]
+    pub cvd_filter: CvdFilter,
+
+    #[serde(default = "def_one")]
+    pub ui_scale: f32,
+
+    #[serde(default = "def_canvas_supersample")]
+    pub canvas_supersample: f32,
+
+    // Text font, as fontconfig names them, and the families tried in order for
+    // characters it doesn't have
+    #[serde(default = "def_font_family")]
+    pub font_family: String,
+    #[serde(default = "def_font_style")]
+    pub font_style: String,
+    #[serde(default)]
+    pub font_fallbacks: Vec<String>,
+
+    #[serde(default = "def_symbol_font")]
+    pub symbol_font: String,
+
+    #[serde(default = "def_theme")]
+    pub theme: String,
+
+    // Colors that replace the theme's, keyed by theme field
+    #[serde(default)]
+    pub theme_colors: HashMap<String, String>,
+
+    // Laser color in each pointer mode
+    #[serde(default)]
+    pub pointer_colors: PointerColors,
+
+    #[serde(default = "def_radial_menu_button")]
+    pub radial_menu_button: String,
+
+    // Changes to single keys of the keyboard layout, by their name in main_layout
+    #[serde(default)]
+    pub key_overrides: HashMap<String, KeyOverride>,
+
+    // Flicking the stick sideways moves through the controls of the panel pointed at
+    #[serde(default = "def_true")]
+    pub controller_nav: bool,
+
+    // Presses the control picked with the stick
+    #[serde(default = "def_nav_activate_button")]
+    pub nav_activate_button: String,
+
+    // Spare controller buttons bound to quick actions
+    #[serde(default)]
+    pub button_bindings: Vec<ButtonBinding>,
+
+    // Held on both controllers at once, puts every overlay and the watch back in reach
+    #[serde(default)]
+    pub recenter_gesture: Option<BindingButton>,
+
+    #[serde(default = "def_recenter_hold_ms")]
+    pub recenter_hold_ms: u32,
+
+    // Trigger with the palm turned up, away from overlays, brings the keyboard to the hand
+    #[serde(default = "def_false")]
+    pub palm_up_summons_keyboard: bool,
+
+    // What the trigger clicks on screens, per pointer mode
+    #[serde(default)]
+    pub pointer_buttons: PointerButtons,
+
+    #[serde(default = "def_watch_hand")]
+    pub watch_hand: String,
+
+    // Placement on watch_hand, as saved from edit mode; the built-in one if not set
+    #[serde(default)]
+    pub watch_pos: Option<[f32; 3]>,
+    #[serde(default)]
+    pub watch_rot: Option<[f32; 4]>,
+
+    #[serde(default = "def_show_screens")]
+    pub show_screens: Vec<String>,
+
+    #[serde(default = "def_capture_method")]
+    pub capture_method: String,
+
+    // Per-output placement, keyed by output name
+    #[serde(default)]
+    pub outputs: HashMap<String, OutputConfig>,
+
+    // Update rate limits, keyed by overlay name
+    #[serde(default)]
+    pub max_fps: HashMap<String, f32>,
+
+    #[serde(default)]
+    pub accessibility: AccessibilityConfig,
+
+    // Carries the overlays along with the head while lying down, toggled from the watch
+    #[serde(default)]
+    pub bed_mode: BedModeConfig,
+
+    // Commands behind the buttons of the power menu, opened from the watch
+    #[serde(default)]
+    pub power_menu: PowerMenuConfig,
+
+    // Logs how long captures, canvases and overlays take, and shows it on a HUD
+    #[serde(default = "def_false")]
+    pub perf_stats: bool,
+
+    // Measures clicks on screens from the trigger to uinput and to the next captured frame
+    #[serde(default = "def_false")]
+    pub latency_stats: bool,
+
+    // Shows desktop notifications as toasts, and so in the history
+    #[serde(default = "def_true")]
+    pub desktop_notifications: bool,
+
+    // Desktop shortcut through xdg-desktop-portal, disabled if not set
+    #[serde(default)]
+    pub global_shortcut: Option<GlobalShortcutConfig>,
+
+    // Keeps the desktop from locking or blanking while the overlay runs
+    #[serde(default = "def_true")]
+    pub idle_inhibit: bool,
+
+    // Only inhibit while a screen is shown
+    #[serde(default = "def_false")]
+    pub idle_inhibit_screens_only: bool,
+
+    // Caps capture and canvas updates while idle or on battery
+    #[serde(default = "def_false")]
+    pub power_save: bool,
+
+    #[serde(default = "def_power_save_fps")]
+    pub power_save_fps: f32,
+
+    // Seconds without any pointer on an overlay before power saving kicks in
+    #[serde(default = "def_power_save_idle_secs")]
+    pub power_save_idle_secs: u32,
+
+    #[serde(default = "def_true")]
+    pub power_save_on_battery: bool,
+
+    // Warns when a controller or headset battery drops to this charge, 0.0 - 1.0
+    #[serde(default = "def_low_battery_threshold")]
+    pub low_battery_threshold: f32,
+
+    // Warns when a controller stops being tracked
+    #[serde(default = "def_true")]
+    pub tracking_alerts: bool,
+
+    // Beeps along with battery and tracking warnings
+    #[serde(default = "def_true")]
+    pub alert_sound: bool,
+
+    // How much the magnifier enlarges the screen under the pointer
+    #[serde(default = "def_magnifier_zoom")]
+    pub magnifier_zoom: f32,
+
+    // UDP port to receive OSC messages on, disabled if not set
+    #[serde(default)]
+    pub osc_listen_port: Option<u16>,
+
+    // Address the OSC listener binds to, 0.0.0.0 to take messages from other machines too
+    #[serde(default = "def_osc_listen_address")]
+    pub osc_listen_address: String,
+
+    // host:port to send OSC messages to, disabled if not set
+    #[serde(default)]
+    pub osc_send_address: Option<String>,
+
+    // Number received on /wlx/watch/metric, shown on the watch if set
+    #[serde(default)]
+    pub osc_metric: Option<MetricConfig>,
+
+    // Current conditions on the watch, disabled if not set
+    #[serde(default)]
+    pub weather: Option<WeatherConfig>,
+
+    // V4L2 devices shown as overlays
+    #[serde(default)]
+    pub webcams: Vec<WebcamConfig>,
+
     #[serde(default = "def_pw_tokens")]
     pub pw_tokens: Vec<(String, String)>,
 }
 
+// How to read the colors of a captured screen.
+// The panel shader expects sRGB-encoded values; auto goes by the format of each imported buffer.
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorSpace {
+    #[default]
+    Auto,
+    // values are sRGB-encoded, shown as they are
+    Srgb,
+    // values are linear, encoded to sRGB before they are shown
+    Linear,
+}
+
+// Stereo 3D content on a screen, such as a video player in a 3D mode. Each eye sees its own
+// half, stretched back to the frame's shape as half-width and half-height formats squeeze them.
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StereoMode {
+    #[default]
+    None,
+    // side by side, left eye first
+    Sbs,
+    // top and bottom, left eye on top
+    Tb,
+}
+
+impl StereoMode {
+    // Size of one eye's half of the texture, in uv
+    pub fn uv_scale(self) -> Vec2 {
+        match self {
+            StereoMode::None => vec2(1., 1.),
+            StereoMode::Sbs => vec2(0.5, 1.),
+            StereoMode::Tb => vec2(1., 0.5),
+        }
+    }
+
+    // Where the right eye's half starts, the left eye's starts at 0
+    pub fn right_eye_offset(self) -> Vec2 {
+        match self {
+            StereoMode::None => vec2(0., 0.),
+            StereoMode::Sbs => vec2(0.5, 0.),
+            StereoMode::Tb => vec2(0., 0.5),
+        }
+    }
+}
+
+// Correction applied to captured screens for color-blind users
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CvdFilter {
+    #[default]
+    None,
+    // red-weak
+    Protanopia,
+    // green-weak
+    Deuteranopia,
+    // blue-weak
+    Tritanopia,
+}
+
+// What tapping a modifier on the keyboard overlay does
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StickyKeys {
+    // held only while pressed
+    Off,
+    // latched until the next key, tapping again releases it
+    #[default]
+    Once,
+    // latched until the next key, tapping again locks it until a third tap
+    Lock,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum BindingHand {
+    Left,
+    Right,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum BindingButton {
+    X1,
+    X2,
+    StickClick,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonAction {
+    ToggleKeyboard,
+    // shows the keyboard in front of the controller
+    SummonKeyboard,
+    // the overlay closest to the controller, shown or not
+    ToggleNearest,
+    RecenterOverlays,
+    Screenshot,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortcutAction {
+    // hides every screen and the keyboard, and brings them back
+    ToggleOverlays,
+    ToggleKeyboard,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct GlobalShortcutConfig {
+    // only a suggestion, the desktop decides and lets the user change it
+    #[serde(default = "def_shortcut_trigger")]
+    pub trigger: String,
+    #[serde(default = "def_shortcut_action")]
+    pub action: ShortcutAction,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ButtonBinding {
+    pub hand: BindingHand,
+    pub button: BindingButton,
+    pub action: ButtonAction,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+pub struct OutputConfig {
+    // Takes precedence over show_screens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visible: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<[f32; 12]>,
+    // Takes precedence over the backend's color space
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_space: Option<ColorSpace>,
+    // Takes precedence over pointer_buttons
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointer_buttons: Option<PointerButtons>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stereo: Option<StereoMode>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Back,
+    Forward,
+}
+
+impl MouseButton {
+    pub fn code(self) -> u16 {
+        match self {
+            MouseButton::Left => MOUSE_LEFT,
+            MouseButton::Right => MOUSE_RIGHT,
+            MouseButton::Middle => MOUSE_MIDDLE,
+            MouseButton::Back => MOUSE_BACK,
+            MouseButton::Forward => MOUSE_FORWARD,
+        }
+    }
+}
+
+// Mouse button sent by the trigger in each pointer mode
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PointerButtons {
+    #[serde(default = "def_button_norm")]
+    pub norm: MouseButton,
+    #[serde(default = "def_button_shift")]
+    pub shift: MouseButton,
+    #[serde(default = "def_button_alt")]
+    pub alt: MouseButton,
+}
+
+impl Default for PointerButtons {
+    fn default() -> Self {
+        PointerButtons {
+            norm: def_button_norm(),
+            shift: def_button_shift(),
+            alt: def_button_alt(),
+        }
+    }
+}
+
+// Anything not set is taken from the layout
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct KeyOverride {
+    // a key, macro or exec_commands name to act as instead
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub label: Option<Vec<String>>,
+    #[serde(default, rename = "macro")]
+    pub macro_verbs: Option<Vec<String>>,
+    #[serde(default)]
+    pub exec: Option<ExecCommand>,
+}
+
+// Laser color in each pointer mode, as HTML hex strings
+#[derive(Deserialize, Serialize, Clone)]
+pub struct PointerColors {
+    #[serde(default = "def_pointer_color_norm")]
+    pub norm: String,
+    #[serde(default = "def_pointer_color_shift")]
+    pub shift: String,
+    #[serde(default = "def_pointer_color_alt")]
+    pub alt: String,
+}
+
+impl Default for PointerColors {
+    fn default() -> Self {
+        serde_yaml::from_value(Value::Mapping(Default::default()))
+            .expect("PointerColors fields must all have defaults")
+    }
+}
+
+// Values below low or above high are shown in their own color
+#[derive(Deserialize, Serialize, Clone)]
+pub struct MetricConfig {
+    #[serde(default = "def_metric_label")]
+    pub label: String,
+    #[serde(default)]
+    pub low: Option<f32>,
+    #[serde(default)]
+    pub high: Option<f32>,
+    #[serde(default = "def_metric_low_color")]
+    pub low_color: String,
+    #[serde(default = "def_metric_high_color")]
+    pub high_color: String,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct WeatherConfig {
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default = "def_weather_provider_url")]
+    pub provider_url: String,
+    #[serde(default = "def_false")]
+    pub fahrenheit: bool,
+    #[serde(default = "def_weather_interval_mins")]
+    pub interval_mins: u32,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct WebcamConfig {
+    pub device: String,
+    // overlay name, "Webcam <n>" if not set
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default = "def_webcam_width")]
+    pub width: u32,
+    #[serde(default = "def_webcam_height")]
+    pub height: u32,
+    #[serde(default = "def_webcam_fps")]
+    pub fps: u32,
+    #[serde(default = "def_true")]
+    pub visible: bool,
+}
+
+// Larger text, thicker lasers, stronger highlights and finer hit-testing, all off unless enabled
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AccessibilityConfig {
+    #[serde(default = "def_false")]
+    pub enabled: bool,
+    #[serde(default = "def_font_scale")]
+    pub font_scale: f32,
+    #[serde(default = "def_laser_scale")]
+    pub laser_scale: f32,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct BedModeConfig {
+    // degrees, positive tilts the overlays up
+    #[serde(default = "def_bed_pitch_offset")]
+    pub pitch_offset: f32,
+    // fraction of the head's rotation the overlays follow
+    #[serde(default = "def_bed_sensitivity")]
+    pub sensitivity: f32,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct PowerMenuConfig {
+    // program and arguments, run without a shell
+    #[serde(default = "def_power_lock")]
+    pub lock: Vec<String>,
+    #[serde(default = "def_power_suspend")]
+    pub suspend: Vec<String>,
+    #[serde(default = "def_power_logout")]
+    pub logout: Vec<String>,
+    #[serde(default = "def_power_shutdown")]
+    pub shutdown: Vec<String>,
+    // how long a button needs holding before its command runs
+    #[serde(default = "def_power_hold_ms")]
+    pub hold_ms: u32,
+}
+
+impl Default for PowerMenuConfig {
+    fn default() -> Self {
+        serde_yaml::from_value(Value::Mapping(Default::default()))
+            .expect("PowerMenuConfig fields must all have defaults")
+    }
+}
+
+impl Default for BedModeConfig {
+    fn default() -> Self {
+        serde_yaml::from_value(Value::Mapping(Default::default()))
+            .expect("BedModeConfig fields must all have defaults")
+    }
+}
+
+impl Default for AccessibilityConfig {
+    fn default() -> Self {
+        serde_yaml::from_value(Value::Mapping(Default::default()))
+            .expect("AccessibilityConfig fields must all have defaults")
+    }
+}
+
+impl AccessibilityConfig {
+    pub fn font_scale(&self) -> f32 {
+        if self.enabled {
+            self.font_scale
+        } else {
+            1.0
+        }
+    }
+
+    pub fn laser_scale(&self) -> f32 {
+        if self.enabled {
+            self.laser_scale
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Default for GeneralConfig {
+    // Every field has a serde default, so an empty mapping gives the built-in values
+    fn default() -> Self {
+        serde_yaml::from_value(Value::Mapping(Default::default()))
+            .expect("GeneralConfig fields must all have defaults")
+    }
+}
+
 impl GeneralConfig {
-    fn panic(msg: &str) {
-        panic!("GeneralConfig: {}", msg);
+    fn sanitize_range(
+        name: &str,
+        val: &mut f32,
+        default: f32,
+        from: f32,
+        to: f32,
+    ) -> Result<(), String> {
+        if !val.is_normal() || *val < from || *val > to {
+            let msg = format!(
+                "{} needs to be between {} and {}, using {}",
+                name, from, to, default
+            );
+            *val = default;
+            return Err(msg);
+        }
+        Ok(())
     }
 
-    fn sanitize_range(name: &str, val: f32, from: f32, to: f32) {
-        if !val.is_normal() || val < from || val > to {
-            panic!(
-                "GeneralConfig: {} needs to be between {} and {}",
-                name, from, to
+    fn sanitize_color(name: &str, val: &mut String, default: String) -> Result<(), String> {
+        if !gui::is_html_color(val) {
+            let msg = format!(
+                "{} needs to be a color like #rrggbb, using {}",
+                name, default
             );
+            *val = default;
+            return Err(msg);
+        }
+        Ok(())
+    }
+
+    // Replaces out-of-range values with their defaults, returning a message for each
+    fn post_load(&mut self) -> Vec<String> {
+        let defaults = GeneralConfig::default();
+        [
+            GeneralConfig::sanitize_range(
+                "grab_threshold",
+                &mut self.grab_threshold,
+                defaults.grab_threshold,
+                0.0,
+                1.0,
+            ),
+            GeneralConfig::sanitize_range(
+                "trigger_threshold",
+                &mut self.trigger_threshold,
+                defaults.trigger_threshold,
+                0.0,
+                1.0,
+            ),
+            GeneralConfig::sanitize_range(
+                "keyboard_scale",
+                &mut self.keyboard_scale,
+                defaults.keyboard_scale,
+                0.0,
+                5.0,
+            ),
+            GeneralConfig::sanitize_range(
+                "desktop_view_scale",
+                &mut self.desktop_view_scale,
+                defaults.desktop_view_scale,
+                0.0,
+                5.0,
+            ),
+            GeneralConfig::sanitize_range(
+                "watch_scale",
+                &mut self.watch_scale,
+                defaults.watch_scale,
+                0.0,
+                5.0,
+            ),
+            GeneralConfig::sanitize_range(
+                "ui_scale",
+                &mut self.ui_scale,
+                defaults.ui_scale,
+                0.25,
+                4.0,
+            ),
+            GeneralConfig::sanitize_range(
+                "canvas_supersample",
+                &mut self.canvas_supersample,
+                defaults.canvas_supersample,
+                1.0,
+                MAX_CANVAS_SUPERSAMPLE,
+            ),
+            GeneralConfig::sanitize_range(
+                "screen_anisotropy",
+                &mut self.screen_anisotropy,
+                defaults.screen_anisotropy,
+                1.0,
+                16.0,
+            ),
+            GeneralConfig::sanitize_range(
+                "accessibility.font_scale",
+                &mut self.accessibility.font_scale,
+                defaults.accessibility.font_scale,
+                0.5,
+                MAX_FONT_SCALE,
+            ),
+            GeneralConfig::sanitize_range(
+                "accessibility.laser_scale",
+                &mut self.accessibility.laser_scale,
+                defaults.accessibility.laser_scale,
+                0.5,
+                10.0,
+            ),
+            GeneralConfig::sanitize_range(
+                "bed_mode.sensitivity",
+                &mut self.bed_mode.sensitivity,
+                defaults.bed_mode.sensitivity,
+                0.0,
+                1.0,
+            ),
+            GeneralConfig::sanitize_range(
+                "low_battery_threshold",
+                &mut self.low_battery_threshold,
+                defaults.low_battery_threshold,
+                0.0,
+                1.0,
+            ),
+            GeneralConfig::sanitize_range(
+                "magnifier_zoom",
+                &mut self.magnifier_zoom,
+                defaults.magnifier_zoom,
+                1.0,
+                16.0,
+            ),
+            GeneralConfig::sanitize_range(
+                "power_save_fps",
+                &mut self.power_save_fps,
+                defaults.power_save_fps,
+                1.0,
+                60.0,
+            ),
+            GeneralConfig::sanitize_color(
+                "pointer_colors.norm",
+                &mut self.pointer_colors.norm,
+                defaults.pointer_colors.norm,
+            ),
+            GeneralConfig::sanitize_color(
+                "pointer_colors.shift",
+                &mut self.pointer_colors.shift,
+                defaults.pointer_colors.shift,
+            ),
+            GeneralConfig::sanitize_color(
+                "pointer_colors.alt",
+                &mut self.pointer_colors.alt,
+                defaults.pointer_colors.alt,
+            ),
+        ]
+        .into_iter()
+        .chain(self.sanitize_theme_colors())
+        .chain(self.sanitize_show_screens())
+        .filter_map(Result::err)
+        .collect()
+    }
+
+    // Drops patterns that don't compile, so they aren't reported again for every screen
+    fn sanitize_show_screens(&mut self) -> Vec<Result<(), String>> {
+        let mut errors = vec![];
+        self.show_screens
+            .retain(|pattern| match screen_pattern(pattern) {
+                Ok(_) => true,
+                Err(e) => {
+                    errors.push(Err(format!(
+                        "show_screens: {} is not a valid pattern, ignoring it: {}",
+                        pattern, e
+                    )));
+                    false
+                }
+            });
+        errors
+    }
+
+    // Drops overrides that aren't colors, the theme keeps its own for those
+    fn sanitize_theme_colors(&mut self) -> Vec<Result<(), String>> {
+        let mut errors = vec![];
+        self.theme_colors.retain(|key, val| {
+            let valid = gui::is_html_color(val);
+            if !valid {
+                errors.push(Err(format!(
+                    "theme_colors.{} needs to be a color like #rrggbb, ignoring it",
+                    key
+                )));
+            }
+            valid
+        });
+        errors
+    }
+}
+
+// One show_screens entry: an output name, a glob with * and ?, or a /regex/. A ! in front
+// makes it exclude what it matches; gives whether it includes, and the regex it matches with.
+fn screen_pattern(pattern: &str) -> Result<(bool, Regex), regex::Error> {
+    let (include, pattern) = match pattern.strip_prefix('!') {
+        Some(pattern) => (false, pattern),
+        None => (true, pattern),
+    };
+    let regex = match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+        Some(regex) => Regex::new(regex)?,
+        None => {
+            let mut source = String::from("^");
+            for c in pattern.chars() {
+                match c {
+                    '*' => source.push_str(".*"),
+                    '?' => source.push('.'),
+                    c => source.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+                }
+            }
+            source.push('$');
+            Regex::new(&source)?
+        }
+    };
+    Ok((include, regex))
+}
+
+// Whether show_screens picks the output. The last entry that matches decides, and a list of
+// only exclusions starts from every screen, so [DP-*, "!DP-2"] and ["!eDP-1"] both work.
+pub fn screen_selected(patterns: &[String], name: &str) -> bool {
+    let mut selected = !patterns.is_empty() && patterns.iter().all(|p| p.starts_with('!'));
+    for pattern in patterns {
+        if let Ok((include, regex)) = screen_pattern(pattern) {
+            if regex.is_match(name) {
+                selected = include;
+            }
         }
     }
+    selected
+}
+
+impl GeneralConfig {
+    // Mouse buttons for a screen, from its output's section or the global setting
+    pub fn pointer_buttons(&self, output: &str) -> PointerButtons {
+        self.outputs
+            .get(output)
+            .and_then(|o| o.pointer_buttons)
+            .unwrap_or(self.pointer_buttons)
+    }
 
-    fn load_from_disk() -> GeneralConfig {
-        let config = load_general();
-        config.post_load();
-        config
+    // Pointer the watch follows; pointer 0 is the right controller
+    pub fn watch_hand_index(&self) -> usize {
+        if self.watch_hand == "right" {
+            0
+        } else {
+            1
+        }
     }
 
-    fn post_load(&self) {
-        GeneralConfig::sanitize_range("grab_threshold", self.grab_threshold, 0.0, 1.0);
-        GeneralConfig::sanitize_range("trigger_threshold", self.trigger_threshold, 0.0, 1.0);
-        GeneralConfig::sanitize_range("keyboard_scale", self.keyboard_scale, 0.0, 5.0);
-        GeneralConfig::sanitize_range("desktop_view_scale", self.desktop_view_scale, 0.0, 5.0);
-        GeneralConfig::sanitize_range("watch_scale", self.watch_scale, 0.0, 5.0);
+    // Applies show_screens and the output's section, if any, to a screen overlay
+    pub fn apply_output(&self, screen: &mut OverlayData) {
+        let output = self.outputs.get(&*screen.name);
+        screen.want_visible = output
+            .and_then(|o| o.visible)
+            .unwrap_or_else(|| screen_selected(&self.show_screens, &screen.name));
+        screen.stereo = output.and_then(|o| o.stereo).unwrap_or_default();
+
+        let Some(output) = output else {
+            return;
+        };
+        if let Some(scale) = output.scale {
+            screen.scale = scale;
+        }
+        if let Some(transform) = output.transform {
+            screen.saved_transform = Some(Affine3A::from_cols_array(&transform));
+        }
     }
 }
 
-pub fn load_keyboard() -> keyboard::Layout {
+// Logs a config problem and shows it in VR
+pub fn report_error(msg: &str) {
+    toast::notify(NotifyLevel::Error, msg);
+}
+
+pub fn load_keyboard() -> Result<keyboard::Layout, serde_yaml::Error> {
     let yaml_data = load_with_fallback!("keyboard.yaml", "res/keyboard.yaml");
-    serde_yaml::from_str(&yaml_data).expect("Failed to parse keyboard.yaml")
+    serde_yaml::from_str(&yaml_data)
 }
 
+// Never fails: parse errors fall back to the defaults and bad values are reset,
+// with each problem reported
 pub fn load_general() -> GeneralConfig {
-    let mut yaml_data = load_with_fallback!("config.yaml", "res/config.yaml");
+    match try_load_general() {
+        Ok(config) => config,
+        Err(e) => {
+            report_error(&format!(
+                "Failed to parse config.yaml, using defaults: {}",
+                e
+            ));
+            GeneralConfig::default()
+        }
+    }
+}
+
+fn try_load_general() -> Result<GeneralConfig, serde_yaml::Error> {
+    let yaml_data = load_with_fallback!("config.yaml", "res/config.yaml");
+    let mut config: Value = serde_yaml::from_str(&yaml_data)?;
 
-    // Add files from conf.d directory
+    // Merge fragments from the conf.d directory over the base config, in alphabetical order
     let path_conf_d = get_conf_d_path();
-    if let Ok(paths_unsorted) = std::fs::read_dir(path_conf_d) {
-        // Sort paths alphabetically
-        let mut paths: Vec<_> = paths_unsorted.map(|r| r.unwrap()).collect();
-        paths.sort_by_key(|dir| dir.path());
+    if let Ok(entries) = std::fs::read_dir(path_conf_d) {
+        let mut paths: Vec<_> = entries
+            .filter_map(|r| r.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && is_yaml(path))
+            .collect();
+        paths.sort();
+
         for path in paths {
-            if !path.file_type().unwrap().is_file() {
+            println!("Loading config file {}", path.to_string_lossy());
+
+            match load_fragment(&path) {
+                Ok(fragment) => merge_yaml(&mut config, fragment),
+                Err(e) => report_error(&format!("Skipping {}: {}", path.to_string_lossy(), e)),
+            }
+        }
+    }
+
+    // The active profile goes on top of everything else
+    if let Some(profile) = get_profile() {
+        let path = get_profile_path(&profile);
+        match load_fragment(&path) {
+            Ok(fragment) => merge_yaml(&mut config, fragment),
+            Err(e) => report_error(&format!("Failed to load profile {}: {}", profile, e)),
+        }
+    }
+
+    // Environment variables win over every file
+    merge_yaml(&mut config, env_overrides());
+
+    let mut config: GeneralConfig = serde_yaml::from_value(config)?;
+    for msg in config.post_load() {
+        report_error(&format!("config.yaml: {}", msg));
+    }
+    Ok(config)
+}
+
+// Values are parsed as YAML, so numbers and booleans work as expected.
+// Keys that take a list also accept a plain value, split on commas: WLXO_SHOW_SCREENS=DP-1,HDMI-A-1
+fn env_overrides() -> Value {
+    let defaults = serde_yaml::to_value(GeneralConfig::default()).unwrap_or_default();
+    let mut mapping = serde_yaml::Mapping::new();
+    for (name, value) in std::env::vars() {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        if name == ENV_LOG_LEVEL || key.is_empty() {
+            continue;
+        }
+
+        let key = key.to_lowercase();
+        let value = env_value(&defaults, &key, &value);
+        info!("Config override from {}", name);
+        mapping.insert(Value::String(key), value);
+    }
+    Value::Mapping(mapping)
+}
+
+// defaults: the default config as YAML, telling which keys take a list
+fn env_value(defaults: &Value, key: &str, value: &str) -> Value {
+    let parsed = serde_yaml::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+    if !defaults.get(key).is_some_and(Value::is_sequence) {
+        return parsed;
+    }
+    match parsed {
+        Value::Sequence(_) => parsed,
+        Value::String(s) => Value::Sequence(
+            s.split(',')
+                .map(|part| Value::String(part.trim().to_string()))
+                .collect(),
+        ),
+        Value::Null => Value::Sequence(vec![]),
+        scalar => Value::Sequence(vec![scalar]),
+    }
+}
+
+fn load_fragment(path: &Path) -> Result<Value, String> {
+    let data = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_yaml::from_str(&data).map_err(|e| e.to_string())
+}
+
+fn get_profile_path(name: &str) -> PathBuf {
+    let mut path = config_io::get_config_file_path("profiles");
+    path.push(format!("{}.yaml", name));
+    path
+}
+
+pub fn get_profile() -> Option<String> {
+    PROFILE.lock().ok().and_then(|p| p.clone())
+}
+
+pub fn set_profile(name: Option<String>) {
+    if let Ok(mut profile) = PROFILE.lock() {
+        *profile = name;
+    }
+}
+
+// Names of the yaml files in the profiles directory, sorted
+pub fn list_profiles() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(config_io::get_config_file_path("profiles")) else {
+        return Vec::new();
+    };
+    let mut names: Vec<_> = entries
+        .filter_map(|r| r.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_yaml(path))
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+        .collect();
+    names.sort();
+    names
+}
+
+// Switches to the given profile, or the plain config for None, and applies it right away
+pub fn switch_profile(app: &mut AppState, overlays: &mut [OverlayData], name: Option<String>) {
+    info!(
+        "Switching to profile {}",
+        name.as_deref().unwrap_or("default")
+    );
+    set_profile(name);
+    match try_load_general() {
+        Ok(config) => {
+            for (idx, _) in app.screens.iter() {
+                config.apply_output(&mut overlays[*idx]);
+            }
+            app.session.show_screens = config.show_screens.clone();
+            apply_general(app, overlays, config);
+        }
+        Err(e) => report_error(&format!("Failed to switch profile: {}", e)),
+    }
+}
+
+// Writes the screens' current placement to conf.d/layout.yaml, making it the new default.
+// Hidden screens keep their previously saved position.
+pub fn save_layout(app: &AppState, overlays: &[OverlayData]) {
+    let mut outputs = HashMap::new();
+    for (idx, name) in app.screens.iter() {
+        let screen = &overlays[*idx];
+        let previous = app.session.config.outputs.get(&**name);
+        let transform = match screen.relative_to {
+            RelativeTo::None if screen.visible => Some(screen.transform.to_cols_array()),
+            _ => previous.and_then(|o| o.transform),
+        };
+        outputs.insert(
+            name.to_string(),
+            OutputConfig {
+                visible: Some(screen.want_visible),
+                scale: Some(screen.scale),
+                transform,
+                color_space: previous.and_then(|o| o.color_space),
+                pointer_buttons: previous.and_then(|o| o.pointer_buttons),
+                stereo: previous.and_then(|o| o.stereo),
+            },
+        );
+    }
+
+    let mut layout = serde_yaml::Mapping::new();
+    match serde_yaml::to_value(outputs) {
+        Ok(outputs) => layout.insert("outputs".into(), outputs),
+        Err(e) => return report_error(&format!("Failed to save layout: {}", e)),
+    };
+
+    let mut path = get_conf_d_path();
+    path.push("layout.yaml");
+    // the layout is already what's shown, reloading it would only rebuild the UI
+    ignore_next_change("layout.yaml");
+    let result = serde_yaml::to_string(&layout)
+        .map_err(|e| e.to_string())
+        .and_then(|yaml| std::fs::write(&path, yaml).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => toast::notify(
+            NotifyLevel::Info,
+            &format!("Layout saved to {}", path.to_string_lossy()),
+        ),
+        Err(e) => {
+            take_self_written("layout.yaml");
+            report_error(&format!("Failed to save layout: {}", e));
+        }
+    }
+}
+
+fn apply_general(app: &mut AppState, overlays: &mut [OverlayData], config: GeneralConfig) {
+    app.fc.set_fonts(
+        &config.font_family,
+        &config.font_style,
+        &config.font_fallbacks,
+    );
+    app.session.theme = Theme::load(&config.theme).with_colors(&config.theme_colors);
+    gui::set_accessibility(&config.accessibility);
+    let placement_changed = config.watch_pos != app.session.config.watch_pos
+        || config.watch_rot != app.session.config.watch_rot;
+    let watch_hand = config.watch_hand_index();
+    if watch_hand != app.session.watch_hand {
+        app.session.watch_hand = watch_hand;
+        mirror_watch(&mut app.session);
+    }
+    if config.osc_send_address != app.session.config.osc_send_address {
+        app.osc = config.osc_send_address.as_deref().and_then(OscSender::new);
+    }
+    app.session.config = config;
+    if placement_changed {
+        app.session.load_watch_placement();
+    }
+    app.session.load_pointer_colors();
+    rebuild_ui(app, overlays);
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "yaml" || ext == "yml")
+}
+
+// Mappings are merged key by key, other values in the overlay replace the base one
+// Nulls, such as from an empty file, leave the base untouched
+fn merge_yaml(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_yaml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (_, Value::Null) => {}
+        (base, overlay) => *base = overlay,
+    }
+}
+
+// conf.d files about to be written with what the app already has applied
+static SELF_WRITTEN: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn ignore_next_change(name: &str) {
+    if let Ok(mut names) = SELF_WRITTEN.lock() {
+        names.push(name.to_string());
+    }
+}
+
+fn take_self_written(name: &str) -> bool {
+    let Ok(mut names) = SELF_WRITTEN.lock() else {
+        return false;
+    };
+    match names.iter().position(|n| n == name) {
+        Some(i) => {
+            names.swap_remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+// Whether the event is about a file the config is read from. Every event has to come through
+// here, so each self-written file is skipped exactly once.
+fn is_config_change(e: &Event<&OsStr>, root: &WatchDescriptor, conf_d: &WatchDescriptor) -> bool {
+    let Some(name) = e.name else {
+        return false;
+    };
+    let conf_d_file = e.wd == *conf_d
+        && is_yaml(Path::new(name))
+        && !name.to_str().is_some_and(take_self_written);
+    conf_d_file || (e.wd == *root && name == "config.yaml")
+}
+
+// Reloads config.yaml and conf.d whenever they change, applying the result on the next frame
+pub fn watch_general() {
+    let mut inotify = match Inotify::init() {
+        Ok(inotify) => inotify,
+        Err(e) => {
+            error!("Failed to watch config for changes: {}", e);
+            return;
+        }
+    };
+
+    let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::DELETE;
+    let root = inotify
+        .watches()
+        .add(config_io::CONFIG_ROOT_PATH.as_path(), mask);
+    let conf_d = inotify.watches().add(get_conf_d_path(), mask);
+    let (Ok(root), Ok(conf_d)) = (root, conf_d) else {
+        error!("Failed to watch config for changes");
+        return;
+    };
+
+    thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        loop {
+            let changed = match inotify.read_events_blocking(&mut buffer) {
+                Ok(events) => events.fold(false, |changed, e| {
+                    is_config_change(&e, &root, &conf_d) || changed
+                }),
+                Err(e) => {
+                    error!("Stopped watching config: {}", e);
+                    return;
+                }
+            };
+            if !changed {
                 continue;
             }
 
-            println!("Loading config file {}", path.path().to_string_lossy());
+            // Editors often save in several steps, let them settle. The reload covers what came
+            // in meanwhile, its events are only gone through for the self-written files.
+            thread::sleep(Duration::from_millis(250));
+            while let Ok(events) = inotify.read_events(&mut buffer) {
+                let mut drained = 0;
+                for e in events {
+                    is_config_change(&e, &root, &conf_d);
+                    drained += 1;
+                }
+                if drained == 0 {
+                    break;
+                }
+            }
 
-            if let Ok(data) = std::fs::read_to_string(path.path()) {
-                yaml_data.push('\n'); // Just in case, if end of the config file was not newline
-                yaml_data.push_str(data.as_str());
-            } else {
-                // Shouldn't happen anyways
-                error!("Failed to load {}", path.path().to_string_lossy());
+            match try_load_general() {
+                Ok(config) => {
+                    if let Ok(mut tasks) = TASKS.lock() {
+                        tasks.push_back(Box::new(move |_sk, app, overlays| {
+                            apply_general(app, overlays, config);
+                            toast::notify(NotifyLevel::Info, "Config reloaded");
+                        }));
+                    }
+                }
+                Err(e) => report_error(&format!("Not reloading config.yaml: {}", e)),
             }
         }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Value {
+        serde_yaml::from_str("show_screens: []\nui_scale: 1.0\nfont_family: Sans").unwrap()
     }
 
-    serde_yaml::from_str(&yaml_data).expect("Failed to parse config.yaml")
+    fn strings(items: &[&str]) -> Value {
+        Value::Sequence(items.iter().map(|s| Value::String(s.to_string())).collect())
+    }
+
+    fn patterns(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn screen_pattern_globs_and_regexes() {
+        let (include, regex) = screen_pattern("DP-*").unwrap();
+        assert!(include);
+        assert!(regex.is_match("DP-1"));
+        assert!(!regex.is_match("eDP-1"));
+
+        let (include, regex) = screen_pattern("!HDMI-A-?").unwrap();
+        assert!(!include);
+        assert!(regex.is_match("HDMI-A-1"));
+        assert!(!regex.is_match("HDMI-A-10"));
+
+        let (include, regex) = screen_pattern("/^(DP|HDMI)-/").unwrap();
+        assert!(include);
+        assert!(regex.is_match("HDMI-A-1"));
+        assert!(!regex.is_match("eDP-1"));
+
+        // glob characters other than * and ? are literal
+        let (_, regex) = screen_pattern("DP.1").unwrap();
+        assert!(!regex.is_match("DP-1"));
+
+        assert!(screen_pattern("/(/").is_err());
+    }
+
+    #[test]
+    fn screen_selected_last_match_wins() {
+        assert!(!screen_selected(&[], "DP-1"));
+        assert!(screen_selected(&patterns(&["DP-1"]), "DP-1"));
+        assert!(!screen_selected(&patterns(&["DP-1"]), "DP-2"));
+
+        let list = patterns(&["DP-*", "!DP-2"]);
+        assert!(screen_selected(&list, "DP-1"));
+        assert!(!screen_selected(&list, "DP-2"));
+        assert!(!screen_selected(&list, "HDMI-A-1"));
+
+        let list = patterns(&["!DP-*", "DP-2"]);
+        assert!(!screen_selected(&list, "DP-1"));
+        assert!(screen_selected(&list, "DP-2"));
+    }
+
+    #[test]
+    fn screen_selected_exclusions_only() {
+        let list = patterns(&["!eDP-1"]);
+        assert!(screen_selected(&list, "DP-1"));
+        assert!(!screen_selected(&list, "eDP-1"));
+    }
+
+    #[test]
+    fn screen_selected_skips_bad_patterns() {
+        let list = patterns(&["/(/", "DP-1"]);
+        assert!(screen_selected(&list, "DP-1"));
+        assert!(!screen_selected(&list, "DP-2"));
+    }
+
+    #[test]
+    fn env_scalars_are_parsed_as_yaml() {
+        let defaults = defaults();
+        assert_eq!(env_value(&defaults, "ui_scale", "1.5"), Value::from(1.5));
+        assert_eq!(
+            env_value(&defaults, "perf_stats", "true"),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            env_value(&defaults, "font_family", "Noto Sans, Bold"),
+            Value::String("Noto Sans, Bold".into())
+        );
+    }
+
+    #[test]
+    fn env_lists_accept_single_values() {
+        let defaults = defaults();
+        assert_eq!(
+            env_value(&defaults, "show_screens", "DP-1"),
+            strings(&["DP-1"])
+        );
+        assert_eq!(
+            env_value(&defaults, "show_screens", "DP-1, HDMI-A-1"),
+            strings(&["DP-1", "HDMI-A-1"])
+        );
+        assert_eq!(
+            env_value(&defaults, "show_screens", "[DP-1, DP-2]"),
+            strings(&["DP-1", "DP-2"])
+        );
+        assert_eq!(
+            env_value(&defaults, "show_screens", "1"),
+            Value::Sequence(vec![Value::from(1)])
+        );
+        assert_eq!(
+            env_value(&defaults, "show_screens", ""),
+            Value::Sequence(vec![])
+        );
+    }
+
+    #[test]
+    fn env_list_keys_come_from_the_defaults() {
+        let defaults = serde_yaml::to_value(GeneralConfig::default()).unwrap();
+        assert_eq!(
+            env_value(&defaults, "show_screens", "DP-1"),
+            strings(&["DP-1"])
+        );
+        assert_eq!(
+            env_value(&defaults, "terminal_command", "foot"),
+            strings(&["foot"])
+        );
+    }
 }
@@ -37,7 +37,7 @@ pub fn ensure_config_root() -> PathBuf {
     path
 }
 
-fn get_config_file_path(filename: &str) -> PathBuf {
+pub fn get_config_file_path(filename: &str) -> PathBuf {
     let mut config_root = CONFIG_ROOT_PATH.clone();
     config_root.push(filename);
     config_root
@@ -0,0 +1,197 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use stereokit::{SkDraw, Tex};
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+use crate::{
+    desktop::{
+        capture::{
+            pw_capture::{pipewire_select_screen, PipewireCapture},
+            wlr_dmabuf_capture::WlrDmabufCapture,
+        },
+        load_pw_token_config,
+        retry::show_retry_panel,
+        save_pw_token_config,
+        wl_client::WlClientState,
+    },
+    overlay::OverlayRenderer,
+    toast::{self, NotifyLevel},
+    AppState, TASKS,
+};
+
+// Retrying a screen that keeps failing only pops up a toast this often, the log gets every failure
+const FAILURE_TOAST_INTERVAL: Duration = Duration::from_secs(30);
+
+static LAST_FAILURE_TOAST: Lazy<Mutex<HashMap<Arc<str>, Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn should_toast_failure(name: &Arc<str>) -> bool {
+    let Ok(mut last) = LAST_FAILURE_TOAST.lock() else {
+        return true;
+    };
+    let now = Instant::now();
+    match last.get(name) {
+        Some(at) if now.duration_since(*at) < FAILURE_TOAST_INTERVAL => false,
+        _ => {
+            last.insert(name.clone(), now);
+            true
+        }
+    }
+}
+
+// How a screen is going to be captured, decided when its overlay is made
+pub enum CaptureSource {
+    // the wl_output's registry name
+    WlrDmabuf { output_id: u32 },
+    Pipewire { dmabuf: bool },
+}
+
+enum Started {
+    Ready(Box<dyn OverlayRenderer>),
+    // the portal dialog is still open on the desktop
+    Waiting,
+    Failed,
+}
+
+// Sets the capture up the first time the screen is shown, so screens that stay hidden don't
+// ask the portal or keep a stream or a connection of their own
+pub struct LazyCapture {
+    name: Arc<str>,
+    source: CaptureSource,
+    capture: Option<Box<dyn OverlayRenderer>>,
+    // the portal's answer, asked for on the runtime so VR keeps running meanwhile
+    selection: Option<oneshot::Receiver<Result<u32, String>>>,
+    // set up failed, the retry panel takes its place on the next frame
+    failed: bool,
+}
+
+impl LazyCapture {
+    pub fn new(name: Arc<str>, source: CaptureSource) -> Self {
+        LazyCapture {
+            name,
+            source,
+            capture: None,
+            selection: None,
+            failed: false,
+        }
+    }
+
+    fn create(&mut self, app: &mut AppState) -> Started {
+        match self.source {
+            CaptureSource::WlrDmabuf { output_id } => {
+                // the compositor may have gone away since the screen was made
+                let Some(wl) = WlClientState::try_new() else {
+                    return Started::Failed;
+                };
+                match WlrDmabufCapture::try_new(wl, self.name.clone(), output_id) {
+                    Some(capture) => Started::Ready(capture),
+                    None => Started::Failed,
+                }
+            }
+            CaptureSource::Pipewire { dmabuf } => {
+                let selection = self.selection.get_or_insert_with(|| {
+                    let (tx, rx) = oneshot::channel();
+                    app.rt.spawn(select_screen(self.name.clone(), tx));
+                    rx
+                });
+                match selection.try_recv() {
+                    Err(TryRecvError::Empty) => Started::Waiting,
+                    Err(TryRecvError::Closed) => Started::Failed,
+                    Ok(Ok(node_id)) => {
+                        info!("{}: Node id: {}", self.name, node_id);
+                        Started::Ready(Box::new(PipewireCapture::new(
+                            self.name.clone(),
+                            node_id,
+                            60,
+                            dmabuf,
+                        )))
+                    }
+                    Ok(Err(e)) => {
+                        error!("{}: Pipewire screen selection failed: {}", self.name, e);
+                        Started::Failed
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl OverlayRenderer for LazyCapture {
+    fn init(&mut self, sk: &SkDraw, app: &mut AppState) {
+        if let Some(capture) = self.capture.as_mut() {
+            capture.init(sk, app);
+        }
+    }
+    fn pause(&mut self, app: &mut AppState) {
+        if let Some(capture) = self.capture.as_mut() {
+            capture.pause(app);
+        }
+    }
+    fn resume(&mut self, app: &mut AppState) {
+        if let Some(capture) = self.capture.as_mut() {
+            capture.resume(app);
+        }
+    }
+    fn release(&mut self, app: &mut AppState) {
+        if let Some(capture) = self.capture.as_mut() {
+            capture.release(app);
+        }
+    }
+    fn render(&mut self, sk: &SkDraw, tex: &Tex, app: &mut AppState) {
+        if self.capture.is_none() && !self.failed {
+            if self.selection.is_none() {
+                info!("{}: Starting capture", self.name);
+            }
+            match self.create(app) {
+                Started::Ready(mut capture) => {
+                    self.selection = None;
+                    capture.init(sk, app);
+                    self.capture = Some(capture);
+                }
+                Started::Waiting => {}
+                Started::Failed => {
+                    self.selection = None;
+                    self.failed = true;
+                    if should_toast_failure(&self.name) {
+                        toast::notify(
+                            NotifyLevel::Warn,
+                            &format!("{}: could not capture this screen", self.name),
+                        );
+                    } else {
+                        warn!("{}: Could not start capture", self.name);
+                    }
+                    let name = self.name.clone();
+                    if let Ok(mut tasks) = TASKS.lock() {
+                        tasks.push_back(Box::new(move |_sk, app, o| {
+                            show_retry_panel(app, o, &name);
+                        }));
+                    }
+                }
+            }
+        }
+        if let Some(capture) = self.capture.as_mut() {
+            capture.render(sk, tex, app);
+        }
+    }
+}
+
+// Asks the portal for the screen, keeping the restore token it hands out
+async fn select_screen(name: Arc<str>, tx: oneshot::Sender<Result<u32, String>>) {
+    let mut pw_tokens = load_pw_token_config().unwrap_or_default();
+    let pw_tokens_copy = pw_tokens.clone();
+    let node_id = pipewire_select_screen(&name, &mut pw_tokens)
+        .await
+        .map_err(|e| e.to_string());
+    if pw_tokens_copy != pw_tokens {
+        if let Err(err) = save_pw_token_config(&pw_tokens) {
+            error!("Failed to save Pipewire token config: {}", err);
+        }
+    }
+    let _ = tx.send(node_id);
+}
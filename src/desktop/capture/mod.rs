@@ -1,2 +1,3 @@
+pub mod lazy_capture;
 pub mod pw_capture;
 pub mod wlr_dmabuf_capture;
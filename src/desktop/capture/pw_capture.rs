@@ -1,4 +1,5 @@
-use std::collections::BTreeMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::Cursor;
 use std::mem::MaybeUninit;
 use std::ptr::null_mut;
@@ -6,13 +7,15 @@ use std::sync::Arc;
 use std::sync::{Mutex, RwLock};
 use std::thread::JoinHandle;
 
-use crate::desktop::frame::{
-    texture_load_dmabuf, texture_load_memfd, texture_load_memptr, MemPtrFrame,
-};
+use crate::desktop::frame::{texture_load_dmabuf, texture_load_pixels, PixelFrame};
+use crate::desktop::reconnect;
+use crate::desktop::triple_buffer::{triple_buffer, TripleReader, TripleWriter};
+use crate::latency;
 use crate::overlay::OverlayRenderer;
+use crate::power;
 use crate::AppState;
 use crate::{
-    desktop::frame::{DmabufFrame, DrmFormat, FrameFormat, FramePlane, MemFdFrame},
+    desktop::frame::{DmabufFrame, DrmFormat, FrameFormat, FramePlane},
     gl::egl::{
         eglQueryDmaBufFormatsEXT, eglQueryDmaBufModifiersEXT, DRM_FORMAT_ABGR8888,
         DRM_FORMAT_ARGB8888, DRM_FORMAT_XBGR8888, DRM_FORMAT_XRGB8888, EGL_TRUE,
@@ -36,10 +39,13 @@ use pipewire::spa::data::DataType;
 use pipewire::spa::pod::serialize::PodSerializer;
 use pipewire::spa::pod::{ChoiceValue, Object, Property, PropertyFlags, Value};
 use pipewire::spa::utils::{Choice, ChoiceEnum, ChoiceFlags, Fraction, Id, Rectangle};
-use pipewire::stream::{Stream, StreamFlags};
+use pipewire::stream::{Stream, StreamFlags, StreamState};
 use pipewire::{Context, Error, MainLoop};
 use stereokit::StereoKitMultiThread;
 
+// DMA-Bufs the driver wouldn't import in a row before the stream is asked for copies instead
+const MAX_DMABUF_FAILURES: u32 = 10;
+
 static FORMATS: Lazy<Arc<Vec<DrmFormat>>> = Lazy::new(|| Arc::new(load_dmabuf_formats()));
 
 pub async fn pipewire_select_screen(
@@ -93,12 +99,14 @@ pub async fn pipewire_select_screen(
 
 pub enum PipewireFrame {
     Dmabuf(DmabufFrame),
-    MemFd(MemFdFrame),
-    MemPtr(MemPtrFrame),
+    // shared memory, already copied out on the capture thread
+    Pixels(PixelFrame),
 }
 
 struct StreamData {
     format: Option<FrameFormat>,
+    // whether the negotiated format came with a modifier, i.e. is DMA-Buf
+    has_modifier: bool,
     stream: Option<Stream<i32>>,
 }
 
@@ -106,46 +114,121 @@ impl StreamData {
     fn new() -> Self {
         StreamData {
             format: None,
+            has_modifier: false,
             stream: None,
         }
     }
 }
 
+// Sent from the render thread to the capture thread's loop
+enum StreamCommand {
+    Quit,
+    SetFps(u32),
+}
+
+// Names of the screens whose screencast streams are currently running
+static ACTIVE_STREAMS: Mutex<BTreeSet<Arc<str>>> = Mutex::new(BTreeSet::new());
+
+pub fn active_streams() -> Vec<Arc<str>> {
+    ACTIVE_STREAMS
+        .lock()
+        .map(|streams| streams.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn set_stream_active(name: &Arc<str>, active: bool) {
+    if let Ok(mut streams) = ACTIVE_STREAMS.lock() {
+        if active {
+            streams.insert(name.clone());
+        } else {
+            streams.remove(name);
+        }
+    }
+}
+
 pub struct PipewireCapture {
     name: Arc<str>,
     node_id: u32,
     fps: u32,
+    // what the running stream was asked for, lower than fps while saving power
+    stream_fps: u32,
     dmabuf: bool,
-    frame: Arc<Mutex<Option<PipewireFrame>>>,
+    dmabuf_failures: u32,
+    frame: Option<TripleReader<PipewireFrame>>,
     handle: Option<JoinHandle<Result<(), Error>>>,
+    commands: Option<pipewire::channel::Sender<StreamCommand>>,
+}
+
+impl Drop for PipewireCapture {
+    fn drop(&mut self) {
+        self.stop();
+    }
 }
 
 impl OverlayRenderer for PipewireCapture {
     fn init(&mut self, _sk: &stereokit::SkDraw, _app: &mut AppState) {
         self.start();
     }
+    fn release(&mut self, _app: &mut AppState) {
+        self.stop();
+    }
     fn pause(&mut self, _app: &mut crate::AppState) {}
     fn resume(&mut self, _app: &mut crate::AppState) {}
-    fn render(&mut self, sk: &stereokit::SkDraw, tex: &stereokit::Tex, _app: &mut crate::AppState) {
-        if let Ok(mut pw_frame) = self.frame.lock() {
-            if let Some(pw_frame) = pw_frame.take() {
+    fn render(&mut self, sk: &stereokit::SkDraw, tex: &stereokit::Tex, app: &mut crate::AppState) {
+        if self.handle.as_ref().is_some_and(|h| h.is_finished()) {
+            if let Some(Err(e)) = self.handle.take().and_then(|h| h.join().ok()) {
+                error!("{}: pipewire error: {}", &self.name, e);
+            }
+            reconnect::connection_lost();
+            return;
+        }
+        if let Some(reader) = self.frame.as_mut() {
+            if let Some(pw_frame) = reader.read() {
                 match pw_frame {
                     PipewireFrame::Dmabuf(frame) => {
+                        // taken out to be dropped after the import as before, pixels are reused
+                        let frame = std::mem::take(frame);
                         if frame.is_valid() {
                             let handle =
                                 unsafe { sk.tex_get_surface(tex.as_ref()) as usize as u32 };
-                            texture_load_dmabuf(handle, &frame);
+                            if texture_load_dmabuf(handle, &frame) {
+                                self.dmabuf_failures = 0;
+                            } else {
+                                self.dmabuf_failures += 1;
+                            }
                         }
                     }
-                    PipewireFrame::MemFd(frame) => {
-                        let handle = unsafe { sk.tex_get_surface(tex.as_ref()) as usize as u32 };
-                        texture_load_memfd(handle, &frame);
-                    }
-                    PipewireFrame::MemPtr(frame) => {
+                    PipewireFrame::Pixels(frame) => {
                         let handle = unsafe { sk.tex_get_surface(tex.as_ref()) as usize as u32 };
-                        texture_load_memptr(handle, &frame);
+                        texture_load_pixels(handle, frame);
                     }
                 }
+                latency::frame_shown(&self.name);
+            }
+        }
+        if self.dmabuf_failures >= MAX_DMABUF_FAILURES {
+            warn!(
+                "{}: DMA-Buf import keeps failing, switching to shared memory",
+                &self.name
+            );
+            self.dmabuf_failures = 0;
+            self.dmabuf = false;
+            self.stop();
+            self.start();
+        }
+
+        // the compositor only renders and copies as many frames as the stream allows, so saving
+        // power asks it for fewer instead of throwing the extra ones away here
+        let fps = if power::is_saving() {
+            (app.session.config.power_save_fps.ceil() as u32).clamp(1, self.fps)
+        } else {
+            self.fps
+        };
+        if fps != self.stream_fps {
+            info!("{}: asking for {} fps", &self.name, fps);
+            self.stream_fps = fps;
+            if let Some(commands) = self.commands.as_ref() {
+                let _ = commands.send(StreamCommand::SetFps(fps));
             }
         }
     }
@@ -157,21 +240,38 @@ impl PipewireCapture {
             name,
             node_id,
             fps,
+            stream_fps: fps,
             dmabuf,
-            frame: Arc::new(Mutex::new(None)),
+            dmabuf_failures: 0,
+            frame: None,
             handle: None,
+            commands: None,
         }
     }
 
     fn start(&mut self) {
+        let (writer, reader) = triple_buffer();
+        let (commands, command_receiver) = pipewire::channel::channel();
+        self.frame = Some(reader);
+        self.commands = Some(commands);
         self.handle = Some(main_loop(
             self.name.clone(),
             self.node_id,
-            self.fps,
+            self.stream_fps,
             self.dmabuf,
-            self.frame.clone(),
+            writer,
+            command_receiver,
         ));
     }
+
+    fn stop(&mut self) {
+        if let Some(commands) = self.commands.take() {
+            let _ = commands.send(StreamCommand::Quit);
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
 }
 
 fn main_loop(
@@ -179,15 +279,62 @@ fn main_loop(
     node_id: u32,
     fps: u32,
     dmabuf: bool,
-    frame: Arc<Mutex<Option<PipewireFrame>>>,
+    writer: TripleWriter<PipewireFrame>,
+    commands: pipewire::channel::Receiver<StreamCommand>,
 ) -> JoinHandle<Result<(), Error>> {
     std::thread::spawn(move || {
         let main_loop = MainLoop::new()?;
         let context = Context::new(&main_loop)?;
-        let _core = context.connect(None)?;
+        let core = context.connect(None)?;
+
+        // the daemon went away (error on the core object, id 0), let the loop exit
+        // so the capture gets recreated
+        let _core_listener = core
+            .add_listener_local()
+            .error({
+                let main_loop = main_loop.clone();
+                move |id, _seq, res, message| {
+                    if id == 0 && res == -libc::EPIPE {
+                        warn!("pipewire: {}", message);
+                        main_loop.quit();
+                    }
+                }
+            })
+            .register();
 
         let data = Arc::new(RwLock::new(StreamData::new()));
 
+        let _commands = commands.attach(&main_loop, {
+            let name = name.clone();
+            let data = data.clone();
+            let main_loop = main_loop.clone();
+            move |command| match command {
+                StreamCommand::Quit => main_loop.quit(),
+                // renegotiates the format already in use with the new cap, the stream keeps
+                // running rather than being torn down and set up again
+                StreamCommand::SetFps(fps) => {
+                    let Ok(data) = data.read() else {
+                        return;
+                    };
+                    let (Some(format), Some(stream)) = (data.format, &data.stream) else {
+                        return;
+                    };
+                    let fmt = DrmFormat {
+                        code: format.format,
+                        modifiers: if data.has_modifier {
+                            vec![format.modifier]
+                        } else {
+                            vec![]
+                        },
+                    };
+                    let params = format_get_params(Some(&fmt), fps);
+                    if let Err(e) = stream.update_params(&mut [params.as_ptr() as _]) {
+                        error!("{}: failed to update framerate: {}", &name, e);
+                    }
+                }
+            }
+        });
+
         let stream = Stream::<i32>::with_user_data(
             &main_loop,
             &name,
@@ -224,6 +371,7 @@ fn main_loop(
 
                 if let Ok(ref mut data) = data.write() {
                     data.format = Some(format);
+                    data.has_modifier = info.flags & libspa_sys::SPA_VIDEO_FLAG_MODIFIER != 0;
 
                     if let Some(stream) = &data.stream {
                         let params = format_dmabuf_params();
@@ -236,13 +384,20 @@ fn main_loop(
         })
         .state_changed({
             let name = name.clone();
+            let main_loop = main_loop.clone();
             move |old, new| {
                 info!("{}: stream state changed: {:?} -> {:?}", &name, old, new);
+                set_stream_active(&name, matches!(new, StreamState::Streaming));
+                if let StreamState::Error(_) = new {
+                    main_loop.quit();
+                }
             }
         })
         .process({
             let name = name.clone();
             let data = data.clone();
+            // the stream only calls back on this thread
+            let writer = RefCell::new(writer);
             move |stream, _| {
                 let mut maybe_buffer = None;
                 // discard all but the freshest ingredients
@@ -267,38 +422,53 @@ fn main_loop(
                             })
                             .collect();
 
-                        if let Ok(mut frame) = frame.lock() {
-                            match datas[0].type_() {
-                                DataType::DmaBuf => {
-                                    let mut dmabuf = DmabufFrame {
-                                        fmt: format,
-                                        num_planes: planes.len(),
-                                        ..Default::default()
-                                    };
-                                    dmabuf.planes[..planes.len()]
-                                        .copy_from_slice(&planes[..planes.len()]);
-
-                                    *frame = Some(PipewireFrame::Dmabuf(dmabuf));
-                                }
-                                DataType::MemFd => {
-                                    *frame = Some(PipewireFrame::MemFd(MemFdFrame {
-                                        fmt: format,
-                                        plane: FramePlane {
-                                            fd: datas[0].as_raw().fd as _,
-                                            offset: datas[0].chunk().offset(),
-                                            stride: datas[0].chunk().stride(),
-                                        },
-                                    }));
+                        let new_frame = match datas[0].type_() {
+                            DataType::DmaBuf => {
+                                let mut dmabuf = DmabufFrame {
+                                    fmt: format,
+                                    num_planes: planes.len(),
+                                    ..Default::default()
+                                };
+                                dmabuf.planes[..planes.len()]
+                                    .copy_from_slice(&planes[..planes.len()]);
+
+                                PipewireFrame::Dmabuf(dmabuf)
+                            }
+                            // mapped by the stream, but only until the buffer goes back, so the
+                            // copy is made here rather than on the render thread
+                            DataType::MemFd | DataType::MemPtr => {
+                                let raw = datas[0].as_raw();
+                                if raw.data.is_null() {
+                                    info!("{}: buffer not mapped", &name);
+                                    return;
                                 }
-                                DataType::MemPtr => {
-                                    *frame = Some(PipewireFrame::MemPtr(MemPtrFrame {
-                                        fmt: format,
-                                        ptr: datas[0].as_raw().data as _,
-                                    }));
+                                let mapped = unsafe {
+                                    std::slice::from_raw_parts(
+                                        raw.data as *const u8,
+                                        raw.maxsize as usize,
+                                    )
+                                };
+                                let chunk = datas[0].chunk();
+                                // a frame the render thread is done with is copied over, rather
+                                // than allocating a whole frame each time
+                                let mut frame = match writer.borrow_mut().slot().take() {
+                                    Some(PipewireFrame::Pixels(frame)) => frame,
+                                    _ => PixelFrame::default(),
+                                };
+                                if !frame.copy_from(
+                                    format,
+                                    mapped,
+                                    chunk.offset() as usize,
+                                    chunk.stride().max(0) as usize,
+                                ) {
+                                    info!("{}: buffer too small for {:?}", &name, format);
+                                    return;
                                 }
-                                _ => panic!("Unknown data type"),
+                                PipewireFrame::Pixels(frame)
                             }
-                        }
+                            _ => panic!("Unknown data type"),
+                        };
+                        writer.borrow_mut().write(new_frame);
                     } else {
                         info!("{}: no format", &name);
                     }
@@ -334,6 +504,7 @@ fn main_loop(
         }
 
         main_loop.run();
+        set_stream_active(&name, false);
         warn!("{}: pipewire loop exited", &name);
         Ok::<(), Error>(())
     })
@@ -461,6 +632,19 @@ fn format_get_params(fmt: Option<&DrmFormat>, fps: u32) -> SpaPod {
                 },
             ))),
         },
+        // the cap the compositor paces its frames to
+        Property {
+            key: libspa_sys::SPA_FORMAT_VIDEO_maxFramerate,
+            flags: PropertyFlags::empty(),
+            value: Value::Choice(ChoiceValue::Fraction(Choice(
+                ChoiceFlags::from_bits_truncate(0),
+                ChoiceEnum::Range {
+                    default: Fraction { num: fps, denom: 1 },
+                    min: Fraction { num: 1, denom: 1 },
+                    max: Fraction { num: fps, denom: 1 },
+                },
+            ))),
+        },
     ];
     if let Some(fmt) = fmt {
         properties.push(Property {
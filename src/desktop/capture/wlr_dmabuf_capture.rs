@@ -3,8 +3,9 @@ use std::sync::{Arc, Mutex};
 use crate::{
     desktop::{
         frame::{texture_load_dmabuf, DmabufFrame, FRAME_FAILED, FRAME_READY},
-        wl_client::{OutputState, WlClientState},
+        wl_client::WlClientState,
     },
+    latency,
     overlay::OverlayRenderer,
     AppState,
 };
@@ -13,24 +14,23 @@ use stereokit::StereoKitMultiThread;
 use tokio::task::JoinHandle;
 
 pub struct WlrDmabufCapture {
-    output_idx: usize,
+    name: Arc<str>,
+    // the wl_output's registry name, which stays valid when other outputs come and go
+    output_id: u32,
     wl: Arc<Mutex<WlClientState>>,
     task_handle: Option<JoinHandle<Arc<Mutex<DmabufFrame>>>>,
 }
 
 impl WlrDmabufCapture {
-    pub fn try_new(wl: WlClientState, output: &OutputState) -> Option<Box<dyn OverlayRenderer>> {
-        let mut output_idx = None;
-        for i in 0..wl.outputs.len() {
-            if wl.outputs[i].id == output.id {
-                output_idx = Some(i);
-                break;
-            }
-        }
-
-        if let Some(output_idx) = output_idx {
+    pub fn try_new(
+        wl: WlClientState,
+        name: Arc<str>,
+        output_id: u32,
+    ) -> Option<Box<dyn OverlayRenderer>> {
+        if wl.outputs.iter().any(|o| o.id == output_id) {
             Some(Box::new(WlrDmabufCapture {
-                output_idx,
+                name,
+                output_id,
                 wl: Arc::new(Mutex::new(wl)),
                 task_handle: None,
             }))
@@ -65,6 +65,7 @@ impl OverlayRenderer for WlrDmabufCapture {
                                     let handle =
                                         unsafe { sk.tex_get_surface(tex.as_ref()) as usize as u32 };
                                     texture_load_dmabuf(handle, &frame);
+                                    latency::frame_shown(&self.name);
                                 }
                             }
                             _ => {}
@@ -77,11 +78,11 @@ impl OverlayRenderer for WlrDmabufCapture {
         }
 
         let wl = self.wl.clone();
-        let output_idx = self.output_idx;
+        let output_id = self.output_id;
         self.task_handle = Some(app.rt.spawn(async move {
             let frame = Arc::new(Mutex::new(DmabufFrame::default()));
             if let Ok(mut wl) = wl.lock() {
-                wl.request_dmabuf_frame(output_idx, frame.clone());
+                wl.request_dmabuf_frame(output_id, frame.clone());
             }
             frame
         }));
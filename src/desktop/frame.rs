@@ -1,18 +1,26 @@
-use std::{ffi::CStr, mem::MaybeUninit, os::fd::RawFd, ptr};
+use std::{ffi::CStr, mem::MaybeUninit, os::fd::RawFd, ptr, sync::Mutex};
 
 use gles31::{
-    glBindBuffer, glBindTexture, glGetError, glGetString, glPixelStorei, glTexImage2D, GL_NO_ERROR,
-    GL_PIXEL_UNPACK_BUFFER, GL_RGBA, GL_RGBA8, GL_TEXTURE_2D, GL_UNPACK_ALIGNMENT,
-    GL_UNSIGNED_BYTE, GL_VENDOR,
+    glBindBuffer, glBindTexture, glGetString, glPixelStorei, glTexImage2D, GL_PIXEL_UNPACK_BUFFER,
+    GL_RGBA, GL_RGBA8, GL_TEXTURE_2D, GL_UNPACK_ALIGNMENT, GL_UNPACK_ROW_LENGTH, GL_UNSIGNED_BYTE,
+    GL_VENDOR,
 };
-use libc::{close, mmap, munmap, MAP_SHARED, PROT_READ};
+use libc::{close, fstat, mmap, munmap, MAP_FAILED, MAP_SHARED, PROT_READ};
 use log::debug;
 use once_cell::sync::Lazy;
 
-use crate::gl::egl::{
-    eglCreateImage, eglDestroyImage, eglGetError, glEGLImageTargetTexture2DOES,
-    DRM_FORMAT_ABGR8888, DRM_FORMAT_ARGB8888, DRM_FORMAT_XBGR8888, DRM_FORMAT_XRGB8888,
-    EGL_LINUX_DMABUF_EXT, EGL_SUCCESS,
+use crate::{
+    gl::egl::{
+        eglCreateImage, eglDestroyImage, eglGetError, glEGLImageTargetTexture2DOES,
+        DRM_FORMAT_ABGR16161616F, DRM_FORMAT_ABGR8888, DRM_FORMAT_ARGB8888,
+        DRM_FORMAT_XBGR16161616F, DRM_FORMAT_XBGR8888, DRM_FORMAT_XRGB8888, EGL_CONTEXT_LOST,
+        EGL_LINUX_DMABUF_EXT, EGL_SUCCESS,
+    },
+    gl::{
+        check::{check_gl, check_gl_result},
+        context, pool,
+    },
+    perf::{self, Section},
 };
 
 #[rustfmt::skip]
@@ -155,6 +163,43 @@ pub struct MemPtrFrame {
     pub ptr: usize,
 }
 
+// Pixels copied out of shared memory, rows packed without padding
+#[derive(Default)]
+pub struct PixelFrame {
+    pub fmt: FrameFormat,
+    pub data: Vec<u8>,
+}
+
+impl PixelFrame {
+    // Replaces the pixels, reusing the allocation. False if the rows would run past the end
+    // of the mapping.
+    pub fn copy_from(
+        &mut self,
+        fmt: FrameFormat,
+        src: &[u8],
+        offset: usize,
+        stride: usize,
+    ) -> bool {
+        let row = fmt.w as usize * 4;
+        let stride = if stride == 0 { row } else { stride };
+        let rows = fmt.h as usize;
+        if stride < row {
+            return false;
+        }
+        self.fmt = fmt;
+        self.data.clear();
+        self.data.reserve(row * rows);
+        for y in 0..rows {
+            let start = offset + y * stride;
+            let Some(src_row) = src.get(start..start + row) else {
+                return false;
+            };
+            self.data.extend_from_slice(src_row);
+        }
+        true
+    }
+}
+
 const GL_RGB: u32 = 0x1907;
 const GL_BGR: u32 = 0x80E0;
 const GL_BGRA: u32 = 0x80E1;
@@ -183,11 +228,12 @@ fn fmt_to_gl(fmt: &FrameFormat) -> (u32, u32) {
 }
 
 pub fn texture_load_memptr(texture: u32, f: &MemPtrFrame) {
+    let _timer = perf::time(Section::Capture);
     unsafe {
         let (fmt, pf) = fmt_to_gl(&f.fmt);
 
         glBindTexture(GL_TEXTURE_2D, texture);
-        debug_assert_eq!(glGetError(), GL_NO_ERROR);
+        check_gl();
 
         glTexImage2D(
             GL_TEXTURE_2D,
@@ -200,34 +246,86 @@ pub fn texture_load_memptr(texture: u32, f: &MemPtrFrame) {
             GL_UNSIGNED_BYTE,
             f.ptr as _,
         );
-        debug_assert_eq!(glGetError(), GL_NO_ERROR);
+        check_gl();
+    }
+    mark_new_frame(texture);
+}
+
+pub fn texture_load_pixels(texture: u32, f: &PixelFrame) {
+    let _timer = perf::time(Section::Capture);
+    unsafe {
+        let (fmt, pf) = fmt_to_gl(&f.fmt);
+
+        glBindBuffer(GL_PIXEL_UNPACK_BUFFER, 0);
+        check_gl();
+
+        glBindTexture(GL_TEXTURE_2D, texture);
+        check_gl();
+
+        glPixelStorei(GL_UNPACK_ALIGNMENT, 4);
+        check_gl();
+
+        glTexImage2D(
+            GL_TEXTURE_2D,
+            0,
+            fmt as _,
+            f.fmt.w,
+            f.fmt.h,
+            0,
+            pf,
+            GL_UNSIGNED_BYTE,
+            f.data.as_ptr() as _,
+        );
+        check_gl();
+
+        glBindTexture(GL_TEXTURE_2D, 0);
+        check_gl();
+    }
+    mark_new_frame(texture);
+}
+
+// Whether the file holds a whole frame of 4 byte pixels. Reading past its end would SIGBUS.
+pub fn memfd_fits(fd: RawFd, fmt: &FrameFormat, stride: i32) -> bool {
+    if fd <= 0 || stride < 0 || stride % 4 != 0 || (stride as u64) < fmt.w as u64 * 4 {
+        return false;
+    }
+    let mut stat = MaybeUninit::<libc::stat>::zeroed();
+    if unsafe { fstat(fd, stat.as_mut_ptr()) } != 0 {
+        return false;
     }
+    let file_size = unsafe { stat.assume_init() }.st_size as u64;
+    file_size >= fmt.h as u64 * stride as u64
 }
 
 pub fn texture_load_memfd(texture: u32, f: &MemFdFrame) {
+    let _timer = perf::time(Section::Capture);
+    // checked again here, the client may have shrunk it since
+    if !memfd_fits(f.plane.fd, &f.fmt, f.plane.stride) {
+        debug!("memfd is smaller than its frame");
+        return;
+    }
     unsafe {
         let fd = f.plane.fd;
 
-        if fd <= 0 {
-            return;
-        }
-
         let size = f.fmt.h as usize * f.plane.stride as usize;
 
         let ptr = mmap(ptr::null_mut(), size, PROT_READ, MAP_SHARED, fd, 0);
 
-        if ptr.is_null() {
+        if ptr == MAP_FAILED {
             return;
         }
 
         glBindBuffer(GL_PIXEL_UNPACK_BUFFER, 0);
-        debug_assert_eq!(glGetError(), GL_NO_ERROR);
+        check_gl();
 
         glBindTexture(GL_TEXTURE_2D, texture);
-        debug_assert_eq!(glGetError(), GL_NO_ERROR);
+        check_gl();
 
         glPixelStorei(GL_UNPACK_ALIGNMENT, 4);
-        debug_assert_eq!(glGetError(), GL_NO_ERROR);
+        check_gl();
+
+        glPixelStorei(GL_UNPACK_ROW_LENGTH, f.plane.stride / 4);
+        check_gl();
 
         let (fmt, pf) = fmt_to_gl(&f.fmt);
         //glTexSubImage2D(GL_TEXTURE_2D, 0, 0, 0, f.fmt.w, f.fmt.h, GL_BGRA, GL_UNSIGNED_BYTE, ptr);
@@ -243,37 +341,121 @@ pub fn texture_load_memfd(texture: u32, f: &MemFdFrame) {
             GL_UNSIGNED_BYTE,
             ptr,
         );
-        debug_assert_eq!(glGetError(), GL_NO_ERROR);
+        check_gl();
+
+        glPixelStorei(GL_UNPACK_ROW_LENGTH, 0);
+        check_gl();
 
         glBindTexture(GL_TEXTURE_2D, 0);
-        debug_assert_eq!(glGetError(), GL_NO_ERROR);
+        check_gl();
 
         munmap(ptr, size);
     }
+    mark_new_frame(texture);
 }
 
-pub fn texture_load_dmabuf(texture: u32, frame: &DmabufFrame) {
+// Textures that got a new frame since the overlay last looked, so mipmaps are only rebuilt then
+static NEW_FRAMES: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+fn mark_new_frame(texture: u32) {
+    if let Ok(mut textures) = NEW_FRAMES.lock() {
+        if !textures.contains(&texture) {
+            textures.push(texture);
+        }
+    }
+}
+
+pub fn take_new_frame(texture: u32) -> bool {
+    let Ok(mut textures) = NEW_FRAMES.lock() else {
+        return true;
+    };
+    match textures.iter().position(|t| *t == texture) {
+        Some(i) => {
+            textures.swap_remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+// Half-float buffers hold linear light, as compositors use them for HDR and wide gamut;
+// the 8-bit ones hold the desktop's sRGB values
+pub fn is_linear_format(format: u32) -> bool {
+    matches!(format, DRM_FORMAT_ABGR16161616F | DRM_FORMAT_XBGR16161616F)
+}
+
+// Textures whose last imported buffer holds linear values, for ColorSpace::Auto
+static LINEAR_TEXTURES: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+pub fn holds_linear(texture: u32) -> bool {
+    LINEAR_TEXTURES
+        .lock()
+        .is_ok_and(|linear| linear.contains(&texture))
+}
+
+fn set_holds_linear(texture: u32, linear: bool) {
+    let Ok(mut textures) = LINEAR_TEXTURES.lock() else {
+        return;
+    };
+    let found = textures.iter().position(|t| *t == texture);
+    match (found, linear) {
+        (None, true) => textures.push(texture),
+        (Some(i), false) => {
+            textures.swap_remove(i);
+        }
+        _ => {}
+    }
+}
+
+// The GL names kept here meant textures of the old context, new ones may reuse them
+pub fn context_lost() {
+    if let Ok(mut textures) = NEW_FRAMES.lock() {
+        textures.clear();
+    }
+    if let Ok(mut textures) = LINEAR_TEXTURES.lock() {
+        textures.clear();
+    }
+}
+
+// Returns whether the driver took the buffer, captures fall back to copies if it keeps refusing
+pub fn texture_load_dmabuf(texture: u32, frame: &DmabufFrame) -> bool {
+    let _timer = perf::time(Section::Capture);
     let attribs = frame.get_attribs();
 
     let egl_image = eglCreateImage(EGL_LINUX_DMABUF_EXT, attribs.as_ptr());
-    if eglGetError() != EGL_SUCCESS {
-        debug!("eglCreateImage failed");
-        return;
+    match eglGetError() {
+        EGL_SUCCESS => {}
+        EGL_CONTEXT_LOST => {
+            context::mark_lost("eglCreateImage returned EGL_CONTEXT_LOST");
+            return false;
+        }
+        _ => {
+            debug!("eglCreateImage failed");
+            return false;
+        }
     }
 
     unsafe {
         glBindTexture(GL_TEXTURE_2D, texture);
-        debug_assert_eq!(glGetError(), GL_NO_ERROR);
+        check_gl();
     }
 
     glEGLImageTargetTexture2DOES(GL_TEXTURE_2D as _, egl_image);
-    debug_assert_eq!(unsafe { glGetError() }, GL_NO_ERROR);
+    let imported = check_gl_result();
+    pool::mark_imported(texture);
+    if imported {
+        mark_new_frame(texture);
+    }
+    set_holds_linear(texture, imported && is_linear_format(frame.fmt.format));
 
     unsafe {
         glBindTexture(GL_TEXTURE_2D, 0);
-        debug_assert_eq!(glGetError(), GL_NO_ERROR);
+        check_gl();
     }
 
     eglDestroyImage(egl_image);
-    debug_assert_eq!(eglGetError(), EGL_SUCCESS);
+    if eglGetError() != EGL_SUCCESS {
+        debug!("eglDestroyImage failed");
+    }
+    imported
 }
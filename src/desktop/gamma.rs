@@ -0,0 +1,199 @@
+use std::{
+    fs::File,
+    io::Write,
+    os::fd::{AsFd, FromRawFd},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use log::{info, warn};
+use smithay_client_toolkit::reexports::protocols_wlr::gamma_control::v1::client::{
+    zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1,
+    zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+};
+use wayland_client::{
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_output::WlOutput, wl_registry::WlRegistry},
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+};
+
+// Gamma tables only last as long as their control, so these stay around while dimmed
+struct Dimmer {
+    queue: EventQueue<GammaState>,
+    state: GammaState,
+    brightness: f32,
+}
+
+#[derive(Default)]
+struct GammaState {
+    // control and its ramp size, once the compositor has sent it
+    controls: Vec<(ZwlrGammaControlV1, u32)>,
+}
+
+static DIMMER: Mutex<Option<Dimmer>> = Mutex::new(None);
+
+// Set once the compositor turned us down, so a dragged slider reports it only once
+static UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+
+pub fn brightness() -> f32 {
+    DIMMER
+        .lock()
+        .ok()
+        .and_then(|d| d.as_ref().map(|d| d.brightness))
+        .unwrap_or(1.)
+}
+
+// Scales the real monitors' gamma ramps. 1.0 hands them back to the compositor.
+pub fn set_brightness(brightness: f32) -> Result<(), String> {
+    let Ok(mut dimmer) = DIMMER.lock() else {
+        return Err("Gamma state is poisoned".into());
+    };
+
+    if brightness >= 1. {
+        if let Some(dimmer) = dimmer.take() {
+            for (control, _) in dimmer.state.controls.iter() {
+                control.destroy();
+            }
+            let _ = dimmer.queue.flush();
+            info!("Monitor brightness restored");
+        }
+        return Ok(());
+    }
+
+    if dimmer.is_none() {
+        if UNSUPPORTED.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        match create_dimmer() {
+            Ok(d) => *dimmer = Some(d),
+            Err(e) => {
+                UNSUPPORTED.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
+    }
+    let dimmer = dimmer.as_mut().unwrap();
+    dimmer.brightness = brightness;
+
+    for (control, size) in dimmer.state.controls.iter() {
+        if *size == 0 {
+            continue;
+        }
+        let file = gamma_table(*size, brightness).map_err(|e| e.to_string())?;
+        control.set_gamma(file.as_fd());
+    }
+    dimmer
+        .queue
+        .roundtrip(&mut dimmer.state)
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn create_dimmer() -> Result<Dimmer, String> {
+    let conn = Connection::connect_to_env().map_err(|e| e.to_string())?;
+    let (globals, mut queue) =
+        registry_queue_init::<GammaState>(&conn).map_err(|e| e.to_string())?;
+    let qh = queue.handle();
+
+    let manager: ZwlrGammaControlManagerV1 = globals
+        .bind(&qh, 1..=1, ())
+        .map_err(|_| "Compositor does not support gamma control".to_string())?;
+
+    for global in globals.contents().clone_list() {
+        if global.interface == WlOutput::interface().name {
+            let output: WlOutput = globals.registry().bind(global.name, 1, &qh, ());
+            manager.get_gamma_control(&output, &qh, ());
+        }
+    }
+
+    // the ramp sizes come back as events
+    let mut state = GammaState::default();
+    queue.roundtrip(&mut state).map_err(|e| e.to_string())?;
+    Ok(Dimmer {
+        queue,
+        state,
+        brightness: 1.,
+    })
+}
+
+// Red, green and blue ramps of 16-bit values, one after the other
+fn gamma_table(size: u32, brightness: f32) -> std::io::Result<File> {
+    let fd = unsafe { libc::memfd_create(b"wlx-gamma\0".as_ptr() as _, libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut file = unsafe { File::from_raw_fd(fd) };
+
+    let max = (size - 1).max(1) as f32;
+    let ramp: Vec<u8> = (0..size)
+        .flat_map(|i| ((i as f32 / max * brightness * 65535.) as u16).to_ne_bytes())
+        .collect();
+    for _ in 0..3 {
+        file.write_all(&ramp)?;
+    }
+    Ok(file)
+}
+
+impl Dispatch<ZwlrGammaControlV1, ()> for GammaState {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwlrGammaControlV1,
+        event: <ZwlrGammaControlV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_gamma_control_v1::Event::GammaSize { size } => {
+                state.controls.push((proxy.clone(), size));
+            }
+            // e.g. another program such as a night light already owns the ramps
+            zwlr_gamma_control_v1::Event::Failed => {
+                warn!("Compositor refused gamma control for a monitor");
+                state.controls.retain(|(c, _)| c != proxy);
+                proxy.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+// Plumbing below
+
+impl Dispatch<ZwlrGammaControlManagerV1, ()> for GammaState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwlrGammaControlManagerV1,
+        _event: <ZwlrGammaControlManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlOutput, ()> for GammaState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WlOutput,
+        _event: <WlOutput as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for GammaState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WlRegistry,
+        _event: <WlRegistry as Proxy>::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
@@ -0,0 +1,216 @@
+use std::{sync::Arc, thread, time::Duration};
+
+use glam::Vec2;
+use log::{info, warn};
+use wayland_client::protocol::wl_output::Transform;
+
+use crate::{
+    desktop::{
+        load_pw_token_config, try_create_screen,
+        wl_client::{OutputState, WlClientState},
+    },
+    input::INPUT,
+    overlay::{OverlayData, SplitOverlayBackend},
+    state::SessionState,
+    watch::rebuild_ui,
+    AppState, TASKS,
+};
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+// What a screen overlay is built from, so a change means rebuilding it
+#[derive(Clone, Copy, PartialEq)]
+struct Geometry {
+    size: (i32, i32),
+    transform: Transform,
+    logical_pos: Vec2,
+    logical_size: Vec2,
+}
+
+impl Geometry {
+    fn of(output: &OutputState) -> Self {
+        Geometry {
+            size: output.size,
+            transform: output.transform,
+            logical_pos: output.logical_pos,
+            logical_size: output.logical_size,
+        }
+    }
+}
+
+fn snapshot(wl: &WlClientState) -> Vec<(Arc<str>, Geometry)> {
+    wl.ready_outputs()
+        .map(|o| (o.name.clone(), Geometry::of(o)))
+        .collect()
+}
+
+// Keeps a connection open just to hear about outputs being plugged in, removed or changed.
+// While the compositor is away, reconnect takes care of the screens instead.
+pub fn start_output_watch() {
+    thread::spawn(|| loop {
+        if let Some(wl) = WlClientState::try_new() {
+            watch_outputs(wl);
+        }
+        thread::sleep(RETRY_INTERVAL);
+    });
+}
+
+fn watch_outputs(mut wl: WlClientState) {
+    let mut known = snapshot(&wl);
+    loop {
+        let queue = wl.queue.clone();
+        let Ok(mut queue) = queue.lock() else {
+            return;
+        };
+        if queue.blocking_dispatch(&mut wl).is_err() {
+            return;
+        }
+        drop(queue);
+
+        let current = snapshot(&wl);
+        let extent = wl.get_desktop_extent();
+        for (name, geometry) in current.iter() {
+            let name = name.clone();
+            match known.iter().find(|(n, _)| *n == name) {
+                None => {
+                    queue_task(move |app, overlays| output_added(app, overlays, name, extent));
+                }
+                Some((_, old)) if old != geometry => {
+                    let rotated = old.transform != geometry.transform;
+                    queue_task(move |app, overlays| {
+                        output_changed(app, overlays, name, extent, rotated)
+                    });
+                }
+                _ => {}
+            }
+        }
+        for (name, _) in known.iter() {
+            if !current.iter().any(|(n, _)| n == name) {
+                let name = name.clone();
+                queue_task(move |app, overlays| output_removed(app, overlays, name, extent));
+            }
+        }
+        known = current;
+    }
+}
+
+fn queue_task(f: impl FnOnce(&mut AppState, &mut Vec<OverlayData>) + Send + 'static) {
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(move |_sk, app, overlays| f(app, overlays)));
+    }
+}
+
+fn output_added(app: &mut AppState, overlays: &mut Vec<OverlayData>, name: Arc<str>, extent: Vec2) {
+    if app.screens.iter().any(|(_, n)| *n == name) {
+        return;
+    }
+    info!("{}: Output plugged in", name);
+
+    if let Ok(mut uinput) = INPUT.lock() {
+        uinput.set_desktop_extent(extent);
+    }
+
+    let Some(screen) = create_screen(app, &name) else {
+        warn!("{}: Could not capture the new output", name);
+        return;
+    };
+    add_screen(app, overlays, screen);
+    rebuild_ui(app, overlays);
+}
+
+// Rebuilds the capture, mesh and pointer mapping after a mode, rotation or layout change
+fn output_changed(
+    app: &mut AppState,
+    overlays: &mut [OverlayData],
+    name: Arc<str>,
+    extent: Vec2,
+    rotated: bool,
+) {
+    let Some((idx, _)) = app.screens.iter().find(|(_, n)| *n == name) else {
+        return;
+    };
+    let idx = *idx;
+    info!("{}: Output changed", name);
+
+    if let Ok(mut uinput) = INPUT.lock() {
+        uinput.set_desktop_extent(extent);
+    }
+
+    let Some(screen) = create_screen(app, &name) else {
+        warn!("{}: Could not capture the changed output", name);
+        return;
+    };
+
+    let overlay = &mut overlays[idx];
+    overlay.hide(app);
+    // stays where it is, unless it needs to be turned to the new orientation
+    if rotated {
+        overlay.saved_transform = None;
+    }
+    overlay.release_gfx(app);
+    overlay.backend = screen.backend;
+    overlay.size = screen.size;
+    overlay.spawn_rotation = screen.spawn_rotation;
+    overlay.color_space = screen.color_space;
+}
+
+// Captures the named output with a fresh connection, whose output list is up to date
+pub fn create_screen(app: &mut AppState, name: &str) -> Option<OverlayData> {
+    let mut wl = WlClientState::try_new()?;
+    let output_idx = wl.outputs.iter().position(|o| &*o.name == name)?;
+    if let Ok(pw_tokens) = load_pw_token_config() {
+        wl.pw_tokens = pw_tokens;
+    }
+
+    try_create_screen(&mut wl, output_idx, &app.session)
+}
+
+// Adds a screen for an output that has none. One that was unplugged earlier gets its old
+// slot back, so overlay indices stay put.
+pub fn add_screen(app: &mut AppState, overlays: &mut Vec<OverlayData>, mut screen: OverlayData) {
+    app.session.config.apply_output(&mut screen);
+    let name = screen.name.clone();
+    if let Some(idx) = overlays.iter().position(|o| o.name == name) {
+        let overlay = &mut overlays[idx];
+        overlay.backend = screen.backend;
+        overlay.size = screen.size;
+        overlay.spawn_rotation = screen.spawn_rotation;
+        overlay.color_space = screen.color_space;
+        overlay.stereo = screen.stereo;
+        overlay.want_visible = screen.want_visible;
+        app.screens.push((idx, name));
+    } else {
+        // an output seen in an earlier session goes back where it was
+        if let (None, Ok(state)) = (screen.saved_transform, SessionState::load()) {
+            screen.saved_transform = state.screen_transform(&name);
+        }
+        app.screens.push((overlays.len(), name));
+        overlays.push(screen);
+    }
+}
+
+// The overlay keeps its slot, but drops its capture and textures
+fn output_removed(
+    app: &mut AppState,
+    overlays: &mut Vec<OverlayData>,
+    name: Arc<str>,
+    extent: Vec2,
+) {
+    let Some(pos) = app.screens.iter().position(|(_, n)| *n == name) else {
+        return;
+    };
+    info!("{}: Output removed", name);
+
+    if let Ok(mut uinput) = INPUT.lock() {
+        uinput.set_desktop_extent(extent);
+    }
+
+    let (idx, _) = app.screens.remove(pos);
+    let overlay = &mut overlays[idx];
+    overlay.want_visible = false;
+    overlay.hide(app);
+    overlay.release_gfx(app);
+    overlay.backend = Box::<SplitOverlayBackend>::default();
+
+    rebuild_ui(app, overlays);
+}
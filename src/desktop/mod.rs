@@ -3,23 +3,22 @@ use std::{
     error::Error,
     f32::consts::PI,
     path::PathBuf,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
 use glam::{vec2, Affine2, Quat, Vec2, Vec3};
-use log::{info, warn};
+use log::info;
 use serde::{Deserialize, Serialize};
 use wayland_client::protocol::wl_output::Transform;
 
 use crate::{
     config_io,
-    desktop::capture::{
-        pw_capture::{pipewire_select_screen, PipewireCapture},
-        wlr_dmabuf_capture::WlrDmabufCapture,
-    },
-    input::{INPUT, MOUSE_LEFT, MOUSE_MIDDLE, MOUSE_RIGHT},
+    desktop::capture::lazy_capture::{CaptureSource, LazyCapture},
+    input::INPUT,
     interactions::{InteractionHandler, PointerHit, POINTER_ALT, POINTER_SHIFT},
-    overlay::{OverlayData, OverlayRenderer, SplitOverlayBackend},
+    latency,
+    overlay::{OverlayData, SplitOverlayBackend},
     AppSession,
 };
 
@@ -27,16 +26,31 @@ use self::wl_client::WlClientState;
 
 pub mod capture;
 pub mod frame;
+pub mod gamma;
+pub mod hotplug;
+pub mod output_power;
+pub mod picker;
+pub mod reconnect;
+pub mod retry;
+pub mod toplevel;
+pub mod triple_buffer;
 pub mod wl_client;
 
 struct ScreenInteractionHandler {
+    // output name, to look up its pointer_buttons
+    name: Arc<str>,
     next_scroll: Instant,
     next_move: Instant,
     mouse_transform: Affine2,
 }
 
 impl ScreenInteractionHandler {
-    fn new(pos: Vec2, size: Vec2, transform: Transform) -> ScreenInteractionHandler {
+    fn new(
+        name: Arc<str>,
+        pos: Vec2,
+        size: Vec2,
+        transform: Transform,
+    ) -> ScreenInteractionHandler {
         let transform = match transform {
             Transform::_90 | Transform::Flipped90 => Affine2::from_cols(
                 vec2(0., size.y),
@@ -57,6 +71,7 @@ impl ScreenInteractionHandler {
         };
 
         ScreenInteractionHandler {
+            name,
             next_scroll: Instant::now(),
             next_move: Instant::now(),
             mouse_transform: transform,
@@ -75,11 +90,13 @@ impl InteractionHandler for ScreenInteractionHandler {
     }
     fn on_pointer(&mut self, session: &AppSession, hit: &PointerHit, pressed: bool) {
         if let Ok(mut input) = INPUT.lock() {
+            let buttons = session.config.pointer_buttons(&self.name);
             let btn = match hit.mode {
-                POINTER_SHIFT => MOUSE_RIGHT,
-                POINTER_ALT => MOUSE_MIDDLE,
-                _ => MOUSE_LEFT,
-            };
+                POINTER_SHIFT => buttons.shift,
+                POINTER_ALT => buttons.alt,
+                _ => buttons.norm,
+            }
+            .code();
 
             if pressed {
                 self.next_move = Instant::now()
@@ -87,6 +104,9 @@ impl InteractionHandler for ScreenInteractionHandler {
             }
 
             input.send_button(btn, pressed);
+            if pressed {
+                latency::input_sent(&self.name);
+            }
 
             let pos = self.mouse_transform.transform_point2(hit.uv);
             input.mouse_move(pos);
@@ -157,68 +177,91 @@ pub fn load_pw_token_config() -> Result<BTreeMap<String, String>, Box<dyn Error>
     Ok(map)
 }
 
-pub async fn try_create_screen(
+pub fn try_create_screen(
     wl: &mut WlClientState,
     idx: usize,
     session: &AppSession,
 ) -> Option<OverlayData> {
     let output = &wl.outputs[idx];
     info!(
-        "{}: Res {}x{} Size {:?} Pos {:?}",
-        output.name, output.size.0, output.size.1, output.logical_size, output.logical_pos,
+        "{}: Res {}x{} Size {:?} Pos {:?} Scale {}",
+        output.name,
+        output.size.0,
+        output.size.1,
+        output.logical_size,
+        output.logical_pos,
+        output.scale(),
     );
 
     let size = (output.size.0, output.size.1);
-    let mut capture: Option<Box<dyn OverlayRenderer>> = None;
+    let source;
+    let mut color_space;
 
     if session.capture_method == "auto" && wl.maybe_wlr_dmabuf_mgr.is_some() {
         info!("{}: Using Wlr DMA-Buf", &output.name);
-        let wl = WlClientState::new();
-        capture = WlrDmabufCapture::try_new(wl, output);
+        source = CaptureSource::WlrDmabuf {
+            output_id: output.id,
+        };
+        color_space = session.config.wlr_color_space;
     } else {
         info!("{}: Using Pipewire capture", &output.name);
-
-        if let Ok(node_id) = pipewire_select_screen(output.name.as_ref(), &mut wl.pw_tokens).await {
-            info!("Node id: {}", node_id);
-            capture = Some(Box::new(PipewireCapture::new(
-                output.name.clone(),
-                node_id,
-                60,
-                session.capture_method != "pw-fallback",
-            )));
+        color_space = session.config.pipewire_color_space;
+
+        // without a token the portal shows its dialog on the desktop, so pick in VR first
+        if session.config.pw_picker
+            && !wl.pw_tokens.contains_key(&*output.name)
+            && picker::defer(&output.name)
+        {
+            return None;
         }
-    }
-    if let Some(capture) = capture {
-        let backend = Box::new(SplitOverlayBackend {
-            renderer: capture,
-            interaction: Box::new(ScreenInteractionHandler::new(
-                output.logical_pos,
-                output.logical_size,
-                output.transform,
-            )),
-        });
-
-        let axis = Vec3::new(0., 0., 1.);
-
-        let angle = match output.transform {
-            Transform::_90 | Transform::Flipped90 => PI / 2.,
-            Transform::_180 | Transform::Flipped180 => PI,
-            Transform::_270 | Transform::Flipped270 => -PI / 2.,
-            _ => 0.,
-        };
 
-        Some(OverlayData {
-            name: output.name.clone(),
-            size,
-            scale: session.config.desktop_view_scale,
-            show_hide: true,
-            grabbable: true,
-            backend,
-            spawn_rotation: Quat::from_axis_angle(axis, angle),
-            ..Default::default()
-        })
-    } else {
-        warn!("{}: Will not be used", &output.name);
-        None
+        source = CaptureSource::Pipewire {
+            dmabuf: session.capture_method != "pw-fallback",
+        };
+    }
+    if let Some(output_space) = session
+        .config
+        .outputs
+        .get(&*output.name)
+        .and_then(|o| o.color_space)
+    {
+        color_space = output_space;
     }
+    info!("{}: Color space {:?}", &output.name, color_space);
+
+    // the portal is asked, or the capture connection made, once the screen is first shown
+    let backend = Box::new(SplitOverlayBackend {
+        renderer: Box::new(LazyCapture::new(output.name.clone(), source)),
+        interaction: Box::new(ScreenInteractionHandler::new(
+            output.name.clone(),
+            output.logical_pos,
+            output.exact_logical_size(),
+            output.transform,
+        )),
+    });
+
+    let axis = Vec3::new(0., 0., 1.);
+
+    let angle = match output.transform {
+        Transform::_90 | Transform::Flipped90 => PI / 2.,
+        Transform::_180 | Transform::Flipped180 => PI,
+        Transform::_270 | Transform::Flipped270 => -PI / 2.,
+        _ => 0.,
+    };
+
+    Some(OverlayData {
+        name: output.name.clone(),
+        size,
+        scale: session.config.desktop_view_scale,
+        show_hide: true,
+        grabbable: true,
+        remember_transform: true,
+        backend,
+        spawn_rotation: Quat::from_axis_angle(axis, angle),
+        mipmaps: session.config.screen_mipmaps,
+        color_space,
+        color_test: session.config.color_test,
+        cvd_filter: session.config.cvd_filter,
+        ..Default::default()
+    })
 }
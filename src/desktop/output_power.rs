@@ -0,0 +1,206 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use log::{info, warn};
+use smithay_client_toolkit::reexports::protocols_wlr::output_power_management::v1::client::{
+    zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1,
+    zwlr_output_power_v1::{self, Mode, ZwlrOutputPowerV1},
+};
+use wayland_client::{
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{
+        wl_output::{self, WlOutput},
+        wl_registry::WlRegistry,
+    },
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+};
+
+use crate::overlay::OverlayData;
+
+// Controls are kept until the monitors are turned back on, since some compositors
+// restore the mode as soon as they are destroyed
+struct PowerOff {
+    queue: EventQueue<PowerState>,
+    state: PowerState,
+    controls: Vec<OutputControl>,
+}
+
+struct OutputControl {
+    control: ZwlrOutputPowerV1,
+    // index into PowerState::names
+    idx: usize,
+    on: bool,
+}
+
+static POWER_OFF: Mutex<Option<PowerOff>> = Mutex::new(None);
+static DISPLAYS_OFF: AtomicBool = AtomicBool::new(false);
+
+pub fn displays_off() -> bool {
+    DISPLAYS_OFF.load(Ordering::Relaxed)
+}
+
+// Turns the physical monitors off or back on. Captures of wlroots compositors stop
+// updating while the monitors are off, since nothing is rendered for them, so update()
+// keeps the monitors of screens shown in VR on.
+pub fn set_displays_off(off: bool) -> Result<(), String> {
+    let Ok(mut power_off) = POWER_OFF.lock() else {
+        return Err("Display power state is poisoned".into());
+    };
+
+    if !off {
+        if let Some(mut state) = power_off.take() {
+            for output in state.controls.iter() {
+                output.control.set_mode(Mode::On);
+                output.control.destroy();
+            }
+            let _ = state.queue.roundtrip(&mut state.state);
+            info!("Monitors turned back on");
+        }
+        DISPLAYS_OFF.store(false, Ordering::Relaxed);
+        return Ok(());
+    }
+    if power_off.is_some() {
+        return Ok(());
+    }
+
+    let conn = Connection::connect_to_env().map_err(|e| e.to_string())?;
+    let (globals, mut queue) =
+        registry_queue_init::<PowerState>(&conn).map_err(|e| e.to_string())?;
+    let qh = queue.handle();
+
+    let manager: ZwlrOutputPowerManagerV1 = globals
+        .bind(&qh, 1..=1, ())
+        .map_err(|_| "Compositor does not support turning monitors off".to_string())?;
+
+    let mut state = PowerState::default();
+    let mut controls = vec![];
+    for global in globals.contents().clone_list() {
+        if global.interface == WlOutput::interface().name {
+            // names are sent from version 4
+            let idx = state.names.len();
+            state.names.push(None);
+            let output: WlOutput =
+                globals
+                    .registry()
+                    .bind(global.name, global.version.min(4), &qh, idx);
+            let control = manager.get_output_power(&output, &qh, ());
+            control.set_mode(Mode::Off);
+            controls.push(OutputControl {
+                control,
+                idx,
+                on: false,
+            });
+        }
+    }
+    queue.roundtrip(&mut state).map_err(|e| e.to_string())?;
+
+    info!("Turned {} monitors off", controls.len());
+    *power_off = Some(PowerOff {
+        queue,
+        state,
+        controls,
+    });
+    DISPLAYS_OFF.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+// Once per frame: while the monitors are off, those of the screens shown in VR are kept on,
+// so their captures keep updating
+pub fn update(overlays: &[OverlayData], screens: &[(usize, Arc<str>)]) {
+    if !displays_off() {
+        return;
+    }
+    let Ok(mut power_off) = POWER_OFF.lock() else {
+        return;
+    };
+    let Some(power_off) = power_off.as_mut() else {
+        return;
+    };
+
+    let mut changed = false;
+    for output in power_off.controls.iter_mut() {
+        let Some(name) = power_off.state.names[output.idx].as_deref() else {
+            continue;
+        };
+        let shown = screens
+            .iter()
+            .any(|(idx, screen)| &**screen == name && overlays[*idx].visible);
+        if shown != output.on {
+            output
+                .control
+                .set_mode(if shown { Mode::On } else { Mode::Off });
+            output.on = shown;
+            changed = true;
+        }
+    }
+    if changed {
+        let _ = power_off.queue.flush();
+    }
+    let _ = power_off.queue.dispatch_pending(&mut power_off.state);
+}
+
+#[derive(Default)]
+struct PowerState {
+    // output names, by the index the wl_output was bound with
+    names: Vec<Option<String>>,
+}
+
+impl Dispatch<ZwlrOutputPowerV1, ()> for PowerState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwlrOutputPowerV1,
+        event: <ZwlrOutputPowerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let zwlr_output_power_v1::Event::Failed = event {
+            warn!("Compositor refused to change a monitor's power mode");
+        }
+    }
+}
+
+impl Dispatch<WlOutput, usize> for PowerState {
+    fn event(
+        state: &mut Self,
+        _proxy: &WlOutput,
+        event: <WlOutput as Proxy>::Event,
+        idx: &usize,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Name { name } = event {
+            if let Some(slot) = state.names.get_mut(*idx) {
+                *slot = Some(name);
+            }
+        }
+    }
+}
+
+// Plumbing below
+
+impl Dispatch<ZwlrOutputPowerManagerV1, ()> for PowerState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwlrOutputPowerManagerV1,
+        _event: <ZwlrOutputPowerManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for PowerState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WlRegistry,
+        _event: <WlRegistry as Proxy>::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
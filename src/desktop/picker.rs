@@ -0,0 +1,206 @@
+use std::sync::{Arc, Mutex};
+
+use glam::Vec3;
+use log::info;
+
+use crate::{
+    config::report_error,
+    gui::{color_parse, Canvas, TextOverflow},
+    overlay::{OverlayData, RelativeTo},
+    watch::rebuild_ui,
+    AppSession, TASKS,
+};
+
+use super::hotplug::{add_screen, create_screen};
+
+const WIDTH: f32 = 500.;
+const HEIGHT: f32 = 360.;
+const ROW_HEIGHT: f32 = 36.;
+const BUTTON_H: f32 = 36.;
+
+#[derive(Default)]
+struct Picker {
+    // bumped with every change, so the panel knows to refresh
+    generation: u64,
+    // outputs without a restore token, waiting to be picked
+    pending: Vec<Arc<str>>,
+    // picked in VR, so the portal dialog is wanted for them
+    picked: Vec<Arc<str>>,
+    // applied to the panel by the frame loop; screens are made inside tasks, which can't
+    // queue tasks of their own to show it
+    show: Option<bool>,
+}
+
+static PICKER: Mutex<Option<Picker>> = Mutex::new(None);
+
+fn with_picker<T>(f: impl FnOnce(&mut Picker) -> T) -> Option<T> {
+    let mut picker = PICKER.lock().ok()?;
+    Some(f(picker.get_or_insert_with(Picker::default)))
+}
+
+// Whether to hold off on asking the portal for an output, which would open its dialog on the
+// desktop. Outputs held off are listed in VR, and asked for once picked there.
+pub fn defer(name: &Arc<str>) -> bool {
+    let deferred = with_picker(|picker| {
+        if let Some(pos) = picker.picked.iter().position(|n| n == name) {
+            picker.picked.remove(pos);
+            return false;
+        }
+        if !picker.pending.contains(name) {
+            picker.pending.push(name.clone());
+            picker.generation += 1;
+        }
+        true
+    })
+    .unwrap_or(false);
+
+    if deferred {
+        info!("{}: Waiting to be picked in VR", name);
+        set_visible(true);
+    }
+    deferred
+}
+
+// The output was asked for in VR, so its screen goes to the portal without waiting again
+pub fn mark_picked(name: &Arc<str>) {
+    with_picker(|picker| {
+        if !picker.picked.contains(name) {
+            picker.picked.push(name.clone());
+        }
+    });
+}
+
+fn set_visible(visible: bool) {
+    with_picker(|picker| picker.show = Some(visible));
+}
+
+// Once per frame: shows or hides the panel as asked since the last frame
+pub fn update(overlays: &mut [OverlayData]) {
+    let Some(Some(visible)) = with_picker(|picker| picker.show.take()) else {
+        return;
+    };
+    if let Some(overlay) = overlays.iter_mut().find(|o| &*o.name == "Picker") {
+        overlay.want_visible = visible;
+    }
+}
+
+// Asks the portal for the output now, and shows its screen if the dialog was confirmed
+fn pick(name: Arc<str>) {
+    let remaining = with_picker(|picker| {
+        picker.pending.retain(|n| *n != name);
+        picker.picked.push(name.clone());
+        picker.generation += 1;
+        picker.pending.len()
+    });
+    if remaining == Some(0) {
+        set_visible(false);
+    }
+
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(move |_sk, app, o| {
+            let Some(screen) = create_screen(app, &name) else {
+                report_error(&format!("{}: Capture was not allowed", name));
+                return;
+            };
+            add_screen(app, o, screen);
+            if let Some((idx, _)) = app.screens.iter().find(|(_, n)| *n == name) {
+                o[*idx].want_visible = true;
+            }
+            rebuild_ui(app, o);
+        }));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PickerControl {
+    // generation the items were built from
+    List(u64),
+}
+
+#[derive(Default)]
+struct PickerData {
+    // output names, in the order of the list rows
+    names: Vec<Arc<str>>,
+}
+
+// Screens that need the desktop's permission before they can be captured through Pipewire
+pub fn create_picker(session: &AppSession) -> OverlayData {
+    let mut canvas: Canvas<PickerData, PickerControl> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        PickerData::default(),
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.font_size = theme.font_size;
+    canvas.fg_color = color_parse(&theme.text_color);
+    canvas.label(
+        10.,
+        30.,
+        WIDTH - 20.,
+        30.,
+        "Pick a screen to capture".into(),
+    );
+    canvas.font_size = theme.font_size * 3 / 4;
+    canvas.fg_color = color_parse(&theme.toggle_text_color);
+    let hint = canvas.label(
+        10.,
+        55.,
+        WIDTH - 20.,
+        20.,
+        "Then confirm the same screen in the dialog on your monitor, just once".into(),
+    );
+    canvas.controls[hint].set_overflow(TextOverflow::Ellipsis);
+
+    canvas.font_size = theme.font_size;
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+    let list_h = HEIGHT - BUTTON_H - 90.;
+    let list = canvas.list(10., 70., WIDTH - 20., list_h, ROW_HEIGHT);
+    canvas.controls[list].on_update = Some(|control, data| {
+        let Some((generation, pending)) =
+            with_picker(|picker| (picker.generation, picker.pending.clone()))
+        else {
+            return;
+        };
+        let state = Some(PickerControl::List(generation));
+        if control.state == state {
+            return;
+        }
+        control.set_items(pending.clone());
+        data.names = pending;
+        control.state = state;
+    });
+    canvas.controls[list].on_select = Some(|_control, row, data| {
+        if let Some(name) = data.names.get(row) {
+            pick(name.clone());
+        }
+    });
+
+    canvas.bg_color = color_parse(&theme.keyboard_button_color);
+    let later = canvas.button(
+        10.,
+        HEIGHT - BUTTON_H - 10.,
+        WIDTH - 20.,
+        BUTTON_H,
+        "Not now".into(),
+    );
+    canvas.controls[later].on_press = Some(|_control, _session, _data| {
+        set_visible(false);
+    });
+
+    OverlayData {
+        name: "Picker".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.4 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: Vec3::NEG_Z * 0.7,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::{info, warn};
+
+use crate::{
+    desktop::{
+        hotplug::{add_screen, create_screen},
+        load_pw_token_config, toplevel, try_create_screen,
+        wl_client::WlClientState,
+    },
+    external,
+    input::INPUT,
+    overlay::{OverlayData, SplitOverlayBackend},
+    schedule_after,
+    watch::rebuild_ui,
+    wm, AppState, TASKS,
+};
+
+const BACKOFF_MIN: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+// Screens still missing after the connection is back are tried this many more times, then
+// given up on until the output is plugged in again
+const SCREEN_RETRIES: u32 = 5;
+
+static LOST: AtomicBool = AtomicBool::new(false);
+static RECONNECTING: AtomicBool = AtomicBool::new(false);
+
+// Bumped by each teardown, so retries queued before it leave the new screens alone
+static EPOCH: AtomicU32 = AtomicU32::new(0);
+
+// Called by the captures when the compositor or the pipewire daemon goes away
+pub fn connection_lost() {
+    LOST.store(true, Ordering::Relaxed);
+}
+
+// Tears down the screens once a capture reports a lost connection, then reconnects in the background
+pub fn update(app: &mut AppState, overlays: &mut [OverlayData]) {
+    if RECONNECTING.load(Ordering::Relaxed) || !LOST.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    RECONNECTING.store(true, Ordering::Relaxed);
+    EPOCH.fetch_add(1, Ordering::Relaxed);
+    warn!("Lost the desktop connection, removing screens until it is back");
+
+    let mut was_visible = vec![];
+    for (idx, name) in app.screens.iter() {
+        let overlay = &mut overlays[*idx];
+        if overlay.want_visible {
+            was_visible.push(name.clone());
+        }
+        overlay.want_visible = false;
+        overlay.hide(app);
+        overlay.release_gfx(app);
+        // drops the capture along with its connection
+        overlay.backend = Box::<SplitOverlayBackend>::default();
+    }
+
+    thread::spawn(move || {
+        let mut backoff = BACKOFF_MIN;
+        loop {
+            thread::sleep(backoff);
+            if let Some(wl) = WlClientState::try_new() {
+                queue_restore(wl, was_visible);
+                return;
+            }
+            backoff = (backoff * 2).min(BACKOFF_MAX);
+        }
+    });
+}
+
+fn queue_restore(wl: WlClientState, was_visible: Vec<Arc<str>>) {
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(move |_sk, app, overlays| {
+            restore_screens(wl, app, overlays, &was_visible);
+            RECONNECTING.store(false, Ordering::Relaxed);
+        }));
+    }
+}
+
+fn restore_screens(
+    mut wl: WlClientState,
+    app: &mut AppState,
+    overlays: &mut Vec<OverlayData>,
+    was_visible: &[Arc<str>],
+) {
+    info!("Desktop connection is back, restoring screens");
+
+    if let Ok(mut uinput) = INPUT.lock() {
+        uinput.set_desktop_extent(wl.get_desktop_extent());
+    }
+    if let Ok(pw_tokens) = load_pw_token_config() {
+        wl.pw_tokens = pw_tokens;
+    }
+
+    // outputs unplugged while the connection was down keep their slot, without a screen
+    let before = app.screens.len();
+    app.screens
+        .retain(|(_, name)| wl.outputs.iter().any(|o| o.name == *name));
+
+    let mut missing: Vec<Arc<str>> = app.screens.iter().map(|(_, n)| n.clone()).collect();
+    let mut changed = app.screens.len() != before;
+    for i in 0..wl.outputs.len() {
+        let Some(screen) = try_create_screen(&mut wl, i, &app.session) else {
+            continue;
+        };
+
+        if missing.contains(&screen.name) {
+            missing.retain(|n| *n != screen.name);
+            let visible = was_visible.contains(&screen.name);
+            put_back(app, overlays, screen, visible);
+        } else {
+            add_screen(app, overlays, screen);
+            changed = true;
+        }
+    }
+
+    // new or removed outputs change the buttons on the watch
+    if changed {
+        rebuild_ui(app, overlays);
+    }
+
+    reconnect_integrations();
+
+    // only the screens still missing are tried again, the rest stay as they are
+    if !missing.is_empty() {
+        let epoch = EPOCH.load(Ordering::Relaxed);
+        queue_retry(missing, was_visible.to_vec(), epoch, 0);
+    }
+}
+
+// Gives the screen's old slot its new capture
+fn put_back(app: &mut AppState, overlays: &mut [OverlayData], screen: OverlayData, visible: bool) {
+    let Some((idx, _)) = app.screens.iter().find(|(_, n)| *n == screen.name) else {
+        return;
+    };
+    let overlay = &mut overlays[*idx];
+    overlay.release_gfx(app);
+    overlay.backend = screen.backend;
+    overlay.size = screen.size;
+    overlay.spawn_rotation = screen.spawn_rotation;
+    overlay.color_space = screen.color_space;
+    overlay.want_visible = visible;
+}
+
+fn queue_retry(missing: Vec<Arc<str>>, was_visible: Vec<Arc<str>>, epoch: u32, attempt: u32) {
+    let delay = (BACKOFF_MIN * 2u32.pow(attempt)).min(BACKOFF_MAX);
+    schedule_after(
+        delay,
+        Box::new(move |_sk, app, overlays| {
+            retry_missing(app, overlays, missing, was_visible, epoch, attempt);
+        }),
+    );
+}
+
+fn retry_missing(
+    app: &mut AppState,
+    overlays: &mut Vec<OverlayData>,
+    mut missing: Vec<Arc<str>>,
+    was_visible: Vec<Arc<str>>,
+    epoch: u32,
+    attempt: u32,
+) {
+    if epoch != EPOCH.load(Ordering::Relaxed) {
+        return;
+    }
+    // unplugged meanwhile, hotplug took care of it
+    missing.retain(|name| app.screens.iter().any(|(_, n)| n == name));
+
+    missing.retain(|name| {
+        let Some(screen) = create_screen(app, name) else {
+            return true;
+        };
+        info!("{}: Screen is back", name);
+        let visible = was_visible.contains(name);
+        put_back(app, overlays, screen, visible);
+        false
+    });
+    if missing.is_empty() {
+        return;
+    }
+
+    if attempt + 1 < SCREEN_RETRIES {
+        queue_retry(missing, was_visible, epoch, attempt + 1);
+        return;
+    }
+
+    // the slots stay, so the screens come back where they were if the outputs return
+    for name in missing.iter() {
+        warn!(
+            "{}: Giving up on this screen until its output is back",
+            name
+        );
+    }
+    app.screens.retain(|(_, n)| !missing.contains(n));
+    rebuild_ui(app, overlays);
+}
+
+// The window list, window manager IPC and external overlay socket may have gone down
+// along with the compositor
+fn reconnect_integrations() {
+    if !toplevel::is_available() {
+        toplevel::start_toplevel_tracking();
+    }
+    wm::start();
+    external::start_external_server();
+}
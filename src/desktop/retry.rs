@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use glam::Quat;
+use log::info;
+
+use crate::{
+    config::{report_error, ColorSpace, StereoMode},
+    gui::{color_parse, Canvas, TextOverflow},
+    overlay::OverlayData,
+    AppState, TASKS,
+};
+
+use super::{hotplug::create_screen, picker};
+
+const WIDTH: f32 = 400.;
+const HEIGHT: f32 = 120.;
+
+// Stands in for a screen whose capture couldn't be started, such as when the portal dialog
+// was cancelled, so it can be tried again without a restart
+pub fn show_retry_panel(app: &mut AppState, overlays: &mut [OverlayData], name: &Arc<str>) {
+    let Some(overlay) = overlays.iter_mut().find(|o| o.name == *name) else {
+        return;
+    };
+    let canvas = create_retry_canvas(app, name.clone());
+
+    // the main loop shows it again, now with the panel
+    overlay.hide(app);
+    overlay.release_gfx(app);
+    overlay.size = (canvas.width as _, canvas.height as _);
+    overlay.backend = Box::new(canvas);
+    overlay.spawn_rotation = Quat::IDENTITY;
+    overlay.color_space = ColorSpace::Srgb;
+    overlay.stereo = StereoMode::None;
+}
+
+fn create_retry_canvas(app: &AppState, name: Arc<str>) -> Canvas<Arc<str>, ()> {
+    let session = &app.session;
+    let mut canvas = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        name.clone(),
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.font_size = theme.font_size;
+    canvas.fg_color = color_parse(&theme.text_color);
+    let text = format!("Could not capture {}", name);
+    let label = canvas.label(10., 30., WIDTH - 20., 30., text.into());
+    canvas.controls[label].set_overflow(TextOverflow::Ellipsis);
+
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+    let retry = canvas.button(10., 60., WIDTH - 20., 45., "Retry capture".into());
+    canvas.controls[retry].on_press = Some(|_control, _session, name| {
+        let name = name.clone();
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(move |_sk, app, o| {
+                retry_capture(app, o, &name);
+            }));
+        }
+    });
+
+    canvas
+}
+
+// Puts a fresh capture back on the screen, which starts once it's shown again
+fn retry_capture(app: &mut AppState, overlays: &mut [OverlayData], name: &Arc<str>) {
+    info!("{}: Retrying capture", name);
+    // asked for in VR just now, so the portal dialog is wanted without going to the picker
+    picker::mark_picked(name);
+    let Some(mut screen) = create_screen(app, name) else {
+        report_error(&format!("{}: Could not capture this screen", name));
+        return;
+    };
+    app.session.config.apply_output(&mut screen);
+    let Some(overlay) = overlays.iter_mut().find(|o| o.name == *name) else {
+        return;
+    };
+    overlay.hide(app);
+    overlay.release_gfx(app);
+    overlay.backend = screen.backend;
+    overlay.size = screen.size;
+    overlay.spawn_rotation = screen.spawn_rotation;
+    overlay.color_space = screen.color_space;
+    overlay.stereo = screen.stereo;
+    overlay.want_visible = true;
+}
@@ -0,0 +1,218 @@
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use log::{error, info};
+use once_cell::sync::Lazy;
+use smithay_client_toolkit::reexports::protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+use wayland_client::{
+    event_created_child,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_registry::WlRegistry, wl_seat::WlSeat},
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+
+const STATE_MINIMIZED: u32 = 1;
+const STATE_ACTIVATED: u32 = 2;
+
+#[derive(Clone)]
+pub struct Toplevel {
+    pub handle: ZwlrForeignToplevelHandleV1,
+    pub title: Arc<str>,
+    pub app_id: Arc<str>,
+    pub minimized: bool,
+    pub activated: bool,
+}
+
+// Open windows as last reported by the compositor; generation changes with every update
+#[derive(Default)]
+pub struct ToplevelList {
+    pub generation: u64,
+    pub toplevels: Vec<Toplevel>,
+    conn: Option<Connection>,
+    seat: Option<WlSeat>,
+}
+
+pub static TOPLEVELS: Lazy<Mutex<ToplevelList>> = Lazy::new(|| Mutex::new(ToplevelList::default()));
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ToplevelAction {
+    Activate,
+    Minimize,
+    Close,
+}
+
+pub fn is_available() -> bool {
+    TOPLEVELS.lock().is_ok_and(|t| t.conn.is_some())
+}
+
+// Requests are sent right away from the calling thread
+pub fn toplevel_action(handle: &ZwlrForeignToplevelHandleV1, action: ToplevelAction) {
+    let Ok(list) = TOPLEVELS.lock() else {
+        return;
+    };
+    match action {
+        ToplevelAction::Activate => {
+            if let Some(seat) = list.seat.as_ref() {
+                handle.unset_minimized();
+                handle.activate(seat);
+            }
+        }
+        ToplevelAction::Minimize => handle.set_minimized(),
+        ToplevelAction::Close => handle.close(),
+    }
+    if let Some(conn) = list.conn.as_ref() {
+        let _ = conn.flush();
+    }
+}
+
+struct ToplevelState;
+
+// Tracks windows on a connection of its own, so the capture queue isn't held up
+pub fn start_toplevel_tracking() {
+    let Ok(conn) = Connection::connect_to_env() else {
+        return;
+    };
+    let Ok((globals, mut queue)) = registry_queue_init::<ToplevelState>(&conn) else {
+        return;
+    };
+    let qh = queue.handle();
+
+    let manager: Result<ZwlrForeignToplevelManagerV1, _> = globals.bind(&qh, 1..=3, ());
+    if manager.is_err() {
+        info!("Compositor does not support foreign toplevel management");
+        return;
+    }
+    let seat: Option<WlSeat> = globals.bind(&qh, 1..=1, ()).ok();
+
+    if let Ok(mut list) = TOPLEVELS.lock() {
+        list.conn = Some(conn);
+        list.seat = seat;
+    }
+
+    thread::spawn(move || {
+        let mut state = ToplevelState;
+        loop {
+            if let Err(e) = queue.blocking_dispatch(&mut state) {
+                error!("Stopped tracking windows: {}", e);
+                // the handles died with the connection; tracking starts over on reconnect
+                if let Ok(mut list) = TOPLEVELS.lock() {
+                    list.conn = None;
+                    list.seat = None;
+                    list.toplevels.clear();
+                    list.generation += 1;
+                }
+                return;
+            }
+        }
+    });
+}
+
+fn with_toplevel(handle: &ZwlrForeignToplevelHandleV1, f: impl FnOnce(&mut Toplevel)) {
+    if let Ok(mut list) = TOPLEVELS.lock() {
+        if let Some(toplevel) = list.toplevels.iter_mut().find(|t| t.handle == *handle) {
+            f(toplevel);
+        }
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for ToplevelState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwlrForeignToplevelManagerV1,
+        event: <ZwlrForeignToplevelManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            if let Ok(mut list) = TOPLEVELS.lock() {
+                list.toplevels.push(Toplevel {
+                    handle: toplevel,
+                    title: "".into(),
+                    app_id: "".into(),
+                    minimized: false,
+                    activated: false,
+                });
+            }
+        }
+    }
+
+    event_created_child!(ToplevelState, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ())
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for ToplevelState {
+    fn event(
+        _state: &mut Self,
+        proxy: &ZwlrForeignToplevelHandleV1,
+        event: <ZwlrForeignToplevelHandleV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => {
+                with_toplevel(proxy, |t| t.title = title.into());
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                with_toplevel(proxy, |t| t.app_id = app_id.into());
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
+                let states: Vec<u32> = state
+                    .chunks_exact(4)
+                    .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+                    .collect();
+                with_toplevel(proxy, |t| {
+                    t.minimized = states.contains(&STATE_MINIMIZED);
+                    t.activated = states.contains(&STATE_ACTIVATED);
+                });
+            }
+            // changes are only visible once the compositor says they are complete
+            zwlr_foreign_toplevel_handle_v1::Event::Done => {
+                if let Ok(mut list) = TOPLEVELS.lock() {
+                    list.generation += 1;
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+                if let Ok(mut list) = TOPLEVELS.lock() {
+                    list.toplevels.retain(|t| t.handle != *proxy);
+                    list.generation += 1;
+                }
+                proxy.destroy();
+            }
+            _ => {}
+        }
+    }
+}
+
+// Plumbing below
+
+impl Dispatch<WlSeat, ()> for ToplevelState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WlSeat,
+        _event: <WlSeat as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for ToplevelState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WlRegistry,
+        _event: <WlRegistry as Proxy>::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
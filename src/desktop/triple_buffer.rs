@@ -0,0 +1,87 @@
+use std::{
+    cell::UnsafeCell,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+};
+
+// Set on the shared index when it holds a value the reader hasn't seen yet
+const DIRTY: u8 = 0b100;
+const INDEX: u8 = 0b011;
+
+// Hands the latest value from one thread to another without locking.
+// The writer and the reader each own one slot, the third one is swapped between them.
+struct Slots<T> {
+    slots: [UnsafeCell<Option<T>>; 3],
+    shared: AtomicU8,
+}
+
+// Each slot is only touched by the side that currently owns its index
+unsafe impl<T: Send> Sync for Slots<T> {}
+
+pub struct TripleWriter<T> {
+    slots: Arc<Slots<T>>,
+    index: u8,
+}
+
+pub struct TripleReader<T> {
+    slots: Arc<Slots<T>>,
+    index: u8,
+}
+
+pub fn triple_buffer<T>() -> (TripleWriter<T>, TripleReader<T>) {
+    let slots = Arc::new(Slots {
+        slots: Default::default(),
+        shared: AtomicU8::new(1),
+    });
+    (
+        TripleWriter {
+            slots: slots.clone(),
+            index: 0,
+        },
+        TripleReader { slots, index: 2 },
+    )
+}
+
+impl<T> TripleWriter<T> {
+    // Publishes a value, replacing the previous one if it wasn't read yet
+    pub fn write(&mut self, value: T) {
+        *self.slot() = Some(value);
+        self.publish();
+    }
+
+    // The writer's own slot. After a read() it holds a value the reader is done with, which
+    // can be filled in again rather than made anew.
+    pub fn slot(&mut self) -> &mut Option<T> {
+        unsafe { &mut *self.slots.slots[self.index as usize].get() }
+    }
+
+    // Publishes what is in the writer's slot
+    pub fn publish(&mut self) {
+        let previous = self.slots.shared.swap(self.index | DIRTY, Ordering::AcqRel);
+        self.index = previous & INDEX;
+    }
+}
+
+impl<T> TripleReader<T> {
+    // The latest value, if one was written since the last call
+    pub fn take(&mut self) -> Option<T> {
+        if self.slots.shared.load(Ordering::Acquire) & DIRTY == 0 {
+            return None;
+        }
+        let previous = self.slots.shared.swap(self.index, Ordering::AcqRel);
+        self.index = previous & INDEX;
+        unsafe { (*self.slots.slots[self.index as usize].get()).take() }
+    }
+
+    // Same as take, but the value stays in its slot and goes back to the writer's slot() later
+    pub fn read(&mut self) -> Option<&mut T> {
+        if self.slots.shared.load(Ordering::Acquire) & DIRTY == 0 {
+            return None;
+        }
+        let previous = self.slots.shared.swap(self.index, Ordering::AcqRel);
+        self.index = previous & INDEX;
+        unsafe { (*self.slots.slots[self.index as usize].get()).as_mut() }
+    }
+}
@@ -18,12 +18,15 @@ use wayland_client::{
     globals::{registry_queue_init, GlobalListContents},
     protocol::{
         wl_output::{self, Transform, WlOutput},
-        wl_registry::WlRegistry,
+        wl_registry::{self, WlRegistry},
     },
     Connection, Dispatch, EventQueue, Proxy, QueueHandle,
 };
 
-use crate::desktop::frame::{FramePlane, FRAME_FAILED};
+use crate::desktop::{
+    frame::{FramePlane, FRAME_FAILED},
+    reconnect,
+};
 
 use super::frame::{DmabufFrame, FRAME_PENDING, FRAME_READY};
 
@@ -39,6 +42,45 @@ pub struct OutputState {
     done: bool,
 }
 
+impl OutputState {
+    // Called when the compositor has sent a full description, again after each change
+    fn finish(&mut self) {
+        if self.logical_size.x < 0. {
+            self.logical_pos.x += self.logical_size.x;
+            self.logical_size.x *= -1.;
+        }
+        if self.logical_size.y < 0. {
+            self.logical_pos.y += self.logical_size.y;
+            self.logical_size.y *= -1.;
+        }
+        self.done = true;
+    }
+
+    // Pixel size with the output's rotation applied, as it is laid out on the desktop
+    pub fn transformed_size(&self) -> Vec2 {
+        match self.transform {
+            Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => {
+                vec2(self.size.1 as _, self.size.0 as _)
+            }
+            _ => vec2(self.size.0 as _, self.size.1 as _),
+        }
+    }
+
+    // Pixels per logical unit. Fractional scales come in steps of 1/120.
+    pub fn scale(&self) -> f32 {
+        if self.logical_size.x <= 0. || self.size.0 <= 0 {
+            return 1.;
+        }
+        (self.transformed_size().x / self.logical_size.x * 120.).round() / 120.
+    }
+
+    // The logical size before the compositor rounded it to whole units, which is off by
+    // a fraction of a unit on fractionally scaled outputs
+    pub fn exact_logical_size(&self) -> Vec2 {
+        self.transformed_size() / self.scale()
+    }
+}
+
 pub struct WlClientState {
     pub connection: Arc<Connection>,
     pub xdg_output_mgr: ZxdgOutputManagerV1,
@@ -52,15 +94,18 @@ pub struct WlClientState {
 
 impl WlClientState {
     pub fn new() -> Self {
-        let connection = Connection::connect_to_env().expect("wayland connection");
-        let (globals, queue) = registry_queue_init::<Self>(&connection).expect("wayland globals");
+        Self::try_new().expect("wayland connection")
+    }
+
+    // None if the compositor is not reachable, e.g. while it restarts
+    pub fn try_new() -> Option<Self> {
+        let connection = Connection::connect_to_env().ok()?;
+        let (globals, queue) = registry_queue_init::<Self>(&connection).ok()?;
         let qh = queue.handle();
 
         let mut state = Self {
             connection: Arc::new(connection),
-            xdg_output_mgr: globals
-                .bind(&qh, 2..=3, ())
-                .expect(ZxdgOutputManagerV1::interface().name),
+            xdg_output_mgr: globals.bind(&qh, 2..=3, ()).ok()?,
             maybe_wlr_dmabuf_mgr: globals.bind(&qh, 1..=1, ()).ok(),
             outputs: vec![],
             desktop_rect: (0, 0),
@@ -71,31 +116,40 @@ impl WlClientState {
 
         for o in globals.contents().clone_list().iter() {
             if o.interface == WlOutput::interface().name {
-                let wl_output: WlOutput = globals.registry().bind(o.name, o.version, &qh, o.name);
+                state.add_output(globals.registry(), o.name, o.version);
+            }
+        }
 
-                state.xdg_output_mgr.get_xdg_output(&wl_output, &qh, o.name);
+        state.dispatch();
 
-                let unknown: Arc<str> = "Unknown".into();
+        Some(state)
+    }
 
-                let output = OutputState {
-                    wl_output,
-                    id: o.name,
-                    name: unknown.clone(),
-                    model: unknown,
-                    size: (0, 0),
-                    logical_pos: Vec2::ZERO,
-                    logical_size: Vec2::ZERO,
-                    transform: Transform::Normal,
-                    done: false,
-                };
+    fn add_output(&mut self, registry: &WlRegistry, id: u32, version: u32) {
+        // the newest wl_output this client knows
+        let wl_output: WlOutput = registry.bind(id, version.min(4), &self.queue_handle, id);
 
-                state.outputs.push(output);
-            }
-        }
+        self.xdg_output_mgr
+            .get_xdg_output(&wl_output, &self.queue_handle, id);
 
-        state.dispatch();
+        let unknown: Arc<str> = "Unknown".into();
+
+        self.outputs.push(OutputState {
+            wl_output,
+            id,
+            name: unknown.clone(),
+            model: unknown,
+            size: (0, 0),
+            logical_pos: Vec2::ZERO,
+            logical_size: Vec2::ZERO,
+            transform: Transform::Normal,
+            done: false,
+        });
+    }
 
-        state
+    // Outputs whose description is complete
+    pub fn ready_outputs(&self) -> impl Iterator<Item = &OutputState> {
+        self.outputs.iter().filter(|o| o.done)
     }
 
     pub fn get_desktop_extent(&self) -> Vec2 {
@@ -107,14 +161,13 @@ impl WlClientState {
         extent
     }
 
-    pub fn request_dmabuf_frame(&mut self, output_idx: usize, frame: Arc<Mutex<DmabufFrame>>) {
+    // Does nothing if the output has been unplugged
+    pub fn request_dmabuf_frame(&mut self, output_id: u32, frame: Arc<Mutex<DmabufFrame>>) {
+        let Some(output) = self.outputs.iter().find(|o| o.id == output_id) else {
+            return;
+        };
         if let Some(dmabuf_manager) = self.maybe_wlr_dmabuf_mgr.as_ref() {
-            let _ = dmabuf_manager.capture_output(
-                1,
-                &self.outputs[output_idx].wl_output,
-                &self.queue_handle,
-                frame,
-            );
+            let _ = dmabuf_manager.capture_output(1, &output.wl_output, &self.queue_handle, frame);
 
             self.dispatch();
         }
@@ -122,7 +175,10 @@ impl WlClientState {
 
     pub fn dispatch(&mut self) {
         if let Ok(mut queue_mut) = self.queue.clone().lock() {
-            let _ = queue_mut.blocking_dispatch(self);
+            if let Err(e) = queue_mut.blocking_dispatch(self) {
+                warn!("Wayland connection lost: {}", e);
+                reconnect::connection_lost();
+            }
         }
     }
 }
@@ -154,15 +210,7 @@ impl Dispatch<ZxdgOutputV1, u32> for WlClientState {
             }
             zxdg_output_v1::Event::Done => {
                 if let Some(output) = state.outputs.iter_mut().find(|o| o.id == *data) {
-                    if output.logical_size.x < 0. {
-                        output.logical_pos.x += output.logical_size.x;
-                        output.logical_size.x *= -1.;
-                    }
-                    if output.logical_size.y < 0. {
-                        output.logical_pos.y += output.logical_size.y;
-                        output.logical_size.y *= -1.;
-                    }
-                    output.done = true;
+                    output.finish();
                 }
             }
             _ => {}
@@ -180,7 +228,19 @@ impl Dispatch<WlOutput, u32> for WlClientState {
         _qhandle: &QueueHandle<Self>,
     ) {
         match event {
-            wl_output::Event::Mode { width, height, .. } => {
+            wl_output::Event::Mode {
+                flags,
+                width,
+                height,
+                ..
+            } => {
+                // compositors may list every supported mode, only the current one matters
+                let current = flags
+                    .into_result()
+                    .is_ok_and(|f| f.contains(wl_output::Mode::Current));
+                if !current {
+                    return;
+                }
                 if let Some(output) = state.outputs.iter_mut().find(|o| o.id == *data) {
                     output.size = (width, height);
                 }
@@ -193,6 +253,12 @@ impl Dispatch<WlOutput, u32> for WlClientState {
                     output.transform = transform.into_result().unwrap_or(Transform::Normal);
                 }
             }
+            // xdg_output v3 no longer sends its own done event
+            wl_output::Event::Done => {
+                if let Some(output) = state.outputs.iter_mut().find(|o| o.id == *data) {
+                    output.finish();
+                }
+            }
             _ => {}
         }
     }
@@ -272,6 +338,37 @@ impl Dispatch<ZwlrExportDmabufFrameV1, Arc<Mutex<DmabufFrame>>> for WlClientStat
     }
 }
 
+// Outputs plugged in or removed after startup
+impl Dispatch<WlRegistry, GlobalListContents> for WlClientState {
+    fn event(
+        state: &mut Self,
+        proxy: &WlRegistry,
+        event: <WlRegistry as Proxy>::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } if interface == WlOutput::interface().name => {
+                state.add_output(proxy, name, version);
+            }
+            wl_registry::Event::GlobalRemove { name } => {
+                if let Some(idx) = state.outputs.iter().position(|o| o.id == name) {
+                    let output = state.outputs.remove(idx);
+                    if output.wl_output.version() >= 3 {
+                        output.wl_output.release();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 // Plumbing below
 
 impl Dispatch<WlRegistry, ()> for WlClientState {
@@ -309,15 +406,3 @@ impl Dispatch<ZwlrExportDmabufManagerV1, ()> for WlClientState {
     ) {
     }
 }
-
-impl Dispatch<WlRegistry, GlobalListContents> for WlClientState {
-    fn event(
-        _state: &mut Self,
-        _proxy: &WlRegistry,
-        _event: <WlRegistry as Proxy>::Event,
-        _data: &GlobalListContents,
-        _conn: &Connection,
-        _qhandle: &QueueHandle<Self>,
-    ) {
-    }
-}
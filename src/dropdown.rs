@@ -0,0 +1,151 @@
+use std::sync::{Arc, Mutex};
+
+use glam::vec3;
+
+use crate::{
+    gui::{color_parse, Canvas},
+    overlay::{OverlayData, RelativeTo},
+    AppSession, TASKS,
+};
+
+const WIDTH: f32 = 360.;
+const HEIGHT: f32 = 320.;
+const ROW_HEIGHT: f32 = 36.;
+const BUTTON_H: f32 = 36.;
+
+// Choices offered for the control with the given focus id
+#[derive(Default)]
+struct Popup {
+    // bumped with every open, so the list knows to refresh
+    generation: u64,
+    owner: usize,
+    items: Vec<Arc<str>>,
+    selected: Option<usize>,
+}
+
+static POPUP: Mutex<Option<Popup>> = Mutex::new(None);
+static RESULT: Mutex<Option<(usize, usize)>> = Mutex::new(None);
+
+// Shows the choices for a dropdown control, with the current one marked
+pub fn open(owner: usize, items: Vec<Arc<str>>, selected: Option<usize>) {
+    if let Ok(mut popup) = POPUP.lock() {
+        let generation = popup.as_ref().map(|p| p.generation + 1).unwrap_or(0);
+        *popup = Some(Popup {
+            generation,
+            owner,
+            items,
+            selected,
+        });
+    }
+    set_visible(true);
+}
+
+// The row picked for the given control, once one was
+pub fn take_result(owner: usize) -> Option<usize> {
+    let mut result = RESULT.lock().ok()?;
+    match *result {
+        Some((o, row)) if o == owner => {
+            *result = None;
+            Some(row)
+        }
+        _ => None,
+    }
+}
+
+fn set_visible(visible: bool) {
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(move |_sk, _app, o| {
+            for overlay in o.iter_mut() {
+                if &*overlay.name == "Dropdown" {
+                    overlay.want_visible = visible;
+                }
+            }
+        }));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DropdownControl {
+    // generation the items were built from
+    List(u64),
+}
+
+pub fn create_dropdown(session: &AppSession) -> OverlayData {
+    let mut canvas: Canvas<(), DropdownControl> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        (),
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.font_size = theme.font_size;
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+    let list_h = HEIGHT - BUTTON_H - 30.;
+    let list = canvas.list(10., 10., WIDTH - 20., list_h, ROW_HEIGHT);
+    canvas.controls[list].on_update = Some(|control, _data| {
+        let Ok(popup) = POPUP.lock() else {
+            return;
+        };
+        let Some(popup) = popup.as_ref() else {
+            return;
+        };
+        let state = Some(DropdownControl::List(popup.generation));
+        if control.state == state {
+            return;
+        }
+        let items = popup
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let marker = if popup.selected == Some(i) {
+                    "▶ "
+                } else {
+                    ""
+                };
+                format!("{}{}", marker, item).into()
+            })
+            .collect();
+        control.set_items(items);
+        control.state = state;
+    });
+    canvas.controls[list].on_select = Some(|_control, row, _data| {
+        let Ok(popup) = POPUP.lock() else {
+            return;
+        };
+        if let Some(popup) = popup.as_ref() {
+            if let Ok(mut result) = RESULT.lock() {
+                *result = Some((popup.owner, row));
+            }
+        }
+        set_visible(false);
+    });
+
+    canvas.bg_color = color_parse(&theme.keyboard_button_color);
+    let cancel = canvas.button(
+        10.,
+        HEIGHT - BUTTON_H - 10.,
+        WIDTH - 20.,
+        BUTTON_H,
+        "Cancel".into(),
+    );
+    canvas.controls[cancel].on_press = Some(|_control, _session, _data| {
+        set_visible(false);
+    });
+
+    OverlayData {
+        name: "Dropdown".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.25 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: vec3(0., -0.1, -0.45),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,214 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use glam::{vec3, Affine3A, Vec3};
+use once_cell::sync::Lazy;
+use stereokit::{Color32, SkDraw, StereoKitDraw, StereoKitMultiThread};
+
+use crate::{
+    gui::{color_parse, Canvas, TextOverflow},
+    overlay::{OverlayData, RelativeTo},
+    AppSession,
+};
+
+const WIDTH: f32 = 360.;
+const HEIGHT: f32 = 90.;
+
+// How close two overlays need to be, in meters, for a guide to show up
+const GUIDE_TOLERANCE: f32 = 0.03;
+
+// Fraction of the shorter side covered by each corner handle
+const HANDLE_LENGTH: f32 = 0.15;
+
+const OUTLINE_COLOR: Color32 = Color32 {
+    r: 255,
+    g: 255,
+    b: 255,
+    a: 160,
+};
+const HANDLE_COLOR: Color32 = Color32 {
+    r: 255,
+    g: 200,
+    b: 40,
+    a: 255,
+};
+const HEIGHT_GUIDE_COLOR: Color32 = Color32 {
+    r: 80,
+    g: 220,
+    b: 255,
+    a: 255,
+};
+const DISTANCE_GUIDE_COLOR: Color32 = Color32 {
+    r: 120,
+    g: 255,
+    b: 120,
+    a: 255,
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static READOUT: Lazy<Mutex<Arc<str>>> = Lazy::new(|| Mutex::new("".into()));
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn toggle() {
+    ENABLED.fetch_xor(true, Ordering::Relaxed);
+}
+
+// Overlays that are arranged in the world, as opposed to following the head or a hand
+pub fn is_arrangeable(overlay: &OverlayData) -> bool {
+    overlay.show_hide && matches!(overlay.relative_to, RelativeTo::None)
+}
+
+// Overlays worn on a wrist, which can be moved to a new spot on either one
+pub fn is_wearable(overlay: &OverlayData) -> bool {
+    &*overlay.name == "Watch"
+}
+
+fn readout() -> Arc<str> {
+    READOUT
+        .lock()
+        .map(|r| r.clone())
+        .unwrap_or_else(|_| "".into())
+}
+
+fn set_readout(text: Arc<str>) {
+    if let Ok(mut r) = READOUT.lock() {
+        *r = text;
+    }
+}
+
+// Once per frame: shows the HUD while in edit mode, and draws handles and guides
+pub fn update(sk: &SkDraw, hmd: &Affine3A, overlays: &mut [OverlayData], grabbed: Option<usize>) {
+    let enabled = is_enabled();
+    for overlay in overlays.iter_mut() {
+        if &*overlay.name == "Edit" {
+            overlay.want_visible = enabled;
+        }
+    }
+    if !enabled {
+        return;
+    }
+
+    for overlay in overlays.iter() {
+        if overlay.visible && (is_arrangeable(overlay) || is_wearable(overlay)) {
+            draw_handles(sk, overlay);
+        }
+    }
+
+    let Some(grabbed) = grabbed.map(|i| &overlays[i]) else {
+        set_readout("Grip an overlay to move it, scroll to push/pull".into());
+        return;
+    };
+
+    let center: Vec3 = grabbed.transform.translation.into();
+    let head: Vec3 = hmd.translation.into();
+    let distance = (center - head).length();
+    set_readout(
+        format!(
+            "{}: {:.2} m away, {:.2} m high, scale {:.2}",
+            grabbed.name,
+            distance,
+            center.y - head.y,
+            grabbed.scale
+        )
+        .into(),
+    );
+
+    // Guides towards the other overlays that line up with the grabbed one
+    for other in overlays.iter() {
+        if std::ptr::eq(other, grabbed) || !other.visible || !is_arrangeable(other) {
+            continue;
+        }
+        let other_center: Vec3 = other.transform.translation.into();
+        if (other_center.y - center.y).abs() < GUIDE_TOLERANCE {
+            sk.line_add(
+                center,
+                other_center,
+                HEIGHT_GUIDE_COLOR,
+                HEIGHT_GUIDE_COLOR,
+                0.003,
+            );
+        }
+        if ((other_center - head).length() - distance).abs() < GUIDE_TOLERANCE {
+            sk.line_add(
+                head + (center - head) * 0.9,
+                head + (other_center - head) * 0.9,
+                DISTANCE_GUIDE_COLOR,
+                DISTANCE_GUIDE_COLOR,
+                0.003,
+            );
+        }
+    }
+}
+
+// Outline with thicker corners, in the overlay's own space
+fn draw_handles(sk: &SkDraw, overlay: &OverlayData) {
+    let (w, h) = (overlay.size.0 as f32, overlay.size.1 as f32);
+    let (half_w, half_h) = if w >= h { (1., h / w) } else { (w / h, 1.) };
+    let handle = half_w.min(half_h) * 2. * HANDLE_LENGTH;
+
+    sk.hierarchy_push(overlay.transform);
+    let corners = [
+        vec3(-half_w, -half_h, 0.),
+        vec3(half_w, -half_h, 0.),
+        vec3(half_w, half_h, 0.),
+        vec3(-half_w, half_h, 0.),
+    ];
+    for (i, corner) in corners.iter().enumerate() {
+        let next = corners[(i + 1) % corners.len()];
+        let prev = corners[(i + corners.len() - 1) % corners.len()];
+        let a = sk.hierarchy_to_world_point(*corner);
+        let b = sk.hierarchy_to_world_point(next);
+        sk.line_add(a, b, OUTLINE_COLOR, OUTLINE_COLOR, 0.002);
+
+        for towards in [next, prev] {
+            let end = *corner + (towards - *corner).normalize() * handle;
+            let end = sk.hierarchy_to_world_point(end);
+            sk.line_add(a, end, HANDLE_COLOR, HANDLE_COLOR, 0.006);
+        }
+    }
+    sk.hierarchy_pop();
+}
+
+// Head-locked panel with the position and scale of the overlay being moved
+pub fn create_edit_hud(session: &AppSession) -> OverlayData {
+    let mut canvas: Canvas<(), ()> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        (),
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.font_size = theme.font_size;
+    canvas.fg_color = color_parse(&theme.toggle_text_color);
+    canvas.label(10., 30., WIDTH - 20., 30., "Edit mode".into());
+
+    canvas.font_size = 14;
+    canvas.fg_color = color_parse(&theme.text_color);
+    let label = canvas.label(10., 70., WIDTH - 20., 24., "".into());
+    canvas.controls[label].set_overflow(TextOverflow::Ellipsis);
+    canvas.controls[label].on_update = Some(|control, _data| {
+        control.set_text(&readout());
+    });
+
+    OverlayData {
+        name: "Edit".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.18 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::Head,
+        spawn_point: vec3(0., -0.25, -0.6),
+        grabbable: false,
+        ..Default::default()
+    }
+}
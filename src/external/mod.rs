@@ -0,0 +1,522 @@
+// Lets other applications show their own surfaces as overlays.
+//
+// Clients connect to $XDG_RUNTIME_DIR/wlx-overlay-x-ext.sock and send one JSON object per line.
+// File descriptors are passed with SCM_RIGHTS alongside the line that needs them.
+//
+//   {"msg":"hello","name":"clock","width":512,"height":256}
+//       Creates the overlay "ext:clock", or takes it over again after a reconnect.
+//       Width and height go from 1 to 8192.
+//   {"msg":"shm","format":<fourcc>,"stride":2048}                       + 1 fd
+//   {"msg":"dmabuf","format":<fourcc>,"modifier":0,"planes":[{"offset":0,"stride":2048}]}
+//                                                                       + 1 fd per plane
+//       Sets the buffer the overlay shows. Formats: ARGB8888, XRGB8888, ABGR8888, XBGR8888.
+//   {"msg":"frame"}
+//       The buffer has new contents.
+//   {"msg":"visible","visible":true}
+//
+// The overlay sends back pointer events, with x and y from 0 to 1:
+//   {"event":"hover","x":0.5,"y":0.5}
+//   {"event":"button","pressed":true,"x":0.5,"y":0.5}
+//   {"event":"scroll","delta":-0.4}
+//   {"event":"leave"}
+//
+// When the client disconnects, its overlay is hidden.
+
+use std::{
+    collections::VecDeque,
+    io::ErrorKind,
+    mem::size_of,
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use glam::Vec3;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use stereokit::{SkDraw, StereoKitMultiThread, Tex};
+
+use crate::{
+    desktop::frame::{
+        memfd_fits, texture_load_dmabuf, texture_load_memfd, DmabufFrame, FrameFormat, FramePlane,
+        MemFdFrame,
+    },
+    gl::egl::{DRM_FORMAT_ABGR8888, DRM_FORMAT_ARGB8888, DRM_FORMAT_XBGR8888, DRM_FORMAT_XRGB8888},
+    interactions::{InteractionHandler, PointerHit},
+    overlay::{OverlayBackend, OverlayData, OverlayRenderer, RelativeTo},
+    AppSession, AppState, TASKS,
+};
+
+const NAME_PREFIX: &str = "ext:";
+
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Deserialize)]
+#[serde(tag = "msg", rename_all = "snake_case")]
+enum ClientMessage {
+    Hello {
+        name: String,
+        width: u32,
+        height: u32,
+    },
+    Shm {
+        format: u32,
+        stride: i32,
+    },
+    Dmabuf {
+        format: u32,
+        modifier: u64,
+        planes: Vec<PlaneInfo>,
+    },
+    Frame,
+    Visible {
+        visible: bool,
+    },
+}
+
+#[derive(Deserialize)]
+struct PlaneInfo {
+    offset: u32,
+    stride: i32,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum ServerEvent {
+    Hover { x: f32, y: f32 },
+    Button { pressed: bool, x: f32, y: f32 },
+    Scroll { delta: f32 },
+    Leave,
+}
+
+enum ExternalBuffer {
+    Shm(MemFdFrame),
+    Dmabuf(DmabufFrame),
+}
+
+impl Drop for ExternalBuffer {
+    fn drop(&mut self) {
+        // DmabufFrame closes its own fds
+        if let ExternalBuffer::Shm(frame) = self {
+            unsafe { libc::close(frame.plane.fd) };
+        }
+    }
+}
+
+#[derive(Default)]
+struct ExternalFrame {
+    size: Option<(u32, u32)>,
+    buffer: Option<ExternalBuffer>,
+    dirty: bool,
+}
+
+// Events not yet taken by a slow client are dropped past this, whole lines at a time
+const MAX_QUEUED_EVENTS: usize = 64 * 1024;
+
+// Largest width or height a client may ask for, each is a texture of that size
+const MAX_SIZE: u32 = 8192;
+
+struct ExternalBackend {
+    frame: Arc<Mutex<ExternalFrame>>,
+    events: UnixStream,
+    // the part of the event lines the socket didn't take yet
+    queued: Vec<u8>,
+}
+
+pub fn socket_path() -> PathBuf {
+    let mut path = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    path.push("wlx-overlay-x-ext.sock");
+    path
+}
+
+pub fn start_external_server() {
+    // also called after the compositor comes back, the listener may well still be up
+    if LISTENING.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let path = socket_path();
+    let _ = std::fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to listen on {}: {}", path.to_string_lossy(), e);
+            LISTENING.store(false, Ordering::Relaxed);
+            return;
+        }
+    };
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    thread::spawn(move || handle_client(stream));
+                }
+                Err(e) => {
+                    error!("Stopped accepting external overlays: {}", e);
+                    LISTENING.store(false, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
+    });
+}
+
+fn handle_client(stream: UnixStream) {
+    let mut name: Option<String> = None;
+    let frame = Arc::new(Mutex::new(ExternalFrame::default()));
+
+    let mut pending = Vec::new();
+    let mut fds = VecDeque::new();
+    let mut buf = [0u8; 4096];
+
+    loop {
+        let len = match recv_with_fds(stream.as_raw_fd(), &mut buf, &mut fds) {
+            Ok(0) | Err(_) => break,
+            Ok(len) => len,
+        };
+        pending.extend_from_slice(&buf[..len]);
+
+        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let msg = match serde_json::from_slice::<ClientMessage>(&line) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    warn!("External overlay: invalid message: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = handle_message(msg, &stream, &mut name, &frame, &mut fds) {
+                warn!("External overlay: {}", e);
+            }
+        }
+    }
+
+    for fd in fds {
+        unsafe { libc::close(fd) };
+    }
+    if let Some(name) = name {
+        info!("{}: Client disconnected", name);
+        set_visible(name, false);
+    }
+}
+
+fn handle_message(
+    msg: ClientMessage,
+    stream: &UnixStream,
+    name: &mut Option<String>,
+    frame: &Arc<Mutex<ExternalFrame>>,
+    fds: &mut VecDeque<RawFd>,
+) -> Result<(), String> {
+    match msg {
+        ClientMessage::Hello {
+            name: client_name,
+            width,
+            height,
+        } => {
+            if width == 0 || height == 0 || width > MAX_SIZE || height > MAX_SIZE {
+                return Err(format!("hello with a size of {}x{}", width, height));
+            }
+            // stays blocking for the reads here, events are sent with MSG_DONTWAIT
+            let events = stream.try_clone().map_err(|e| e.to_string())?;
+            let overlay_name = format!("{}{}", NAME_PREFIX, client_name);
+            info!("{}: Client connected", overlay_name);
+            register(overlay_name.clone(), width, height, frame.clone(), events);
+            *name = Some(overlay_name);
+        }
+        ClientMessage::Shm { format, stride } => {
+            check_format(format)?;
+            let fd = fds.pop_front().ok_or("shm needs a file descriptor")?;
+            let (w, h) = overlay_size(frame)?;
+            // wrapped right away, so the fd is closed if it's turned down
+            let buffer = ExternalBuffer::Shm(MemFdFrame {
+                fmt: FrameFormat {
+                    w,
+                    h,
+                    format,
+                    modifier: 0,
+                },
+                plane: FramePlane {
+                    fd,
+                    offset: 0,
+                    stride,
+                },
+            });
+            if !matches!(&buffer, ExternalBuffer::Shm(shm) if memfd_fits(fd, &shm.fmt, stride)) {
+                return Err(format!("shm of stride {} doesn't hold {}x{}", stride, w, h));
+            }
+            set_buffer(frame, buffer);
+        }
+        ClientMessage::Dmabuf {
+            format,
+            modifier,
+            planes,
+        } => {
+            check_format(format)?;
+            if planes.is_empty() || planes.len() > 4 || fds.len() < planes.len() {
+                return Err("dmabuf needs 1 to 4 planes, each with a file descriptor".into());
+            }
+            let (w, h) = overlay_size(frame)?;
+            let mut dmabuf = DmabufFrame {
+                fmt: FrameFormat {
+                    w,
+                    h,
+                    format,
+                    modifier,
+                },
+                num_planes: planes.len(),
+                ..Default::default()
+            };
+            for (i, plane) in planes.iter().enumerate() {
+                dmabuf.planes[i] = FramePlane {
+                    fd: fds.pop_front().unwrap_or(-1),
+                    offset: plane.offset,
+                    stride: plane.stride,
+                };
+            }
+            set_buffer(frame, ExternalBuffer::Dmabuf(dmabuf));
+        }
+        ClientMessage::Frame => {
+            if let Ok(mut frame) = frame.lock() {
+                frame.dirty = true;
+            }
+        }
+        ClientMessage::Visible { visible } => {
+            let name = name.clone().ok_or("visible before hello")?;
+            set_visible(name, visible);
+        }
+    }
+    Ok(())
+}
+
+fn check_format(format: u32) -> Result<(), String> {
+    match format {
+        DRM_FORMAT_ARGB8888 | DRM_FORMAT_XRGB8888 | DRM_FORMAT_ABGR8888 | DRM_FORMAT_XBGR8888 => {
+            Ok(())
+        }
+        _ => Err(format!("Unsupported format 0x{:x}", format)),
+    }
+}
+
+// Buffers must match the size given in hello
+fn overlay_size(frame: &Arc<Mutex<ExternalFrame>>) -> Result<(u32, u32), String> {
+    frame
+        .lock()
+        .ok()
+        .and_then(|frame| frame.size)
+        .ok_or_else(|| "buffer before hello".to_string())
+}
+
+fn set_buffer(frame: &Arc<Mutex<ExternalFrame>>, buffer: ExternalBuffer) {
+    if let Ok(mut frame) = frame.lock() {
+        frame.buffer = Some(buffer);
+        frame.dirty = true;
+    }
+}
+
+fn set_visible(name: String, visible: bool) {
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(move |_sk, _app, overlays| {
+            if let Some(overlay) = overlays.iter_mut().find(|o| *o.name == *name) {
+                overlay.want_visible = visible;
+            }
+        }));
+    }
+}
+
+// Adds the overlay, or gives an existing one of the same name to the new connection
+fn register(
+    name: String,
+    width: u32,
+    height: u32,
+    frame: Arc<Mutex<ExternalFrame>>,
+    events: UnixStream,
+) {
+    if let Ok(mut frame) = frame.lock() {
+        frame.size = Some((width, height));
+        frame.buffer = None;
+    }
+
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(move |_sk, app, overlays| {
+            let backend = Box::new(ExternalBackend {
+                frame,
+                events,
+                queued: Vec::new(),
+            });
+            if let Some(overlay) = overlays.iter_mut().find(|o| *o.name == *name) {
+                overlay.hide(app);
+                overlay.release_gfx(app);
+                overlay.backend = backend;
+                overlay.size = (width as _, height as _);
+                overlay.want_visible = true;
+                return;
+            }
+            overlays.push(create_external_overlay(name, width, height, backend));
+        }));
+    }
+}
+
+fn create_external_overlay(
+    name: String,
+    width: u32,
+    height: u32,
+    backend: Box<dyn OverlayBackend>,
+) -> OverlayData {
+    OverlayData {
+        name: name.into(),
+        size: (width as _, height as _),
+        width: 0.5,
+        backend,
+        want_visible: true,
+        grabbable: true,
+        relative_to: RelativeTo::None,
+        spawn_point: Vec3::NEG_Z * 0.8,
+        ..Default::default()
+    }
+}
+
+impl ExternalBackend {
+    fn send(&mut self, event: ServerEvent) {
+        let Ok(mut json) = serde_json::to_string(&event) else {
+            return;
+        };
+        json.push('\n');
+        // a slow client misses events rather than stalling the frame
+        if self.queued.len() + json.len() <= MAX_QUEUED_EVENTS {
+            self.queued.extend_from_slice(json.as_bytes());
+        }
+        self.flush();
+    }
+
+    // Writes what the socket takes, keeping the rest so lines are never cut short
+    fn flush(&mut self) {
+        while !self.queued.is_empty() {
+            let sent = unsafe {
+                libc::send(
+                    self.events.as_raw_fd(),
+                    self.queued.as_ptr() as _,
+                    self.queued.len(),
+                    libc::MSG_DONTWAIT | libc::MSG_NOSIGNAL,
+                )
+            };
+            match sent {
+                n if n > 0 => {
+                    self.queued.drain(..n as usize);
+                }
+                n if n < 0 && std::io::Error::last_os_error().kind() == ErrorKind::Interrupted => {}
+                // the socket is full or gone, the rest waits for the next frame
+                _ => return,
+            }
+        }
+    }
+}
+
+impl OverlayBackend for ExternalBackend {}
+
+impl OverlayRenderer for ExternalBackend {
+    fn init(&mut self, _sk: &SkDraw, _app: &mut AppState) {
+        if let Ok(mut frame) = self.frame.lock() {
+            frame.dirty = true;
+        }
+    }
+    fn pause(&mut self, _app: &mut AppState) {}
+    fn resume(&mut self, _app: &mut AppState) {}
+    fn render(&mut self, sk: &SkDraw, tex: &Tex, _app: &mut AppState) {
+        self.flush();
+        let Ok(mut frame) = self.frame.lock() else {
+            return;
+        };
+        if !frame.dirty {
+            return;
+        }
+        frame.dirty = false;
+
+        let handle = unsafe { sk.tex_get_surface(tex) as usize as u32 };
+        match frame.buffer.as_ref() {
+            Some(ExternalBuffer::Shm(shm)) => texture_load_memfd(handle, shm),
+            Some(ExternalBuffer::Dmabuf(dmabuf)) => {
+                texture_load_dmabuf(handle, dmabuf);
+            }
+            None => {}
+        }
+    }
+}
+
+impl InteractionHandler for ExternalBackend {
+    fn on_hover(&mut self, hit: &PointerHit) {
+        if hit.primary {
+            self.send(ServerEvent::Hover {
+                x: hit.uv.x,
+                y: hit.uv.y,
+            });
+        }
+    }
+    fn on_left(&mut self, _hand: usize) {
+        self.send(ServerEvent::Leave);
+    }
+    fn on_pointer(&mut self, _session: &AppSession, hit: &PointerHit, pressed: bool) {
+        self.send(ServerEvent::Button {
+            pressed,
+            x: hit.uv.x,
+            y: hit.uv.y,
+        });
+    }
+    fn on_scroll(&mut self, _hit: &PointerHit, delta: f32) {
+        self.send(ServerEvent::Scroll { delta });
+    }
+}
+
+// recv() that also collects file descriptors passed with SCM_RIGHTS
+fn recv_with_fds(
+    socket: RawFd,
+    buf: &mut [u8],
+    fds: &mut VecDeque<RawFd>,
+) -> std::io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as _,
+        iov_len: buf.len(),
+    };
+    // u64 for alignment of the cmsg headers
+    let mut control = [0u64; 32];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as _;
+    msg.msg_controllen = size_of::<[u64; 32]>() as _;
+
+    let len = loop {
+        let len = unsafe { libc::recvmsg(socket, &mut msg, libc::MSG_CMSG_CLOEXEC) };
+        if len >= 0 {
+            break len;
+        }
+        let e = std::io::Error::last_os_error();
+        if e.kind() != ErrorKind::Interrupted {
+            return Err(e);
+        }
+    };
+
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                let count =
+                    ((*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize) / size_of::<RawFd>();
+                for i in 0..count {
+                    fds.push_back(data.add(i).read_unaligned());
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    Ok(len as usize)
+}
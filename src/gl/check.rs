@@ -0,0 +1,133 @@
+use std::{
+    collections::HashMap,
+    panic::Location,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use gles31::{
+    glGetError, GL_INVALID_ENUM, GL_INVALID_FRAMEBUFFER_OPERATION, GL_INVALID_OPERATION,
+    GL_INVALID_VALUE, GL_NO_ERROR, GL_OUT_OF_MEMORY,
+};
+use log::error;
+use once_cell::sync::Lazy;
+
+use super::context;
+
+// KHR_robustness, core in GLES 3.2
+const GL_CONTEXT_LOST: u32 = 0x0507;
+
+// A call site that keeps failing is logged again at most this often, with how many it was
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+// glGetError keeps returning flags until they're all read; drivers have a handful at most
+const MAX_FLAGS: usize = 8;
+
+static ERROR_COUNT: AtomicU64 = AtomicU64::new(0);
+
+struct SiteStats {
+    last_log: Option<Instant>,
+    // seen since the last log line
+    unlogged: u64,
+}
+
+static SITES: Lazy<Mutex<HashMap<&'static Location<'static>, SiteStats>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn error_name(error: u32) -> &'static str {
+    match error {
+        GL_INVALID_ENUM => "GL_INVALID_ENUM",
+        GL_INVALID_VALUE => "GL_INVALID_VALUE",
+        GL_INVALID_OPERATION => "GL_INVALID_OPERATION",
+        GL_INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        GL_OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        GL_CONTEXT_LOST => "GL_CONTEXT_LOST",
+        _ => "unknown error",
+    }
+}
+
+// After the calls before it, in debug builds. glGetError makes drivers with threaded dispatch
+// wait for their queue, so release builds only sample once per frame, see check_frame.
+#[track_caller]
+pub fn check_gl() -> bool {
+    if cfg!(debug_assertions) {
+        read_errors()
+    } else {
+        true
+    }
+}
+
+// For the few calls whose failure is acted on, such as a DMA-Buf import; checks in any build
+#[track_caller]
+pub fn check_gl_result() -> bool {
+    read_errors()
+}
+
+// Once per frame, catches what release builds no longer check after each call
+#[track_caller]
+pub fn check_frame() {
+    read_errors();
+}
+
+// Reads the GL error flags, logging any against the caller's file and line. Returns whether
+// there were none.
+#[track_caller]
+fn read_errors() -> bool {
+    let mut errors = [GL_NO_ERROR; MAX_FLAGS];
+    let mut num_errors = 0;
+    while num_errors < MAX_FLAGS {
+        let error = unsafe { glGetError() };
+        if error == GL_NO_ERROR {
+            break;
+        }
+        errors[num_errors] = error;
+        num_errors += 1;
+        if error == GL_CONTEXT_LOST {
+            context::mark_lost("GL_CONTEXT_LOST");
+            break;
+        }
+    }
+    if num_errors == 0 {
+        return true;
+    }
+    ERROR_COUNT.fetch_add(num_errors as u64, Ordering::Relaxed);
+
+    let site = Location::caller();
+    let Ok(mut sites) = SITES.lock() else {
+        return false;
+    };
+    let now = Instant::now();
+    let stats = sites.entry(site).or_insert(SiteStats {
+        last_log: None,
+        unlogged: 0,
+    });
+    stats.unlogged += num_errors as u64;
+    if stats
+        .last_log
+        .is_some_and(|last| now.duration_since(last) < LOG_INTERVAL)
+    {
+        return false;
+    }
+
+    let names: Vec<_> = errors[..num_errors]
+        .iter()
+        .map(|e| format!("{} (0x{:x})", error_name(*e), e))
+        .collect();
+    error!(
+        "GL error at {}: {}, {} there since the last report",
+        site,
+        names.join(", "),
+        stats.unlogged
+    );
+    stats.last_log = Some(now);
+    stats.unlogged = 0;
+    false
+}
+
+// GL errors seen since startup, for the perf HUD
+pub fn error_count() -> u64 {
+    ERROR_COUNT.load(Ordering::Relaxed)
+}
@@ -0,0 +1,99 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use gles31::GL_NO_ERROR;
+use log::{error, warn};
+use stereokit::SkDraw;
+
+use crate::{
+    desktop::frame,
+    gl::{
+        egl::{
+            eglGetCurrentContext, eglGetCurrentDisplay, glGetGraphicsResetStatus, EGL_CONTEXT,
+            EGL_DISPLAY,
+        },
+        pool::TexturePool,
+        GlRenderer,
+    },
+    overlay::OverlayData,
+    perf,
+    toast::{self, NotifyLevel},
+    AppState,
+};
+
+// Bumped each time our resources are made again in a new context. GL objects remember the one
+// they were made in and don't delete their handles once it's gone, those may name new objects.
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+
+static LOST: AtomicBool = AtomicBool::new(false);
+
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+pub fn generation() -> u32 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+pub fn is_current(generation: u32) -> bool {
+    generation == self::generation()
+}
+
+// Called where EGL or GL report the context gone; the user is told at the next update()
+pub fn mark_lost(reason: &str) {
+    if !LOST.swap(true, Ordering::Relaxed) {
+        error!("GL context lost: {}", reason);
+    }
+}
+
+// Once per frame, before the overlays render. The reset status is only ever set for a context
+// created with reset notification, so mostly it's GL_CONTEXT_LOST and EGL_CONTEXT_LOST that
+// get here. A lost context can't be rebuilt in, that waits for StereoKit to make a new one
+// current, which is when everything is made again.
+pub fn update(sk: &SkDraw, app: &mut AppState, overlays: &mut [OverlayData]) {
+    let status = glGetGraphicsResetStatus();
+    if status != GL_NO_ERROR {
+        mark_lost(&format!("graphics reset status 0x{:x}", status));
+    }
+
+    let current = eglGetCurrentContext() as usize;
+    if current != 0 && current != EGL_CONTEXT.load(Ordering::Relaxed) {
+        EGL_CONTEXT.store(current, Ordering::Relaxed);
+        EGL_DISPLAY.store(eglGetCurrentDisplay() as _, Ordering::Relaxed);
+        LOST.store(false, Ordering::Relaxed);
+        REPORTED.store(false, Ordering::Relaxed);
+        rebuild(sk, app, overlays);
+        return;
+    }
+
+    if !LOST.load(Ordering::Relaxed) || REPORTED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    toast::notify(
+        NotifyLevel::Error,
+        "The graphics context was lost, overlays come back once StereoKit makes a new one",
+    );
+}
+
+// Everything made through GL is made again: the renderer's shaders and buffers, the glyph
+// atlases, the timer queries, and each overlay's textures along with what its backend drew
+fn rebuild(sk: &SkDraw, app: &mut AppState, overlays: &mut [OverlayData]) {
+    warn!("New GL context, rebuilding GL resources");
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+
+    app.gl = GlRenderer::new();
+    app.fc.context_lost();
+    perf::context_lost();
+
+    for overlay in overlays.iter_mut() {
+        overlay.release_gfx(app);
+    }
+    // what the released textures hold was drawn in the old context
+    app.tex_pool = TexturePool::default();
+    frame::context_lost();
+    for overlay in overlays.iter_mut().filter(|o| o.visible) {
+        overlay.create_gfx(sk, app);
+    }
+
+    toast::notify(
+        NotifyLevel::Warn,
+        "The graphics context was replaced, overlays were redrawn",
+    );
+}
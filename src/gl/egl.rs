@@ -18,6 +18,7 @@ pub type EGLDisplay = *const u8;
 
 pub const EGL_TRUE: EGLenum = 1;
 pub const EGL_SUCCESS: EGLenum = 0x3000;
+pub const EGL_CONTEXT_LOST: EGLenum = 0x300E;
 pub const EGL_LINUX_DMABUF_EXT: EGLenum = 0x3270;
 
 const EGL_PLATFORM_WAYLAND_EXT: EGLenum = 0x31D8;
@@ -28,6 +29,8 @@ pub const DRM_FORMAT_ARGB8888: FourCC = 0x34325241;
 pub const DRM_FORMAT_ABGR8888: FourCC = 0x34324241;
 pub const DRM_FORMAT_XRGB8888: FourCC = 0x34325258;
 pub const DRM_FORMAT_XBGR8888: FourCC = 0x34324258;
+pub const DRM_FORMAT_ABGR16161616F: FourCC = 0x48344241;
+pub const DRM_FORMAT_XBGR16161616F: FourCC = 0x48344258;
 
 #[allow(non_upper_case_globals)]
 static glEGLImageTargetTexture2DOES_p: AtomicUsize = AtomicUsize::new(0);
@@ -42,6 +45,22 @@ pub fn glEGLImageTargetTexture2DOES(target: i32, egl_image: EGLImage) {
     }
 }
 
+#[allow(non_upper_case_globals)]
+static glGetGraphicsResetStatus_p: AtomicUsize = AtomicUsize::new(0);
+
+// GL_NO_ERROR when the driver doesn't support robustness, it can't tell us then
+#[allow(non_snake_case)]
+pub fn glGetGraphicsResetStatus() -> u32 {
+    let u = glGetGraphicsResetStatus_p.load(Ordering::Relaxed);
+    if u == 0 {
+        return 0;
+    }
+    unsafe {
+        let _func_p: unsafe extern "C" fn() -> u32 = core::mem::transmute(u);
+        _func_p()
+    }
+}
+
 #[allow(non_upper_case_globals)]
 static eglCreateImage_p: AtomicUsize = AtomicUsize::new(0);
 
@@ -100,6 +119,34 @@ pub fn eglGetError() -> EGLenum {
     }
 }
 
+#[allow(non_upper_case_globals)]
+static eglGetCurrentContext_p: AtomicUsize = AtomicUsize::new(0);
+
+#[inline]
+#[allow(non_snake_case)]
+pub fn eglGetCurrentContext() -> EGLContext {
+    let u = eglGetCurrentContext_p.load(Ordering::Relaxed);
+    debug_assert_ne!(u, 0);
+    unsafe {
+        let _func_p: unsafe extern "C" fn() -> EGLContext = core::mem::transmute(u);
+        _func_p()
+    }
+}
+
+#[allow(non_upper_case_globals)]
+static eglGetCurrentDisplay_p: AtomicUsize = AtomicUsize::new(0);
+
+#[inline]
+#[allow(non_snake_case)]
+pub fn eglGetCurrentDisplay() -> EGLDisplay {
+    let u = eglGetCurrentDisplay_p.load(Ordering::Relaxed);
+    debug_assert_ne!(u, 0);
+    unsafe {
+        let _func_p: unsafe extern "C" fn() -> EGLDisplay = core::mem::transmute(u);
+        _func_p()
+    }
+}
+
 #[allow(non_upper_case_globals)]
 static eglQueryDmaBufFormatsEXT_p: AtomicUsize = AtomicUsize::new(0);
 
@@ -175,6 +222,18 @@ pub fn gl_init(sk: &stereokit::SkSingle) {
         eglQueryDmaBufModifiersEXT_p.store(p0 as usize, Ordering::Relaxed);
         debug_assert_ne!(p0, 0 as _);
 
+        // core in GLES 3.2, an extension before that
+        let p0 = [
+            b"glGetGraphicsResetStatus\0".as_slice(),
+            b"glGetGraphicsResetStatusEXT\0".as_slice(),
+            b"glGetGraphicsResetStatusKHR\0".as_slice(),
+        ]
+        .iter()
+        .map(|name| proc_fn(name.as_ptr()))
+        .find(|p| !p.is_null())
+        .unwrap_or(std::ptr::null());
+        glGetGraphicsResetStatus_p.store(p0 as usize, Ordering::Relaxed);
+
         let egl_context = sk.backend_opengl_egl_get_context();
         EGL_CONTEXT.store(egl_context as _, Ordering::Relaxed);
 
@@ -213,5 +272,15 @@ pub fn gl_init(sk: &stereokit::SkSingle) {
         let error_fn: Symbol<unsafe extern "C" fn() -> i32> =
             lib.get(b"eglGetError").expect("Unable to load eglGetError");
         eglGetError_p.store(error_fn.into_raw().into_raw() as _, Ordering::Relaxed);
+
+        let context_fn: Symbol<unsafe extern "C" fn() -> EGLContext> = lib
+            .get(b"eglGetCurrentContext")
+            .expect("Unable to load eglGetCurrentContext");
+        eglGetCurrentContext_p.store(context_fn.into_raw().into_raw() as _, Ordering::Relaxed);
+
+        let display_fn: Symbol<unsafe extern "C" fn() -> EGLDisplay> = lib
+            .get(b"eglGetCurrentDisplay")
+            .expect("Unable to load eglGetCurrentDisplay");
+        eglGetCurrentDisplay_p.store(display_fn.into_raw().into_raw() as _, Ordering::Relaxed);
     }
 }
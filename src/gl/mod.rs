@@ -1,30 +1,48 @@
 use std::{mem::size_of, ptr::null};
 
-use glam::Vec3;
+use glam::{Mat3, Vec3};
 use gles31::{
     glActiveTexture, glAttachShader, glBindBuffer, glBindFramebuffer, glBindTexture,
     glBindVertexArray, glBlendEquationSeparate, glBlendFuncSeparate, glBufferData,
     glCheckFramebufferStatus, glClear, glClearColor, glColorMask, glCompileShader, glCreateProgram,
     glCreateShader, glDeleteBuffers, glDeleteFramebuffers, glDeleteProgram, glDeleteShader,
-    glDeleteTextures, glDeleteVertexArrays, glDetachShader, glDrawBuffers, glDrawElements,
-    glEnable, glEnableVertexAttribArray, glFramebufferTexture2D, glGenBuffers, glGenFramebuffers,
-    glGenTextures, glGenVertexArrays, glGetError, glGetShaderInfoLog, glGetShaderiv,
-    glGetUniformLocation, glLinkProgram, glShaderSource, glTexImage2D, glTexParameteri,
-    glUniform1i, glUniform4f, glUseProgram, glVertexAttribPointer, glViewport, GL_ARRAY_BUFFER,
-    GL_BLEND, GL_CLAMP_TO_EDGE, GL_COLOR_ATTACHMENT0, GL_COLOR_BUFFER_BIT, GL_COMPILE_STATUS,
-    GL_DRAW_FRAMEBUFFER, GL_ELEMENT_ARRAY_BUFFER, GL_FALSE, GL_FLOAT, GL_FRAGMENT_SHADER,
-    GL_FRAMEBUFFER_COMPLETE, GL_FUNC_ADD, GL_INFO_LOG_LENGTH, GL_LINEAR, GL_NO_ERROR, GL_ONE,
-    GL_ONE_MINUS_SRC_ALPHA, GL_PIXEL_PACK_BUFFER, GL_PIXEL_UNPACK_BUFFER, GL_RGBA, GL_SRC_ALPHA,
-    GL_SRGB8_ALPHA8, GL_STATIC_DRAW, GL_TEXTURE0, GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER,
+    glDeleteTextures, glDeleteVertexArrays, glDetachShader, glDisable, glDrawBuffers,
+    glDrawElements, glEnable, glEnableVertexAttribArray, glFramebufferTexture2D, glGenBuffers,
+    glGenFramebuffers, glGenTextures, glGenVertexArrays, glGenerateMipmap, glGetError,
+    glGetShaderInfoLog, glGetShaderiv, glGetUniformLocation, glLinkProgram, glPixelStorei,
+    glReadPixels, glScissor, glShaderSource, glTexImage2D, glTexParameterf, glTexParameteri,
+    glUniform1i, glUniformMatrix3fv, glUseProgram, glVertexAttribPointer, glViewport,
+    GL_ARRAY_BUFFER, GL_BLEND, GL_CLAMP_TO_EDGE, GL_COLOR_ATTACHMENT0, GL_COLOR_BUFFER_BIT,
+    GL_COMPILE_STATUS, GL_DRAW_FRAMEBUFFER, GL_ELEMENT_ARRAY_BUFFER, GL_FALSE, GL_FLOAT,
+    GL_FRAGMENT_SHADER, GL_FRAMEBUFFER_COMPLETE, GL_FUNC_ADD, GL_INFO_LOG_LENGTH, GL_LINEAR,
+    GL_LINEAR_MIPMAP_LINEAR, GL_ONE, GL_ONE_MINUS_SRC_ALPHA, GL_PACK_ALIGNMENT,
+    GL_PIXEL_PACK_BUFFER, GL_PIXEL_UNPACK_BUFFER, GL_READ_FRAMEBUFFER, GL_RGBA, GL_SCISSOR_TEST,
+    GL_SRC_ALPHA, GL_STATIC_DRAW, GL_TEXTURE0, GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER,
     GL_TEXTURE_MIN_FILTER, GL_TEXTURE_WRAP_S, GL_TEXTURE_WRAP_T, GL_TRIANGLES, GL_UNSIGNED_BYTE,
     GL_UNSIGNED_INT, GL_VERTEX_SHADER,
 };
 use stereokit::{SkDraw, StereoKitMultiThread};
 
+use crate::config::CvdFilter;
+
+use self::check::check_gl;
+
+pub mod check;
+pub mod context;
 pub mod egl;
+pub mod pool;
 
 pub const PANEL_SHADER_BYTES: &[u8] = include_bytes!("shaders/unlit_simula.sks");
 
+// The same shader with its GLSL vertex stages computing
+//   uv = input_uv * uv_scale + uv_offset * view_index
+// so uv_offset moves the right eye's view to its half of stereo content. Its SPIR-V and HLSL
+// stages are the mono ones, StereoKit's GLES backend only reads the GLSL.
+pub const PANEL_STEREO_SHADER_BYTES: &[u8] = include_bytes!("shaders/unlit_simula_stereo.sks");
+
+// EXT_texture_filter_anisotropic
+const GL_TEXTURE_MAX_ANISOTROPY_EXT: u32 = 0x84FE;
+
 // --- GlTexture ---
 
 pub struct GlTexture {
@@ -33,49 +51,62 @@ pub struct GlTexture {
     pub height: u32,
     pub format: i32,
     pub target: u32,
+    // the context::generation() it was made in
+    pub context: u32,
 }
 
 impl GlTexture {
-    pub fn new() -> GlTexture {
+    // format: the internal format it will be allocated with
+    pub fn new(format: i32) -> GlTexture {
         let mut handle: u32 = 0;
 
         unsafe {
             glGenTextures(1, &mut handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
+
+            glBindTexture(GL_TEXTURE_2D, handle);
+            check_gl();
         }
 
         let tex = GlTexture {
             handle,
             width: 0,
             height: 0,
-            format: GL_SRGB8_ALPHA8 as i32,
+            format,
             target: GL_TEXTURE_2D,
+            context: context::generation(),
         };
 
         unsafe {
             glTexParameteri(tex.target, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE as i32);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
             glTexParameteri(tex.target, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE as i32);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
             glTexParameteri(tex.target, GL_TEXTURE_MIN_FILTER, GL_LINEAR as i32);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
             glTexParameteri(tex.target, GL_TEXTURE_MAG_FILTER, GL_LINEAR as i32);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
 
         tex
     }
 
-    pub fn from_handle(handle: u32, width: u32, height: u32) -> GlTexture {
+    pub fn from_handle(handle: u32, width: u32, height: u32, format: i32) -> GlTexture {
         GlTexture {
             handle,
             width,
             height,
-            format: GL_SRGB8_ALPHA8 as i32,
+            format,
             target: GL_TEXTURE_2D,
+            context: context::generation(),
         }
     }
 
+    // Made in a context that has since been lost, the handle means nothing now
+    pub fn is_stale(&self) -> bool {
+        !context::is_current(self.context)
+    }
+
     pub fn allocate_empty(&mut self, width: u32, height: u32, format: i32) {
         self.allocate(width, height, format, std::ptr::null());
     }
@@ -87,10 +118,10 @@ impl GlTexture {
 
         unsafe {
             glBindBuffer(GL_PIXEL_UNPACK_BUFFER, 0);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glBindBuffer(GL_PIXEL_PACK_BUFFER, 0);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glTexImage2D(
                 self.target,
@@ -103,17 +134,17 @@ impl GlTexture {
                 GL_UNSIGNED_BYTE,
                 data as _,
             );
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 
     pub fn bind(&self, slot: u32) {
         unsafe {
             glActiveTexture(GL_TEXTURE0 + slot);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glBindTexture(self.target, self.handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 
@@ -128,9 +159,13 @@ impl GlTexture {
 
 impl Drop for GlTexture {
     fn drop(&mut self) {
+        // the handle may name something else in the new context
+        if !context::is_current(self.context) {
+            return;
+        }
         unsafe {
             glDeleteTextures(1, &self.handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 }
@@ -139,12 +174,22 @@ impl Drop for GlTexture {
 
 const UNIFORM_TEX0: usize = 0;
 const UNIFORM_COL0: usize = 1;
-
-const UNIFORM_NAMES: [&str; 2] = ["uTexture0\0", "uColor\0"];
+const UNIFORM_SIM: usize = 2;
+const UNIFORM_SHIFT: usize = 3;
+const UNIFORM_LINEAR_IN: usize = 4;
+
+const UNIFORM_NAMES: [&str; 5] = [
+    "uTexture0\0",
+    "uColor\0",
+    "uSim\0",
+    "uShift\0",
+    "uLinearIn\0",
+];
 
 pub struct GlShader {
     pub handle: u32,
     pub locations: Vec<i32>,
+    pub context: u32,
 }
 
 impl GlShader {
@@ -154,29 +199,30 @@ impl GlShader {
 
         unsafe {
             let program = glCreateProgram();
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glAttachShader(program, vert);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
             glAttachShader(program, frag);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glLinkProgram(program);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glDetachShader(program, vert);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
             glDetachShader(program, frag);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glDeleteShader(vert);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
             glDeleteShader(frag);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             GlShader {
                 handle: program,
-                locations: vec![-1, -1],
+                locations: vec![-1; UNIFORM_NAMES.len()],
+                context: context::generation(),
             }
         }
     }
@@ -184,7 +230,7 @@ impl GlShader {
     fn load_shader(shader_type: u32, src: &str) -> u32 {
         unsafe {
             let shader = glCreateShader(shader_type);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glShaderSource(
                 shader,
@@ -192,10 +238,10 @@ impl GlShader {
                 &src.as_ptr() as *const *const u8,
                 &(src.len() as i32) as *const _,
             );
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glCompileShader(shader);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             let mut status = 0i32;
             glGetShaderiv(shader, GL_COMPILE_STATUS, &mut status);
@@ -220,7 +266,7 @@ impl GlShader {
     pub fn use_shader(&self) {
         unsafe {
             glUseProgram(self.handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 
@@ -228,7 +274,7 @@ impl GlShader {
         unsafe {
             let name = UNIFORM_NAMES[uniform];
             let location = glGetUniformLocation(self.handle, name.as_ptr());
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
             debug_assert_ne!(location, -1);
             self.locations[uniform] = location;
         }
@@ -237,6 +283,9 @@ impl GlShader {
 
 impl Drop for GlShader {
     fn drop(&mut self) {
+        if !context::is_current(self.context) {
+            return;
+        }
         unsafe { glDeleteProgram(self.handle) };
     }
 }
@@ -245,6 +294,7 @@ impl Drop for GlShader {
 
 pub struct GlFramebuffer {
     pub handle: u32,
+    pub context: u32,
 }
 
 impl GlFramebuffer {
@@ -253,16 +303,19 @@ impl GlFramebuffer {
 
         unsafe {
             glGenFramebuffers(1, &mut handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
 
-        GlFramebuffer { handle }
+        GlFramebuffer {
+            handle,
+            context: context::generation(),
+        }
     }
 
     pub fn bind(&self, texture: u32) {
         unsafe {
             glBindFramebuffer(GL_DRAW_FRAMEBUFFER, self.handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glFramebufferTexture2D(
                 GL_DRAW_FRAMEBUFFER,
@@ -271,10 +324,10 @@ impl GlFramebuffer {
                 texture,
                 0,
             );
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glDrawBuffers(1, &GL_COLOR_ATTACHMENT0);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             let status = glCheckFramebufferStatus(GL_DRAW_FRAMEBUFFER);
             debug_assert_eq!(status, GL_FRAMEBUFFER_COMPLETE);
@@ -284,9 +337,12 @@ impl GlFramebuffer {
 
 impl Drop for GlFramebuffer {
     fn drop(&mut self) {
+        if !context::is_current(self.context) {
+            return;
+        }
         unsafe {
             glDeleteFramebuffers(1, &self.handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 }
@@ -296,6 +352,7 @@ impl Drop for GlFramebuffer {
 pub struct GlBuffer {
     pub handle: u32,
     pub buffer_type: u32,
+    pub context: u32,
 }
 
 impl GlBuffer {
@@ -303,12 +360,13 @@ impl GlBuffer {
         let mut handle = 0u32;
         unsafe {
             glGenBuffers(1, &mut handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
 
         GlBuffer {
             handle,
             buffer_type,
+            context: context::generation(),
         }
     }
 
@@ -322,30 +380,33 @@ impl GlBuffer {
                 data.as_ptr() as _,
                 GL_STATIC_DRAW,
             );
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 
     pub fn bind(&self) {
         unsafe {
             glBindBuffer(self.buffer_type, self.handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 
     pub fn unbind(&self) {
         unsafe {
             glBindBuffer(self.buffer_type, 0);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 }
 
 impl Drop for GlBuffer {
     fn drop(&mut self) {
+        if !context::is_current(self.context) {
+            return;
+        }
         unsafe {
             glDeleteBuffers(1, &self.handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 }
@@ -356,6 +417,7 @@ pub struct GlVertexArray {
     pub handle: u32,
     pub vbo: GlBuffer,
     pub ebo: GlBuffer,
+    pub context: u32,
 }
 
 impl GlVertexArray {
@@ -363,16 +425,21 @@ impl GlVertexArray {
         let mut handle = 0u32;
         unsafe {
             glGenVertexArrays(1, &mut handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
 
-        GlVertexArray { handle, vbo, ebo }
+        GlVertexArray {
+            handle,
+            vbo,
+            ebo,
+            context: context::generation(),
+        }
     }
 
     pub fn bind(&self) {
         unsafe {
             glBindVertexArray(self.handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
         self.vbo.bind();
         self.ebo.bind();
@@ -381,7 +448,7 @@ impl GlVertexArray {
     pub fn unbind(&self) {
         unsafe {
             glBindVertexArray(0);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
         self.vbo.unbind();
         self.ebo.unbind();
@@ -408,18 +475,21 @@ impl GlVertexArray {
                 vert_size * t_size as u32,
                 (offset * t_size as i32) as *const _,
             );
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
             glEnableVertexAttribArray(index);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 }
 
 impl Drop for GlVertexArray {
     fn drop(&mut self) {
+        if !context::is_current(self.context) {
+            return;
+        }
         unsafe {
             glDeleteVertexArrays(1, &self.handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 }
@@ -431,16 +501,71 @@ const FRAG_COLOR: &str = include_str!("shaders/color.frag");
 const FRAG_SPRITE: &str = include_str!("shaders/sprite.frag");
 const FRAG_GLYPH: &str = include_str!("shaders/glyph.frag");
 const FRAG_SRGB: &str = include_str!("shaders/srgb.frag");
+const FRAG_SRGB_ENCODE: &str = include_str!("shaders/srgb_encode.frag");
+const FRAG_DALTONIZE: &str = include_str!("shaders/daltonize.frag");
+
+// Machado et al. 2009, full severity, rows applied to linear RGB
+#[rustfmt::skip]
+const SIM_PROTANOPIA: [f32; 9] = [
+    0.152286, 1.052583, -0.204868,
+    0.114503, 0.786281, 0.099216,
+    -0.003882, -0.048116, 1.051998,
+];
+#[rustfmt::skip]
+const SIM_DEUTERANOPIA: [f32; 9] = [
+    0.367322, 0.860646, -0.227968,
+    0.280085, 0.672501, 0.047413,
+    -0.011820, 0.042940, 0.968881,
+];
+#[rustfmt::skip]
+const SIM_TRITANOPIA: [f32; 9] = [
+    1.255528, -0.076749, -0.178779,
+    -0.078411, 0.930809, 0.147602,
+    0.004733, 0.691367, 0.303900,
+];
+
+// Red-green errors go to green and blue, blue-yellow errors to red and green
+#[rustfmt::skip]
+const SHIFT_RED_GREEN: [f32; 9] = [
+    0.0, 0.0, 0.0,
+    0.7, 1.0, 0.0,
+    0.7, 0.0, 1.0,
+];
+#[rustfmt::skip]
+const SHIFT_BLUE_YELLOW: [f32; 9] = [
+    1.0, 0.0, 0.7,
+    0.0, 1.0, 0.7,
+    0.0, 0.0, 0.0,
+];
+
+// Quads that share a shader and texture are collected and drawn together
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BatchKind {
+    Sprite,
+    Glyph,
+    Color,
+    // sprite, converted from linear to sRGB
+    Encode,
+    // sprite, corrected for a color vision deficiency
+    Daltonize,
+}
+
+// X, Y, U, V, R, G, B, A
+const VERT_SIZE: usize = 8;
 
 pub struct GlRenderer {
     vao: GlVertexArray,
     framebuffer: GlFramebuffer,
     vertices: Vec<f32>,
     indices: Vec<u32>,
+    batch: Option<(BatchKind, u32)>,
     shader_sprite: GlShader,
     shader_glyph: GlShader,
     shader_color: GlShader,
     shader_srgb: GlShader,
+    shader_encode: GlShader,
+    shader_daltonize: GlShader,
+    clip_stack: Vec<[f32; 4]>,
     width: u32,
     height: u32,
 }
@@ -450,47 +575,45 @@ impl GlRenderer {
         let vbo = GlBuffer::new(GL_ARRAY_BUFFER);
         let ebo = GlBuffer::new(GL_ELEMENT_ARRAY_BUFFER);
 
-        #[rustfmt::skip]
-        let vertices: Vec<f32> = vec![
-        //  X   Y    U   V
-            0., 0.,  0., 0.,
-            0., 1.,  0., 1.,
-            1., 0.,  1., 0.,
-            1., 1.,  1., 1.,
-       ];
-
-        let indices: Vec<u32> = vec![2, 1, 0, 1, 2, 3];
-
-        vbo.data(&vertices);
-        ebo.data(&indices);
-
         let vao = GlVertexArray::new(vbo, ebo);
 
-        vao.vert_attrib_ptr::<f32>(0, 2, GL_FLOAT, 4, 0);
-        vao.vert_attrib_ptr::<f32>(1, 2, GL_FLOAT, 4, 2);
+        vao.vert_attrib_ptr::<f32>(0, 2, GL_FLOAT, VERT_SIZE as _, 0);
+        vao.vert_attrib_ptr::<f32>(1, 2, GL_FLOAT, VERT_SIZE as _, 2);
+        vao.vert_attrib_ptr::<f32>(2, 4, GL_FLOAT, VERT_SIZE as _, 4);
 
         let mut shader_sprite = GlShader::new(VERT_COMMON, FRAG_SPRITE);
         shader_sprite.has_uniform(UNIFORM_TEX0);
 
         let mut shader_glyph = GlShader::new(VERT_COMMON, FRAG_GLYPH);
         shader_glyph.has_uniform(UNIFORM_TEX0);
-        shader_glyph.has_uniform(UNIFORM_COL0);
 
-        let mut shader_color = GlShader::new(VERT_COMMON, FRAG_COLOR);
-        shader_color.has_uniform(UNIFORM_COL0);
+        let shader_color = GlShader::new(VERT_COMMON, FRAG_COLOR);
 
         let mut shader_srgb = GlShader::new(VERT_COMMON, FRAG_SRGB);
         shader_srgb.has_uniform(UNIFORM_TEX0);
 
+        let mut shader_encode = GlShader::new(VERT_COMMON, FRAG_SRGB_ENCODE);
+        shader_encode.has_uniform(UNIFORM_TEX0);
+
+        let mut shader_daltonize = GlShader::new(VERT_COMMON, FRAG_DALTONIZE);
+        shader_daltonize.has_uniform(UNIFORM_TEX0);
+        shader_daltonize.has_uniform(UNIFORM_SIM);
+        shader_daltonize.has_uniform(UNIFORM_SHIFT);
+        shader_daltonize.has_uniform(UNIFORM_LINEAR_IN);
+
         GlRenderer {
             vao,
             framebuffer: GlFramebuffer::new(),
-            vertices,
-            indices,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            batch: None,
             shader_sprite,
             shader_glyph,
             shader_color,
             shader_srgb,
+            shader_encode,
+            shader_daltonize,
+            clip_stack: Vec::new(),
             width: 0,
             height: 0,
         }
@@ -516,10 +639,10 @@ impl GlRenderer {
     fn begin(&mut self) {
         unsafe {
             glViewport(0, 0, self.width as _, self.height as _);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glEnable(GL_BLEND);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glBlendFuncSeparate(
                 GL_SRC_ALPHA,
@@ -527,17 +650,52 @@ impl GlRenderer {
                 GL_ONE,
                 GL_ONE_MINUS_SRC_ALPHA,
             );
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glBlendEquationSeparate(GL_FUNC_ADD, GL_FUNC_ADD);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
             glColorMask(1, 1, 1, 1);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 
-    fn use_rect(&mut self, x: f32, y: f32, w: f32, h: f32) {
+    // uv: [u0, v0, u1, v1] of the texture to map onto the rect
+    #[allow(clippy::too_many_arguments)]
+    fn push_quad(
+        &mut self,
+        kind: BatchKind,
+        texture: u32,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        uv: [f32; 4],
+        color: [f32; 4],
+    ) {
+        self.push_quad_colors(kind, texture, x, y, w, h, uv, [color; 4]);
+    }
+
+    // Same as push_quad, with a color per corner: top left, bottom left, top right, bottom right
+    // Colors are interpolated across the quad's two triangles, so only linear gradients come out
+    // exact
+    #[allow(clippy::too_many_arguments)]
+    fn push_quad_colors(
+        &mut self,
+        kind: BatchKind,
+        texture: u32,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        uv: [f32; 4],
+        colors: [[f32; 4]; 4],
+    ) {
+        if self.batch != Some((kind, texture)) {
+            self.flush();
+            self.batch = Some((kind, texture));
+        }
+
         let rw = self.width as f32;
         let rh = self.height as f32;
 
@@ -547,35 +705,57 @@ impl GlRenderer {
         let x1 = w / rw + x0;
         let y1 = h / rh + y0;
 
-        self.vertices[0] = x0;
-        self.vertices[4] = x0;
-
-        self.vertices[8] = x1;
-        self.vertices[12] = x1;
-
-        self.vertices[1] = y0;
-        self.vertices[9] = y0;
-
-        self.vertices[5] = y1;
-        self.vertices[13] = y1;
-
-        self.vao.vbo.data(&self.vertices);
+        let base = (self.vertices.len() / VERT_SIZE) as u32;
+        for ((vx, vy, u, v), color) in [
+            (x0, y0, uv[0], uv[1]),
+            (x0, y1, uv[0], uv[3]),
+            (x1, y0, uv[2], uv[1]),
+            (x1, y1, uv[2], uv[3]),
+        ]
+        .into_iter()
+        .zip(colors)
+        {
+            self.vertices.extend_from_slice(&[vx, vy, u, v]);
+            self.vertices.extend_from_slice(&color);
+        }
+        self.indices
+            .extend([2, 1, 0, 1, 2, 3].iter().map(|i| base + i));
     }
 
-    pub fn draw_sprite_full(&mut self, texture: u32) {
-        self.use_rect(0., 0., self.width as _, self.height as _);
-        self.vao.bind();
-
-        self.shader_sprite.use_shader();
+    // Draws the quads collected so far, in one call
+    fn flush(&mut self) {
+        let Some((kind, texture)) = self.batch.take() else {
+            return;
+        };
+        if self.indices.is_empty() {
+            return;
+        }
 
-        let location = self.shader_sprite.locations[UNIFORM_TEX0];
-        debug_assert_ne!(location, -1);
+        self.vao.bind();
+        self.vao.vbo.data(&self.vertices);
+        self.vao.ebo.data(&self.indices);
+
+        let shader = match kind {
+            BatchKind::Sprite => &self.shader_sprite,
+            BatchKind::Glyph => &self.shader_glyph,
+            BatchKind::Color => &self.shader_color,
+            BatchKind::Encode => &self.shader_encode,
+            BatchKind::Daltonize => &self.shader_daltonize,
+        };
+        shader.use_shader();
 
         unsafe {
-            glBindTexture(GL_TEXTURE_2D, texture);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
-            glUniform1i(location, 0);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            if kind != BatchKind::Color {
+                let location = shader.locations[UNIFORM_TEX0];
+                debug_assert_ne!(location, -1);
+
+                glActiveTexture(GL_TEXTURE0);
+                check_gl();
+                glBindTexture(GL_TEXTURE_2D, texture);
+                check_gl();
+                glUniform1i(location, 0);
+                check_gl();
+            }
 
             glDrawElements(
                 GL_TRIANGLES,
@@ -583,97 +763,282 @@ impl GlRenderer {
                 GL_UNSIGNED_INT,
                 null(),
             );
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
+
+        self.vertices.clear();
+        self.indices.clear();
     }
 
-    pub fn draw_sprite(&mut self, texture: &GlTexture, x: f32, y: f32, w: f32, h: f32) {
-        self.use_rect(x, y, w, h);
-        self.vao.bind();
+    pub fn draw_sprite_full(&mut self, texture: u32) {
+        self.push_full(BatchKind::Sprite, texture);
+    }
 
-        self.shader_sprite.use_shader();
-        texture.bind(0);
+    fn push_full(&mut self, kind: BatchKind, texture: u32) {
+        let (w, h) = (self.width as f32, self.height as f32);
+        self.push_quad(kind, texture, 0., 0., w, h, [0., 0., 1., 1.], [1.; 4]);
+    }
+
+    // Replaces the whole target with the texture, scaled to fit, without blending
+    pub fn blit(&mut self, texture: u32) {
+        self.blit_as(BatchKind::Sprite, texture);
+    }
 
-        let location = self.shader_sprite.locations[UNIFORM_TEX0];
-        debug_assert_ne!(location, -1);
+    // Same as blit, for a texture holding linear values
+    pub fn blit_encoded(&mut self, texture: u32) {
+        self.blit_as(BatchKind::Encode, texture);
+    }
+
+    // Same as blit, with colors adjusted for the given color vision deficiency
+    pub fn blit_daltonized(&mut self, texture: u32, filter: CvdFilter, linear: bool) {
+        let (sim, shift) = match filter {
+            CvdFilter::None => {
+                return if linear {
+                    self.blit_encoded(texture)
+                } else {
+                    self.blit(texture)
+                };
+            }
+            CvdFilter::Protanopia => (SIM_PROTANOPIA, SHIFT_RED_GREEN),
+            CvdFilter::Deuteranopia => (SIM_DEUTERANOPIA, SHIFT_RED_GREEN),
+            CvdFilter::Tritanopia => (SIM_TRITANOPIA, SHIFT_BLUE_YELLOW),
+        };
+        // the constants are written row by row, GL wants columns
+        let sim = Mat3::from_cols_array(&sim).transpose().to_cols_array();
+        let shift = Mat3::from_cols_array(&shift).transpose().to_cols_array();
+
+        self.flush();
+        let shader = &self.shader_daltonize;
+        shader.use_shader();
         unsafe {
-            glUniform1i(location, 0);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            glUniformMatrix3fv(shader.locations[UNIFORM_SIM], 1, GL_FALSE, sim.as_ptr());
+            check_gl();
+            glUniformMatrix3fv(shader.locations[UNIFORM_SHIFT], 1, GL_FALSE, shift.as_ptr());
+            check_gl();
+            glUniform1i(shader.locations[UNIFORM_LINEAR_IN], linear as _);
+            check_gl();
+        }
+        self.blit_as(BatchKind::Daltonize, texture);
+    }
 
-            glDrawElements(
-                GL_TRIANGLES,
-                self.indices.len() as _,
-                GL_UNSIGNED_INT,
-                null(),
-            );
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+    // Same as blit, but only the part of the texture within uv: [u0, v0, u1, v1]
+    pub fn blit_region(&mut self, texture: u32, uv: [f32; 4]) {
+        self.blit_uv(BatchKind::Sprite, texture, uv);
+    }
+
+    fn blit_as(&mut self, kind: BatchKind, texture: u32) {
+        self.blit_uv(kind, texture, [0., 0., 1., 1.]);
+    }
+
+    fn blit_uv(&mut self, kind: BatchKind, texture: u32, uv: [f32; 4]) {
+        self.flush();
+        unsafe {
+            glDisable(GL_BLEND);
+            check_gl();
+        }
+
+        let (w, h) = (self.width as f32, self.height as f32);
+        self.push_quad(kind, texture, 0., 0., w, h, uv, [1.; 4]);
+        self.flush();
+
+        unsafe {
+            glEnable(GL_BLEND);
+            check_gl();
         }
     }
 
+    pub fn draw_sprite(&mut self, texture: &GlTexture, x: f32, y: f32, w: f32, h: f32) {
+        self.push_quad(
+            BatchKind::Sprite,
+            texture.handle,
+            x,
+            y,
+            w,
+            h,
+            [0., 0., 1., 1.],
+            [1.; 4],
+        );
+    }
+
     pub fn draw_color(&mut self, color: Vec3, alpha: f32, x: f32, y: f32, w: f32, h: f32) {
-        self.use_rect(x, y, w, h);
+        self.push_quad(
+            BatchKind::Color,
+            0,
+            x,
+            y,
+            w,
+            h,
+            [0., 0., 1., 1.],
+            [color.x, color.y, color.z, alpha],
+        );
+    }
 
-        self.vao.bind();
-        self.shader_color.use_shader();
-        let location = self.shader_color.locations[UNIFORM_COL0];
-        unsafe {
-            glUniform4f(location, color.x, color.y, color.z, alpha);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+    // Fills the rect with a blend from one RGBA color to the other, left to right or top to bottom
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_gradient(
+        &mut self,
+        from: [f32; 4],
+        to: [f32; 4],
+        vertical: bool,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+    ) {
+        let colors = if vertical {
+            [from, to, from, to]
+        } else {
+            [from, from, to, to]
+        };
+        self.push_quad_colors(BatchKind::Color, 0, x, y, w, h, [0., 0., 1., 1.], colors);
+    }
 
-            glDrawElements(
-                GL_TRIANGLES,
-                self.indices.len() as _,
-                GL_UNSIGNED_INT,
-                null(),
-            );
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+    // Draws a glyph from an atlas texture; uv selects the glyph within it
+    // Color glyphs (emoji) are drawn as-is, others are tinted with the given color
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_glyph(
+        &mut self,
+        texture: u32,
+        uv: [f32; 4],
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        color: Vec3,
+        colored: bool,
+    ) {
+        if colored {
+            self.push_quad(BatchKind::Sprite, texture, x, y, w, h, uv, [1.; 4]);
+        } else {
+            let color = [color.x, color.y, color.z, 1.];
+            self.push_quad(BatchKind::Glyph, texture, x, y, w, h, uv, color);
         }
     }
 
-    pub fn draw_glyph(&mut self, texture: u32, x: f32, y: f32, w: f32, h: f32, color: Vec3) {
-        self.use_rect(x, y, w, h);
+    // Restricts drawing to the given rect until the matching unclip() or end()
+    // Nested clips are intersected with the enclosing one
+    pub fn clip(&mut self, x: f32, y: f32, w: f32, h: f32) {
+        self.flush();
+        let mut rect = [x, y, x + w, y + h];
+        if let Some(outer) = self.clip_stack.last() {
+            rect[0] = rect[0].max(outer[0]);
+            rect[1] = rect[1].max(outer[1]);
+            rect[2] = rect[2].min(outer[2]).max(rect[0]);
+            rect[3] = rect[3].min(outer[3]).max(rect[1]);
+        }
+        self.clip_stack.push(rect);
+        Self::apply_clip(Some(&rect));
+    }
 
-        self.vao.bind();
-        self.shader_glyph.use_shader();
-        let tex0 = self.shader_glyph.locations[UNIFORM_TEX0];
-        debug_assert_ne!(tex0, -1);
-        let col0 = self.shader_glyph.locations[UNIFORM_COL0];
-        debug_assert_ne!(col0, -1);
+    pub fn unclip(&mut self) {
+        self.flush();
+        self.clip_stack.pop();
+        Self::apply_clip(self.clip_stack.last());
+    }
 
+    fn apply_clip(rect: Option<&[f32; 4]>) {
+        unsafe {
+            if let Some(rect) = rect {
+                glEnable(GL_SCISSOR_TEST);
+                check_gl();
+                glScissor(
+                    rect[0] as _,
+                    rect[1] as _,
+                    (rect[2] - rect[0]) as _,
+                    (rect[3] - rect[1]) as _,
+                );
+                check_gl();
+            } else {
+                glDisable(GL_SCISSOR_TEST);
+                check_gl();
+            }
+        }
+    }
+
+    // Copies a texture back to memory as tightly packed RGBA rows, top row first
+    pub fn read_pixels(&mut self, texture: u32, width: u32, height: u32) -> Vec<u8> {
+        self.flush();
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        unsafe {
+            glBindFramebuffer(GL_READ_FRAMEBUFFER, self.framebuffer.handle);
+            check_gl();
+
+            glFramebufferTexture2D(
+                GL_READ_FRAMEBUFFER,
+                GL_COLOR_ATTACHMENT0,
+                GL_TEXTURE_2D,
+                texture,
+                0,
+            );
+            check_gl();
+
+            glBindBuffer(GL_PIXEL_PACK_BUFFER, 0);
+            check_gl();
+
+            glPixelStorei(GL_PACK_ALIGNMENT, 1);
+            check_gl();
+
+            glReadPixels(
+                0,
+                0,
+                width as _,
+                height as _,
+                GL_RGBA,
+                GL_UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as _,
+            );
+            check_gl();
+
+            glBindFramebuffer(GL_READ_FRAMEBUFFER, 0);
+            check_gl();
+        }
+        pixels
+    }
+
+    // Rebuilds the mip chain from level 0 and samples it trilinearly
+    pub fn generate_mipmaps(&self, texture: u32, anisotropy: f32) {
         unsafe {
             glBindTexture(GL_TEXTURE_2D, texture);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
-            glUniform1i(tex0, 0);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
 
-            glUniform4f(col0, color.x, color.y, color.z, 1.);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            glGenerateMipmap(GL_TEXTURE_2D);
+            check_gl();
 
-            glDrawElements(
-                GL_TRIANGLES,
-                self.indices.len() as _,
-                GL_UNSIGNED_INT,
-                null(),
+            glTexParameteri(
+                GL_TEXTURE_2D,
+                GL_TEXTURE_MIN_FILTER,
+                GL_LINEAR_MIPMAP_LINEAR as i32,
             );
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
+
+            // drivers without the extension flag an error, which is harmless here
+            glTexParameterf(GL_TEXTURE_2D, GL_TEXTURE_MAX_ANISOTROPY_EXT, anisotropy);
+            glGetError();
+
+            glBindTexture(GL_TEXTURE_2D, 0);
+            check_gl();
         }
     }
 
-    pub fn clear(&self) {
+    pub fn clear(&mut self) {
+        self.flush();
         unsafe {
             glClearColor(0., 0., 0., 0.);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
             glClear(GL_COLOR_BUFFER_BIT);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 
     pub fn end(&mut self) {
+        self.flush();
+        self.clip_stack.clear();
+        Self::apply_clip(None);
         self.vao.unbind();
         unsafe {
             glBindFramebuffer(GL_DRAW_FRAMEBUFFER, 0);
             self.vao.unbind();
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+            check_gl();
         }
     }
 }
@@ -0,0 +1,99 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use stereokit::{SkDraw, StereoKitMultiThread, Tex, TextureFormat, TextureType};
+
+use crate::{gl::GlRenderer, overlay::COLOR_TRANSPARENT};
+
+// Past this, the oldest unused textures are freed
+const MAX_POOLED: usize = 8;
+
+// GL names of textures whose storage was replaced by an imported dmabuf. Those aren't pooled:
+// whoever took one next would be drawing into the old capture's buffer.
+static IMPORTED: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+pub fn mark_imported(handle: u32) {
+    if let Ok(mut imported) = IMPORTED.lock() {
+        if !imported.contains(&handle) {
+            imported.push(handle);
+        }
+    }
+}
+
+fn take_imported(handle: u32) -> bool {
+    let Ok(mut imported) = IMPORTED.lock() else {
+        return false;
+    };
+    let found = imported.iter().position(|h| *h == handle);
+    found.map(|i| imported.swap_remove(i)).is_some()
+}
+
+struct PooledTex {
+    tex: Tex,
+    width: i32,
+    height: i32,
+    mips: bool,
+}
+
+// Keeps released overlay and canvas textures for reuse, so rebuilding the UI or restarting
+// a capture doesn't keep allocating and freeing VRAM of the same sizes
+#[derive(Default)]
+pub struct TexturePool {
+    free: VecDeque<PooledTex>,
+}
+
+impl TexturePool {
+    // Returns a transparent RGBA texture, reusing a pooled one of the same size if there is one
+    pub fn take(
+        &mut self,
+        sk: &SkDraw,
+        gl: &mut GlRenderer,
+        width: i32,
+        height: i32,
+        mips: bool,
+    ) -> Tex {
+        let found = self
+            .free
+            .iter()
+            .position(|p| p.width == width && p.height == height && p.mips == mips);
+
+        if let Some(pooled) = found.and_then(|i| self.free.remove(i)) {
+            gl.begin_sk(sk, &pooled.tex);
+            gl.clear();
+            gl.end();
+            return pooled.tex;
+        }
+
+        let tex_type = if mips {
+            TextureType::IMAGE
+        } else {
+            TextureType::IMAGE_NO_MIPS
+        };
+        sk.tex_gen_color(
+            COLOR_TRANSPARENT,
+            width,
+            height,
+            tex_type,
+            TextureFormat::RGBA32,
+        )
+    }
+
+    // Same as give, for a texture a capture may have imported a dmabuf into; those are freed
+    pub fn give_captured(&mut self, tex: Tex, handle: u32, width: i32, height: i32) {
+        if take_imported(handle) {
+            return;
+        }
+        self.give(tex, width, height, false);
+    }
+
+    pub fn give(&mut self, tex: Tex, width: i32, height: i32, mips: bool) {
+        self.free.push_back(PooledTex {
+            tex,
+            width,
+            height,
+            mips,
+        });
+        while self.free.len() > MAX_POOLED {
+            self.free.pop_front();
+        }
+    }
+}
@@ -1,12 +1,11 @@
 #version 300 es
 precision highp float;
 in vec2 fUv;
-
-uniform vec4 uColor;
+in vec4 fColor;
 
 out vec4 FragColor;
 
 void main()
 {
-    FragColor = uColor;
+    FragColor = fColor;
 }
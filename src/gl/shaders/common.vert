@@ -2,10 +2,13 @@
 
 layout (location = 0) in vec2 vPos;
 layout (location = 1) in vec2 vUv;
+layout (location = 2) in vec4 vColor;
 
 out vec2 fUv;
+out vec4 fColor;
 
 void main() {
     fUv = vUv;
+    fColor = vColor;
     gl_Position = vec4(vPos * 2. - 1., 0., 1.);
 }
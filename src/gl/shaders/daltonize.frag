@@ -0,0 +1,40 @@
+#version 300 es
+precision highp float;
+in vec2 fUv;
+
+uniform sampler2D uTexture0;
+// Simulates the deficiency on linear RGB
+uniform mat3 uSim;
+// Moves the contrast that is lost into channels that are still seen
+uniform mat3 uShift;
+// The texture holds linear values rather than sRGB
+uniform bool uLinearIn;
+
+out vec4 FragColor;
+
+vec3 to_linear(vec3 c)
+{
+    bvec3 cutoff = lessThan(c, vec3(0.04045));
+    vec3 higher = pow((c + vec3(0.055)) / vec3(1.055), vec3(2.4));
+    vec3 lower = c / vec3(12.92);
+    return mix(higher, lower, cutoff);
+}
+
+vec3 to_srgb(vec3 c)
+{
+    bvec3 cutoff = lessThan(c, vec3(0.0031308));
+    vec3 higher = vec3(1.055) * pow(c, vec3(1.0 / 2.4)) - vec3(0.055);
+    vec3 lower = c * vec3(12.92);
+    return mix(higher, lower, cutoff);
+}
+
+void main()
+{
+    FragColor = texture(uTexture0, fUv);
+
+    vec3 rgb = uLinearIn ? FragColor.rgb : to_linear(FragColor.rgb);
+    vec3 err = rgb - uSim * rgb;
+    rgb = clamp(rgb + uShift * err, 0.0, 1.0);
+
+    FragColor.rgb = to_srgb(rgb);
+}
@@ -3,14 +3,14 @@
 precision highp float;
 
 in vec2 fUv;
+in vec4 fColor;
 
 uniform sampler2D uTexture0;
-uniform vec4 uColor;
 
 out vec4 FragColor;
 
 void main()
 {
     float r = texture(uTexture0, fUv).r;
-    FragColor = vec4(r,r,r,r) * uColor;
+    FragColor = vec4(r,r,r,r) * fColor;
 }
@@ -0,0 +1,18 @@
+#version 300 es
+precision highp float;
+in vec2 fUv;
+
+uniform sampler2D uTexture0;
+
+out vec4 FragColor;
+
+void main()
+{
+    FragColor = texture(uTexture0, fUv);
+
+    bvec3 cutoff = lessThan(FragColor.rgb, vec3(0.0031308));
+    vec3 higher = vec3(1.055) * pow(FragColor.rgb, vec3(1.0 / 2.4)) - vec3(0.055);
+    vec3 lower = FragColor.rgb * vec3(12.92);
+
+    FragColor.rgb = mix(higher, lower, cutoff);
+}
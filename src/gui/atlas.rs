@@ -0,0 +1,149 @@
+use gles31::{
+    glBindBuffer, glBindTexture, glPixelStorei, glTexImage2D, glTexSubImage2D,
+    GL_PIXEL_UNPACK_BUFFER, GL_TEXTURE_2D, GL_UNPACK_ALIGNMENT, GL_UNSIGNED_BYTE,
+};
+
+use crate::gl::{check::check_gl, GlTexture};
+
+const PAGE_SIZE: u32 = 1024;
+
+// Empty pixels after each bitmap, so linear filtering doesn't bleed into the neighbours
+const PADDING: u32 = 1;
+
+// Packs bitmaps onto shelves of large textures, adding pages as they fill up
+pub struct GlyphAtlas {
+    pages: Vec<GlTexture>,
+    // pages holding glyphs, the rest are kept from before a clear() to be filled again
+    used: usize,
+    internal_format: u32,
+    format: u32,
+    bytes_per_pixel: usize,
+    shelf_x: u32,
+    shelf_y: u32,
+    shelf_h: u32,
+}
+
+pub struct AtlasRegion {
+    pub texture: u32,
+    pub uv: [f32; 4],
+}
+
+impl GlyphAtlas {
+    pub fn new(internal_format: u32, format: u32, bytes_per_pixel: usize) -> Self {
+        Self {
+            pages: Vec::new(),
+            used: 0,
+            internal_format,
+            format,
+            bytes_per_pixel,
+            shelf_x: 0,
+            shelf_y: 0,
+            shelf_h: 0,
+        }
+    }
+
+    // data: tightly packed rows of width * bytes_per_pixel
+    pub fn insert(&mut self, width: u32, height: u32, data: &[u8]) -> Option<AtlasRegion> {
+        let w = width + PADDING;
+        let h = height + PADDING;
+        if w > PAGE_SIZE || h > PAGE_SIZE {
+            return None;
+        }
+
+        if self.shelf_x + w > PAGE_SIZE {
+            self.shelf_x = 0;
+            self.shelf_y += self.shelf_h;
+            self.shelf_h = 0;
+        }
+        if self.used == 0 || self.shelf_y + h > PAGE_SIZE {
+            self.add_page();
+        }
+
+        let (x, y) = (self.shelf_x, self.shelf_y);
+        let page = &self.pages[self.used - 1];
+        unsafe {
+            glBindBuffer(GL_PIXEL_UNPACK_BUFFER, 0);
+            check_gl();
+
+            glBindTexture(GL_TEXTURE_2D, page.handle);
+            check_gl();
+
+            glPixelStorei(GL_UNPACK_ALIGNMENT, 1);
+            check_gl();
+
+            glTexSubImage2D(
+                GL_TEXTURE_2D,
+                0,
+                x as _,
+                y as _,
+                width as _,
+                height as _,
+                self.format,
+                GL_UNSIGNED_BYTE,
+                data.as_ptr() as _,
+            );
+            check_gl();
+        }
+
+        self.shelf_x += w;
+        self.shelf_h = self.shelf_h.max(h);
+
+        let size = PAGE_SIZE as f32;
+        Some(AtlasRegion {
+            texture: page.handle,
+            uv: [
+                x as f32 / size,
+                y as f32 / size,
+                (x + width) as f32 / size,
+                (y + height) as f32 / size,
+            ],
+        })
+    }
+
+    // Forgets every glyph, keeping the pages to be reused
+    pub fn clear(&mut self) {
+        self.used = 0;
+    }
+
+    fn add_page(&mut self) {
+        if let Some(page) = self.pages.get(self.used) {
+            unsafe {
+                glBindTexture(GL_TEXTURE_2D, page.handle);
+                check_gl();
+            }
+        } else {
+            let mut tex = GlTexture::new(self.internal_format as _);
+            tex.width = PAGE_SIZE;
+            tex.height = PAGE_SIZE;
+            self.pages.push(tex);
+        }
+
+        // old glyphs are wiped too, they'd bleed into the padding of new ones
+        let zeroes = vec![0u8; (PAGE_SIZE * PAGE_SIZE) as usize * self.bytes_per_pixel];
+        unsafe {
+            glBindBuffer(GL_PIXEL_UNPACK_BUFFER, 0);
+            check_gl();
+
+            glPixelStorei(GL_UNPACK_ALIGNMENT, 1);
+            check_gl();
+
+            glTexImage2D(
+                GL_TEXTURE_2D,
+                0,
+                self.internal_format as _,
+                PAGE_SIZE as _,
+                PAGE_SIZE as _,
+                0,
+                self.format,
+                GL_UNSIGNED_BYTE,
+                zeroes.as_ptr() as _,
+            );
+            check_gl();
+        }
+
+        self.used += 1;
+        self.shelf_x = 0;
+        self.shelf_y = 0;
+        self.shelf_h = 0;
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use once_cell::sync::Lazy;
+
+// Text input from the on-screen keyboard is routed here instead of uinput
+// while a text field holds the focus.
+
+pub enum TextEdit {
+    Insert(String),
+    Backspace,
+    Submit,
+    Cancel,
+}
+
+struct TextFocus {
+    owner: usize,
+    edits: VecDeque<TextEdit>,
+}
+
+static FOCUS: Lazy<Mutex<Option<TextFocus>>> = Lazy::new(|| Mutex::new(None));
+static NEXT_FOCUS_ID: AtomicUsize = AtomicUsize::new(1);
+
+pub fn new_focus_id() -> usize {
+    NEXT_FOCUS_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub fn take_focus(owner: usize) {
+    if let Ok(mut focus) = FOCUS.lock() {
+        *focus = Some(TextFocus {
+            owner,
+            edits: VecDeque::new(),
+        });
+    }
+}
+
+pub fn release_focus(owner: usize) {
+    if let Ok(mut focus) = FOCUS.lock() {
+        if focus.as_ref().is_some_and(|f| f.owner == owner) {
+            *focus = None;
+        }
+    }
+}
+
+pub fn is_focused() -> bool {
+    FOCUS.lock().map(|f| f.is_some()).unwrap_or(false)
+}
+
+// Returns false if no text field has the focus
+pub fn send_edit(edit: TextEdit) -> bool {
+    if let Ok(mut focus) = FOCUS.lock() {
+        if let Some(focus) = focus.as_mut() {
+            focus.edits.push_back(edit);
+            return true;
+        }
+    }
+    false
+}
+
+// Returns None if the owner does not have the focus
+pub fn drain_edits(owner: usize) -> Option<Vec<TextEdit>> {
+    if let Ok(mut focus) = FOCUS.lock() {
+        if let Some(focus) = focus.as_mut() {
+            if focus.owner == owner {
+                return Some(focus.edits.drain(..).collect());
+            }
+        }
+    }
+    None
+}
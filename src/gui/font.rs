@@ -1,25 +1,69 @@
-use std::{rc::Rc, str::FromStr};
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    rc::Rc,
+    str::FromStr,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
 
 use fontconfig::{FontConfig, OwnedPattern};
-use freetype::{bitmap::PixelMode, face::LoadFlag, Face, Library};
-use gles31::{
-    glBindBuffer, glBindTexture, glGetError, glPixelStorei, glTexImage2D, GL_NO_ERROR,
-    GL_PACK_ALIGNMENT, GL_PIXEL_UNPACK_BUFFER, GL_R8, GL_TEXTURE_2D, GL_UNPACK_ALIGNMENT,
-    GL_UNSIGNED_BYTE, GL_UNSIGNED_INT, GL_UNSIGNED_SHORT,
-};
+use freetype::{bitmap::PixelMode, face::LoadFlag, ffi::FT_Select_Size, Face, Library};
+use gles31::{GL_R8, GL_RGBA, GL_RGBA8};
+use harfbuzz_rs::{shape, Direction, Owned, UnicodeBuffer};
 use idmap::IdMap;
-use log::debug;
-use stereokit::{SkDraw, StereoKitMultiThread, Tex, TextureType};
+use log::{debug, error};
+use unicode_bidi::BidiInfo;
 
-use crate::overlay::COLOR_FALLBACK;
+use super::atlas::GlyphAtlas;
 
-const PRIMARY_FONT: &str = "LiberationSans";
 const GL_RED: u32 = 0x1903;
 
+// Glyphs uploaded to the atlas per frame, so a screen of new text doesn't stall one frame
+const UPLOADS_PER_FRAME: usize = 16;
+
 pub struct FontCache {
     fc: FontConfig,
     ft: Library,
-    collections: IdMap<isize, FontCollection>,
+    // family used when a canvas doesn't name one, then the ones tried for glyphs it lacks
+    primary: Arc<str>,
+    style: String,
+    fallbacks: Vec<String>,
+    families: HashMap<Arc<str>, IdMap<isize, FontCollection>>,
+    atlas: GlyphAtlas,
+    color_atlas: GlyphAtlas,
+    raster_tx: Sender<RasterRequest>,
+    raster_rx: Receiver<RasterResult>,
+    // Bumped whenever rasterized glyphs land in the atlas, so canvases know to redraw text
+    generation: u32,
+    // Bumped by set_fonts, so glyphs still being rasterized for the old fonts are dropped
+    epoch: u32,
+}
+
+// (family, size, font index, glyph index) of a glyph in the cache
+type GlyphKey = (Arc<str>, isize, usize, usize);
+
+struct RasterRequest {
+    key: GlyphKey,
+    epoch: u32,
+    path: String,
+    index: isize,
+}
+
+struct RasterResult {
+    key: GlyphKey,
+    epoch: u32,
+    width: u32,
+    rows: u32,
+    // tightly packed: 1 byte per pixel, or RGBA if color
+    data: Vec<u8>,
+    color: bool,
+    top: f32,
+    left: f32,
+    advance: f32,
 }
 
 struct FontCollection {
@@ -27,45 +71,164 @@ struct FontCollection {
     cp_map: IdMap<usize, usize>,
 }
 
+// The face itself lives on the rasterizer thread, this only keeps what shaping needs
 struct Font {
-    face: Face,
+    hb: Owned<harfbuzz_rs::Font<'static>>,
     path: String,
     index: isize,
     size: isize,
+    // Bitmap-only fonts have fixed sizes, so their glyphs get scaled to the requested size
+    scale: f32,
     glyphs: IdMap<usize, Rc<Glyph>>,
 }
 
 pub struct Glyph {
-    pub tex: Option<Tex>,
+    // atlas page and the glyph's rect within it
+    pub tex: Option<u32>,
+    pub uv: [f32; 4],
     pub top: f32,
     pub left: f32,
     pub width: f32,
     pub height: f32,
     pub advance: f32,
+    // RGBA bitmap that should not be tinted, such as an emoji
+    pub color: bool,
+}
+
+// A glyph as placed by the shaper, whose offsets and advance depend on its neighbours
+pub struct ShapedGlyph {
+    glyph: Rc<Glyph>,
+    pub top: f32,
+    pub left: f32,
+    pub advance: f32,
+}
+
+impl Deref for ShapedGlyph {
+    type Target = Glyph;
+
+    fn deref(&self) -> &Glyph {
+        &self.glyph
+    }
 }
 
 impl FontCache {
-    pub fn new() -> Self {
+    pub fn new(family: &str, style: &str, fallbacks: &[String]) -> Self {
         let ft = Library::init().expect("Failed to initialize freetype");
         let fc = FontConfig::default();
 
+        let (raster_tx, requests) = mpsc::channel();
+        let (results, raster_rx) = mpsc::channel();
+        thread::Builder::new()
+            .name("glyph-raster".into())
+            .spawn(move || raster_thread(requests, results))
+            .expect("Failed to start glyph rasterizer");
+
         FontCache {
             fc,
             ft,
-            collections: IdMap::new(),
+            primary: family.into(),
+            style: style.to_string(),
+            fallbacks: fallbacks.to_vec(),
+            families: HashMap::new(),
+            atlas: GlyphAtlas::new(GL_R8, GL_RED, 1),
+            color_atlas: GlyphAtlas::new(GL_RGBA8, GL_RGBA, 4),
+            raster_tx,
+            raster_rx,
+            generation: 0,
+            epoch: 0,
         }
     }
 
-    pub fn get_text_size(&mut self, text: &str, size: isize, sk: &SkDraw) -> (f32, f32) {
+    // Drops what was loaded for the old fonts; text is shaped anew on the next redraw
+    pub fn set_fonts(&mut self, family: &str, style: &str, fallbacks: &[String]) {
+        if *self.primary == *family && self.style == style && self.fallbacks == fallbacks {
+            return;
+        }
+        self.primary = family.into();
+        self.style = style.to_string();
+        self.fallbacks = fallbacks.to_vec();
+        self.families.clear();
+        self.atlas.clear();
+        self.color_atlas.clear();
+        self.generation = self.generation.wrapping_add(1);
+        self.epoch = self.epoch.wrapping_add(1);
+    }
+
+    // The atlas pages went with a lost GL context: forgets every glyph, so they're
+    // rasterized and uploaded again as text gets redrawn
+    pub fn context_lost(&mut self) {
+        self.atlas = GlyphAtlas::new(GL_R8, GL_RED, 1);
+        self.color_atlas = GlyphAtlas::new(GL_RGBA8, GL_RGBA, 4);
+        self.families.clear();
+        self.generation = self.generation.wrapping_add(1);
+        self.epoch = self.epoch.wrapping_add(1);
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    // Moves a few finished glyphs from the rasterizer into the atlas. Call once per frame.
+    pub fn upload_pending(&mut self) {
+        let mut uploaded = false;
+        for result in self.raster_rx.try_iter().take(UPLOADS_PER_FRAME) {
+            if result.epoch != self.epoch {
+                continue;
+            }
+            let (family, size, font_idx, gid) = result.key;
+            let Some(font) = self
+                .families
+                .get_mut(&family)
+                .and_then(|c| c.get_mut(size))
+                .and_then(|c| c.fonts.get_mut(font_idx))
+            else {
+                continue;
+            };
+
+            let region = if result.width > 0 && result.rows > 0 {
+                let atlas = if result.color {
+                    &mut self.color_atlas
+                } else {
+                    &mut self.atlas
+                };
+                atlas.insert(result.width, result.rows, &result.data)
+            } else {
+                None
+            };
+
+            let scale = font.scale;
+            font.glyphs.insert(
+                gid,
+                Rc::new(Glyph {
+                    tex: region.as_ref().map(|r| r.texture),
+                    uv: region.map(|r| r.uv).unwrap_or([0.; 4]),
+                    top: result.top * scale,
+                    left: result.left * scale,
+                    advance: result.advance * scale,
+                    width: result.width as f32 * scale,
+                    height: result.rows as f32 * scale,
+                    color: result.color,
+                }),
+            );
+            uploaded = true;
+        }
+        if uploaded {
+            self.generation = self.generation.wrapping_add(1);
+        }
+    }
+
+    // font: a fontconfig family name such as a symbol or icon font; None for the primary font
+    pub fn get_text_size(&mut self, text: &str, font: Option<&str>, size: isize) -> (f32, f32) {
         let sizef = size as f32;
 
         let height = sizef + ((text.lines().count() as f32) - 1f32) * (sizef * 1.5);
 
         let mut max_w = sizef * 0.33;
         for line in text.lines() {
-            let w: f32 = line
-                .chars()
-                .map(|c| self.get_glyph_for_cp(c as usize, size, sk).advance)
+            let w: f32 = self
+                .get_glyphs(line, font, size)
+                .iter()
+                .map(|g| g.advance)
                 .sum();
 
             if w > max_w {
@@ -75,19 +238,97 @@ impl FontCache {
         (max_w, height)
     }
 
-    pub fn get_glyphs(&mut self, text: &str, size: isize, sk: &SkDraw) -> Vec<Rc<Glyph>> {
+    pub fn get_glyphs(&mut self, text: &str, font: Option<&str>, size: isize) -> Vec<ShapedGlyph> {
+        let primary = self.primary.clone();
+        let family = font.unwrap_or(&primary);
         let mut glyphs = Vec::new();
         for line in text.lines() {
-            for c in line.chars() {
-                glyphs.push(self.get_glyph_for_cp(c as usize, size, sk));
+            // Reorder mixed-direction text into visual runs, each shaped left to right
+            let bidi = BidiInfo::new(line, None);
+            for para in bidi.paragraphs.iter() {
+                let (levels, runs) = bidi.visual_runs(para, para.range.clone());
+                for run in runs {
+                    let rtl = levels[run.start].is_rtl();
+                    let mut font_runs = self.split_font_runs(&line[run], family, size);
+                    if rtl {
+                        font_runs.reverse();
+                    }
+                    for (font_idx, text) in font_runs {
+                        self.shape_run(text, family, size, font_idx, rtl, &mut glyphs);
+                    }
+                }
             }
         }
         glyphs
     }
 
-    fn get_font_for_cp(&mut self, cp: usize, size: isize) -> usize {
-        if !self.collections.contains_key(size) {
-            self.collections.insert(
+    // Splits text into runs that can be shaped with a single font
+    fn split_font_runs<'a>(
+        &mut self,
+        text: &'a str,
+        family: &str,
+        size: isize,
+    ) -> Vec<(usize, &'a str)> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        let mut current = None;
+        for (i, c) in text.char_indices() {
+            let idx = self.get_font_for_cp(c as usize, family, size);
+            match current {
+                Some(prev) if prev != idx => {
+                    runs.push((prev, &text[start..i]));
+                    start = i;
+                    current = Some(idx);
+                }
+                None => current = Some(idx),
+                _ => {}
+            }
+        }
+        if let Some(prev) = current {
+            runs.push((prev, &text[start..]));
+        }
+        runs
+    }
+
+    fn shape_run(
+        &mut self,
+        text: &str,
+        family: &str,
+        size: isize,
+        font_idx: usize,
+        rtl: bool,
+        out: &mut Vec<ShapedGlyph>,
+    ) {
+        let font = &self.families[family][size].fonts[font_idx];
+        let buffer = UnicodeBuffer::new()
+            .add_str(text)
+            .set_direction(if rtl { Direction::Rtl } else { Direction::Ltr })
+            .guess_segment_properties();
+        let output = shape(&font.hb, buffer, &[]);
+
+        for (info, pos) in output
+            .get_glyph_infos()
+            .iter()
+            .zip(output.get_glyph_positions())
+        {
+            let glyph = self.get_glyph(info.codepoint as usize, family, size, font_idx);
+            out.push(ShapedGlyph {
+                top: glyph.top + (pos.y_offset as f32) / 64.,
+                left: glyph.left + (pos.x_offset as f32) / 64.,
+                advance: (pos.x_advance as f32) / 64.,
+                glyph,
+            });
+        }
+    }
+
+    fn get_font_for_cp(&mut self, cp: usize, family: &str, size: isize) -> usize {
+        if !self.families.contains_key(family) {
+            self.families.insert(family.into(), IdMap::new());
+        }
+        let collections = self.families.get_mut(family).unwrap();
+
+        if !collections.contains_key(size) {
+            collections.insert(
                 size,
                 FontCollection {
                     fonts: Vec::new(),
@@ -95,150 +336,282 @@ impl FontCache {
                 },
             );
         }
-        let coll = self.collections.get_mut(size).unwrap();
+        let coll = collections.get_mut(size).unwrap();
 
         if let Some(font) = coll.cp_map.get(cp) {
             return *font;
         }
 
-        let pattern_str = format!("{PRIMARY_FONT}-{size}:style=bold:charset={cp:04x}");
-
-        let mut pattern =
-            OwnedPattern::from_str(&pattern_str).expect("Failed to create fontconfig pattern");
-        self.fc
-            .substitute(&mut pattern, fontconfig::MatchKind::Pattern);
-        pattern.default_substitute();
-
-        let pattern = pattern.font_match(&mut self.fc);
+        // The primary font and then each fallback, if it has the glyph; after that, whatever
+        // fontconfig finds that covers it
+        let mut patterns = vec![];
+        if *self.primary == *family {
+            let style = if self.style.is_empty() {
+                String::new()
+            } else {
+                format!(":style={}", self.style)
+            };
+            for name in std::iter::once(family).chain(self.fallbacks.iter().map(|f| f.as_str())) {
+                patterns.push((format!("{name}-{size}{style}"), true));
+            }
+            patterns.push((format!("{family}-{size}{style}:charset={cp:04x}"), false));
+        } else {
+            patterns.push((format!("{family}-{size}:charset={cp:04x}"), false));
+        }
 
-        if let Some(path) = pattern.filename() {
-            debug!(
-                "Loading font: {} {}pt",
-                pattern.name().unwrap_or(path),
-                size
-            );
+        let matched = patterns.into_iter().find_map(|(pattern, needs_cp)| {
+            let matched = match_font(&mut self.fc, &self.ft, &pattern, size)?;
+            (!needs_cp || matched.face.get_char_index(cp) > 0).then_some(matched)
+        });
+
+        let coll = self
+            .families
+            .get_mut(family)
+            .and_then(|c| c.get_mut(size))
+            .unwrap();
+        let Some(MatchedFont {
+            face,
+            name,
+            path,
+            index: font_idx,
+            scale,
+        }) = matched
+        else {
+            coll.cp_map.insert(cp, 0);
+            return 0;
+        };
 
-            let font_idx = pattern.face_index().unwrap_or(0);
+        // a fallback can also be the font fontconfig picks last
+        if let Some(idx) = coll
+            .fonts
+            .iter()
+            .position(|f| f.path == path && f.index == font_idx)
+        {
+            coll.cp_map.insert(cp, idx);
+            return idx;
+        }
 
-            let face = self
-                .ft
-                .new_face(path, font_idx as _)
-                .expect("Failed to load font face");
-            face.set_char_size(size << 6, size << 6, 96, 96)
-                .expect("Failed to set font size");
+        debug!("Loading font: {} {}pt", name, size);
 
-            let idx = coll.fonts.len();
-            for cp in 0..0xFFFF {
-                if coll.cp_map.contains_key(cp) {
-                    continue;
-                }
-                let g = face.get_char_index(cp);
-                if g > 0 {
-                    coll.cp_map.insert(cp, idx);
-                }
+        let mut hb = harfbuzz_rs::Font::new(
+            harfbuzz_rs::Face::from_file(&path, font_idx as _)
+                .expect("Failed to load font face for shaping"),
+        );
+        // Same pixel size as freetype at 96 dpi, in 26.6 fixed point
+        let hb_scale = (size as f32 * 96. / 72. * 64.) as i32;
+        hb.set_scale(hb_scale, hb_scale);
+
+        let idx = coll.fonts.len();
+        for cp in 0..0xFFFF {
+            if coll.cp_map.contains_key(cp) {
+                continue;
+            }
+            let g = face.get_char_index(cp);
+            if g > 0 {
+                coll.cp_map.insert(cp, idx);
             }
-
-            let zero_glyph = Rc::new(Glyph {
-                tex: None,
-                top: 0.,
-                left: 0.,
-                width: 0.,
-                height: 0.,
-                advance: size as f32 / 3.,
-            });
-            let mut glyphs = IdMap::new();
-            glyphs.insert(0, zero_glyph);
-
-            let font = Font {
-                face,
-                path: path.to_string(),
-                size,
-                index: font_idx as _,
-                glyphs,
-            };
-            coll.fonts.push(font);
-
-            idx
-        } else {
-            coll.cp_map.insert(cp, 0);
-            0
         }
-    }
+        // codepoints outside the BMP, such as most emoji, are not covered above
+        if !coll.cp_map.contains_key(cp) {
+            coll.cp_map.insert(cp, idx);
+        }
 
-    fn get_glyph_for_cp(&mut self, cp: usize, size: isize, sk: &SkDraw) -> Rc<Glyph> {
-        let key = self.get_font_for_cp(cp, size);
+        let zero_glyph = Rc::new(Glyph {
+            tex: None,
+            uv: [0.; 4],
+            top: 0.,
+            left: 0.,
+            width: 0.,
+            height: 0.,
+            advance: size as f32 / 3.,
+            color: false,
+        });
+        let mut glyphs = IdMap::new();
+        glyphs.insert(0, zero_glyph);
+
+        let font = Font {
+            hb,
+            path,
+            size,
+            index: font_idx,
+            scale,
+            glyphs,
+        };
+        coll.fonts.push(font);
 
-        let font = &mut self.collections[size].fonts[key];
+        idx
+    }
 
-        if let Some(glyph) = font.glyphs.get(cp) {
+    // gid: a glyph index within the font, as given by the shaper.
+    // New glyphs are rasterized in the background and come back empty until uploaded.
+    fn get_glyph(&mut self, gid: usize, family: &str, size: isize, font_idx: usize) -> Rc<Glyph> {
+        let (family, collections) = self.families.get_key_value(family).unwrap();
+        if let Some(glyph) = collections[size].fonts[font_idx].glyphs.get(gid) {
             return glyph.clone();
         }
 
-        if font.face.load_char(cp, LoadFlag::DEFAULT).is_err() {
-            return font.glyphs[0].clone();
+        let family = family.clone();
+        let font = &mut self.families.get_mut(&family).unwrap()[size].fonts[font_idx];
+        let request = RasterRequest {
+            key: (family, size, font_idx, gid),
+            epoch: self.epoch,
+            path: font.path.clone(),
+            index: font.index,
+        };
+        if self.raster_tx.send(request).is_err() {
+            error!("Glyph rasterizer is gone");
         }
 
-        let glyph = font.face.glyph();
-        if glyph.render_glyph(freetype::RenderMode::Normal).is_err() {
-            return font.glyphs[0].clone();
-        }
+        // placeholder until the result is uploaded, also stops the glyph being requested twice
+        let placeholder = font.glyphs[0].clone();
+        font.glyphs.insert(gid, placeholder.clone());
+        placeholder
+    }
+}
 
-        let bmp = glyph.bitmap();
-        let buf = bmp.buffer();
-        let metrics = glyph.metrics();
+struct MatchedFont {
+    face: Face,
+    name: String,
+    path: String,
+    index: isize,
+    // for bitmap-only fonts, see Font
+    scale: f32,
+}
 
-        let (pf, pt) = match bmp.pixel_mode() {
-            Ok(PixelMode::Gray) => (GL_RED, GL_UNSIGNED_BYTE),
-            Ok(PixelMode::Gray2) => (GL_RED, GL_UNSIGNED_SHORT),
-            Ok(PixelMode::Gray4) => (GL_RED, GL_UNSIGNED_INT),
-            _ => return font.glyphs[0].clone(),
-        };
+// The font fontconfig picks for the pattern, with its face set to the size
+fn match_font(
+    fc: &mut FontConfig,
+    ft: &Library,
+    pattern: &str,
+    size: isize,
+) -> Option<MatchedFont> {
+    let mut pattern = OwnedPattern::from_str(pattern).expect("Failed to create fontconfig pattern");
+    fc.substitute(&mut pattern, fontconfig::MatchKind::Pattern);
+    pattern.default_substitute();
+
+    let pattern = pattern.font_match(fc);
+    let path = pattern.filename()?;
+    let index = pattern.face_index().unwrap_or(0) as isize;
+
+    let mut face = ft.new_face(path, index).expect("Failed to load font face");
+
+    let scale = if face.is_scalable() {
+        face.set_char_size(size << 6, size << 6, 96, 96)
+            .expect("Failed to set font size");
+        1.
+    } else {
+        // Color emoji fonts only come with fixed-size strikes
+        unsafe { FT_Select_Size(face.raw_mut(), 0) };
+        let ppem = face.size_metrics().map(|m| m.y_ppem).unwrap_or(0);
+        if ppem > 0 {
+            (size as f32 * 96. / 72.) / ppem as f32
+        } else {
+            1.
+        }
+    };
+
+    Some(MatchedFont {
+        face,
+        name: pattern.name().unwrap_or(path).to_string(),
+        path: path.to_string(),
+        index,
+        scale,
+    })
+}
 
-        let tex = sk.tex_gen_color(
-            COLOR_FALLBACK,
-            bmp.width() as _,
-            bmp.rows() as _,
-            TextureType::IMAGE_NO_MIPS,
-            stereokit::TextureFormat::R8,
-        );
-        unsafe {
-            let handle = sk.tex_get_surface(tex.as_ref()) as usize as u32;
-            glBindBuffer(GL_PIXEL_UNPACK_BUFFER, 0);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
-
-            glBindTexture(GL_TEXTURE_2D, handle);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
-
-            glPixelStorei(GL_PACK_ALIGNMENT, 1);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
-
-            glPixelStorei(GL_UNPACK_ALIGNMENT, 1);
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
-
-            glTexImage2D(
-                GL_TEXTURE_2D,
-                0,
-                GL_R8 as _,
-                bmp.width() as _,
-                bmp.rows() as _,
-                0,
-                pf,
-                pt,
-                buf.as_ptr() as _,
-            );
-            debug_assert_eq!(glGetError(), GL_NO_ERROR);
+// Loads its own copies of the faces, since freetype objects can't be shared across threads
+fn raster_thread(requests: Receiver<RasterRequest>, results: Sender<RasterResult>) {
+    let ft = match Library::init() {
+        Ok(ft) => ft,
+        Err(e) => {
+            error!("Glyph rasterizer: failed to initialize freetype: {}", e);
+            return;
         }
+    };
+    let mut faces: HashMap<(String, isize, isize), Face> = HashMap::new();
+
+    for request in requests {
+        let (_, size, _, gid) = request.key;
+        let face_key = (request.path.clone(), request.index, size);
+        if !faces.contains_key(&face_key) {
+            let Ok(mut face) = ft.new_face(&request.path, request.index) else {
+                continue;
+            };
+            if face.is_scalable() {
+                if face.set_char_size(size << 6, size << 6, 96, 96).is_err() {
+                    continue;
+                }
+            } else {
+                unsafe { FT_Select_Size(face.raw_mut(), 0) };
+            }
+            faces.insert(face_key.clone(), face);
+        }
+        let face = &faces[&face_key];
 
-        let g = Glyph {
-            tex: Some(tex),
-            top: (metrics.horiBearingY >> 6i64) as _,
-            left: (metrics.horiBearingX >> 6i64) as _,
-            advance: (metrics.horiAdvance >> 6i64) as _,
-            width: bmp.width() as _,
-            height: bmp.rows() as _,
-        };
+        if let Some(result) = rasterize(face, gid, request.key, request.epoch) {
+            if results.send(result).is_err() {
+                return;
+            }
+        }
+    }
+}
 
-        font.glyphs.insert(cp, Rc::new(g));
-        font.glyphs[cp].clone()
+fn rasterize(face: &Face, gid: usize, key: GlyphKey, epoch: u32) -> Option<RasterResult> {
+    face.load_glyph(gid as _, LoadFlag::DEFAULT | LoadFlag::COLOR)
+        .ok()?;
+
+    let glyph = face.glyph();
+    glyph.render_glyph(freetype::RenderMode::Normal).ok()?;
+
+    let bmp = glyph.bitmap();
+    let buf = bmp.buffer();
+    let metrics = glyph.metrics();
+
+    let (width, rows, pitch) = (
+        bmp.width() as usize,
+        bmp.rows() as usize,
+        bmp.pitch() as usize,
+    );
+    let (data, color) = match bmp.pixel_mode() {
+        Ok(PixelMode::Gray) => {
+            let mut data = Vec::with_capacity(width * rows);
+            for row in 0..rows {
+                data.extend_from_slice(&buf[row * pitch..row * pitch + width]);
+            }
+            (data, false)
+        }
+        Ok(PixelMode::Bgra) => (bgra_to_rgba(buf, width, rows, pitch), true),
+        _ => return None,
+    };
+
+    Some(RasterResult {
+        key,
+        epoch,
+        width: width as _,
+        rows: rows as _,
+        data,
+        color,
+        top: (metrics.horiBearingY >> 6i64) as f32,
+        left: (metrics.horiBearingX >> 6i64) as f32,
+        advance: (metrics.horiAdvance >> 6i64) as f32,
+    })
+}
+
+// Freetype gives premultiplied BGRA, while the renderer blends straight RGBA
+fn bgra_to_rgba(buf: &[u8], width: usize, rows: usize, pitch: usize) -> Vec<u8> {
+    let mut rgba = Vec::with_capacity(width * rows * 4);
+    for row in 0..rows {
+        for px in buf[row * pitch..row * pitch + width * 4].chunks_exact(4) {
+            let a = px[3];
+            let unmul = |c: u8| {
+                if a == 0 {
+                    0
+                } else {
+                    ((c as u32 * 255) / a as u32).min(255) as u8
+                }
+            };
+            rgba.extend_from_slice(&[unmul(px[2]), unmul(px[1]), unmul(px[0]), a]);
+        }
     }
+    rgba
 }
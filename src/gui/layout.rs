@@ -0,0 +1,119 @@
+use glam::Vec2;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Rect {
+    pub const fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.x + other.w
+            && other.x < self.x + self.w
+            && self.y < other.y + other.h
+            && other.y < self.y + self.h
+    }
+
+    // Shrinks the rect by padding on each side
+    pub fn inset(&self, padding: Vec2) -> Self {
+        Self {
+            x: self.x + padding.x,
+            y: self.y + padding.y,
+            w: (self.w - 2. * padding.x).max(0.),
+            h: (self.h - 2. * padding.y).max(0.),
+        }
+    }
+
+    pub fn scaled(&self, scale: f32) -> Self {
+        Self {
+            x: self.x * scale,
+            y: self.y * scale,
+            w: self.w * scale,
+            h: self.h * scale,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    Horizontal,
+    Vertical,
+}
+
+// Distributes a rect among its children proportionally to their weights,
+// then insets each child by the padding.
+#[derive(Debug, Clone, Copy)]
+pub struct FlexLayout {
+    pub direction: Direction,
+    pub padding: Vec2,
+}
+
+impl FlexLayout {
+    pub fn row() -> Self {
+        Self {
+            direction: Direction::Horizontal,
+            padding: Vec2::ZERO,
+        }
+    }
+
+    pub fn column() -> Self {
+        Self {
+            direction: Direction::Vertical,
+            padding: Vec2::ZERO,
+        }
+    }
+
+    pub fn with_padding(mut self, padding: Vec2) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn layout(&self, rect: Rect, weights: &[f32]) -> Vec<Rect> {
+        let total: f32 = weights.iter().sum();
+        if total <= 0. {
+            return vec![Rect::new(rect.x, rect.y, 0., 0.); weights.len()];
+        }
+
+        let mut offset = 0f32;
+        weights
+            .iter()
+            .map(|weight| {
+                let frac = weight / total;
+                let child = match self.direction {
+                    Direction::Horizontal => {
+                        Rect::new(rect.x + rect.w * offset, rect.y, rect.w * frac, rect.h)
+                    }
+                    Direction::Vertical => {
+                        Rect::new(rect.x, rect.y + rect.h * offset, rect.w, rect.h * frac)
+                    }
+                };
+                offset += frac;
+                child.inset(self.padding)
+            })
+            .collect()
+    }
+
+    // Splits the rect into count children of equal size
+    pub fn layout_even(&self, rect: Rect, count: usize) -> Vec<Rect> {
+        self.layout(rect, &vec![1.; count])
+    }
+}
+
+// Splits a rect into equally sized cells, in row-major order
+pub fn grid(rect: Rect, cols: usize, rows: usize, padding: Vec2) -> Vec<Rect> {
+    FlexLayout::column()
+        .layout_even(rect, rows)
+        .into_iter()
+        .flat_map(|row| {
+            FlexLayout::row()
+                .with_padding(padding)
+                .layout_even(row, cols)
+        })
+        .collect()
+}
@@ -1,28 +1,71 @@
-use std::sync::Arc;
+use std::{
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
-use glam::{Vec2, Vec3};
-use stereokit::{SkDraw, StereoKitMultiThread, Tex, TextureFormat, TextureType};
+use glam::{vec2, Vec2, Vec3};
+use gles31::GL_RGBA8;
+use log::error;
+use stereokit::{SkDraw, StereoKitMultiThread, Tex};
 
+use self::{
+    focus::TextEdit,
+    image::{load_png, Image},
+    layout::Rect,
+};
 use crate::{
-    interactions::InteractionHandler,
-    overlay::{OverlayBackend, OverlayRenderer, COLOR_TRANSPARENT},
+    config::AccessibilityConfig,
+    config_io, dropdown,
+    gl::GlTexture,
+    interactions::{InteractionHandler, Nav},
+    numpad,
+    overlay::{OverlayBackend, OverlayRenderer},
+    perf::{self, Section},
     AppSession, AppState,
 };
 
+pub mod atlas;
+pub mod focus;
 pub mod font;
+pub mod image;
+pub mod layout;
+pub mod theme;
 
 const RES_DIVIDER: usize = 4;
+// Hit-test cell size in accessibility mode, so small controls are easier to tell apart
+const RES_DIVIDER_FINE: usize = 2;
 
-struct Rect {
-    x: f32,
-    y: f32,
-    w: f32,
-    h: f32,
+// Set from the config; canvases pick these up when they're created
+static TEXT_SCALE: Mutex<f32> = Mutex::new(1.0);
+static FINE_HIT_TEST: AtomicBool = AtomicBool::new(false);
+static STRONG_HIGHLIGHT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_accessibility(config: &AccessibilityConfig) {
+    if let Ok(mut scale) = TEXT_SCALE.lock() {
+        *scale = config.font_scale();
+    }
+    FINE_HIT_TEST.store(config.enabled, Ordering::Relaxed);
+    STRONG_HIGHLIGHT.store(config.enabled, Ordering::Relaxed);
 }
 
+const TOOLTIP_DELAY: Duration = Duration::from_millis(700);
+const TOOLTIP_FONT_SIZE: isize = 14;
+const TOOLTIP_PADDING: f32 = 4.;
+
+// Holding a slider still for this long opens the numpad for it
+const LONG_PRESS_DELAY: Duration = Duration::from_millis(600);
+// How far the pointer may wander during a long press, in unscaled pixels
+const LONG_PRESS_SLOP: f32 = 8.;
+
 struct CanvasGl {
     tex_bg: Tex,
     tex_fg: Tex,
+    // Full-resolution composite, downsampled into the overlay texture when supersampling
+    tex_ss: Option<Tex>,
 }
 
 // Parses a color from a HTML hex string
@@ -34,50 +77,168 @@ pub fn color_parse(html_hex: &str) -> Vec3 {
     color
 }
 
+// Whether the string is a color color_parse can read, #rrggbb
+pub fn is_html_color(s: &str) -> bool {
+    s.len() == 7 && s.starts_with('#') && s[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// Formats a color as a HTML hex string
+pub fn color_to_html(color: Vec3) -> String {
+    let c = (color.clamp(Vec3::ZERO, Vec3::ONE) * 255.).round();
+    format!("#{:02x}{:02x}{:02x}", c.x as u8, c.y as u8, c.z as u8)
+}
+
+// All components 0.0 - 1.0, hue included
+pub fn hsv_to_rgb(hsv: Vec3) -> Vec3 {
+    let h = hsv.x.rem_euclid(1.) * 6.;
+    let c = hsv.z * hsv.y;
+    let x = c * (1. - (h % 2. - 1.).abs());
+    let rgb = match h as u32 {
+        0 => Vec3::new(c, x, 0.),
+        1 => Vec3::new(x, c, 0.),
+        2 => Vec3::new(0., c, x),
+        3 => Vec3::new(0., x, c),
+        4 => Vec3::new(x, 0., c),
+        _ => Vec3::new(c, 0., x),
+    };
+    rgb + Vec3::splat(hsv.z - c)
+}
+
+pub fn rgb_to_hsv(rgb: Vec3) -> Vec3 {
+    let max = rgb.max_element();
+    let delta = max - rgb.min_element();
+    let h = if delta <= 0. {
+        0.
+    } else if max == rgb.x {
+        ((rgb.y - rgb.z) / delta).rem_euclid(6.)
+    } else if max == rgb.y {
+        (rgb.z - rgb.x) / delta + 2.
+    } else {
+        (rgb.x - rgb.y) / delta + 4.
+    };
+    let s = if max > 0. { delta / max } else { 0. };
+    Vec3::new(h / 6., s, max)
+}
+
+// A run of text with its own color and size, for use in labels.
+// None inherits the value from the control.
+#[derive(Clone)]
+pub struct TextSpan {
+    pub text: Arc<str>,
+    pub color: Option<Vec3>,
+    pub size: Option<isize>,
+}
+
+impl TextSpan {
+    pub fn new(text: &str, color: Option<Vec3>, size: Option<isize>) -> Self {
+        Self {
+            text: text.into(),
+            color,
+            size,
+        }
+    }
+}
+
+// How a control's text is fitted into the width of its rect
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextOverflow {
+    #[default]
+    Visible,
+    Wrap,
+    Ellipsis,
+}
+
 pub struct Canvas<T1, T2> {
     pub data: T1,
+    // Size of the overlay texture
     pub width: usize,
     pub height: usize,
+    // Size everything is drawn at, larger than the above when supersampling
+    render_width: usize,
+    render_height: usize,
     pub controls: Vec<Control<T1, T2>>,
 
     pub fg_color: Vec3,
     pub bg_color: Vec3,
     pub font_size: isize,
+    // fontconfig family for the text of new controls, such as monospace; None for the primary
+    pub font: Option<Arc<str>>,
+
+    // Multiplier applied to all coordinates and font sizes given to the builders,
+    // including the supersampling factor
+    scale: f32,
+    // Extra multiplier for font sizes only
+    text_scale: f32,
 
     interact_map: Vec<Option<u8>>,
+    res_divider: usize,
     interact_stride: usize,
     interact_rows: usize,
 
     hover_controls: [Option<usize>; 2],
     pressed_controls: [Option<usize>; 2],
+    // When and where a slider was pressed, and its value before that
+    long_press: [Option<(Instant, Vec2, f32)>; 2],
+
+    hover_since: [Option<Instant>; 2],
+    hover_pos: [Vec2; 2],
+    tooltip: Option<(usize, Rect)>,
+
+    // Control picked with the controller stick, see on_nav
+    nav_control: Option<usize>,
+
+    // FontCache generation the text was last drawn at
+    glyph_generation: u32,
 
     gl: Option<CanvasGl>,
 }
 
 impl<T1, T2> Canvas<T1, T2> {
-    pub fn new(width: usize, height: usize, data: T1) -> Self {
-        let stride = width / RES_DIVIDER;
-        let rows = height / RES_DIVIDER;
+    pub fn new(width: usize, height: usize, scale: f32, supersample: f32, data: T1) -> Self {
+        let render_width = (width as f32 * scale * supersample) as usize;
+        let render_height = (height as f32 * scale * supersample) as usize;
+        let width = (width as f32 * scale) as usize;
+        let height = (height as f32 * scale) as usize;
+        let res_divider = if FINE_HIT_TEST.load(Ordering::Relaxed) {
+            RES_DIVIDER_FINE
+        } else {
+            RES_DIVIDER
+        };
+        let stride = render_width / res_divider;
+        let rows = render_height / res_divider;
 
         Self {
             data,
             width,
             height,
+            render_width,
+            render_height,
             interact_map: vec![None; stride * rows],
+            res_divider,
             interact_stride: stride,
             interact_rows: rows,
             controls: Vec::new(),
             bg_color: Vec3::ZERO,
             fg_color: Vec3::ONE,
             font_size: 16,
+            font: None,
+            scale: scale * supersample,
+            text_scale: TEXT_SCALE.lock().map(|s| *s).unwrap_or(1.0),
             hover_controls: [None, None],
             pressed_controls: [None, None],
+            long_press: [None, None],
+            hover_since: [None, None],
+            hover_pos: [Vec2::ZERO; 2],
+            tooltip: None,
+            nav_control: None,
+            glyph_generation: 0,
             gl: None,
         }
     }
 
     // Creates a panel with bg_color inherited from the canvas
     pub fn panel(&mut self, x: f32, y: f32, w: f32, h: f32) -> usize {
+        let Rect { x, y, w, h } = self.scaled(x, y, w, h);
         self.controls.push(Control {
             rect: Rect { x, y, w, h },
             bg_color: self.bg_color,
@@ -89,11 +250,13 @@ impl<T1, T2> Canvas<T1, T2> {
 
     // Creates a label with fg_color, font_size inherited from the canvas
     pub fn label(&mut self, x: f32, y: f32, w: f32, h: f32, text: Arc<str>) -> usize {
+        let Rect { x, y, w, h } = self.scaled(x, y, w, h);
         self.controls.push(Control {
             rect: Rect { x, y, w, h },
             text,
             fg_color: self.fg_color,
-            size: self.font_size,
+            size: self.scaled_font_size(),
+            font: self.font.clone(),
             on_render_fg: Some(Control::render_text),
             ..Default::default()
         });
@@ -102,11 +265,13 @@ impl<T1, T2> Canvas<T1, T2> {
 
     // Creates a label with fg_color, font_size inherited from the canvas
     pub fn label_centered(&mut self, x: f32, y: f32, w: f32, h: f32, text: Arc<str>) -> usize {
+        let Rect { x, y, w, h } = self.scaled(x, y, w, h);
         self.controls.push(Control {
             rect: Rect { x, y, w, h },
             text,
             fg_color: self.fg_color,
-            size: self.font_size,
+            size: self.scaled_font_size(),
+            font: self.font.clone(),
             on_render_fg: Some(Control::render_text_centered),
             ..Default::default()
         });
@@ -115,6 +280,7 @@ impl<T1, T2> Canvas<T1, T2> {
 
     // Creates a button with fg_color, bg_color, font_size inherited from the canvas
     pub fn button(&mut self, x: f32, y: f32, w: f32, h: f32, text: Arc<str>) -> usize {
+        let Rect { x, y, w, h } = self.scaled(x, y, w, h);
         let idx = self.controls.len();
 
         self.interactive_set_idx(x, y, w, h, idx);
@@ -124,7 +290,8 @@ impl<T1, T2> Canvas<T1, T2> {
             text,
             fg_color: self.fg_color,
             bg_color: self.bg_color,
-            size: self.font_size,
+            size: self.scaled_font_size(),
+            font: self.font.clone(),
             on_render_bg: Some(Control::render_rect),
             on_render_fg: Some(Control::render_text_centered),
             on_render_hl: Some(Control::render_highlight),
@@ -135,6 +302,9 @@ impl<T1, T2> Canvas<T1, T2> {
     }
 
     pub fn key_button(&mut self, x: f32, y: f32, w: f32, h: f32, label: &Vec<String>) -> usize {
+        let Rect { x, y, w, h } = self.scaled(x, y, w, h);
+        let font_size = self.scaled_font_size();
+        let margin = 4. * self.scale;
         let idx = self.controls.len();
         self.interactive_set_idx(x, y, w, h, idx);
 
@@ -150,22 +320,23 @@ impl<T1, T2> Canvas<T1, T2> {
             self.controls.push(Control {
                 rect: if i == 0 {
                     Rect {
-                        x: x + 4.,
-                        y: y + (self.font_size as f32) + 4.,
+                        x: x + margin,
+                        y: y + (font_size as f32) + margin,
                         w,
                         h,
                     }
                 } else {
                     Rect {
                         x: x + w * 0.5,
-                        y: y + h - (self.font_size as f32) + 4.,
+                        y: y + h - (font_size as f32) + margin,
                         w,
                         h,
                     }
                 },
                 text: Arc::from(item.as_str()),
                 fg_color: self.fg_color,
-                size: self.font_size,
+                size: font_size,
+                font: self.font.clone(),
                 on_render_fg: Some(Control::render_text),
                 ..Default::default()
             });
@@ -174,13 +345,201 @@ impl<T1, T2> Canvas<T1, T2> {
         idx
     }
 
+    // Creates a slider with fg_color, bg_color inherited from the canvas
+    pub fn slider(
+        &mut self,
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        range: RangeInclusive<f32>,
+        step: f32,
+    ) -> usize {
+        let Rect { x, y, w, h } = self.scaled(x, y, w, h);
+        let idx = self.controls.len();
+
+        self.interactive_set_idx(x, y, w, h, idx);
+
+        self.controls.push(Control {
+            rect: Rect { x, y, w, h },
+            fg_color: self.fg_color,
+            bg_color: self.bg_color,
+            value: *range.start(),
+            min: *range.start(),
+            max: *range.end(),
+            step,
+            focus_id: focus::new_focus_id(),
+            on_drag: Some(Control::slider_drag),
+            on_poll: Some(Control::slider_poll),
+            on_render_bg: Some(Control::render_rect),
+            on_render_fg: Some(Control::render_slider),
+            on_render_hl: Some(Control::render_highlight),
+            ..Default::default()
+        });
+
+        idx
+    }
+
+    // Creates a toggle switch with fg_color, bg_color inherited from the canvas
+    pub fn toggle(&mut self, x: f32, y: f32, w: f32, h: f32, on: bool) -> usize {
+        let Rect { x, y, w, h } = self.scaled(x, y, w, h);
+        let idx = self.controls.len();
+
+        self.interactive_set_idx(x, y, w, h, idx);
+
+        self.controls.push(Control {
+            rect: Rect { x, y, w, h },
+            fg_color: self.fg_color,
+            bg_color: self.bg_color,
+            value: if on { 1. } else { 0. },
+            on_press: Some(Control::toggle_press),
+            test_highlight: Some(Control::toggle_test),
+            on_render_bg: Some(Control::render_rect),
+            on_render_fg: Some(Control::render_toggle),
+            on_render_hl: Some(Control::render_highlight),
+            ..Default::default()
+        });
+
+        idx
+    }
+
+    // Creates a color picker: a saturation/value square above a hue bar
+    // on_change fires while it's dragged, on_release once it's let go
+    pub fn color_picker(&mut self, x: f32, y: f32, w: f32, h: f32, color: Vec3) -> usize {
+        let Rect { x, y, w, h } = self.scaled(x, y, w, h);
+        let idx = self.controls.len();
+
+        self.interactive_set_idx(x, y, w, h, idx);
+
+        self.controls.push(Control {
+            rect: Rect { x, y, w, h },
+            bg_color: self.bg_color,
+            hsv: rgb_to_hsv(color),
+            on_drag: Some(Control::color_picker_drag),
+            on_render_bg: Some(Control::render_rect),
+            on_render_fg: Some(Control::render_color_picker),
+            ..Default::default()
+        });
+
+        idx
+    }
+
+    // Creates a scrollable list of text rows with fg_color, bg_color, font_size inherited from the canvas
+    // Rows outside of the list's rect are neither rendered nor hit-tested
+    pub fn list(&mut self, x: f32, y: f32, w: f32, h: f32, row_height: f32) -> usize {
+        let Rect { x, y, w, h } = self.scaled(x, y, w, h);
+        let row_height = row_height * self.scale;
+        let idx = self.controls.len();
+
+        self.interactive_set_idx(x, y, w, h, idx);
+
+        self.controls.push(Control {
+            rect: Rect { x, y, w, h },
+            fg_color: self.fg_color,
+            bg_color: self.bg_color,
+            size: self.scaled_font_size(),
+            font: self.font.clone(),
+            row_height,
+            on_click: Some(Control::list_click),
+            on_scroll: Some(Control::list_scroll),
+            on_render_bg: Some(Control::render_rect),
+            on_render_fg: Some(Control::render_list),
+            ..Default::default()
+        });
+
+        idx
+    }
+
+    // Creates a dropdown with fg_color, bg_color, font_size inherited from the canvas
+    // Pressing it opens a popup list of the items; on_select fires once one is picked
+    pub fn dropdown(&mut self, x: f32, y: f32, w: f32, h: f32, items: Vec<Arc<str>>) -> usize {
+        let Rect { x, y, w, h } = self.scaled(x, y, w, h);
+        let idx = self.controls.len();
+
+        self.interactive_set_idx(x, y, w, h, idx);
+
+        self.controls.push(Control {
+            rect: Rect { x, y, w, h },
+            fg_color: self.fg_color,
+            bg_color: self.bg_color,
+            size: self.scaled_font_size(),
+            font: self.font.clone(),
+            items,
+            focus_id: focus::new_focus_id(),
+            on_press: Some(Control::dropdown_press),
+            on_poll: Some(Control::dropdown_poll),
+            on_render_bg: Some(Control::render_rect),
+            on_render_fg: Some(Control::render_dropdown),
+            on_render_hl: Some(Control::render_highlight),
+            ..Default::default()
+        });
+
+        idx
+    }
+
+    // Creates a text field with fg_color, bg_color, font_size inherited from the canvas
+    // Pointing at it routes the on-screen keyboard's input into the field
+    pub fn text_field(&mut self, x: f32, y: f32, w: f32, h: f32, text: Arc<str>) -> usize {
+        let Rect { x, y, w, h } = self.scaled(x, y, w, h);
+        let idx = self.controls.len();
+
+        self.interactive_set_idx(x, y, w, h, idx);
+
+        self.controls.push(Control {
+            rect: Rect { x, y, w, h },
+            text,
+            fg_color: self.fg_color,
+            bg_color: self.bg_color,
+            size: self.scaled_font_size(),
+            font: self.font.clone(),
+            focus_id: focus::new_focus_id(),
+            on_click: Some(Control::text_field_click),
+            on_poll: Some(Control::text_field_poll),
+            on_render_bg: Some(Control::render_rect),
+            on_render_fg: Some(Control::render_text_field),
+            on_render_hl: Some(Control::render_highlight),
+            ..Default::default()
+        });
+
+        idx
+    }
+
+    // Creates an image from a PNG file relative to the config root
+    pub fn image(&mut self, x: f32, y: f32, w: f32, h: f32, file_name: &str) -> usize {
+        let Rect { x, y, w, h } = self.scaled(x, y, w, h);
+        let path = config_io::get_config_file_path(file_name);
+        let image = match load_png(&path) {
+            Ok(image) => Some(image),
+            Err(e) => {
+                error!("Failed to load image {}: {}", path.to_string_lossy(), e);
+                None
+            }
+        };
+
+        self.controls.push(Control {
+            rect: Rect { x, y, w, h },
+            image,
+            on_render_bg: Some(Control::render_image),
+            ..Default::default()
+        });
+        self.controls.len() - 1
+    }
+
+    fn scaled(&self, x: f32, y: f32, w: f32, h: f32) -> Rect {
+        Rect::new(x, y, w, h).scaled(self.scale)
+    }
+
+    fn scaled_font_size(&self) -> isize {
+        (self.font_size as f32 * self.scale * self.text_scale).round() as isize
+    }
+
     fn interactive_set_idx(&mut self, x: f32, y: f32, w: f32, h: f32, idx: usize) {
         let (x, y, w, h) = (x as usize, y as usize, w as usize, h as usize);
 
-        let x_min = (x / RES_DIVIDER).max(0);
-        let y_min = (y / RES_DIVIDER).max(0);
-        let x_max = (x_min + (w / RES_DIVIDER)).min(self.interact_stride - 1);
-        let y_max = (y_min + (h / RES_DIVIDER)).min(self.interact_rows - 1);
+        let x_min = (x / self.res_divider).max(0);
+        let y_min = (y / self.res_divider).max(0);
+        let x_max = (x_min + (w / self.res_divider)).min(self.interact_stride - 1);
+        let y_max = (y_min + (h / self.res_divider)).min(self.interact_rows - 1);
 
         for y in y_min..y_max {
             for x in x_min..x_max {
@@ -190,13 +549,20 @@ impl<T1, T2> Canvas<T1, T2> {
     }
 
     fn interactive_get_idx(&self, uv: Vec2) -> Option<usize> {
-        let x = (uv.x * self.width as f32) as usize;
-        let y = (uv.y * self.height as f32) as usize;
-        let x = (x / RES_DIVIDER).max(0).min(self.interact_stride - 1);
-        let y = (y / RES_DIVIDER).max(0).min(self.interact_rows - 1);
+        let x = (uv.x * self.render_width as f32) as usize;
+        let y = (uv.y * self.render_height as f32) as usize;
+        let x = (x / self.res_divider).max(0).min(self.interact_stride - 1);
+        let y = (y / self.res_divider).max(0).min(self.interact_rows - 1);
         self.interact_map[y * self.interact_stride + x].map(|x| x as usize)
     }
 
+    fn uv_to_pos(&self, uv: Vec2) -> Vec2 {
+        vec2(
+            uv.x * self.render_width as f32,
+            uv.y * self.render_height as f32,
+        )
+    }
+
     fn render_bg(&mut self, sk: &SkDraw, app: &mut AppState) {
         app.gl.begin_sk(sk, &self.gl.as_ref().unwrap().tex_bg);
         app.gl.clear();
@@ -220,17 +586,192 @@ impl<T1, T2> Canvas<T1, T2> {
     }
 }
 
+impl<T1, T2> Canvas<T1, T2> {
+    // Re-renders only the given regions of the fg texture, along with
+    // every control that overlaps them
+    fn render_fg_regions(&mut self, sk: &SkDraw, app: &mut AppState, regions: &[Rect]) {
+        app.gl.begin_sk(sk, &self.gl.as_ref().unwrap().tex_fg);
+        for region in regions {
+            app.gl.clip(region.x, region.y, region.w, region.h);
+            app.gl.clear();
+            for c in self.controls.iter_mut() {
+                if let Some(fun) = c.on_render_fg {
+                    if c.render_bounds().intersects(region) {
+                        fun(c, sk, app);
+                    }
+                }
+            }
+            if let Some((idx, rect)) = self.tooltip {
+                if rect.intersects(region) {
+                    if let Some(text) = self.controls[idx].tooltip.clone() {
+                        render_tooltip(app, &text, &rect, self.scale);
+                    }
+                }
+            }
+            app.gl.unclip();
+        }
+        app.gl.end();
+    }
+
+    // Picks the next or previous control that can be pressed; lists are stepped through row
+    // by row before moving on
+    fn nav_step(&mut self, forward: bool) {
+        if let Some(idx) = self.nav_control {
+            if self.controls[idx].list_step(forward) {
+                return;
+            }
+        }
+
+        let len = self.controls.len() as isize;
+        let step = if forward { 1 } else { -1 };
+        let mut i = match self.nav_control {
+            Some(idx) => idx as isize,
+            None if forward => -1,
+            None => len,
+        };
+        for _ in 0..len {
+            i = (i + step).rem_euclid(len);
+            let c = &mut self.controls[i as usize];
+            if c.on_press.is_none()
+                && c.on_release.is_none()
+                && c.on_click.is_none()
+                && c.on_drag.is_none()
+            {
+                continue;
+            }
+            if c.row_height > 0. && !c.items.is_empty() {
+                let row = if forward { 0 } else { c.items.len() - 1 };
+                c.selected = Some(row);
+                c.scroll_to_row(row);
+            }
+            self.nav_control = Some(i as usize);
+            return;
+        }
+    }
+
+    // Does what pointing at the picked control and clicking would
+    fn nav_activate(&mut self, session: &AppSession) {
+        let Some(idx) = self.nav_control else {
+            return;
+        };
+        let c = &mut self.controls[idx];
+        if c.row_height > 0. {
+            if let (Some(row), Some(fun)) = (c.selected, c.on_select) {
+                fun(c, row, &mut self.data);
+            }
+        } else if c.focus_id != 0 && c.on_drag.is_some() {
+            // sliders can't be dragged without a pointer, so type the value in
+            numpad::open(c.focus_id, c.value, c.min, c.max);
+        } else if let Some(fun) = c.on_press {
+            fun(c, session, &mut self.data);
+            if let Some(fun) = c.on_release {
+                fun(c, &mut self.data);
+            }
+        } else if let Some(fun) = c.on_release {
+            fun(c, &mut self.data);
+        } else if let Some(fun) = c.on_click {
+            let center = vec2(c.rect.x + c.rect.w * 0.5, c.rect.y + c.rect.h * 0.5);
+            fun(c, center, &mut self.data);
+        }
+    }
+
+    // Finds the tooltip to show, if a control has been hovered long enough
+    fn find_tooltip(&self, app: &mut AppState) -> Option<(usize, Rect)> {
+        for hand in 0..2 {
+            let (Some(idx), Some(since)) = (self.hover_controls[hand], self.hover_since[hand])
+            else {
+                continue;
+            };
+            if since.elapsed() < TOOLTIP_DELAY {
+                continue;
+            }
+            let Some(text) = self.controls[idx].tooltip.as_ref() else {
+                continue;
+            };
+            if let Some(current) = self.tooltip.filter(|(i, _)| *i == idx) {
+                return Some(current);
+            }
+
+            let size = (TOOLTIP_FONT_SIZE as f32 * self.scale) as isize;
+            let padding = TOOLTIP_PADDING * self.scale;
+            let (w, h) = app.fc.get_text_size(text, None, size);
+            let w = w + 2. * padding;
+            let h = h + 2. * padding;
+            let pos = self.hover_pos[hand];
+            let x = (pos.x + 8.).min(self.render_width as f32 - w).max(0.);
+            let y = (pos.y - h - 8.).max(0.);
+            return Some((idx, Rect::new(x, y, w, h)));
+        }
+        None
+    }
+}
+
+fn render_tooltip(app: &mut AppState, text: &str, rect: &Rect, scale: f32) {
+    let size = (TOOLTIP_FONT_SIZE as f32 * scale) as isize;
+    let padding = TOOLTIP_PADDING * scale;
+
+    app.gl
+        .draw_color(Vec3::splat(0.1), 0.9, rect.x, rect.y, rect.w, rect.h);
+
+    let mut cur_x = rect.x + padding;
+    let y = rect.y + padding + size as f32;
+    for glyph in app.fc.get_glyphs(text, None, size) {
+        if let Some(handle) = glyph.tex {
+            app.gl.draw_glyph(
+                handle,
+                glyph.uv,
+                cur_x + glyph.left,
+                y - glyph.top,
+                glyph.width,
+                glyph.height,
+                Vec3::ONE,
+                glyph.color,
+            );
+        }
+        cur_x += glyph.advance;
+    }
+}
+
 impl<T1, T2> OverlayBackend for Canvas<T1, T2> {}
 impl<T1, T2> InteractionHandler for Canvas<T1, T2> {
     fn on_left(&mut self, hand: usize) {
         self.hover_controls[hand] = None;
+        self.long_press[hand] = None;
     }
     fn on_hover(&mut self, hit: &crate::interactions::PointerHit) {
-        if let Some(i) = self.interactive_get_idx(hit.uv) {
-            self.hover_controls[hit.hand] = Some(i);
-        } else {
-            self.hover_controls[hit.hand] = None;
+        if let Some(idx) = self.pressed_controls[hit.hand] {
+            let pos = self.uv_to_pos(hit.uv);
+            if let Some((since, start, value)) = self.long_press[hit.hand] {
+                if pos.distance(start) > LONG_PRESS_SLOP * self.scale {
+                    self.long_press[hit.hand] = None;
+                } else if since.elapsed() > LONG_PRESS_DELAY {
+                    // put back what the press itself changed, then type in the value instead
+                    self.long_press[hit.hand] = None;
+                    self.pressed_controls[hit.hand] = None;
+                    let c = &mut self.controls[idx];
+                    let current = c.value;
+                    c.set_value(value);
+                    if c.value != current {
+                        if let Some(fun) = c.on_change {
+                            fun(c, &mut self.data);
+                        }
+                    }
+                    numpad::open(c.focus_id, c.value, c.min, c.max);
+                    return;
+                }
+            }
+            let c = &mut self.controls[idx];
+            if let Some(fun) = c.on_drag {
+                fun(c, pos, &mut self.data);
+            }
+        }
+
+        let idx = self.interactive_get_idx(hit.uv);
+        if idx != self.hover_controls[hit.hand] {
+            self.hover_since[hit.hand] = Some(Instant::now());
         }
+        self.hover_controls[hit.hand] = idx;
+        self.hover_pos[hit.hand] = self.uv_to_pos(hit.uv);
     }
     fn on_pointer(
         &mut self,
@@ -245,38 +786,62 @@ impl<T1, T2> InteractionHandler for Canvas<T1, T2> {
         };
 
         if let Some(idx) = idx {
+            let pos = self.uv_to_pos(hit.uv);
             let c = &mut self.controls[idx];
             if pressed {
+                // controls that only act on release still need to be found again then
+                if c.on_release.is_some() {
+                    self.pressed_controls[hit.hand] = Some(idx);
+                }
                 if let Some(ref mut f) = c.on_press {
                     self.pressed_controls[hit.hand] = Some(idx);
                     f(c, &session, &mut self.data);
                 }
-            } else if let Some(ref mut f) = c.on_release {
+                if let Some(f) = c.on_click {
+                    f(c, pos, &mut self.data);
+                }
+                if let Some(f) = c.on_drag {
+                    self.pressed_controls[hit.hand] = Some(idx);
+                    if c.focus_id != 0 {
+                        self.long_press[hit.hand] = Some((Instant::now(), pos, c.value));
+                    }
+                    f(c, pos, &mut self.data);
+                }
+            } else {
                 self.pressed_controls[hit.hand] = None;
-                f(c, &mut self.data);
+                self.long_press[hit.hand] = None;
+                if let Some(ref mut f) = c.on_release {
+                    f(c, &mut self.data);
+                }
             }
         }
     }
-    fn on_scroll(&mut self, _hit: &crate::interactions::PointerHit, _delta: f32) {}
+    fn on_scroll(&mut self, hit: &crate::interactions::PointerHit, delta: f32) {
+        if let Some(idx) = self.interactive_get_idx(hit.uv) {
+            let c = &mut self.controls[idx];
+            if let Some(fun) = c.on_scroll {
+                fun(c, delta, &mut self.data);
+            }
+        }
+    }
+    fn on_nav(&mut self, session: &AppSession, _hand: usize, nav: Nav) {
+        match nav {
+            Nav::Prev => self.nav_step(false),
+            Nav::Next => self.nav_step(true),
+            Nav::Activate => self.nav_activate(session),
+        }
+    }
 }
 
 impl<T1, T2> OverlayRenderer for Canvas<T1, T2> {
     fn init(&mut self, sk: &stereokit::SkDraw, app: &mut AppState) {
+        let (w, h) = (self.render_width as i32, self.render_height as i32);
+        let supersampled = self.render_width != self.width || self.render_height != self.height;
+
         self.gl = Some(CanvasGl {
-            tex_bg: sk.tex_gen_color(
-                COLOR_TRANSPARENT,
-                self.width as _,
-                self.height as _,
-                TextureType::IMAGE_NO_MIPS,
-                TextureFormat::RGBA32,
-            ),
-            tex_fg: sk.tex_gen_color(
-                COLOR_TRANSPARENT,
-                self.width as _,
-                self.height as _,
-                TextureType::IMAGE_NO_MIPS,
-                TextureFormat::RGBA32,
-            ),
+            tex_bg: app.tex_pool.take(sk, &mut app.gl, w, h, false),
+            tex_fg: app.tex_pool.take(sk, &mut app.gl, w, h, false),
+            tex_ss: supersampled.then(|| app.tex_pool.take(sk, &mut app.gl, w, h, false)),
         });
 
         self.render_bg(sk, app);
@@ -285,29 +850,65 @@ impl<T1, T2> OverlayRenderer for Canvas<T1, T2> {
     }
     fn pause(&mut self, _app: &mut AppState) {}
     fn resume(&mut self, _app: &mut AppState) {}
+    fn release(&mut self, app: &mut AppState) {
+        let Some(gl) = self.gl.take() else {
+            return;
+        };
+        let (w, h) = (self.render_width as i32, self.render_height as i32);
+        app.tex_pool.give(gl.tex_bg, w, h, false);
+        app.tex_pool.give(gl.tex_fg, w, h, false);
+        if let Some(tex_ss) = gl.tex_ss {
+            app.tex_pool.give(tex_ss, w, h, false);
+        }
+    }
     fn render(&mut self, sk: &stereokit::SkDraw, tex: &Tex, app: &mut AppState) {
         if self.gl.is_none() {
             return;
         }
+        let _timer = perf::time(Section::Canvas);
+
+        let mut dirty_rects = Vec::new();
 
-        let mut dirty = false;
+        // glyphs that were still being rasterized were drawn blank, so redraw the text
+        let glyphs_landed = app.fc.generation() != self.glyph_generation;
+        self.glyph_generation = app.fc.generation();
 
         for c in self.controls.iter_mut() {
+            if glyphs_landed && c.on_render_fg.is_some() {
+                c.dirty = true;
+            }
+            if let Some(fun) = c.on_poll {
+                fun(c, &mut self.data);
+            }
             if let Some(fun) = c.on_update {
                 fun(c, &mut self.data);
             }
             if c.dirty {
-                dirty = true;
+                dirty_rects.push(c.render_bounds());
                 c.dirty = false;
             }
         }
 
-        if dirty {
-            self.render_fg(sk, app);
+        let tooltip = self.find_tooltip(app);
+        if tooltip.map(|t| t.0) != self.tooltip.map(|t| t.0) {
+            if let Some((_, rect)) = self.tooltip {
+                dirty_rects.push(rect);
+            }
+            if let Some((_, rect)) = tooltip {
+                dirty_rects.push(rect);
+            }
+            self.tooltip = tooltip;
+        }
+
+        if !dirty_rects.is_empty() {
+            self.render_fg_regions(sk, app, &dirty_rects);
         }
 
         let gl = self.gl.as_ref().unwrap();
-        app.gl.begin_sk(sk, tex);
+        app.gl.begin_sk(sk, gl.tex_ss.as_ref().unwrap_or(tex));
+        if gl.tex_ss.is_some() {
+            app.gl.clear();
+        }
 
         // static background
         let handle = unsafe { sk.tex_get_surface(gl.tex_bg.as_ref()) } as usize as u32;
@@ -324,6 +925,9 @@ impl<T1, T2> OverlayRenderer for Canvas<T1, T2> {
                     render(c, sk, app, false);
                 }
             }
+            if self.nav_control == Some(i) {
+                c.render_nav_focus(app, 2. * self.scale);
+            }
         }
 
         // mostly static text
@@ -331,6 +935,13 @@ impl<T1, T2> OverlayRenderer for Canvas<T1, T2> {
         app.gl.draw_sprite_full(handle);
 
         app.gl.end();
+
+        if let Some(tex_ss) = gl.tex_ss.as_ref() {
+            let handle = unsafe { sk.tex_get_surface(tex_ss.as_ref()) } as usize as u32;
+            app.gl.begin_sk(sk, tex);
+            app.gl.blit(handle);
+            app.gl.end();
+        }
     }
 }
 
@@ -340,13 +951,47 @@ pub struct Control<T1, T2> {
     fg_color: Vec3,
     bg_color: Vec3,
     text: Arc<str>,
+    spans: Vec<TextSpan>,
+    overflow: TextOverflow,
+    tooltip: Option<Arc<str>>,
+    font: Option<Arc<str>>,
     size: isize,
     dirty: bool,
 
+    value: f32,
+    min: f32,
+    max: f32,
+    step: f32,
+
+    // color pickers keep hue and saturation when the value goes to 0
+    hsv: Vec3,
+
+    items: Vec<Arc<str>>,
+    selected: Option<usize>,
+    scroll: f32,
+    row_height: f32,
+
+    focus_id: usize,
+    focused: bool,
+
+    // Bar along the bottom edge, drawn along with the highlight
+    indicator: bool,
+
+    image: Option<Image>,
+    image_tex: Option<GlTexture>,
+
     pub on_update: Option<fn(&mut Self, &mut T1)>,
     pub on_press: Option<fn(&mut Self, session: &AppSession, &mut T1)>,
     pub on_release: Option<fn(&mut Self, &mut T1)>,
     pub test_highlight: Option<fn(&mut Self, &mut T1) -> bool>,
+    pub on_change: Option<fn(&mut Self, &mut T1)>,
+    pub on_select: Option<fn(&mut Self, usize, &mut T1)>,
+    pub on_submit: Option<fn(&mut Self, &mut T1)>,
+
+    on_click: Option<fn(&mut Self, Vec2, &mut T1)>,
+    on_drag: Option<fn(&mut Self, Vec2, &mut T1)>,
+    on_scroll: Option<fn(&mut Self, f32, &mut T1)>,
+    on_poll: Option<fn(&mut Self, &mut T1)>,
 
     on_render_bg: Option<fn(&mut Self, &SkDraw, &mut AppState)>,
     on_render_hl: Option<fn(&mut Self, &SkDraw, &mut AppState, bool)>,
@@ -356,17 +1001,30 @@ pub struct Control<T1, T2> {
 impl<T1, T2> Default for Control<T1, T2> {
     fn default() -> Self {
         Self {
-            rect: Rect {
-                x: 0.,
-                y: 0.,
-                w: 0.,
-                h: 0.,
-            },
+            rect: Rect::default(),
             fg_color: Vec3::ONE,
             bg_color: Vec3::ZERO,
             text: Arc::from(""),
+            spans: Vec::new(),
+            overflow: TextOverflow::Visible,
+            tooltip: None,
+            font: None,
             dirty: false,
             size: 24,
+            value: 0.,
+            min: 0.,
+            max: 1.,
+            step: 0.,
+            hsv: Vec3::ZERO,
+            items: Vec::new(),
+            selected: None,
+            scroll: 0.,
+            row_height: 0.,
+            focus_id: 0,
+            focused: false,
+            indicator: false,
+            image: None,
+            image_tex: None,
             state: None,
             on_update: None,
             on_render_bg: None,
@@ -375,6 +1033,13 @@ impl<T1, T2> Default for Control<T1, T2> {
             test_highlight: None,
             on_press: None,
             on_release: None,
+            on_change: None,
+            on_select: None,
+            on_submit: None,
+            on_click: None,
+            on_drag: None,
+            on_scroll: None,
+            on_poll: None,
         }
     }
 }
@@ -386,6 +1051,7 @@ impl<T1, T2> Control<T1, T2> {
             return;
         }
         self.text = text.into();
+        self.spans.clear();
         self.dirty = true;
     }
 
@@ -394,6 +1060,313 @@ impl<T1, T2> Control<T1, T2> {
         &self.text
     }
 
+    // Replaces the text with multiple differently styled spans
+    pub fn set_spans(&mut self, spans: Vec<TextSpan>) {
+        self.text = spans.iter().map(|s| &*s.text).collect::<String>().into();
+        self.spans = spans;
+        self.dirty = true;
+    }
+
+    // Renders the text using a different font family, e.g. a symbol or icon font
+    // Shown near the pointer after hovering the control for a while
+    pub fn set_tooltip(&mut self, text: &str) {
+        self.tooltip = Some(text.into());
+    }
+
+    // Marks a highlighted control as being in a second, stronger state, e.g. a locked modifier
+    #[inline(always)]
+    pub fn set_indicator(&mut self, on: bool) {
+        self.indicator = on;
+    }
+
+    pub fn set_overflow(&mut self, overflow: TextOverflow) {
+        if self.overflow == overflow {
+            return;
+        }
+        self.overflow = overflow;
+        self.dirty = true;
+    }
+
+    pub fn set_font(&mut self, font: &str) {
+        if self.font.as_deref() == Some(font) {
+            return;
+        }
+        self.font = Some(font.into());
+        self.dirty = true;
+    }
+
+    #[inline(always)]
+    pub fn set_value(&mut self, value: f32) {
+        let value = value.clamp(self.min, self.max);
+        if self.value == value {
+            return;
+        }
+        self.value = value;
+        self.dirty = true;
+    }
+
+    #[inline(always)]
+    pub fn get_value(&self) -> f32 {
+        self.value
+    }
+
+    #[inline(always)]
+    pub fn set_on(&mut self, on: bool) {
+        self.set_value(if on { 1. } else { 0. });
+    }
+
+    #[inline(always)]
+    pub fn is_on(&self) -> bool {
+        self.value > 0.5
+    }
+
+    fn toggle_press(&mut self, _session: &AppSession, data: &mut T1) {
+        self.set_on(!self.is_on());
+        if let Some(fun) = self.on_change {
+            fun(self, data);
+        }
+    }
+
+    fn toggle_test(&mut self, _data: &mut T1) -> bool {
+        self.is_on()
+    }
+
+    pub fn set_color(&mut self, color: Vec3) {
+        if hsv_to_rgb(self.hsv) == color {
+            return;
+        }
+        self.hsv = rgb_to_hsv(color);
+        self.dirty = true;
+    }
+
+    #[inline(always)]
+    pub fn get_color(&self) -> Vec3 {
+        hsv_to_rgb(self.hsv)
+    }
+
+    // Splits the rect into the saturation/value square and the hue bar below it
+    fn color_picker_parts(&self) -> (Rect, Rect) {
+        let Rect { x, y, w, h } = self.rect;
+        (
+            Rect::new(x, y, w, h * 0.8),
+            Rect::new(x, y + h * 0.85, w, h * 0.15),
+        )
+    }
+
+    fn color_picker_drag(&mut self, pos: Vec2, data: &mut T1) {
+        let (sv, hue) = self.color_picker_parts();
+        let before = self.hsv;
+        if pos.y < (sv.y + sv.h + hue.y) * 0.5 {
+            self.hsv.y = ((pos.x - sv.x) / sv.w).clamp(0., 1.);
+            self.hsv.z = 1. - ((pos.y - sv.y) / sv.h).clamp(0., 1.);
+        } else {
+            self.hsv.x = ((pos.x - hue.x) / hue.w).clamp(0., 1.);
+        }
+
+        if self.hsv != before {
+            self.dirty = true;
+            if let Some(fun) = self.on_change {
+                fun(self, data);
+            }
+        }
+    }
+
+    pub fn set_items(&mut self, items: Vec<Arc<str>>) {
+        self.items = items;
+        if self.selected.is_some_and(|i| i >= self.items.len()) {
+            self.selected = None;
+        }
+        self.scroll = self.scroll.clamp(0., self.max_scroll());
+        self.dirty = true;
+    }
+
+    #[inline(always)]
+    pub fn get_items(&self) -> &[Arc<str>] {
+        &self.items
+    }
+
+    #[inline(always)]
+    pub fn get_selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn set_selected(&mut self, selected: Option<usize>) {
+        let selected = selected.filter(|i| *i < self.items.len());
+        if self.selected != selected {
+            self.selected = selected;
+            self.dirty = true;
+        }
+    }
+
+    fn max_scroll(&self) -> f32 {
+        (self.items.len() as f32 * self.row_height - self.rect.h).max(0.)
+    }
+
+    fn list_click(&mut self, pos: Vec2, data: &mut T1) {
+        if self.row_height <= 0. {
+            return;
+        }
+        let row = ((pos.y - self.rect.y + self.scroll) / self.row_height) as usize;
+        if row >= self.items.len() {
+            return;
+        }
+
+        self.selected = Some(row);
+        self.dirty = true;
+        if let Some(fun) = self.on_select {
+            fun(self, row, data);
+        }
+    }
+
+    // Moves the selected row of a list, if there's a row to move to
+    fn list_step(&mut self, forward: bool) -> bool {
+        if self.row_height <= 0. {
+            return false;
+        }
+        let row = match self.selected {
+            Some(row) if forward && row + 1 < self.items.len() => row + 1,
+            Some(row) if !forward && row > 0 => row - 1,
+            _ => return false,
+        };
+        self.selected = Some(row);
+        self.scroll_to_row(row);
+        true
+    }
+
+    fn scroll_to_row(&mut self, row: usize) {
+        let top = row as f32 * self.row_height;
+        if top < self.scroll {
+            self.scroll = top;
+        } else if top + self.row_height > self.scroll + self.rect.h {
+            self.scroll = top + self.row_height - self.rect.h;
+        }
+        self.scroll = self.scroll.clamp(0., self.max_scroll());
+        self.dirty = true;
+    }
+
+    fn list_scroll(&mut self, delta: f32, _data: &mut T1) {
+        let scroll = (self.scroll - delta * self.row_height * 0.25).clamp(0., self.max_scroll());
+        if scroll != self.scroll {
+            self.scroll = scroll;
+            self.dirty = true;
+        }
+    }
+
+    fn dropdown_press(&mut self, _session: &AppSession, _data: &mut T1) {
+        if !self.items.is_empty() {
+            dropdown::open(self.focus_id, self.items.clone(), self.selected);
+        }
+    }
+
+    // Picks up the row chosen in the popup
+    fn dropdown_poll(&mut self, data: &mut T1) {
+        let Some(row) = dropdown::take_result(self.focus_id) else {
+            return;
+        };
+        if row >= self.items.len() {
+            return;
+        }
+        self.selected = Some(row);
+        self.dirty = true;
+        if let Some(fun) = self.on_select {
+            fun(self, row, data);
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    fn text_field_click(&mut self, _pos: Vec2, _data: &mut T1) {
+        focus::take_focus(self.focus_id);
+    }
+
+    fn text_field_poll(&mut self, data: &mut T1) {
+        let Some(edits) = focus::drain_edits(self.focus_id) else {
+            if self.focused {
+                self.focused = false;
+                self.dirty = true;
+            }
+            return;
+        };
+
+        if !self.focused {
+            self.focused = true;
+            self.dirty = true;
+        }
+
+        for edit in edits {
+            match edit {
+                TextEdit::Insert(s) => {
+                    let text = format!("{}{}", self.text, s);
+                    self.set_text(&text);
+                }
+                TextEdit::Backspace => {
+                    let mut text = self.text.to_string();
+                    text.pop();
+                    self.set_text(&text);
+                }
+                TextEdit::Submit => {
+                    focus::release_focus(self.focus_id);
+                    if let Some(fun) = self.on_submit {
+                        fun(self, data);
+                    }
+                    continue;
+                }
+                TextEdit::Cancel => {
+                    focus::release_focus(self.focus_id);
+                    continue;
+                }
+            }
+            if let Some(fun) = self.on_change {
+                fun(self, data);
+            }
+        }
+    }
+
+    fn slider_drag(&mut self, pos: Vec2, data: &mut T1) {
+        let t = ((pos.x - self.rect.x) / self.rect.w).clamp(0., 1.);
+        let mut value = self.min + t * (self.max - self.min);
+        if self.step > 0. {
+            value = self.min + ((value - self.min) / self.step).round() * self.step;
+        }
+
+        let before = self.value;
+        self.set_value(value);
+        if self.value != before {
+            if let Some(fun) = self.on_change {
+                fun(self, data);
+            }
+        }
+    }
+
+    // Picks up a value typed on the numpad
+    fn slider_poll(&mut self, data: &mut T1) {
+        let Some(value) = numpad::take_result(self.focus_id) else {
+            return;
+        };
+        let before = self.value;
+        self.set_value(value);
+        if self.value != before {
+            if let Some(fun) = self.on_change {
+                fun(self, data);
+            }
+        }
+    }
+
+    // Area that the fg layer of this control may draw into.
+    // Text is drawn above its baseline, so the rect is extended upwards.
+    fn render_bounds(&self) -> Rect {
+        let size = self.size as f32;
+        Rect::new(
+            self.rect.x - size * 0.5,
+            self.rect.y - size * 1.5,
+            self.rect.w + size,
+            self.rect.h + size * 2.,
+        )
+    }
+
     fn render_rect(&mut self, _sk: &SkDraw, app: &mut AppState) {
         app.gl.draw_color(
             self.bg_color,
@@ -405,31 +1378,335 @@ impl<T1, T2> Control<T1, T2> {
         );
     }
 
+    fn render_image(&mut self, _sk: &SkDraw, app: &mut AppState) {
+        // uploaded again after the GL context was lost
+        if self.image_tex.as_ref().is_some_and(|tex| tex.is_stale()) {
+            self.image_tex = None;
+        }
+        if self.image_tex.is_none() {
+            if let Some(image) = self.image.as_ref() {
+                let mut tex = GlTexture::new(GL_RGBA8 as _);
+                tex.allocate(
+                    image.width,
+                    image.height,
+                    GL_RGBA8 as _,
+                    image.rgba.as_ptr(),
+                );
+                self.image_tex = Some(tex);
+            }
+        }
+
+        if let Some(tex) = self.image_tex.as_ref() {
+            app.gl
+                .draw_sprite(tex, self.rect.x, self.rect.y, self.rect.w, self.rect.h);
+        }
+    }
+
     fn render_highlight(&mut self, _sk: &SkDraw, app: &mut AppState, strong: bool) {
+        let alpha = match (strong, STRONG_HIGHLIGHT.load(Ordering::Relaxed)) {
+            (true, true) => 0.8,
+            (false, true) => 0.55,
+            (true, false) => 0.5,
+            (false, false) => 0.3,
+        };
         app.gl.draw_color(
             Vec3::ONE,
-            if strong { 0.5 } else { 0.3 },
+            alpha,
             self.rect.x,
             self.rect.y,
             self.rect.w,
             self.rect.h,
         );
+        if strong && self.indicator {
+            let bar_h = self.rect.h * 0.12;
+            app.gl.draw_color(
+                Vec3::ONE,
+                1.,
+                self.rect.x,
+                self.rect.y + self.rect.h - bar_h,
+                self.rect.w,
+                bar_h,
+            );
+        }
+    }
+
+    // Outline around the control picked with the controller stick
+    fn render_nav_focus(&self, app: &mut AppState, width: f32) {
+        let Rect { x, y, w, h } = self.rect;
+        app.gl.draw_color(Vec3::ONE, 1., x, y, w, width);
+        app.gl.draw_color(Vec3::ONE, 1., x, y + h - width, w, width);
+        app.gl.draw_color(Vec3::ONE, 1., x, y, width, h);
+        app.gl.draw_color(Vec3::ONE, 1., x + w - width, y, width, h);
+    }
+
+    fn render_slider(&mut self, _sk: &SkDraw, app: &mut AppState) {
+        let t = if self.max > self.min {
+            (self.value - self.min) / (self.max - self.min)
+        } else {
+            0.
+        };
+
+        let knob_w = self.rect.h * 0.5;
+        let knob_x = self.rect.x + (self.rect.w - knob_w) * t;
+
+        // filled part of the track
+        app.gl.draw_color(
+            self.fg_color,
+            0.5,
+            self.rect.x,
+            self.rect.y + self.rect.h * 0.4,
+            knob_x - self.rect.x,
+            self.rect.h * 0.2,
+        );
+
+        app.gl
+            .draw_color(self.fg_color, 1., knob_x, self.rect.y, knob_w, self.rect.h);
+    }
+
+    fn render_color_picker(&mut self, _sk: &SkDraw, app: &mut AppState) {
+        let (sv, hue) = self.color_picker_parts();
+        let rgba = |c: Vec3, a: f32| [c.x, c.y, c.z, a];
+
+        // white to the full hue, then darkened towards the bottom
+        let pure = hsv_to_rgb(Vec3::new(self.hsv.x, 1., 1.));
+        app.gl
+            .draw_gradient([1.; 4], rgba(pure, 1.), false, sv.x, sv.y, sv.w, sv.h);
+        app.gl.draw_gradient(
+            [0., 0., 0., 0.],
+            [0., 0., 0., 1.],
+            true,
+            sv.x,
+            sv.y,
+            sv.w,
+            sv.h,
+        );
+
+        // hue is linear in RGB between the six primaries and secondaries
+        let seg_w = hue.w / 6.;
+        for i in 0..6 {
+            let from = hsv_to_rgb(Vec3::new(i as f32 / 6., 1., 1.));
+            let to = hsv_to_rgb(Vec3::new((i + 1) as f32 / 6., 1., 1.));
+            let x = hue.x + seg_w * i as f32;
+            app.gl
+                .draw_gradient(rgba(from, 1.), rgba(to, 1.), false, x, hue.y, seg_w, hue.h);
+        }
+
+        let marker = hue.h * 0.4;
+        let mx = sv.x + sv.w * self.hsv.y - marker * 0.5;
+        let my = sv.y + sv.h * (1. - self.hsv.z) - marker * 0.5;
+        app.gl.draw_color(Vec3::ONE, 1., mx, my, marker, marker);
+        app.gl.draw_color(
+            self.get_color(),
+            1.,
+            mx + 2.,
+            my + 2.,
+            marker - 4.,
+            marker - 4.,
+        );
+
+        let hx = hue.x + hue.w * self.hsv.x;
+        app.gl.draw_color(Vec3::ONE, 1., hx - 1.5, hue.y, 3., hue.h);
+    }
+
+    fn render_toggle(&mut self, _sk: &SkDraw, app: &mut AppState) {
+        let knob_w = self.rect.h.min(self.rect.w * 0.5);
+        let knob_x = if self.is_on() {
+            self.rect.x + self.rect.w - knob_w
+        } else {
+            self.rect.x
+        };
+
+        app.gl
+            .draw_color(self.fg_color, 1., knob_x, self.rect.y, knob_w, self.rect.h);
+    }
+
+    fn render_list(&mut self, _sk: &SkDraw, app: &mut AppState) {
+        if self.row_height <= 0. {
+            return;
+        }
+
+        app.gl
+            .clip(self.rect.x, self.rect.y, self.rect.w, self.rect.h);
+
+        // only the rows that intersect the rect
+        let first = (self.scroll / self.row_height) as usize;
+        let last =
+            (((self.scroll + self.rect.h) / self.row_height).ceil() as usize).min(self.items.len());
+
+        for row in first..last {
+            let y = self.rect.y + row as f32 * self.row_height - self.scroll;
+            if self.selected == Some(row) {
+                app.gl.draw_color(
+                    self.fg_color,
+                    0.3,
+                    self.rect.x,
+                    y,
+                    self.rect.w,
+                    self.row_height,
+                );
+            }
+
+            let text = self.items[row].clone();
+            let baseline = y + (self.row_height + self.size as f32) * 0.5;
+            self.render_line(app, &text, self.rect.x + 4., baseline);
+        }
+
+        app.gl.unclip();
+    }
+
+    fn render_dropdown(&mut self, _sk: &SkDraw, app: &mut AppState) {
+        app.gl
+            .clip(self.rect.x, self.rect.y, self.rect.w, self.rect.h);
+
+        let baseline = self.rect.y + (self.rect.h + self.size as f32) * 0.5;
+        if let Some(text) = self.selected.and_then(|i| self.items.get(i)).cloned() {
+            self.render_line(app, &text, self.rect.x + 4., baseline);
+        }
+        let (w, _) = app.fc.get_text_size("▾", self.font.as_deref(), self.size);
+        self.render_line(app, "▾", self.rect.x + self.rect.w - w - 4., baseline);
+
+        app.gl.unclip();
+    }
+
+    fn render_text_field(&mut self, _sk: &SkDraw, app: &mut AppState) {
+        app.gl
+            .clip(self.rect.x, self.rect.y, self.rect.w, self.rect.h);
+
+        let text = self.text.clone();
+        let baseline = self.rect.y + (self.rect.h + self.size as f32) * 0.5;
+        self.render_line(app, &text, self.rect.x + 4., baseline);
+
+        if self.focused {
+            let (w, _) = app.fc.get_text_size(&text, self.font.as_deref(), self.size);
+            let caret_x = if text.is_empty() { 0. } else { w };
+            app.gl.draw_color(
+                self.fg_color,
+                1.,
+                self.rect.x + 5. + caret_x,
+                baseline - self.size as f32,
+                2.,
+                self.size as f32 * 1.2,
+            );
+        }
+
+        app.gl.unclip();
+    }
+
+    fn render_line(&self, app: &mut AppState, line: &str, x: f32, y: f32) {
+        self.render_run(app, line, self.size, self.fg_color, x, y);
+    }
+
+    // Draws a single line of text starting at the baseline x, y; returns the end x
+    fn render_run(
+        &self,
+        app: &mut AppState,
+        line: &str,
+        size: isize,
+        color: Vec3,
+        x: f32,
+        y: f32,
+    ) -> f32 {
+        let mut cur_x = x;
+        for glyph in app.fc.get_glyphs(line, self.font.as_deref(), size) {
+            if let Some(handle) = glyph.tex {
+                app.gl.draw_glyph(
+                    handle,
+                    glyph.uv,
+                    cur_x + glyph.left,
+                    y - glyph.top,
+                    glyph.width,
+                    glyph.height,
+                    color,
+                    glyph.color,
+                );
+            }
+
+            cur_x += glyph.advance;
+        }
+        cur_x
     }
 
-    fn render_text(&mut self, sk: &SkDraw, app: &mut AppState) {
+    fn render_spans(&self, app: &mut AppState) {
+        let mut cur_x = self.rect.x;
         let mut cur_y = self.rect.y;
+        for span in self.spans.iter() {
+            let size = span.size.unwrap_or(self.size);
+            let color = span.color.unwrap_or(self.fg_color);
+            for (i, line) in span.text.split('\n').enumerate() {
+                if i > 0 {
+                    cur_x = self.rect.x;
+                    cur_y += (self.size as f32) * 1.5;
+                }
+                cur_x = self.render_run(app, line, size, color, cur_x, cur_y);
+            }
+        }
+    }
+
+    // Splits the text into lines, wrapping or truncating them to the rect's width
+    fn fit_lines(&self, app: &mut AppState) -> Vec<String> {
+        let font = self.font.as_deref();
+        let width = |app: &mut AppState, text: &str| app.fc.get_text_size(text, font, self.size).0;
+
+        let mut lines = Vec::new();
         for line in self.text.lines() {
+            match self.overflow {
+                TextOverflow::Visible => lines.push(line.to_string()),
+                TextOverflow::Wrap => {
+                    let mut current = String::new();
+                    for word in line.split(' ') {
+                        if current.is_empty() {
+                            current.push_str(word);
+                            continue;
+                        }
+                        let candidate = format!("{} {}", current, word);
+                        if width(app, &candidate) > self.rect.w {
+                            lines.push(std::mem::replace(&mut current, word.to_string()));
+                        } else {
+                            current = candidate;
+                        }
+                    }
+                    lines.push(current);
+                }
+                TextOverflow::Ellipsis => {
+                    if width(app, line) <= self.rect.w {
+                        lines.push(line.to_string());
+                        continue;
+                    }
+                    let mut truncated = line.to_string();
+                    loop {
+                        truncated.pop();
+                        let candidate = format!("{}…", truncated.trim_end());
+                        if truncated.is_empty() || width(app, &candidate) <= self.rect.w {
+                            lines.push(candidate);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        lines
+    }
+
+    fn render_text(&mut self, _sk: &SkDraw, app: &mut AppState) {
+        if !self.spans.is_empty() {
+            self.render_spans(app);
+            return;
+        }
+
+        let mut cur_y = self.rect.y;
+        for line in self.fit_lines(app) {
             let mut cur_x = self.rect.x;
-            for glyph in app.fc.get_glyphs(line, self.size, sk) {
-                if let Some(tex) = &glyph.tex {
-                    let handle = unsafe { sk.tex_get_surface(tex.as_ref()) } as usize as u32;
+            for glyph in app.fc.get_glyphs(&line, self.font.as_deref(), self.size) {
+                if let Some(handle) = glyph.tex {
                     app.gl.draw_glyph(
                         handle,
+                        glyph.uv,
                         cur_x + glyph.left,
                         cur_y - glyph.top,
                         glyph.width,
                         glyph.height,
                         self.fg_color,
+                        glyph.color,
                     );
                 }
 
@@ -438,22 +1715,26 @@ impl<T1, T2> Control<T1, T2> {
             cur_y += (self.size as f32) * 1.5;
         }
     }
-    fn render_text_centered(&mut self, sk: &SkDraw, app: &mut AppState) {
-        let (w, h) = app.fc.get_text_size(&self.text, self.size, sk);
+    fn render_text_centered(&mut self, _sk: &SkDraw, app: &mut AppState) {
+        let lines = self.fit_lines(app);
+        let (w, h) = app
+            .fc
+            .get_text_size(&lines.join("\n"), self.font.as_deref(), self.size);
 
         let mut cur_y = self.rect.y + (self.rect.h) - (h * 0.5);
-        for line in self.text.lines() {
+        for line in lines {
             let mut cur_x = self.rect.x + (self.rect.w * 0.5) - (w * 0.5);
-            for glyph in app.fc.get_glyphs(line, self.size, sk) {
-                if let Some(tex) = &glyph.tex {
-                    let handle = unsafe { sk.tex_get_surface(tex.as_ref()) } as usize as u32;
+            for glyph in app.fc.get_glyphs(&line, self.font.as_deref(), self.size) {
+                if let Some(handle) = glyph.tex {
                     app.gl.draw_glyph(
                         handle,
+                        glyph.uv,
                         cur_x + glyph.left,
                         cur_y - glyph.top,
                         glyph.width,
                         glyph.height,
                         self.fg_color,
+                        glyph.color,
                     );
                 }
 
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::config_io;
+
+pub const BUILTIN_THEMES: [&str; 3] = ["dark", "light", "high_contrast"];
+
+const THEME_DARK: &str = include_str!("../res/themes/dark.yaml");
+const THEME_LIGHT: &str = include_str!("../res/themes/light.yaml");
+const THEME_HIGH_CONTRAST: &str = include_str!("../res/themes/high_contrast.yaml");
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Theme {
+    pub text_color: String,
+
+    pub watch_bg_color: String,
+    pub button_color: String,
+    pub button_text_color: String,
+    pub keyboard_button_color: String,
+    pub screen_button_color: String,
+    pub toggle_text_color: String,
+
+    pub keyboard_bg_color: String,
+    pub key_color: String,
+    pub key_text_color: String,
+
+    pub clock_font_size: isize,
+    pub font_size: isize,
+    pub key_font_size: isize,
+
+    pub button_padding: f32,
+    pub key_padding: f32,
+}
+
+impl Theme {
+    // Loads themes/<name>.yaml from the config root, or a built-in theme by that name.
+    // Falls back to the dark theme if neither can be loaded.
+    pub fn load(name: &str) -> Theme {
+        let yaml = config_io::load(&format!("themes/{}.yaml", name))
+            .or_else(|| builtin_theme(name).map(str::to_string));
+
+        match yaml {
+            Some(yaml) => match serde_yaml::from_str(&yaml) {
+                Ok(theme) => {
+                    info!("Using theme: {}", name);
+                    return theme;
+                }
+                Err(e) => error!("Failed to parse theme {}: {}", name, e),
+            },
+            None => error!("Unknown theme: {}", name),
+        }
+
+        serde_yaml::from_str(THEME_DARK).expect("Failed to parse built-in theme")
+    }
+
+    // Replaces the named colors, such as text_color; config values are checked on load
+    pub fn with_colors(self, colors: &HashMap<String, String>) -> Theme {
+        if colors.is_empty() {
+            return self;
+        }
+        let Ok(Value::Mapping(mut fields)) = serde_yaml::to_value(&self) else {
+            return self;
+        };
+        for (key, color) in colors {
+            match fields.get_mut(key.as_str()) {
+                Some(Value::String(field)) if key.ends_with("_color") => *field = color.clone(),
+                _ => warn!("theme_colors: {} is not a theme color", key),
+            }
+        }
+        serde_yaml::from_value(Value::Mapping(fields)).unwrap_or(self)
+    }
+}
+
+fn builtin_theme(name: &str) -> Option<&'static str> {
+    match name {
+        "dark" => Some(THEME_DARK),
+        "light" => Some(THEME_LIGHT),
+        "high_contrast" => Some(THEME_HIGH_CONTRAST),
+        _ => None,
+    }
+}
+
+// Returns the built-in theme that comes after the given one
+pub fn next_builtin_theme(name: &str) -> &'static str {
+    let idx = BUILTIN_THEMES
+        .iter()
+        .position(|t| *t == name)
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    BUILTIN_THEMES[idx % BUILTIN_THEMES.len()]
+}
@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use tokio::sync::Mutex;
+use zbus::{dbus_proxy, Connection};
+
+use crate::{overlay::OverlayData, AppState};
+
+#[dbus_proxy(
+    interface = "org.freedesktop.ScreenSaver",
+    default_service = "org.freedesktop.ScreenSaver",
+    default_path = "/org/freedesktop/ScreenSaver"
+)]
+trait ScreenSaver {
+    fn inhibit(&self, application_name: &str, reason_for_inhibit: &str) -> zbus::Result<u32>;
+    fn un_inhibit(&self, cookie: u32) -> zbus::Result<()>;
+}
+
+static WANTED: AtomicBool = AtomicBool::new(false);
+
+// The cookie is only valid on the connection that asked for it
+static INHIBITOR: Lazy<Mutex<Option<(Connection, u32)>>> = Lazy::new(|| Mutex::new(None));
+
+// Inhibits or releases the screensaver whenever the config or the visible screens call for it
+pub fn update(app: &AppState, overlays: &[OverlayData]) {
+    let config = &app.session.config;
+    let wanted = config.idle_inhibit
+        && (!config.idle_inhibit_screens_only
+            || app.screens.iter().any(|(idx, _)| overlays[*idx].visible));
+
+    if WANTED.swap(wanted, Ordering::Relaxed) != wanted {
+        app.rt.spawn(set_inhibited(wanted));
+    }
+}
+
+async fn set_inhibited(wanted: bool) {
+    // held across the calls, so quick toggles are applied in order
+    let mut inhibitor = INHIBITOR.lock().await;
+
+    if !wanted {
+        if let Some((conn, cookie)) = inhibitor.take() {
+            if let Ok(proxy) = ScreenSaverProxy::new(&conn).await {
+                let _ = proxy.un_inhibit(cookie).await;
+            }
+            info!("Screensaver allowed again");
+        }
+        return;
+    }
+    if inhibitor.is_some() {
+        return;
+    }
+
+    let result = async {
+        let conn = Connection::session().await?;
+        let proxy = ScreenSaverProxy::new(&conn).await?;
+        let cookie = proxy
+            .inhibit("wlx-overlay-x", "Desktop is in use from VR")
+            .await?;
+        zbus::Result::Ok((conn, cookie))
+    }
+    .await;
+
+    match result {
+        Ok(pair) => {
+            info!("Screensaver inhibited");
+            *inhibitor = Some(pair);
+        }
+        Err(e) => warn!("Could not inhibit the screensaver: {}", e),
+    }
+}
@@ -1,24 +1,46 @@
 use glam::Vec2;
 use input_linux::{
-    AbsoluteAxis, AbsoluteInfo, AbsoluteInfoSetup, EventKind, InputId, Key, RelativeAxis,
+    AbsoluteAxis, AbsoluteInfo, AbsoluteInfoSetup, EventKind, InputId, Key, LedKind, RelativeAxis,
     UInputHandle,
 };
 use libc::{input_event, timeval};
 use log::{error, info};
 use once_cell::sync::Lazy;
-use std::fs::File;
-use std::{mem::transmute, sync::Mutex};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+    mem::transmute,
+    sync::{Mutex, TryLockError},
+};
 use strum::IntoEnumIterator;
 
-use crate::keyboard::{VirtualKey, MODS_TO_KEYS};
+use crate::{
+    keyboard::{VirtualKey, CAPS_LOCK, MODS_TO_KEYS, NUM_LOCK},
+    xtest::XTestProvider,
+};
 
 pub static INPUT: Lazy<Mutex<Box<dyn InputProvider + Send>>> = Lazy::new(|| {
+    // On a bare X11 session, uinput's absolute coordinates don't match the X screen layout
+    let x11_session = std::env::var_os("WAYLAND_DISPLAY").is_none();
+    if x11_session {
+        if let Some(xtest) = XTestProvider::try_new() {
+            info!("Initialized XTest.");
+            return Mutex::new(Box::new(xtest));
+        }
+    }
     if let Some(uinput) = UInputProvider::try_new() {
         info!("Initialized uinput.");
         return Mutex::new(Box::new(uinput));
     }
     error!("Could not create uinput provider. Keyboard/Mouse input will not work!");
     error!("Check if you're in `input` group: `id -nG`");
+    // still reaches X11 apps under XWayland
+    if let Some(xtest) = XTestProvider::try_new() {
+        info!("Falling back to XTest, only X11 apps will get input.");
+        return Mutex::new(Box::new(xtest));
+    }
     Mutex::new(Box::new(DummyProvider {}))
 });
 
@@ -30,6 +52,36 @@ pub trait InputProvider {
     fn send_key(&self, key: u16, down: bool);
     fn set_desktop_extent(&mut self, extent: Vec2);
     fn on_new_frame(&mut self);
+    // Lets go of every key and button that is still down
+    fn release_all(&mut self);
+    // Caps Lock and Num Lock as the desktop has them, None if there's no way to tell
+    fn lock_state(&mut self) -> Option<u8>;
+}
+
+// Releases whatever is held, so the desktop isn't left with a stuck key when we exit or crash.
+// Never blocks, since it runs from the panic hook.
+pub fn release_held_input() {
+    let Some(input) = Lazy::get(&INPUT) else {
+        return;
+    };
+    let mut input = match input.try_lock() {
+        Ok(input) => input,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(TryLockError::WouldBlock) => return,
+    };
+    input.release_all();
+}
+
+// On the shutdown path. Statics are never dropped, so the provider is swapped out here for its
+// Drop to release what's held and remove the virtual device.
+pub fn close_input() {
+    let Some(input) = Lazy::get(&INPUT) else {
+        return;
+    };
+    let mut input = input
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *input = Box::new(DummyProvider {});
 }
 
 pub struct UInputProvider {
@@ -37,6 +89,11 @@ pub struct UInputProvider {
     desktop_extent: Vec2,
     mouse_moved: bool,
     cur_modifiers: u8,
+    // uinput codes of the keys and buttons that are down
+    held: RefCell<BTreeSet<u16>>,
+    // whether the compositor reports lock LEDs to our device, and what they are
+    leds: bool,
+    locks: u8,
 }
 
 pub struct DummyProvider;
@@ -44,6 +101,9 @@ pub struct DummyProvider;
 pub const MOUSE_LEFT: u16 = 0x110;
 pub const MOUSE_RIGHT: u16 = 0x111;
 pub const MOUSE_MIDDLE: u16 = 0x112;
+// BTN_SIDE and BTN_EXTRA, back and forward in browsers
+pub const MOUSE_BACK: u16 = 0x113;
+pub const MOUSE_FORWARD: u16 = 0x114;
 
 const MOUSE_EXTENT: f32 = 32768.;
 
@@ -51,10 +111,20 @@ const EV_SYN: u16 = 0x0;
 const EV_KEY: u16 = 0x1;
 const EV_REL: u16 = 0x2;
 const EV_ABS: u16 = 0x3;
+const EV_LED: u16 = 0x11;
+
+const LED_NUML: u16 = 0x0;
+const LED_CAPSL: u16 = 0x1;
 
 impl UInputProvider {
     fn try_new() -> Option<Self> {
-        if let Ok(file) = File::create("/dev/uinput") {
+        // read as well, since the lock LEDs come back through the same node
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/uinput");
+        if let Ok(file) = file {
             let handle = UInputHandle::new(file);
 
             let id = InputId {
@@ -101,7 +171,7 @@ impl UInputProvider {
                 return None;
             }
 
-            for btn in MOUSE_LEFT..=MOUSE_MIDDLE {
+            for btn in MOUSE_LEFT..=MOUSE_FORWARD {
                 let key: Key = unsafe { transmute(btn) };
                 if handle.set_keybit(key).is_err() {
                     return None;
@@ -125,12 +195,20 @@ impl UInputProvider {
                 return None;
             }
 
+            // optional, lock keys just can't be synced without it
+            let leds = handle.set_evbit(EventKind::Led).is_ok()
+                && handle.set_ledbit(LedKind::NumLock).is_ok()
+                && handle.set_ledbit(LedKind::CapsLock).is_ok();
+
             if handle.create(&id, name, 0, &abs_info).is_ok() {
                 return Some(UInputProvider {
                     handle,
                     desktop_extent: Vec2::ZERO,
                     mouse_moved: false,
                     cur_modifiers: 0,
+                    held: RefCell::new(BTreeSet::new()),
+                    leds,
+                    locks: 0,
                 });
             }
         }
@@ -158,6 +236,7 @@ impl InputProvider for UInputProvider {
         }
     }
     fn send_button(&self, button: u16, down: bool) {
+        self.track(button, down);
         let time = get_time();
         let events = [
             new_event(time, EV_KEY, button, down as _),
@@ -189,6 +268,7 @@ impl InputProvider for UInputProvider {
         self.cur_modifiers = modifiers;
     }
     fn send_key(&self, key: u16, down: bool) {
+        self.track(key - 8, down);
         let time = get_time();
         let events = [
             new_event(time, EV_KEY, key - 8, down as _),
@@ -205,6 +285,61 @@ impl InputProvider for UInputProvider {
     fn on_new_frame(&mut self) {
         self.mouse_moved = false;
     }
+    fn release_all(&mut self) {
+        let held = std::mem::take(&mut *self.held.borrow_mut());
+        let time = get_time();
+        let mut events: Vec<_> = held
+            .into_iter()
+            .map(|code| new_event(time, EV_KEY, code, 0))
+            .collect();
+        events.push(new_event(time, EV_SYN, 0, 0));
+        if let Err(res) = self.handle.write(&events) {
+            error!("{}", res.to_string());
+        }
+        self.cur_modifiers = 0;
+    }
+    fn lock_state(&mut self) -> Option<u8> {
+        if !self.leds {
+            return None;
+        }
+        let mut events = [new_event(get_time(), 0, 0, 0); 16];
+        while let Ok(count) = self.handle.read(&mut events) {
+            if count == 0 {
+                break;
+            }
+            for event in events[..count].iter().filter(|e| e.type_ == EV_LED) {
+                let lock = match event.code {
+                    LED_NUML => NUM_LOCK,
+                    LED_CAPSL => CAPS_LOCK,
+                    _ => continue,
+                };
+                if event.value != 0 {
+                    self.locks |= lock;
+                } else {
+                    self.locks &= !lock;
+                }
+            }
+        }
+        Some(self.locks)
+    }
+}
+
+impl UInputProvider {
+    fn track(&self, code: u16, down: bool) {
+        let mut held = self.held.borrow_mut();
+        if down {
+            held.insert(code);
+        } else {
+            held.remove(&code);
+        }
+    }
+}
+
+impl Drop for UInputProvider {
+    fn drop(&mut self) {
+        self.release_all();
+        let _ = self.handle.dev_destroy();
+    }
 }
 
 impl InputProvider for DummyProvider {
@@ -215,6 +350,10 @@ impl InputProvider for DummyProvider {
     fn send_key(&self, _key: u16, _down: bool) {}
     fn set_desktop_extent(&mut self, _extent: Vec2) {}
     fn on_new_frame(&mut self) {}
+    fn release_all(&mut self) {}
+    fn lock_state(&mut self) -> Option<u8> {
+        None
+    }
 }
 
 #[inline]
@@ -7,13 +7,20 @@ use std::{
 use glam::{vec2, vec3, Affine3A, Vec2, Vec3};
 use log::debug;
 use stereokit::{
-    ButtonState, Color32, CullMode, Handed, Pose, Ray, SkDraw, StereoKitDraw, StereoKitMultiThread,
+    ButtonState, CullMode, Handed, Pose, Ray, SkDraw, StereoKitDraw, StereoKitMultiThread,
     StereoKitSingleThread,
 };
 
 use crate::{
+    bindings::{self, BUTTON_STICK_CLICK, BUTTON_X1, BUTTON_X2},
+    edit_mode,
+    keyboard::summon_keyboard,
+    latency,
     overlay::{OverlayData, RelativeTo},
-    AppSession,
+    power,
+    radial::{reset_all_overlays, RADIAL_DISTANCE},
+    watch::save_watch_placement,
+    AppSession, TASKS,
 };
 
 const HANDS: [Handed; 2] = [Handed::Left, Handed::Right];
@@ -25,16 +32,33 @@ pub const POINTER_NORM: u16 = 0;
 pub const POINTER_SHIFT: u16 = 1;
 pub const POINTER_ALT: u16 = 2;
 
+// Moves through controls without pointing at them, for when aiming is hard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nav {
+    Prev,
+    Next,
+    Activate,
+}
+
+// Stick deflection that counts as a flick, and how far it has to come back before the next
+const NAV_FLICK: f32 = 0.7;
+const NAV_REARM: f32 = 0.3;
+
 pub trait InteractionHandler {
     fn on_hover(&mut self, hit: &PointerHit);
     fn on_left(&mut self, hand: usize);
     fn on_pointer(&mut self, session: &AppSession, hit: &PointerHit, pressed: bool);
     fn on_scroll(&mut self, hit: &PointerHit, delta: f32);
+    // only canvases have controls to move through
+    fn on_nav(&mut self, _session: &AppSession, _hand: usize, _nav: Nav) {}
 }
 
 pub struct InputState {
     pub hmd: Affine3A,
     pointers: [PointerData; 2],
+    // since when recenter_gesture is held on both controllers, and if it went off already
+    recenter_held: Option<Instant>,
+    recenter_done: bool,
 }
 
 pub struct PointerData {
@@ -43,13 +67,20 @@ pub struct PointerData {
     now: PointerState,
     before: PointerState,
     mode: u16,
-    colors: [Color32; 3],
+    // raw analog values, before the thresholds
+    trigger: f32,
+    grip: f32,
     pose: Pose,
     pose3a: Affine3A,
     grabbed_offset: (Vec3, Vec3),
     grabbed_idx: Option<usize>,
+    // overlay in the pointer's space, for the ones worn on a hand
+    grabbed_transform: Affine3A,
+    // let go of since the last update
+    dropped_idx: Option<usize>,
     clicked_idx: Option<usize>,
     hovered_idx: Option<usize>,
+    hovered_uv: Vec2,
     next_push: Instant,
 }
 
@@ -58,7 +89,13 @@ pub struct PointerState {
     pressed: bool,
     grabbing: bool,
     show_hide: bool,
+    radial: bool,
+    // bound buttons held down, see bindings
+    buttons: u8,
     scroll: f32,
+    // stick flicked left (-1) or right (1), until it's let back
+    nav_flick: i8,
+    nav_activate: bool,
 }
 
 pub struct PointerHit {
@@ -74,6 +111,8 @@ impl InputState {
         Self {
             hmd: Affine3A::IDENTITY,
             pointers: [PointerData::new(session, 0), PointerData::new(session, 1)],
+            recenter_held: None,
+            recenter_done: false,
         }
     }
 
@@ -83,6 +122,7 @@ impl InputState {
         for h in 0..2 {
             self.pointers[h].update(session, &hmd_pose, sk);
         }
+        self.update_recenter_gesture(session);
 
         for overlay in interactables.iter_mut() {
             match overlay.relative_to {
@@ -110,9 +150,95 @@ impl InputState {
             }
         }
 
+        for h in 0..2 {
+            let pointer = &self.pointers[h];
+            if pointer.now.radial && !pointer.before.radial {
+                let pos = pointer.pose.position + pointer.pose.forward() * RADIAL_DISTANCE;
+                if let Some(radial) = interactables.iter_mut().find(|o| &*o.name == "Radial") {
+                    // summon at the pointer; spawn_point is what show() resets to
+                    radial.spawn_point = self.hmd.inverse().transform_point3(pos);
+                    radial.transform.translation = pos.into();
+                    radial.realign(&self.hmd);
+                    radial.want_visible = !radial.visible;
+                }
+            }
+        }
+
         for h in 0..2 {
             self.pointers[h].test_interactions(session, &self.hmd, sk, interactables);
         }
+
+        for h in 0..2 {
+            let Some(idx) = self.pointers[h].dropped_idx.take() else {
+                continue;
+            };
+            let overlay = &mut interactables[idx];
+            if edit_mode::is_wearable(overlay) {
+                // worn on the wrist across from the hand that let go of it
+                let hand = 1 - h;
+                let local = self.pointers[hand].pose3a.inverse() * overlay.transform;
+                let (_, rotation, point) = local.to_scale_rotation_translation();
+                overlay.relative_to = RelativeTo::Hand(hand);
+                overlay.spawn_point = point;
+                overlay.spawn_rotation = rotation;
+                save_watch_placement(hand, point, rotation);
+            }
+        }
+    }
+
+    // Trigger and grip of a pointer, for calibrating the thresholds
+    pub fn trigger_grip(&self, pointer: usize) -> (f32, f32) {
+        let p = &self.pointers[pointer];
+        (p.trigger, p.grip)
+    }
+
+    // Overlay under a pointer and where on it, in its 0..1 texture coordinates
+    pub fn hovered(&self, pointer: usize) -> Option<(usize, Vec2)> {
+        let p = &self.pointers[pointer];
+        p.hovered_idx.map(|idx| (idx, p.hovered_uv))
+    }
+
+    // Overlay held by either pointer
+    pub fn grabbed(&self) -> Option<usize> {
+        self.pointers.iter().find_map(|p| p.grabbed_idx)
+    }
+
+    // Position of a pointer that is gripping without holding an overlay
+    pub fn grip(&self, pointer: usize) -> Option<Vec3> {
+        let p = &self.pointers[pointer];
+        (p.now.grabbing && p.grabbed_idx.is_none()).then_some(p.pose.position)
+    }
+
+    // Same, but only on the frame the grip started, and only when not pointing at an overlay
+    pub fn free_grip_start(&self, pointer: usize) -> Option<Vec3> {
+        let p = &self.pointers[pointer];
+        (!p.before.grabbing && p.hovered_idx.is_none())
+            .then(|| self.grip(pointer))
+            .flatten()
+    }
+
+    // Once per hold of recenter_gesture, so it doesn't keep pulling overlays while held
+    fn update_recenter_gesture(&mut self, session: &AppSession) {
+        let Some(button) = session.config.recenter_gesture else {
+            return;
+        };
+        let bit = bindings::button_bit(button);
+        if !self.pointers.iter().all(|p| p.now.buttons & bit != 0) {
+            self.recenter_held = None;
+            self.recenter_done = false;
+            return;
+        }
+        let held = *self.recenter_held.get_or_insert_with(Instant::now);
+        let hold = Duration::from_millis(session.config.recenter_hold_ms as u64);
+        if self.recenter_done || held.elapsed() < hold {
+            return;
+        }
+        self.recenter_done = true;
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(|_sk, app, overlays| {
+                reset_all_overlays(app, overlays);
+            }));
+        }
     }
 }
 
@@ -124,17 +250,28 @@ impl PointerData {
             now: PointerState::default(),
             before: PointerState::default(),
             mode: 0,
+            trigger: 0.,
+            grip: 0.,
             pose: Pose::IDENTITY,
             pose3a: Affine3A::IDENTITY,
             clicked_idx: None,
             grabbed_idx: None,
             grabbed_offset: (Vec3::ZERO, Vec3::ZERO),
+            grabbed_transform: Affine3A::IDENTITY,
+            dropped_idx: None,
             hovered_idx: None,
-            colors: [session.color_norm, session.color_shift, session.color_alt],
+            hovered_uv: Vec2::ZERO,
             next_push: Instant::now(),
         }
     }
 
+    // The controller rolled outwards so the palm faces the sky; the aim's x axis points out
+    // of the left palm, and out of the back of the right hand
+    fn palm_up(&self) -> bool {
+        let side = if self.hand == HAND_LEFT { 1. } else { -1. };
+        (self.pose.orientation * Vec3::X).dot(Vec3::Y) * side > 0.7
+    }
+
     fn update(&mut self, session: &AppSession, hmd: &Pose, sk: &SkDraw) {
         let controller = sk.input_controller(HANDS[self.hand]);
 
@@ -142,12 +279,30 @@ impl PointerData {
         self.pose3a =
             Affine3A::from_rotation_translation(self.pose.orientation, self.pose.position);
 
+        self.trigger = controller.trigger;
+        self.grip = controller.grip;
+
         self.before = self.now;
         self.now.pressed = if self.before.pressed {
             controller.trigger >= (session.config.trigger_threshold - 0.1).max(0.0)
         } else {
             controller.trigger >= session.config.trigger_threshold
         };
+        if self.now.pressed && !self.before.pressed {
+            latency::trigger_pressed();
+            if session.config.palm_up_summons_keyboard
+                && self.hovered_idx.is_none()
+                && self.palm_up()
+            {
+                let hand_pos = self.pose.position;
+                let hmd = Affine3A::from_rotation_translation(hmd.orientation, hmd.position);
+                if let Ok(mut tasks) = TASKS.lock() {
+                    tasks.push_back(Box::new(move |_sk, _app, overlays| {
+                        summon_keyboard(overlays, &hmd, hand_pos);
+                    }));
+                }
+            }
+        }
 
         self.now.grabbing = if self.before.grabbing {
             controller.grip >= (session.config.grab_threshold - 0.05).max(0.0)
@@ -160,8 +315,41 @@ impl PointerData {
         } else {
             false
         };
+        self.now.radial = match session.config.radial_menu_button.as_str() {
+            "stick_click" => controller.stick_click == ButtonState::ACTIVE,
+            "x1" => controller.x1 == ButtonState::ACTIVE,
+            "x2" => controller.x2 == ButtonState::ACTIVE,
+            _ => false,
+        };
         self.now.scroll = controller.stick.y;
 
+        let x = controller.stick.x;
+        self.now.nav_flick = match self.before.nav_flick {
+            f if f != 0 && x.abs() > NAV_REARM => f,
+            _ if x > NAV_FLICK => 1,
+            _ if x < -NAV_FLICK => -1,
+            _ => 0,
+        };
+        self.now.nav_activate = match session.config.nav_activate_button.as_str() {
+            "stick_click" => controller.stick_click == ButtonState::ACTIVE,
+            "x1" => controller.x1 == ButtonState::ACTIVE,
+            "x2" => controller.x2 == ButtonState::ACTIVE,
+            _ => false,
+        };
+
+        self.now.buttons = [
+            (controller.x1, BUTTON_X1),
+            (controller.x2, BUTTON_X2),
+            (controller.stick_click, BUTTON_STICK_CLICK),
+        ]
+        .iter()
+        .filter(|(state, _)| state.contains(ButtonState::ACTIVE))
+        .fold(0, |bits, (_, bit)| bits | bit);
+        let just_pressed = self.now.buttons & !self.before.buttons;
+        if just_pressed != 0 {
+            bindings::dispatch(session, self.hand, just_pressed, self.pose.position);
+        }
+
         // If unpressed (true -> false)
         if self.before.pressed && !self.now.pressed {
             while let Some(action) = self.release_actions.pop_front() {
@@ -185,7 +373,9 @@ impl PointerData {
         sk: &SkDraw,
         interactables: &mut [OverlayData],
     ) {
-        let color = self.colors[self.mode as usize];
+        // read every frame, so color changes show right away
+        let color =
+            [session.color_norm, session.color_shift, session.color_alt][self.mode as usize];
 
         // Grabbing an overlay
         if let Some(grabbed_idx) = self.grabbed_idx {
@@ -197,6 +387,7 @@ impl PointerData {
             } else if !self.now.grabbing {
                 debug!("Pointer {}: Dropped {}", self.hand, grabbed.name);
                 self.grabbed_idx = None;
+                self.dropped_idx = Some(grabbed_idx);
                 grabbed.on_drop();
                 // drop and continue
             } else {
@@ -218,10 +409,14 @@ impl PointerData {
                         self.next_push = Instant::now() + Duration::from_millis(20);
                     }
                 }
-                sk.hierarchy_push(self.pose3a);
-                let grab_point = sk.hierarchy_to_world_point(self.grabbed_offset.0);
-                grabbed.on_move(grab_point.into(), hmd3a);
-                sk.hierarchy_pop();
+                if let RelativeTo::Hand(_) = grabbed.relative_to {
+                    grabbed.transform = self.pose3a * self.grabbed_transform;
+                } else {
+                    sk.hierarchy_push(self.pose3a);
+                    let grab_point = sk.hierarchy_to_world_point(self.grabbed_offset.0);
+                    grabbed.on_move(grab_point.into(), hmd3a);
+                    sk.hierarchy_pop();
+                }
 
                 let mut points = vec![];
                 sk.hierarchy_push(grabbed.transform);
@@ -229,8 +424,9 @@ impl PointerData {
                 points.push(sk.hierarchy_to_world_point(vec3(1., 0., 0.)));
                 sk.hierarchy_pop();
 
+                let width = 0.002 * session.config.accessibility.laser_scale();
                 for p in points.iter() {
-                    sk.line_add(self.pose.position, *p, color, color, 0.002);
+                    sk.line_add(self.pose.position, *p, color, color, width);
                 }
 
                 if self.now.pressed && !self.before.pressed {
@@ -259,11 +455,13 @@ impl PointerData {
 
                 if let Some((hit, _)) = sk.mesh_ray_intersect(&gfx.mesh, ray, CullMode::Back) {
                     let vec = overlay.interaction_transform.transform_point3(hit.pos);
+                    // stereo content is pointed at on the left eye's half
+                    let scale = overlay.stereo.uv_scale();
                     hits[num_hits] = RayHit {
                         idx: i,
                         ray_pos: ray.pos,
                         hit_pos: hit.pos,
-                        uv: vec2(vec.x, vec.y),
+                        uv: vec2(vec.x * scale.x, vec.y * scale.y),
                         dist: Vec3::length(hit.pos - ray.pos),
                     };
                     num_hits += 1;
@@ -301,25 +499,37 @@ impl PointerData {
                 }
             }
             self.hovered_idx = Some(now_idx);
+            self.hovered_uv = hit.uv;
 
             let overlay = &mut interactables[now_idx];
             sk.hierarchy_push(overlay.transform);
-            sk.line_add(hit.ray_pos, hit.hit_pos, color, color, 0.002);
+            let width = 0.002 * session.config.accessibility.laser_scale();
+            sk.line_add(hit.ray_pos, hit.hit_pos, color, color, width);
             sk.hierarchy_pop();
 
             // grab start
-            if self.now.grabbing && !self.before.grabbing && overlay.grabbable {
+            let editing = edit_mode::is_enabled();
+            let edit = editing && edit_mode::is_arrangeable(overlay);
+            // the watch can still be clicked, edit mode is left from it
+            let wear = editing && edit_mode::is_wearable(overlay);
+            if self.now.grabbing && !self.before.grabbing && (overlay.grabbable || edit || wear) {
                 overlay.primary_pointer = Some(self.hand);
                 let mat =
                     Affine3A::from_rotation_translation(self.pose.orientation, self.pose.position);
                 sk.hierarchy_push(mat);
                 self.grabbed_offset.0 = sk.hierarchy_to_local_point(overlay.transform.translation);
                 sk.hierarchy_pop();
+                self.grabbed_transform = self.pose3a.inverse() * overlay.transform;
                 self.grabbed_idx = Some(now_idx);
                 debug!("Pointer {}: Grabbed {}", self.hand, overlay.name);
                 return;
             }
 
+            // while editing the layout, these can only be moved around
+            if edit {
+                return;
+            }
+
             // hover
             if let Some(primary_pointer) = overlay.primary_pointer {
                 hit_data.primary = primary_pointer == self.hand;
@@ -329,11 +539,30 @@ impl PointerData {
             }
 
             overlay.backend.on_hover(&hit_data);
+            power::mark_interaction();
 
             if self.now.scroll.abs() > 0.1 {
                 overlay.backend.on_scroll(&hit_data, self.now.scroll);
             }
 
+            if session.config.controller_nav {
+                let nav = if self.now.nav_flick != self.before.nav_flick {
+                    match self.now.nav_flick {
+                        1 => Some(Nav::Next),
+                        -1 => Some(Nav::Prev),
+                        _ => None,
+                    }
+                } else if self.now.nav_activate && !self.before.nav_activate {
+                    Some(Nav::Activate)
+                } else {
+                    None
+                };
+                if let Some(nav) = nav {
+                    debug!("Pointer {}: on_nav {:?} {}", self.hand, nav, overlay.name);
+                    overlay.backend.on_nav(session, self.hand, nav);
+                }
+            }
+
             if self.now.pressed && !self.before.pressed {
                 overlay.primary_pointer = Some(self.hand);
                 hit_data.primary = true;
@@ -394,7 +623,11 @@ impl Default for PointerState {
             pressed: false,
             grabbing: false,
             show_hide: false,
+            radial: false,
+            buttons: 0,
             scroll: 0.,
+            nav_flick: 0,
+            nav_activate: false,
         }
     }
 }
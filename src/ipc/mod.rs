@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::{error, info};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    runtime::Runtime,
+    sync::oneshot,
+};
+
+use stereokit::SkDraw;
+
+use crate::{overlay::OverlayData, screenshot::screenshot_overlays, AppState, TASKS};
+
+use self::proto::{socket_path, Command, OverlayInfo, Response};
+
+pub mod proto;
+
+// Whether the socket is ours to remove on exit
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+// Accepts wlx-ctl connections; commands are run from the frame loop via TASKS
+pub fn start_server(rt: &Runtime) {
+    let path = socket_path();
+    // a socket that still answers belongs to another running instance, leave it be
+    if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+        error!(
+            "{} is in use by another instance, wlx-ctl commands will go there",
+            path.to_string_lossy()
+        );
+        return;
+    }
+    // left over from a previous run that didn't exit cleanly
+    let _ = std::fs::remove_file(&path);
+
+    let _guard = rt.enter();
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to listen on {}: {}", path.to_string_lossy(), e);
+            return;
+        }
+    };
+    info!("Listening for commands on {}", path.to_string_lossy());
+    LISTENING.store(true, Ordering::Relaxed);
+
+    rt.spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle_client(stream));
+                }
+                Err(e) => {
+                    error!("Stopped accepting commands: {}", e);
+                    return;
+                }
+            }
+        }
+    });
+}
+
+// On the way out, so the next start and wlx-ctl don't find a dead socket
+pub fn stop_server() {
+    if LISTENING.swap(false, Ordering::Relaxed) {
+        let _ = std::fs::remove_file(socket_path());
+    }
+}
+
+async fn handle_client(stream: UnixStream) {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let command = serde_json::from_str::<Command>(&line);
+        let quit = matches!(command, Ok(Command::Quit));
+
+        let response = match command {
+            Ok(Command::Quit) => Response::Ok,
+            Ok(command) => dispatch(command).await,
+            Err(e) => Response::Error {
+                message: format!("Invalid command: {}", e),
+            },
+        };
+
+        let Ok(mut json) = serde_json::to_string(&response) else {
+            return;
+        };
+        json.push('\n');
+        if write.write_all(json.as_bytes()).await.is_err() {
+            return;
+        }
+
+        // only after the reply went out, the same way as on SIGTERM so state is saved and
+        // held input released on the way out
+        if quit {
+            if let Ok(mut tasks) = TASKS.lock() {
+                tasks.push_back(Box::new(|_sk, _app, _overlays| unsafe {
+                    stereokit::sys::sk_quit();
+                }));
+            }
+            return;
+        }
+    }
+}
+
+async fn dispatch(command: Command) -> Response {
+    let (tx, rx) = oneshot::channel();
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(move |sk, app, overlays| {
+            let _ = tx.send(run(command, sk, app, overlays));
+        }));
+    }
+    rx.await.unwrap_or_else(|_| Response::Error {
+        message: "Command was not run".to_string(),
+    })
+}
+
+fn run(
+    command: Command,
+    sk: &SkDraw,
+    app: &mut AppState,
+    overlays: &mut [OverlayData],
+) -> Response {
+    let (name, visible) = match command {
+        Command::Show { overlay } => (overlay, Some(true)),
+        Command::Hide { overlay } => (overlay, Some(false)),
+        Command::Toggle { overlay } => (overlay, None),
+        Command::List => {
+            return Response::Overlays {
+                overlays: overlays
+                    .iter()
+                    .filter(|o| !o.name.is_empty())
+                    .map(|o| OverlayInfo {
+                        name: o.name.to_string(),
+                        visible: o.want_visible,
+                    })
+                    .collect(),
+            };
+        }
+        Command::Screenshot { overlay } => {
+            return match screenshot_overlays(sk, app, overlays, overlay.as_deref()) {
+                Ok(files) => Response::Files {
+                    files: files
+                        .iter()
+                        .map(|f| f.to_string_lossy().to_string())
+                        .collect(),
+                },
+                Err(message) => Response::Error { message },
+            };
+        }
+        Command::Quit => return Response::Ok,
+    };
+
+    let Some(overlay) = overlays.iter_mut().find(|o| *o.name == *name) else {
+        return Response::Error {
+            message: format!("No overlay named {}", name),
+        };
+    };
+    overlay.want_visible = visible.unwrap_or(!overlay.want_visible);
+    Response::Ok
+}
@@ -0,0 +1,40 @@
+// Wire format shared by the overlay and wlx-ctl: one JSON object per line in each direction
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Command {
+    Show { overlay: String },
+    Hide { overlay: String },
+    Toggle { overlay: String },
+    List,
+    // the named overlay, or all visible screens
+    Screenshot { overlay: Option<String> },
+    Quit,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Response {
+    Ok,
+    Error { message: String },
+    Overlays { overlays: Vec<OverlayInfo> },
+    Files { files: Vec<String> },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OverlayInfo {
+    pub name: String,
+    pub visible: bool,
+}
+
+pub fn socket_path() -> PathBuf {
+    let mut path = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    path.push("wlx-overlay-x.sock");
+    path
+}
@@ -1,30 +1,38 @@
 use std::{
     collections::HashMap,
     io::Cursor,
+    path::{Path, PathBuf},
     process::{Child, Command},
     str::FromStr,
     sync::Arc,
 };
 
 use crate::{
-    config,
-    gui::{color_parse, Canvas, Control},
+    audio,
+    config::{self, StickyKeys},
+    gui::{
+        color_parse,
+        focus::{self, TextEdit},
+        layout::{FlexLayout, Rect},
+        Canvas, Control,
+    },
     input::INPUT,
     overlay::OverlayData,
+    physical_keys,
+    scripting::{self, ScriptEvent},
     AppSession,
 };
-use glam::{vec2, vec3};
+use glam::{vec2, vec3, Affine3A, Vec3};
 use idmap::{idmap, IdMap};
 use idmap_derive::IntegerId;
 use log::error;
 use once_cell::sync::Lazy;
 use regex::Regex;
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Source};
+use rodio::{buffer::SamplesBuffer, Decoder, OutputStream, OutputStreamHandle, Source};
 use serde::{Deserialize, Serialize};
 use strum::{EnumIter, EnumString};
 
 const PIXELS_PER_UNIT: f32 = 80.;
-const BUTTON_PADDING: f32 = 4.;
 
 pub fn create_keyboard(session: &AppSession) -> OverlayData {
     let size = vec2(
@@ -32,78 +40,120 @@ pub fn create_keyboard(session: &AppSession) -> OverlayData {
         (LAYOUT.main_layout.len() as f32) * PIXELS_PER_UNIT,
     );
 
+    // With the desktop's own lock state at hand, lock keys are plain keys that it toggles
+    let lock_sync = INPUT
+        .lock()
+        .map(|mut input| input.lock_state().is_some())
+        .unwrap_or(false);
+
     let data = KeyboardData {
         modifiers: 0,
+        latched: 0,
+        locked: 0,
+        locks: 0,
+        mirrored: 0,
         processes: vec![],
-        audio_stream: None,
-        first_try: true,
-        audio_handle: None,
+        click: None,
+        click_failed: false,
     };
 
-    let mut canvas = Canvas::new(size.x as _, size.y as _, data);
+    let mut canvas = Canvas::new(
+        size.x as _,
+        size.y as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        data,
+    );
 
-    canvas.bg_color = color_parse("#101010");
-    canvas.panel(0., 0., size.x, size.y);
+    let theme = &session.theme;
 
-    canvas.font_size = 18;
-    canvas.bg_color = color_parse("#202020");
+    canvas.bg_color = color_parse(&theme.keyboard_bg_color);
+    canvas.panel(0., 0., size.x, size.y);
 
-    let unit_size = size.x / LAYOUT.row_size;
-    let h = unit_size - 2. * BUTTON_PADDING;
+    canvas.font_size = theme.key_font_size;
+    canvas.fg_color = color_parse(&theme.key_text_color);
+    canvas.bg_color = color_parse(&theme.key_color);
 
-    for row in 0..LAYOUT.key_sizes.len() {
-        let y = unit_size * (row as f32) + BUTTON_PADDING;
-        let mut sum_size = 0f32;
+    let rows = FlexLayout::column()
+        .layout_even(Rect::new(0., 0., size.x, size.y), LAYOUT.main_layout.len());
 
-        for col in 0..LAYOUT.key_sizes[row].len() {
-            let my_size = LAYOUT.key_sizes[row][col];
-            let x = unit_size * sum_size + BUTTON_PADDING;
-            let w = unit_size * my_size - 2. * BUTTON_PADDING;
+    for (row, row_rect) in rows.into_iter().enumerate() {
+        let keys = FlexLayout::row()
+            .with_padding(vec2(theme.key_padding, theme.key_padding))
+            .layout(row_rect, &LAYOUT.key_sizes[row]);
 
+        for (col, key_rect) in keys.into_iter().enumerate() {
             if let Some(key) = LAYOUT.main_layout[row][col].as_ref() {
+                // a remapped key works and is labeled like the one it's mapped to
+                let overrides = session.config.key_overrides.get(key);
+                let target = overrides.and_then(|o| o.key.as_deref()).unwrap_or(key);
+
                 let mut maybe_state: Option<KeyButtonData> = None;
-                if let Ok(vk) = VirtualKey::from_str(key) {
-                    if let Some(mods) = KEYS_TO_MODS.get(vk) {
+                if let Some(exec) = overrides.and_then(|o| o.exec.as_ref()) {
+                    maybe_state = exec_state(key, exec);
+                } else if let Some(macro_verbs) = overrides.and_then(|o| o.macro_verbs.as_ref()) {
+                    maybe_state = Some(KeyButtonData::Macro {
+                        verbs: key_events_for_macro(macro_verbs),
+                    });
+                } else if let Ok(vk) = VirtualKey::from_str(target) {
+                    let mods = KEYS_TO_MODS
+                        .get(vk)
+                        .filter(|m| !lock_sync || **m & LOCKS == 0);
+                    if let Some(mods) = mods {
                         maybe_state = Some(KeyButtonData::Modifier {
                             modifier: *mods,
                             sticky: false,
                             pressed: false,
                         });
                     } else {
-                        maybe_state = Some(KeyButtonData::Key { vk, pressed: false });
+                        maybe_state = Some(KeyButtonData::Key {
+                            vk,
+                            pressed: false,
+                            to_field: false,
+                        });
                     }
-                } else if let Some(macro_verbs) = LAYOUT.macros.get(key) {
+                } else if let Some(macro_verbs) = LAYOUT.macros.get(target) {
                     maybe_state = Some(KeyButtonData::Macro {
                         verbs: key_events_for_macro(macro_verbs),
                     });
-                } else if let Some(exec_args) = LAYOUT.exec_commands.get(key) {
-                    maybe_state = Some(KeyButtonData::Exec {
-                        program: exec_args.first().unwrap().clone(),
-                        args: exec_args.iter().skip(1).cloned().collect(),
-                    });
+                } else if let Some(exec) = LAYOUT.exec_commands.get(target) {
+                    maybe_state = exec_state(target, exec);
                 } else {
-                    error!("Unknown key: {}", key);
+                    error!("Unknown key: {}", target);
                 }
 
                 if let Some(state) = maybe_state {
-                    let label = LAYOUT.label_for_key(key);
-                    let idx = canvas.key_button(x, y, w, h, &label);
+                    let tooltip = match &state {
+                        KeyButtonData::Exec { program, args, .. } => {
+                            Some(format!("{} {}", program, args.join(" ")))
+                        }
+                        _ => None,
+                    };
+                    let label = overrides
+                        .and_then(|o| o.label.clone())
+                        .unwrap_or_else(|| LAYOUT.label_for_key(target));
+                    let idx =
+                        canvas.key_button(key_rect.x, key_rect.y, key_rect.w, key_rect.h, &label);
                     let button = &mut canvas.controls[idx];
+                    if lock_sync && matches!(state, KeyButtonData::Key { vk, .. } if is_lock(vk)) {
+                        button.on_update = Some(sync_locks);
+                    }
                     button.state = Some(state);
                     button.on_press = Some(key_press);
                     button.on_release = Some(key_release);
                     button.test_highlight = Some(test_highlight);
+                    if let Some(tooltip) = tooltip {
+                        button.set_tooltip(tooltip.trim_end());
+                    }
                 }
             }
-
-            sum_size += my_size;
         }
     }
 
     OverlayData {
         name: Arc::from("Kbd"),
         show_hide: true,
-        width: LAYOUT.row_size * 0.05,
+        width: LAYOUT.row_size * 0.05 * session.config.ui_scale,
         scale: session.config.keyboard_scale,
         size: (canvas.width as _, canvas.height as _),
         grabbable: true,
@@ -113,17 +163,62 @@ pub fn create_keyboard(session: &AppSession) -> OverlayData {
     }
 }
 
+// How far ahead of the hand, away from the head, a summoned keyboard shows up
+const SUMMON_DISTANCE: f32 = 0.2;
+
+// Shows the keyboard just in front of the hand, tilted towards the head like when spawned
+pub fn summon_keyboard(overlays: &mut [OverlayData], hmd: &Affine3A, hand_pos: Vec3) {
+    let Some(kbd) = overlays.iter_mut().find(|o| &*o.name == "Kbd") else {
+        return;
+    };
+    let mut ahead = hand_pos - Vec3::from(hmd.translation);
+    ahead.y = 0.;
+    let pos = hand_pos + ahead.normalize_or_zero() * SUMMON_DISTANCE;
+
+    kbd.transform.translation = pos.into();
+    kbd.realign(hmd);
+    if !kbd.visible {
+        // show() would put it back where it was hidden or spawned otherwise
+        kbd.saved_transform = Some(kbd.transform);
+        kbd.want_visible = true;
+    }
+}
+
 fn key_press(
     control: &mut Control<KeyboardData, KeyButtonData>,
     session: &AppSession,
     data: &mut KeyboardData,
 ) {
     match control.state.as_mut() {
-        Some(KeyButtonData::Key { vk, pressed }) => {
-            if let Ok(input) = INPUT.lock() {
+        Some(KeyButtonData::Key {
+            vk,
+            pressed,
+            to_field,
+        }) => {
+            scripting::emit(ScriptEvent::KeyPressed(format!("{:?}", vk)));
+            // the desktop may only look at our device's modifiers for our keys
+            let physical = if session.config.mirror_physical_modifiers {
+                physical_keys::held_modifiers()
+            } else {
+                0
+            };
+            if focus::is_focused() {
                 data.key_click(session);
+                let modifiers = data.modifiers | data.locks | physical;
+                if let Some(edit) = text_edit_for_key(*vk, modifiers) {
+                    focus::send_edit(edit);
+                }
+                *pressed = true;
+                *to_field = true;
+            } else if let Ok(mut input) = INPUT.lock() {
+                data.key_click(session);
+                data.mirrored = physical & !data.modifiers;
+                if data.mirrored != 0 {
+                    input.set_modifiers(data.modifiers | data.mirrored);
+                }
                 input.send_key(*vk as _, true);
                 *pressed = true;
+                *to_field = false;
             }
         }
         Some(KeyButtonData::Modifier {
@@ -131,7 +226,11 @@ fn key_press(
             sticky,
             pressed,
         }) => {
-            *sticky = data.modifiers & *modifier == 0;
+            *sticky = if *modifier & LOCKS != 0 {
+                data.modifiers & *modifier == 0
+            } else {
+                data.tap_modifier(*modifier, session.config.sticky_keys)
+            };
             data.modifiers |= *modifier;
             if let Ok(mut input) = INPUT.lock() {
                 data.key_click(session);
@@ -146,28 +245,99 @@ fn key_press(
                     input.send_key(*vk as _, *press);
                 }
             }
+            data.release_latched();
         }
-        Some(KeyButtonData::Exec { program, args }) => {
+        Some(KeyButtonData::Exec {
+            program,
+            args,
+            cwd,
+            env,
+            terminal,
+        }) => {
             // Reap previous processes
             data.processes
                 .retain_mut(|child| !matches!(child.try_wait(), Ok(Some(_))));
 
             data.key_click(session);
-            if let Ok(child) = Command::new(program).args(args).spawn() {
-                data.processes.push(child);
+            let mut command = exec_command(
+                program,
+                args,
+                cwd.as_deref(),
+                env,
+                *terminal,
+                &session.config.terminal_command,
+            );
+            match command.spawn() {
+                Ok(child) => data.processes.push(child),
+                Err(e) => error!("Failed to run {}: {}", program, e),
             }
         }
         None => {}
     }
 }
 
+fn exec_state(key: &str, exec: &ExecCommand) -> Option<KeyButtonData> {
+    let exec = exec.spec();
+    let Some((program, args)) = exec.command.split_first() else {
+        error!("Exec key {} has no command", key);
+        return None;
+    };
+    Some(KeyButtonData::Exec {
+        program: program.clone(),
+        args: args.to_vec(),
+        cwd: exec.cwd.as_deref().map(expand_home),
+        env: exec.env,
+        terminal: exec.terminal,
+    })
+}
+
+// Runs the program in the terminal_command if it asks for one
+pub fn exec_command(
+    program: &str,
+    args: &[String],
+    cwd: Option<&Path>,
+    env: &HashMap<String, String>,
+    terminal: bool,
+    terminal_command: &[String],
+) -> Command {
+    let mut command = match terminal_command.split_first() {
+        Some((term, term_args)) if terminal => {
+            let mut command = Command::new(term);
+            command.args(term_args).arg(program).args(args);
+            command
+        }
+        _ => {
+            let mut command = Command::new(program);
+            command.args(args);
+            command
+        }
+    };
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    command.envs(env.iter());
+    command
+}
+
 fn key_release(control: &mut Control<KeyboardData, KeyButtonData>, data: &mut KeyboardData) {
     match control.state.as_mut() {
-        Some(KeyButtonData::Key { vk, pressed }) => {
-            if let Ok(input) = INPUT.lock() {
-                input.send_key(*vk as _, false);
+        Some(KeyButtonData::Key {
+            vk,
+            pressed,
+            to_field,
+        }) => {
+            // a press that went to a text field never reached the desktop
+            if !*to_field {
+                if let Ok(mut input) = INPUT.lock() {
+                    input.send_key(*vk as _, false);
+                    if data.mirrored != 0 {
+                        data.mirrored = 0;
+                        input.set_modifiers(data.modifiers);
+                    }
+                }
             }
             *pressed = false;
+            data.release_latched();
         }
         Some(KeyButtonData::Modifier {
             modifier,
@@ -179,61 +349,184 @@ fn key_release(control: &mut Control<KeyboardData, KeyButtonData>, data: &mut Ke
                 if let Ok(mut input) = INPUT.lock() {
                     input.set_modifiers(data.modifiers);
                 }
-                *pressed = false;
             }
+            *pressed = false;
         }
         _ => {}
     }
 }
 
+// Latched modifiers are highlighted, locked ones also get the indicator bar
 fn test_highlight(
     control: &mut Control<KeyboardData, KeyButtonData>,
-    _data: &mut KeyboardData,
+    data: &mut KeyboardData,
 ) -> bool {
     match control.state.as_ref() {
-        Some(KeyButtonData::Key { pressed, .. }) => *pressed,
-        Some(KeyButtonData::Modifier { pressed, .. }) => *pressed,
+        Some(KeyButtonData::Key { vk, pressed, .. }) => {
+            *pressed || KEYS_TO_MODS.get(*vk).is_some_and(|m| data.locks & m != 0)
+        }
+        Some(KeyButtonData::Modifier {
+            modifier, pressed, ..
+        }) => {
+            let (modifier, pressed) = (*modifier, *pressed);
+            control.set_indicator(data.locked & modifier != 0);
+            pressed || data.modifiers & modifier != 0
+        }
         _ => false,
     }
 }
 
+fn is_lock(vk: VirtualKey) -> bool {
+    KEYS_TO_MODS.get(vk).is_some_and(|m| m & LOCKS != 0)
+}
+
+// Picks up Caps Lock and Num Lock as toggled from anywhere, including physical keyboards
+fn sync_locks(_control: &mut Control<KeyboardData, KeyButtonData>, data: &mut KeyboardData) {
+    if let Ok(mut input) = INPUT.lock() {
+        if let Some(locks) = input.lock_state() {
+            data.locks = locks;
+        }
+    }
+}
+
+// Translates a key press into an edit for the focused text field
+fn text_edit_for_key(vk: VirtualKey, modifiers: KeyModifier) -> Option<TextEdit> {
+    match vk {
+        VirtualKey::BackSpace => return Some(TextEdit::Backspace),
+        VirtualKey::Return | VirtualKey::KP_Enter => return Some(TextEdit::Submit),
+        VirtualKey::Escape => return Some(TextEdit::Cancel),
+        VirtualKey::Space => return Some(TextEdit::Insert(" ".into())),
+        _ => {}
+    }
+
+    let label = LAYOUT.label_for_key(&format!("{:?}", vk));
+    let base = label.first()?.trim();
+
+    let text = if modifiers & SHIFT != 0 {
+        label
+            .get(1)
+            .map(|s| s.trim().to_string())
+            .unwrap_or_else(|| base.to_uppercase())
+    } else if modifiers & CAPS_LOCK != 0 {
+        base.to_uppercase()
+    } else {
+        base.to_string()
+    };
+
+    if text.chars().count() == 1 {
+        Some(TextEdit::Insert(text))
+    } else {
+        None
+    }
+}
+
 struct KeyboardData {
     modifiers: KeyModifier,
+    // sticky modifiers, held until the next key press or until tapped again
+    latched: KeyModifier,
+    locked: KeyModifier,
+    // Caps Lock and Num Lock as the desktop has them, if it can tell
+    locks: KeyModifier,
+    // held on a physical keyboard, pressed along with the current key
+    mirrored: KeyModifier,
     processes: Vec<Child>,
-    audio_stream: Option<OutputStream>,
-    audio_handle: Option<OutputStreamHandle>,
-    first_try: bool,
+    click: Option<KeyClick>,
+    click_failed: bool,
 }
 
 impl KeyboardData {
+    // Returns whether the modifier stays on after the tap is released
+    fn tap_modifier(&mut self, modifier: KeyModifier, mode: StickyKeys) -> bool {
+        if self.locked & modifier != 0 {
+            self.locked &= !modifier;
+            return false;
+        }
+        if self.latched & modifier != 0 {
+            self.latched &= !modifier;
+            if mode == StickyKeys::Lock {
+                self.locked |= modifier;
+                return true;
+            }
+            return false;
+        }
+        if mode == StickyKeys::Off {
+            return false;
+        }
+        self.latched |= modifier;
+        true
+    }
+
+    // Latched modifiers only last for a single key press
+    fn release_latched(&mut self) {
+        if self.latched == 0 {
+            return;
+        }
+        self.modifiers &= !self.latched;
+        self.latched = 0;
+        if let Ok(mut input) = INPUT.lock() {
+            input.set_modifiers(self.modifiers);
+        }
+    }
+
     fn key_click(&mut self, session: &AppSession) {
         if !session.config.keyboard_sound_enabled {
             return;
         }
 
-        if self.audio_stream.is_none() && self.first_try {
-            self.first_try = false;
-            if let Ok((stream, handle)) = OutputStream::try_default() {
-                self.audio_stream = Some(stream);
-                self.audio_handle = Some(handle);
-            } else {
-                error!("Failed to open audio stream");
-            }
+        if self.click.is_none() && !self.click_failed {
+            self.click = KeyClick::open(&session.config.audio_device);
+            self.click_failed = self.click.is_none();
         }
 
-        if let Some(handle) = &self.audio_handle {
-            let wav = include_bytes!("res/421581.wav");
-            let cursor = Cursor::new(wav);
-            let source = Decoder::new_wav(cursor).unwrap();
-            let _ = handle.play_raw(source.convert_samples());
+        if let Some(click) = &self.click {
+            click.play();
         }
     }
 }
 
+const CLICK_WAV: &[u8] = include_bytes!("res/421581.wav");
+
+// Output stream and decoded sound, kept for as long as the keyboard exists
+// so that a key press only has to hand the samples to the mixer
+struct KeyClick {
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    channels: u16,
+    sample_rate: u32,
+    samples: Vec<f32>,
+}
+
+impl KeyClick {
+    fn open(device: &str) -> Option<Self> {
+        let decoder = Decoder::new_wav(Cursor::new(CLICK_WAV))
+            .map_err(|e| error!("Failed to decode key click: {}", e))
+            .ok()?;
+        let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+        let samples = decoder.convert_samples().collect();
+
+        let (stream, handle) = audio::open_output(device)?;
+        Some(Self {
+            _stream: stream,
+            handle,
+            channels,
+            sample_rate,
+            samples,
+        })
+    }
+
+    // Clicks overlap rather than queue up, so fast typing doesn't lag behind
+    fn play(&self) {
+        let source = SamplesBuffer::new(self.channels, self.sample_rate, self.samples.clone());
+        let _ = self.handle.play_raw(source);
+    }
+}
+
 enum KeyButtonData {
     Key {
         vk: VirtualKey,
         pressed: bool,
+        // the press was typed into a focused text field instead of sent to the desktop
+        to_field: bool,
     },
     Modifier {
         modifier: KeyModifier,
@@ -246,10 +539,13 @@ enum KeyButtonData {
     Exec {
         program: String,
         args: Vec<String>,
+        cwd: Option<PathBuf>,
+        env: HashMap<String, String>,
+        terminal: bool,
     },
 }
 
-static KEYS_TO_MODS: Lazy<IdMap<VirtualKey, KeyModifier>> = Lazy::new(|| {
+pub static KEYS_TO_MODS: Lazy<IdMap<VirtualKey, KeyModifier>> = Lazy::new(|| {
     idmap! {
         VirtualKey::LShift => SHIFT,
         VirtualKey::RShift => SHIFT,
@@ -277,6 +573,7 @@ pub static MODS_TO_KEYS: Lazy<IdMap<KeyModifier, Vec<VirtualKey>>> = Lazy::new(|
 });
 
 static LAYOUT: Lazy<Layout> = Lazy::new(Layout::load_from_disk);
+const BUILTIN_LAYOUT: &str = include_str!("res/keyboard.yaml");
 
 static MACRO_REGEX: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"^([A-Za-z0-1_-]+)(?: +(UP|DOWN))?$").unwrap());
@@ -287,42 +584,127 @@ pub struct Layout {
     row_size: f32,
     key_sizes: Vec<Vec<f32>>,
     main_layout: Vec<Vec<Option<String>>>,
-    exec_commands: HashMap<String, Vec<String>>,
+    exec_commands: HashMap<String, ExecCommand>,
     macros: HashMap<String, Vec<String>>,
     labels: HashMap<String, Vec<String>>,
 }
 
+// Either just the arguments, or the arguments along with how to run them
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum ExecCommand {
+    Args(Vec<String>),
+    Full(ExecSpec),
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ExecSpec {
+    command: Vec<String>,
+    #[serde(default)]
+    cwd: Option<String>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default)]
+    terminal: bool,
+}
+
+impl ExecCommand {
+    fn spec(&self) -> ExecSpec {
+        match self {
+            ExecCommand::Args(command) => ExecSpec {
+                command: command.clone(),
+                ..Default::default()
+            },
+            ExecCommand::Full(spec) => spec.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spec().command.is_empty()
+    }
+
+    // The program to run and its command, unless there's nothing to run
+    pub fn command(&self, terminal_command: &[String]) -> Option<(String, Command)> {
+        let spec = self.spec();
+        let (program, args) = spec.command.split_first()?;
+        let cwd = spec.cwd.as_deref().map(expand_home);
+        let command = exec_command(
+            program,
+            args,
+            cwd.as_deref(),
+            &spec.env,
+            spec.terminal,
+            terminal_command,
+        );
+        Some((program.clone(), command))
+    }
+}
+
+// Paths in the layout may start with ~ for the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(format!("{}{}", home.to_string_lossy(), rest))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
 impl Layout {
+    // A broken keyboard.yaml is reported and replaced by the built-in layout
     fn load_from_disk() -> Layout {
-        let mut layout = config::load_keyboard();
-        layout.post_load();
-        layout
+        let loaded = config::load_keyboard()
+            .map_err(|e| e.to_string())
+            .and_then(|layout| layout.post_load().map(|_| layout));
+
+        match loaded {
+            Ok(layout) => layout,
+            Err(e) => {
+                config::report_error(&format!("keyboard.yaml: {}, using the built-in layout", e));
+                serde_yaml::from_str(BUILTIN_LAYOUT).expect("Failed to parse built-in layout")
+            }
+        }
     }
 
-    fn post_load(&mut self) {
+    fn post_load(&self) -> Result<(), String> {
         for i in 0..self.key_sizes.len() {
             let row = &self.key_sizes[i];
             let width: f32 = row.iter().sum();
             if (width - self.row_size).abs() > 0.001 {
-                panic!(
+                return Err(format!(
                     "Row {} has a width of {}, but the row size is {}",
                     i, width, self.row_size
-                );
+                ));
             }
         }
 
+        for (key, exec) in self.exec_commands.iter() {
+            if exec.is_empty() {
+                return Err(format!("exec_commands.{} has no command", key));
+            }
+        }
+
+        if self.main_layout.len() != self.key_sizes.len() {
+            return Err(format!(
+                "main_layout has {} rows, but key_sizes has {}",
+                self.main_layout.len(),
+                self.key_sizes.len()
+            ));
+        }
+
         for i in 0..self.main_layout.len() {
             let row = &self.main_layout[i];
             let width = row.len();
             if width != self.key_sizes[i].len() {
-                panic!(
+                return Err(format!(
                     "Row {} has {} keys, needs to have {} according to key_sizes",
                     i,
                     width,
                     self.key_sizes[i].len()
-                );
+                ));
             }
         }
+        Ok(())
     }
 
     fn label_for_key(&self, key: &str) -> Vec<String> {
@@ -350,7 +732,7 @@ impl Layout {
     }
 }
 
-fn key_events_for_macro(macro_verbs: &Vec<String>) -> Vec<(VirtualKey, bool)> {
+pub fn key_events_for_macro(macro_verbs: &Vec<String>) -> Vec<(VirtualKey, bool)> {
     let mut key_events = vec![];
     for verb in macro_verbs {
         if let Some(caps) = MACRO_REGEX.captures(verb) {
@@ -389,6 +771,9 @@ pub const NUM_LOCK: KeyModifier = 0x10;
 pub const SUPER: KeyModifier = 0x40;
 pub const META: KeyModifier = 0x80;
 
+// Toggled by the desktop rather than held
+pub const LOCKS: KeyModifier = CAPS_LOCK | NUM_LOCK;
+
 #[allow(non_camel_case_types)]
 #[derive(Debug, PartialEq, Clone, Copy, IntegerId, EnumString, EnumIter)]
 pub enum VirtualKey {
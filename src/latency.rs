@@ -0,0 +1,196 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::info;
+use once_cell::sync::Lazy;
+
+// Percentiles are over the last clicks, logged every so many
+const MAX_SAMPLES: usize = 100;
+const LOG_EVERY: u32 = 20;
+
+// A frame arriving this long after the click isn't counted, the screen likely didn't change
+const FRAME_TIMEOUT: Duration = Duration::from_secs(1);
+
+// The click in flight: trigger press, then the button write to a screen, then its next frame
+struct PendingClick {
+    pressed: Instant,
+    screen: Option<Arc<str>>,
+}
+
+struct LatencyStats {
+    pending: Option<PendingClick>,
+    // trigger to uinput write, and trigger to the next captured frame, in microseconds
+    to_input: VecDeque<u64>,
+    to_frame: VecDeque<u64>,
+    since_log: u32,
+    hud_text: Arc<str>,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static LATENCY: Lazy<Mutex<LatencyStats>> = Lazy::new(|| {
+    Mutex::new(LatencyStats {
+        pending: None,
+        to_input: VecDeque::new(),
+        to_frame: VecDeque::new(),
+        since_log: 0,
+        hud_text: "input: no clicks yet\nframe: no clicks yet".into(),
+    })
+});
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// The trigger crossed trigger_threshold this frame. StereoKit polls the controllers once
+// per frame, so this is as early as we can see it.
+pub fn trigger_pressed() {
+    if !is_enabled() {
+        return;
+    }
+    if let Ok(mut stats) = LATENCY.lock() {
+        stats.pending = Some(PendingClick {
+            pressed: Instant::now(),
+            screen: None,
+        });
+    }
+}
+
+// The button down event was written to uinput for the given screen
+pub fn input_sent(screen: &Arc<str>) {
+    if !is_enabled() {
+        return;
+    }
+    let Ok(mut stats) = LATENCY.lock() else {
+        return;
+    };
+    let Some(pending) = stats.pending.as_mut() else {
+        return;
+    };
+    if pending.screen.is_some() {
+        return;
+    }
+    pending.screen = Some(screen.clone());
+    let elapsed = pending.pressed.elapsed();
+    push_sample(&mut stats.to_input, elapsed);
+}
+
+// A captured frame of the screen was uploaded. The first one after the button write is
+// taken as the one showing the click; frames captured earlier but delivered late make this
+// read a little short, compositors that skip unchanged frames make it read true.
+pub fn frame_shown(screen: &str) {
+    if !is_enabled() {
+        return;
+    }
+    let Ok(mut stats) = LATENCY.lock() else {
+        return;
+    };
+    let Some(pending) = stats.pending.as_ref() else {
+        return;
+    };
+    if pending.screen.as_deref() != Some(screen) {
+        return;
+    }
+    let elapsed = pending.pressed.elapsed();
+    stats.pending = None;
+    if elapsed > FRAME_TIMEOUT {
+        return;
+    }
+    push_sample(&mut stats.to_frame, elapsed);
+
+    stats.hud_text = stats.summary().into();
+    stats.since_log += 1;
+    if stats.since_log >= LOG_EVERY {
+        stats.since_log = 0;
+        info!(
+            "Click latency over the last {} clicks: {}",
+            stats.to_frame.len(),
+            stats.hud_text.replace('\n', ", ")
+        );
+    }
+}
+
+fn push_sample(samples: &mut VecDeque<u64>, elapsed: Duration) {
+    if samples.len() >= MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(elapsed.as_micros() as u64);
+}
+
+impl LatencyStats {
+    fn summary(&self) -> String {
+        let mut text = String::new();
+        for (name, samples) in [("input", &self.to_input), ("frame", &self.to_frame)] {
+            let mut sorted: Vec<_> = samples.iter().copied().collect();
+            sorted.sort_unstable();
+            let _ = write!(text, "{}:", name);
+            for p in [50, 95, 99] {
+                let _ = write!(
+                    text,
+                    " p{} {:.1}ms",
+                    p,
+                    percentile(&sorted, p) as f64 / 1000.
+                );
+            }
+            text.push('\n');
+        }
+        text.trim_end().to_string()
+    }
+}
+
+// Nearest-rank percentile of sorted samples
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((p * sorted.len() + 99) / 100).max(1);
+    sorted[rank - 1]
+}
+
+// Lines for the perf HUD: trigger to uinput write, trigger to the next captured frame
+pub fn hud_text() -> Arc<str> {
+    LATENCY
+        .lock()
+        .map(|stats| stats.hud_text.clone())
+        .unwrap_or_else(|_| "".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_nearest_rank() {
+        let samples: Vec<u64> = (1..=100).collect();
+        assert_eq!(percentile(&samples, 50), 50);
+        assert_eq!(percentile(&samples, 95), 95);
+        assert_eq!(percentile(&samples, 99), 99);
+        assert_eq!(percentile(&samples, 100), 100);
+
+        // ranks round up, so a few samples still give their largest for a high percentile
+        let samples = [10, 20, 30];
+        assert_eq!(percentile(&samples, 50), 20);
+        assert_eq!(percentile(&samples, 95), 30);
+        assert_eq!(percentile(&samples, 34), 20);
+        assert_eq!(percentile(&samples, 33), 10);
+    }
+
+    #[test]
+    fn percentile_edge_cases() {
+        assert_eq!(percentile(&[], 50), 0);
+        assert_eq!(percentile(&[7], 0), 7);
+        assert_eq!(percentile(&[7], 99), 7);
+        assert_eq!(percentile(&[1, 2], 0), 1);
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use chrono::Local;
+use glam::Vec3;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use once_cell::sync::Lazy;
+
+use crate::{
+    gui::{color_parse, Canvas},
+    overlay::{OverlayData, RelativeTo},
+    AppSession,
+};
+
+const MAX_LINES: usize = 500;
+
+const WIDTH: f32 = 700.;
+const HEIGHT: f32 = 450.;
+const ROW_HEIGHT: f32 = 24.;
+const BUTTON_H: f32 = 36.;
+
+#[derive(Default)]
+struct LogBuffer {
+    // bumped with every line, so the viewer knows to refresh
+    generation: u64,
+    lines: VecDeque<(Level, Arc<str>)>,
+}
+
+static LOG_LINES: Lazy<Mutex<LogBuffer>> = Lazy::new(|| Mutex::new(LogBuffer::default()));
+
+// Prints like env_logger does, and keeps the most recent lines for the viewer
+struct LogSink {
+    inner: env_logger::Logger,
+}
+
+impl Log for LogSink {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        self.inner.log(record);
+
+        let line = format!(
+            "{} {} {}: {}",
+            Local::now().format("%H:%M:%S"),
+            &record.level().as_str()[..1],
+            record.target(),
+            record.args()
+        );
+        if let Ok(mut buffer) = LOG_LINES.lock() {
+            if buffer.lines.len() >= MAX_LINES {
+                buffer.lines.pop_front();
+            }
+            buffer.lines.push_back((record.level(), line.into()));
+            buffer.generation += 1;
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+// filter_var: the environment variable holding the env_logger filter
+pub fn init_logger(filter_var: &str) {
+    let inner =
+        env_logger::Builder::from_env(env_logger::Env::default().filter(filter_var)).build();
+    let max_level: LevelFilter = inner.filter();
+    if log::set_boxed_logger(Box::new(LogSink { inner })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LogControl {
+    // generation and level the items were built from
+    List(u64, Level),
+    Filter(Level),
+}
+
+struct LogData {
+    // least severe level shown
+    level: Level,
+}
+
+// Recent log lines, newest first, so problems can be read without leaving VR
+pub fn create_log_viewer(session: &AppSession) -> OverlayData {
+    let mut canvas: Canvas<LogData, LogControl> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        LogData { level: Level::Info },
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    // columns of log lines line up better in a monospace font
+    canvas.font = Some("monospace".into());
+    canvas.font_size = theme.font_size * 3 / 4;
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+
+    let list_h = HEIGHT - BUTTON_H - 30.;
+    let list = canvas.list(10., 10., WIDTH - 20., list_h, ROW_HEIGHT);
+    canvas.controls[list].on_update = Some(|control, data| {
+        let Ok(buffer) = LOG_LINES.lock() else {
+            return;
+        };
+        let state = Some(LogControl::List(buffer.generation, data.level));
+        if control.state == state {
+            return;
+        }
+        let items = buffer
+            .lines
+            .iter()
+            .rev()
+            .filter(|(level, _)| *level <= data.level)
+            .map(|(_, line)| line.clone())
+            .collect();
+        control.set_items(items);
+        control.state = state;
+    });
+
+    canvas.font = None;
+    canvas.font_size = theme.font_size;
+    let filters = [
+        ("Errors", Level::Error),
+        ("Warnings", Level::Warn),
+        ("Info", Level::Info),
+        ("Debug", Level::Debug),
+    ];
+    let button_w = (WIDTH - 20.) / filters.len() as f32;
+    let y = HEIGHT - BUTTON_H - 10.;
+    for (i, (label, level)) in filters.into_iter().enumerate() {
+        let x = 10. + i as f32 * button_w;
+        let idx = canvas.button(x + 2., y, button_w - 4., BUTTON_H, label.into());
+        canvas.controls[idx].state = Some(LogControl::Filter(level));
+        canvas.controls[idx].on_press = Some(|control, _session, data| {
+            if let Some(LogControl::Filter(level)) = control.state {
+                data.level = level;
+            }
+        });
+        canvas.controls[idx].test_highlight =
+            Some(|control, data| control.state == Some(LogControl::Filter(data.level)));
+    }
+
+    OverlayData {
+        name: "Log".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.5 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: Vec3::NEG_Z * 0.7,
+        ..Default::default()
+    }
+}
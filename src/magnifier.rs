@@ -0,0 +1,116 @@
+use std::sync::Mutex;
+
+use glam::{vec3, Vec3};
+use stereokit::{SkDraw, StereoKitMultiThread, Tex};
+
+use crate::{
+    overlay::{OverlayData, OverlayRenderer, RelativeTo, SplitOverlayBackend},
+    AppSession, AppState,
+};
+
+const SIZE: i32 = 400;
+
+// Crosshair at the center, in texture pixels
+const CROSS_LENGTH: f32 = 24.;
+const CROSS_WIDTH: f32 = 2.;
+
+// Above the controller, out of the way of its own laser
+const HAND_OFFSET: Vec3 = Vec3::new(0., 0.07, -0.1);
+
+// Screen texture and the part of it to show, picked once per frame
+static SOURCE: Mutex<Option<(u32, [f32; 4])>> = Mutex::new(None);
+
+// Pointer of the hand without the watch, which does most of the pointing
+fn pointer(session: &AppSession) -> usize {
+    1 - session.watch_hand
+}
+
+// Picks the region around the pointer's hit on a screen, if any
+pub fn update(sk: &SkDraw, app: &AppState, overlays: &[OverlayData]) {
+    let visible = overlays
+        .iter()
+        .any(|o| &*o.name == "Magnifier" && o.visible);
+
+    let source = if visible {
+        find_source(sk, app, overlays)
+    } else {
+        None
+    };
+    if let Ok(mut s) = SOURCE.lock() {
+        *s = source;
+    }
+}
+
+fn find_source(sk: &SkDraw, app: &AppState, overlays: &[OverlayData]) -> Option<(u32, [f32; 4])> {
+    let (idx, uv) = app.input.hovered(pointer(&app.session))?;
+    if !app.screens.iter().any(|(i, _)| *i == idx) {
+        return None;
+    }
+    let screen = &overlays[idx];
+    let gfx = screen.gfx.as_ref()?;
+    let tex = gfx.out_tex.as_ref().unwrap_or(&gfx.tex);
+    let handle = unsafe { sk.tex_get_surface(tex) as usize as u32 };
+
+    // Screen pixels shown across the magnifier
+    let window = SIZE as f32 / app.session.config.magnifier_zoom;
+    let half_u = (window / 2. / screen.size.0 as f32).min(0.5);
+    let half_v = (window / 2. / screen.size.1 as f32).min(0.5);
+    let u = uv.x.clamp(half_u, 1. - half_u);
+    let v = uv.y.clamp(half_v, 1. - half_v);
+
+    Some((handle, [u - half_u, v - half_v, u + half_u, v + half_v]))
+}
+
+struct MagnifierRenderer;
+
+impl OverlayRenderer for MagnifierRenderer {
+    fn init(&mut self, _sk: &SkDraw, _app: &mut AppState) {}
+    fn pause(&mut self, _app: &mut AppState) {}
+    fn resume(&mut self, _app: &mut AppState) {}
+    fn render(&mut self, sk: &SkDraw, tex: &Tex, app: &mut AppState) {
+        let source = SOURCE.lock().ok().and_then(|s| *s);
+
+        app.gl.begin_sk(sk, tex);
+        let Some((handle, uv)) = source else {
+            app.gl.clear();
+            app.gl.end();
+            return;
+        };
+        app.gl.blit_region(handle, uv);
+
+        let center = SIZE as f32 / 2.;
+        let color = vec3(1., 0.2, 0.2);
+        app.gl.draw_color(
+            color,
+            1.,
+            center - CROSS_LENGTH / 2.,
+            center - CROSS_WIDTH / 2.,
+            CROSS_LENGTH,
+            CROSS_WIDTH,
+        );
+        app.gl.draw_color(
+            color,
+            1.,
+            center - CROSS_WIDTH / 2.,
+            center - CROSS_LENGTH / 2.,
+            CROSS_WIDTH,
+            CROSS_LENGTH,
+        );
+        app.gl.end();
+    }
+}
+
+pub fn create_magnifier(session: &AppSession) -> OverlayData {
+    OverlayData {
+        name: "Magnifier".into(),
+        size: (SIZE, SIZE),
+        width: 0.1,
+        backend: Box::new(SplitOverlayBackend {
+            renderer: Box::new(MagnifierRenderer),
+            ..Default::default()
+        }),
+        relative_to: RelativeTo::Hand(pointer(session)),
+        spawn_point: HAND_OFFSET,
+        ..Default::default()
+    }
+}
@@ -1,44 +1,142 @@
 #![allow(dead_code)]
 use std::{
+    cell::RefCell,
     collections::VecDeque,
     path::PathBuf,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
+use alerts::Alerts;
+use audio::create_audio_panel;
+use colors::create_colors_panel;
 use config::GeneralConfig;
 use desktop::{
-    load_pw_token_config, save_pw_token_config, try_create_screen, wl_client::WlClientState,
+    hotplug, load_pw_token_config, output_power,
+    picker::{self, create_picker},
+    reconnect,
+    toplevel::{self, start_toplevel_tracking},
+    try_create_screen,
+    wl_client::WlClientState,
+};
+use dropdown::create_dropdown;
+use edit_mode::create_edit_hud;
+use gl::{
+    egl::gl_init, pool::TexturePool, GlRenderer, PANEL_SHADER_BYTES, PANEL_STEREO_SHADER_BYTES,
 };
-use gl::{egl::gl_init, GlRenderer, PANEL_SHADER_BYTES};
 use glam::{Quat, Vec3};
-use gui::font::FontCache;
+use gui::{font::FontCache, theme::Theme};
 use input::INPUT;
 use interactions::InputState;
 use keyboard::create_keyboard;
-use log::error;
+use log::info;
+use logview::create_log_viewer;
+use magnifier::create_magnifier;
+use monado::Monado;
+use notifications::create_history;
+use numpad::create_numpad;
 use once_cell::sync::Lazy;
+use osc::OscSender;
 use overlay::OverlayData;
+use panels::load_panels;
+use perf::create_perf_hud;
+use power_menu::create_power_menu;
+use radial::create_radial_menu;
+use scripting::Scripts;
+use settings_menu::create_settings_menu;
+use state::{save_state, SessionState};
 use stereokit::*;
-use tokio::runtime::{Builder, Runtime};
-use watch::{create_watch, WATCH_DEFAULT_POS, WATCH_DEFAULT_ROT};
-
+use taskbar::create_taskbar;
+use toast::create_toast;
+use tokio::{
+    runtime::{Builder, Runtime},
+    signal::unix::{signal, SignalKind},
+};
+use watch::{create_watch, mirror_watch, WATCH_DEFAULT_POS, WATCH_DEFAULT_ROT};
+use webcam::create_webcam;
+use wizard::create_wizard;
+use wm::panel::create_wm_panel;
+
+mod alerts;
+mod audio;
+mod bed_mode;
+mod bindings;
+mod colors;
 mod config;
 mod config_io;
 mod desktop;
+mod dropdown;
+mod edit_mode;
+mod external;
 mod gl;
 mod gui;
+mod idle;
 mod input;
 mod interactions;
+mod ipc;
 mod keyboard;
+mod latency;
+mod logview;
+mod magnifier;
+mod monado;
+mod notifications;
+mod numpad;
+mod osc;
 mod overlay;
+mod panels;
+mod perf;
+mod physical_keys;
+mod power;
+mod power_menu;
+mod radial;
+mod screenshot;
+mod scripting;
+mod settings_menu;
+mod shortcuts;
+mod state;
+mod taskbar;
+mod toast;
 mod watch;
-
-pub type Task = Box<dyn FnOnce(&SkDraw, &mut AppState, &mut [OverlayData]) + Send>;
+mod weather;
+mod webcam;
+mod wizard;
+mod wm;
+mod xtest;
+
+// Tasks may push new overlays to the end, but never remove any, so indices stay valid
+pub type Task = Box<dyn FnOnce(&SkDraw, &mut AppState, &mut Vec<OverlayData>) + Send>;
 pub static TASKS: Lazy<Mutex<VecDeque<Task>>> = Lazy::new(|| Mutex::new(VecDeque::new()));
 
+// Tasks waiting for their time, moved to TASKS by the frame loop once due
+static SCHEDULED_TASKS: Mutex<Vec<(Instant, Task)>> = Mutex::new(Vec::new());
+
+// Runs the task on the render thread, on the first frame after the given time
+pub fn schedule_at(at: Instant, task: Task) {
+    if let Ok(mut scheduled) = SCHEDULED_TASKS.lock() {
+        scheduled.push((at, task));
+    }
+}
+
+pub fn schedule_after(delay: Duration, task: Task) {
+    schedule_at(Instant::now() + delay, task);
+}
+
+// Due scheduled tasks, earliest first
+fn take_due_tasks() -> Vec<Task> {
+    let Ok(mut scheduled) = SCHEDULED_TASKS.lock() else {
+        return vec![];
+    };
+    let now = Instant::now();
+    let (mut due, waiting): (Vec<_>, Vec<_>) = scheduled.drain(..).partition(|(at, _)| *at <= now);
+    *scheduled = waiting;
+    due.sort_by_key(|(at, _)| *at);
+    due.into_iter().map(|(_, task)| task).collect()
+}
+
 pub struct AppSession {
     pub config_root_path: PathBuf,
     pub config: GeneralConfig,
+    pub theme: Theme,
 
     pub show_screens: Vec<String>,
     pub show_keyboard: bool,
@@ -62,58 +160,126 @@ impl AppSession {
         let config_root_path = config_io::ensure_config_root();
         println!("Config root path: {}", config_root_path.to_string_lossy());
         let config = config::load_general();
-        AppSession {
+        gui::set_accessibility(&config.accessibility);
+        let theme = Theme::load(&config.theme).with_colors(&config.theme_colors);
+        let show_screens = config.show_screens.clone();
+        let capture_method = config.capture_method.clone();
+        let watch_hand = config.watch_hand_index();
+        let pointer_colors = config.pointer_colors.clone();
+        let mut session = AppSession {
             config_root_path,
             config,
-            show_screens: vec!["DP-3".to_string()],
+            theme,
+            show_screens,
             show_keyboard: false,
-            capture_method: "auto".to_string(),
+            capture_method,
             primary_hand: 1,
             watch_hand: 1,
             watch_pos: WATCH_DEFAULT_POS,
             watch_rot: WATCH_DEFAULT_ROT,
-            color_norm: Color32 {
-                r: 0,
-                g: 255,
-                b: 255,
-                a: 255,
-            },
-            color_shift: Color32 {
-                r: 255,
-                g: 255,
-                b: 0,
-                a: 255,
-            },
-            color_alt: Color32 {
-                r: 255,
-                g: 0,
-                b: 255,
-                a: 255,
-            },
+            color_norm: color32(&pointer_colors.norm),
+            color_shift: color32(&pointer_colors.shift),
+            color_alt: color32(&pointer_colors.alt),
             color_grab: Color32 {
                 r: 255,
                 g: 0,
                 b: 0,
                 a: 255,
             },
+        };
+        // the default placement is for the left wrist
+        if watch_hand != session.watch_hand {
+            session.watch_hand = watch_hand;
+            mirror_watch(&mut session);
+        }
+        session.load_watch_placement();
+        session
+    }
+
+    // A saved placement is already for watch_hand, unlike the default one
+    pub fn load_watch_placement(&mut self) {
+        if let Some(pos) = self.config.watch_pos {
+            self.watch_pos = Vec3::from_array(pos);
+        }
+        if let Some(rot) = self.config.watch_rot {
+            self.watch_rot = Quat::from_array(rot).normalize();
         }
     }
+
+    pub fn load_pointer_colors(&mut self) {
+        let colors = &self.config.pointer_colors;
+        self.color_norm = color32(&colors.norm);
+        self.color_shift = color32(&colors.shift);
+        self.color_alt = color32(&colors.alt);
+    }
+}
+
+// Config colors are checked on load, so this can't fail
+pub fn color32(html_hex: &str) -> Color32 {
+    let c = gui::color_parse(html_hex) * 255.;
+    Color32 {
+        r: c.x as u8,
+        g: c.y as u8,
+        b: c.z as u8,
+        a: 255,
+    }
 }
 
 // Contains runtime resources
 pub struct AppState {
     fc: FontCache,
     gl: GlRenderer,
+    tex_pool: TexturePool,
     input: InputState,
+    monado: Option<Monado>,
+    osc: Option<OscSender>,
     panel_shader: Shader,
+    panel_stereo_shader: Shader,
     rt: Runtime,
     session: AppSession,
+    screens: Vec<(usize, Arc<str>)>,
+    alerts: Alerts,
+    scripts: Option<Scripts>,
 }
 
 impl AppState {
     fn update_input(&mut self, sk: &SkDraw, interactables: &mut [OverlayData]) {
         self.input.update(&self.session, sk, interactables);
     }
+
+    fn update_playspace(&mut self) {
+        if let Some(monado) = self.monado.as_mut() {
+            monado.update_drag(&self.input);
+        }
+    }
+}
+
+// On SIGTERM / SIGINT, leave the frame loop so everything gets cleaned up as on a normal exit.
+// A second signal exits right away, in case the frame loop is stuck.
+fn quit_on_signal(rt: &Runtime) {
+    rt.spawn(async {
+        let Ok(mut term) = signal(SignalKind::terminate()) else {
+            return;
+        };
+        let mut quitting = false;
+        loop {
+            tokio::select! {
+                _ = term.recv() => {},
+                _ = tokio::signal::ctrl_c() => {},
+            }
+            if quitting {
+                input::release_held_input();
+                std::process::exit(1);
+            }
+            quitting = true;
+            info!("Shutting down");
+            if let Ok(mut tasks) = TASKS.lock() {
+                tasks.push_back(Box::new(|_sk, _app, _overlays| unsafe {
+                    stereokit::sys::sk_quit();
+                }));
+            }
+        }
+    });
 }
 
 fn main() {
@@ -138,7 +304,23 @@ fn main() {
         stereokit::sys::ui_enable_far_interact(0);
     };
 
-    env_logger::init();
+    // WLXO_LOG_LEVEL takes precedence over RUST_LOG
+    let log_var = if std::env::var_os(config::ENV_LOG_LEVEL).is_some() {
+        config::ENV_LOG_LEVEL
+    } else {
+        "RUST_LOG"
+    };
+    logview::init_logger(log_var);
+
+    // a panic on the main thread takes the app down and must not leave a key or button held on
+    // the desktop. Other threads' panics may be caught (tokio does) and input keeps working.
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().name() == Some("main") {
+            input::release_held_input();
+        }
+        default_hook(info);
+    }));
 
     let rt = Builder::new_multi_thread()
         .worker_threads(1)
@@ -146,7 +328,38 @@ fn main() {
         .build()
         .unwrap();
 
-    let session = AppSession::load();
+    // --profile <name> selects profiles/<name>.yaml from the config directory
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            config::set_profile(args.next());
+        }
+    }
+
+    // checked before anything gets written to the config directory
+    let first_run = wizard::is_first_run();
+    let mut session = AppSession::load();
+    config::watch_general();
+
+    let state = SessionState::load().unwrap_or_default();
+    state.apply_session(&mut session);
+    quit_on_signal(&rt);
+    ipc::start_server(&rt);
+    external::start_external_server();
+    wm::start();
+    start_toplevel_tracking();
+    if let Some(port) = session.config.osc_listen_port {
+        osc::start_listener(&rt, session.config.osc_listen_address.clone(), port);
+    }
+    if session.config.desktop_notifications {
+        notifications::start_monitor(&rt);
+    }
+    if let Some(shortcut) = session.config.global_shortcut.clone() {
+        shortcuts::start(&rt, shortcut);
+    }
+    if let Some(weather) = session.config.weather.clone() {
+        weather::start(&rt, weather);
+    }
     gl_init(&sk);
 
     let mut overlays: Vec<OverlayData> = vec![];
@@ -168,41 +381,124 @@ fn main() {
         wl.pw_tokens = pw_tokens;
     }
 
-    let pw_tokens_copy = wl.pw_tokens.clone();
-
     for i in 0..wl.outputs.len() {
-        let maybe_screen = rt.block_on(try_create_screen(&mut wl, i, &session));
+        let maybe_screen = try_create_screen(&mut wl, i, &session);
         if let Some(mut screen) = maybe_screen {
-            screen.want_visible = session.show_screens.iter().any(|s| s == &*screen.name);
+            session.config.apply_output(&mut screen);
 
             screens.push((overlays.len(), screen.name.clone()));
             overlays.push(screen);
         }
     }
 
-    if pw_tokens_copy != wl.pw_tokens {
-        // Token list changed, re-create token config file
-        if let Err(err) = save_pw_token_config(&wl.pw_tokens) {
-            error!("Failed to save Pipewire token config: {}", err);
-        }
-    }
+    hotplug::start_output_watch();
 
-    overlays[0] = create_watch(&session, screens);
+    overlays[0] = create_watch(&session, screens.clone());
+    for (i, config) in session.config.webcams.iter().enumerate() {
+        overlays.push(create_webcam(&session, i, config));
+    }
+    overlays.push(create_radial_menu(&session));
+    if toplevel::is_available() {
+        overlays.push(create_taskbar(&session));
+    }
+    if wm::is_available() {
+        overlays.push(create_wm_panel(&session));
+    }
+    overlays.push(create_toast(&session));
+    overlays.push(create_log_viewer(&session));
+    overlays.push(create_history(&session));
+    overlays.push(create_edit_hud(&session));
+    overlays.push(create_magnifier(&session));
+    overlays.push(create_numpad(&session));
+    overlays.push(create_dropdown(&session));
+    overlays.push(create_audio_panel(&session));
+    overlays.push(create_power_menu(&session));
+    overlays.push(create_settings_menu(&session));
+    overlays.push(create_colors_panel(&session));
+    overlays.push(create_picker(&session));
+    if first_run {
+        let mut wizard = create_wizard(&session, &screens);
+        wizard.want_visible = true;
+        overlays.push(wizard);
+    }
+    perf::set_enabled(session.config.perf_stats);
+    latency::set_enabled(session.config.latency_stats);
+    if session.config.perf_stats || session.config.latency_stats {
+        overlays.push(create_perf_hud(&session));
+    }
+    // last, so names already taken are known
+    let panels = load_panels(&session, &overlays);
+    overlays.extend(panels);
+    state.apply_overlays(&mut overlays);
 
     let panel_shader = sk.shader_create_mem(PANEL_SHADER_BYTES).unwrap();
+    let panel_stereo_shader = sk.shader_create_mem(PANEL_STEREO_SHADER_BYTES).unwrap();
     let mut app = Lazy::new(|| AppState {
         gl: GlRenderer::new(),
+        tex_pool: TexturePool::default(),
         input: InputState::new(&session),
+        monado: Monado::open(),
+        osc: session
+            .config
+            .osc_send_address
+            .as_deref()
+            .and_then(OscSender::new),
+        fc: FontCache::new(
+            &session.config.font_family,
+            &session.config.font_style,
+            &session.config.font_fallbacks,
+        ),
         session,
         rt,
-        fc: FontCache::new(),
+        alerts: Alerts::new(),
+        scripts: Scripts::load(),
         panel_shader,
+        panel_stereo_shader,
+        screens,
     });
 
+    let overlays = RefCell::new(overlays);
     sk.run(
         |sk| {
+            let mut overlays = overlays.borrow_mut();
             app.update_input(sk, overlays.as_mut_slice());
+            app.update_playspace();
+            wizard::update(&app.input);
+            magnifier::update(sk, &app, overlays.as_slice());
+            edit_mode::update(
+                sk,
+                &app.input.hmd,
+                overlays.as_mut_slice(),
+                app.input.grabbed(),
+            );
+            bed_mode::update(
+                &app.session.config.bed_mode,
+                &app.input.hmd,
+                overlays.as_mut_slice(),
+                app.input.grabbed(),
+            );
+            toast::update(overlays.as_mut_slice());
+            picker::update(overlays.as_mut_slice());
+            power::update(&app.session.config);
+            output_power::update(overlays.as_slice(), &app.screens);
+            alerts::update(sk, &mut app);
+            idle::update(&app, overlays.as_slice());
+            gl::check::check_frame();
+            gl::context::update(sk, &mut app, overlays.as_mut_slice());
+            app.fc.upload_pending();
+            reconnect::update(&mut app, overlays.as_mut_slice());
+            if let Some(scripts) = app.scripts.as_mut() {
+                scripts.update(overlays.as_mut_slice());
+            }
+            if let Some(osc) = app.osc.as_mut() {
+                osc.update(overlays.as_slice());
+            }
 
+            // Pipewire shm frames arrive already copied out by the capture threads, into
+            // buffers they reuse, and glyphs are rasterized by the font cache's worker. Canvas
+            // draws stay here: they go straight to GL as the controls are walked, there is no
+            // draw list a worker could build. So do the uploads, which are GL calls themselves.
+            let overlays_timer = perf::time(perf::Section::Overlays);
             for overlay in overlays.iter_mut() {
                 if overlay.want_visible && !overlay.visible {
                     overlay.show(sk, &mut app);
@@ -212,17 +508,30 @@ fn main() {
 
                 overlay.render(sk, &mut app);
             }
-
-            if let Ok(mut tasks) = TASKS.lock() {
-                while let Some(task) = tasks.pop_front() {
-                    task(sk, &mut app, overlays.as_mut_slice());
-                }
+            drop(overlays_timer);
+
+            // taken out of the lock first, so a task can queue more; those run next frame
+            let mut tasks = TASKS
+                .lock()
+                .map(|mut tasks| std::mem::take(&mut *tasks))
+                .unwrap_or_default();
+            tasks.extend(take_due_tasks());
+            while let Some(task) = tasks.pop_front() {
+                task(sk, &mut app, &mut overlays);
             }
 
             if let Ok(mut uinput) = INPUT.lock() {
                 uinput.on_new_frame();
             }
+            perf::end_frame();
+        },
+        |_| {
+            save_state(&overlays.borrow());
+            let _ = output_power::set_displays_off(false);
+            input::close_input();
+            ipc::stop_server();
+            // dropping the screens stops their capture threads and pipewire streams
+            overlays.borrow_mut().clear();
         },
-        |_| {},
     );
 }
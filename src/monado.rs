@@ -0,0 +1,232 @@
+use std::{
+    ffi::{c_char, CStr},
+    ptr,
+};
+
+use glam::{Quat, Vec3};
+use libloading::Library;
+use log::{error, info};
+
+use crate::interactions::InputState;
+
+// Minimal bindings to libmonado, loaded at runtime so other runtimes work without it
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MndPose {
+    orientation: [f32; 4],
+    position: [f32; 3],
+}
+
+type MndRoot = std::ffi::c_void;
+
+const MND_SUCCESS: i32 = 0;
+const MND_SPACE_REFERENCE_TYPE_LOCAL: i32 = 1;
+const MND_SPACE_REFERENCE_TYPE_STAGE: i32 = 3;
+
+type RootCreateFn = unsafe extern "C" fn(*mut *mut MndRoot) -> i32;
+type RootDestroyFn = unsafe extern "C" fn(*mut *mut MndRoot);
+type RecenterFn = unsafe extern "C" fn(*mut MndRoot) -> i32;
+type GetOffsetFn = unsafe extern "C" fn(*mut MndRoot, i32, *mut MndPose) -> i32;
+type SetOffsetFn = unsafe extern "C" fn(*mut MndRoot, i32, *const MndPose) -> i32;
+type DeviceCountFn = unsafe extern "C" fn(*mut MndRoot, *mut u32) -> i32;
+type DeviceInfoFn = unsafe extern "C" fn(*mut MndRoot, u32, *mut u32, *mut *const c_char) -> i32;
+type BatteryStatusFn =
+    unsafe extern "C" fn(*mut MndRoot, u32, *mut bool, *mut bool, *mut f32) -> i32;
+
+pub struct Monado {
+    root: *mut MndRoot,
+    destroy: RootDestroyFn,
+    recenter: RecenterFn,
+    get_offset: GetOffsetFn,
+    set_offset: SetOffsetFn,
+    device_count: DeviceCountFn,
+    device_info: DeviceInfoFn,
+    // only in newer versions of libmonado
+    battery_status: Option<BatteryStatusFn>,
+    // the function pointers above are only valid while this is loaded
+    _lib: Library,
+
+    pub drag_enabled: bool,
+    drag: Option<SpaceDrag>,
+}
+
+pub struct Battery {
+    pub device: String,
+    // 0.0 - 1.0
+    pub charge: f32,
+    pub charging: bool,
+}
+
+struct SpaceDrag {
+    pointer: usize,
+    // hand position in the unoffset space, and the offset at the start of the drag
+    start_hand: Vec3,
+    start_offset: Vec3,
+    offset: Vec3,
+    orientation: Quat,
+}
+
+impl Monado {
+    // None if libmonado is not installed or the Monado service is not running
+    pub fn open() -> Option<Monado> {
+        unsafe {
+            let lib = Library::new("libmonado.so")
+                .map_err(|e| info!("libmonado not available: {}", e))
+                .ok()?;
+
+            let create: RootCreateFn = *lib.get(b"mnd_root_create\0").ok()?;
+            let destroy: RootDestroyFn = *lib.get(b"mnd_root_destroy\0").ok()?;
+            let recenter: RecenterFn = *lib.get(b"mnd_root_recenter_local_spaces\0").ok()?;
+            let get_offset: GetOffsetFn =
+                *lib.get(b"mnd_root_get_reference_space_offset\0").ok()?;
+            let set_offset: SetOffsetFn =
+                *lib.get(b"mnd_root_set_reference_space_offset\0").ok()?;
+            let device_count: DeviceCountFn = *lib.get(b"mnd_root_get_device_count\0").ok()?;
+            let device_info: DeviceInfoFn = *lib.get(b"mnd_root_get_device_info\0").ok()?;
+            let battery_status: Option<BatteryStatusFn> = lib
+                .get(b"mnd_root_get_device_battery_status\0")
+                .ok()
+                .map(|f| *f);
+
+            let mut root = ptr::null_mut();
+            let result = create(&mut root);
+            if result != MND_SUCCESS {
+                error!("Failed to connect to Monado: {}", result);
+                return None;
+            }
+            info!("Connected to Monado");
+
+            Some(Monado {
+                root,
+                destroy,
+                recenter,
+                get_offset,
+                set_offset,
+                device_count,
+                device_info,
+                battery_status,
+                _lib: lib,
+                drag_enabled: false,
+                drag: None,
+            })
+        }
+    }
+
+    pub fn recenter(&mut self) -> Result<(), String> {
+        match unsafe { (self.recenter)(self.root) } {
+            MND_SUCCESS => Ok(()),
+            e => Err(format!("Recenter failed: {}", e)),
+        }
+    }
+
+    // Devices that report a battery; empty if this libmonado can't tell
+    pub fn batteries(&self) -> Vec<Battery> {
+        let Some(battery_status) = self.battery_status else {
+            return Vec::new();
+        };
+        let mut count = 0u32;
+        if unsafe { (self.device_count)(self.root, &mut count) } != MND_SUCCESS {
+            return Vec::new();
+        }
+
+        let mut batteries = Vec::new();
+        for index in 0..count {
+            let (mut present, mut charging, mut charge) = (false, false, 0f32);
+            let result = unsafe {
+                battery_status(self.root, index, &mut present, &mut charging, &mut charge)
+            };
+            if result != MND_SUCCESS || !present {
+                continue;
+            }
+
+            let mut id = 0u32;
+            let mut name = ptr::null();
+            let result = unsafe { (self.device_info)(self.root, index, &mut id, &mut name) };
+            let device = if result == MND_SUCCESS && !name.is_null() {
+                unsafe { CStr::from_ptr(name) }
+                    .to_string_lossy()
+                    .to_string()
+            } else {
+                format!("Device {}", index)
+            };
+            batteries.push(Battery {
+                device,
+                charge,
+                charging,
+            });
+        }
+        batteries
+    }
+
+    fn get_offset(&self) -> Option<MndPose> {
+        let mut pose = MndPose {
+            orientation: [0., 0., 0., 1.],
+            position: [0.; 3],
+        };
+        let result =
+            unsafe { (self.get_offset)(self.root, MND_SPACE_REFERENCE_TYPE_STAGE, &mut pose) };
+        (result == MND_SUCCESS).then_some(pose)
+    }
+
+    // Both spaces move together, so apps using either follow the drag
+    fn set_offset(&self, pose: &MndPose) {
+        for space in [
+            MND_SPACE_REFERENCE_TYPE_STAGE,
+            MND_SPACE_REFERENCE_TYPE_LOCAL,
+        ] {
+            let result = unsafe { (self.set_offset)(self.root, space, pose) };
+            if result != MND_SUCCESS {
+                error!("Failed to set playspace offset: {}", result);
+            }
+        }
+    }
+
+    // While drag is enabled, gripping empty space moves the playspace along with the hand
+    pub fn update_drag(&mut self, input: &InputState) {
+        if !self.drag_enabled {
+            self.drag = None;
+            return;
+        }
+
+        if let Some(drag) = self.drag.as_mut() {
+            let Some(hand) = input.grip(drag.pointer) else {
+                self.drag = None;
+                return;
+            };
+            // reported positions move opposite to the offset, so add it back
+            let hand = hand + drag.offset;
+            drag.offset = drag.start_offset + (hand - drag.start_hand);
+            let pose = MndPose {
+                orientation: drag.orientation.to_array(),
+                position: drag.offset.to_array(),
+            };
+            self.set_offset(&pose);
+            return;
+        }
+
+        for pointer in 0..2 {
+            let Some(hand) = input.free_grip_start(pointer) else {
+                continue;
+            };
+            let Some(pose) = self.get_offset() else {
+                return;
+            };
+            let offset = Vec3::from_array(pose.position);
+            self.drag = Some(SpaceDrag {
+                pointer,
+                start_hand: hand + offset,
+                start_offset: offset,
+                offset,
+                orientation: Quat::from_array(pose.orientation),
+            });
+            return;
+        }
+    }
+}
+
+impl Drop for Monado {
+    fn drop(&mut self) {
+        unsafe { (self.destroy)(&mut self.root) };
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use chrono::Local;
+use futures_util::StreamExt;
+use glam::Vec3;
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use tokio::runtime::Runtime;
+use zbus::{zvariant::OwnedValue, Connection, MessageStream};
+
+use crate::{
+    gui::{color_parse, Canvas},
+    overlay::{OverlayData, RelativeTo},
+    scripting::{self, ScriptEvent},
+    toast::{self, NotifyLevel},
+    AppSession,
+};
+
+const MAX_ENTRIES: usize = 100;
+
+const WIDTH: f32 = 600.;
+const HEIGHT: f32 = 400.;
+const ROW_HEIGHT: f32 = 28.;
+const BUTTON_H: f32 = 36.;
+
+const NOTIFY_RULE: &str =
+    "type='method_call',interface='org.freedesktop.Notifications',member='Notify'";
+
+// app_name, replaces_id, app_icon, summary, body, actions, hints, expire_timeout
+type NotifyArgs = (
+    String,
+    u32,
+    String,
+    String,
+    String,
+    Vec<String>,
+    HashMap<String, OwnedValue>,
+    i32,
+);
+
+#[derive(Default)]
+struct History {
+    // bumped with every change, so the panel knows to refresh
+    generation: u64,
+    next_id: u64,
+    entries: VecDeque<(u64, Arc<str>)>,
+}
+
+static HISTORY: Lazy<Mutex<History>> = Lazy::new(|| Mutex::new(History::default()));
+
+// Keeps a toast around after it's gone from the watch
+pub fn record(level: NotifyLevel, text: &str) {
+    let marker = match level {
+        NotifyLevel::Info => "",
+        NotifyLevel::Warn => "⚠ ",
+        NotifyLevel::Error => "✖ ",
+    };
+    let line = format!("{} {}{}", Local::now().format("%H:%M"), marker, text);
+    if let Ok(mut history) = HISTORY.lock() {
+        if history.entries.len() >= MAX_ENTRIES {
+            history.entries.pop_front();
+        }
+        let id = history.next_id;
+        history.next_id += 1;
+        history.entries.push_back((id, line.into()));
+        history.generation += 1;
+    }
+}
+
+fn dismiss(id: u64) {
+    if let Ok(mut history) = HISTORY.lock() {
+        history.entries.retain(|(i, _)| *i != id);
+        history.generation += 1;
+    }
+}
+
+fn clear() {
+    if let Ok(mut history) = HISTORY.lock() {
+        history.entries.clear();
+        history.generation += 1;
+    }
+}
+
+// Eavesdrops on org.freedesktop.Notifications calls, showing each one as a toast
+pub fn start_monitor(rt: &Runtime) {
+    rt.spawn(async {
+        if let Err(e) = monitor().await {
+            warn!("Not showing desktop notifications: {}", e);
+        }
+    });
+}
+
+async fn monitor() -> zbus::Result<()> {
+    // a monitoring connection can't be used for anything else
+    let conn = Connection::session().await?;
+    conn.call_method(
+        Some("org.freedesktop.DBus"),
+        "/org/freedesktop/DBus",
+        Some("org.freedesktop.DBus.Monitoring"),
+        "BecomeMonitor",
+        &(vec![NOTIFY_RULE], 0u32),
+    )
+    .await?;
+    info!("Listening for desktop notifications");
+
+    let mut stream = MessageStream::from(&conn);
+    while let Some(msg) = stream.next().await {
+        let Ok(msg) = msg else {
+            continue;
+        };
+        if !msg.member().is_some_and(|m| m.as_str() == "Notify") {
+            continue;
+        }
+        let Ok((app_name, _, _, summary, body, ..)) = msg.body::<NotifyArgs>() else {
+            continue;
+        };
+        let text = if body.is_empty() {
+            format!("{}: {}", app_name, summary)
+        } else {
+            format!("{}: {} - {}", app_name, summary, body)
+        };
+        toast::notify(NotifyLevel::Info, &text);
+        scripting::emit(ScriptEvent::Notification {
+            app: app_name,
+            summary,
+            body,
+        });
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum HistoryControl {
+    // generation the items were built from
+    List(u64),
+}
+
+#[derive(Default)]
+struct HistoryData {
+    // entry ids, in the order of the list rows
+    ids: Vec<u64>,
+    selected: Option<u64>,
+}
+
+// Past toasts and desktop notifications, newest first
+pub fn create_history(session: &AppSession) -> OverlayData {
+    let mut canvas: Canvas<HistoryData, HistoryControl> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        HistoryData::default(),
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.font_size = theme.font_size * 3 / 4;
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+
+    let list_h = HEIGHT - BUTTON_H - 30.;
+    let list = canvas.list(10., 10., WIDTH - 20., list_h, ROW_HEIGHT);
+    canvas.controls[list].on_update = Some(|control, data| {
+        let Ok(history) = HISTORY.lock() else {
+            return;
+        };
+        let state = Some(HistoryControl::List(history.generation));
+        if control.state == state {
+            return;
+        }
+        data.ids = history.entries.iter().rev().map(|(id, _)| *id).collect();
+        let items = history
+            .entries
+            .iter()
+            .rev()
+            .map(|(_, line)| line.clone())
+            .collect();
+        control.set_items(items);
+        control.state = state;
+        if data.selected.is_some_and(|id| !data.ids.contains(&id)) {
+            data.selected = None;
+        }
+    });
+    canvas.controls[list].on_select = Some(|_control, row, data| {
+        data.selected = data.ids.get(row).copied();
+    });
+
+    canvas.font_size = theme.font_size;
+    let button_w = (WIDTH - 20.) / 2.;
+    let y = HEIGHT - BUTTON_H - 10.;
+    let dismiss_button = canvas.button(12., y, button_w - 4., BUTTON_H, "Dismiss".into());
+    canvas.controls[dismiss_button].on_press = Some(|_control, _session, data| {
+        if let Some(id) = data.selected.take() {
+            dismiss(id);
+        }
+    });
+    let clear_button = canvas.button(
+        12. + button_w,
+        y,
+        button_w - 4.,
+        BUTTON_H,
+        "Clear all".into(),
+    );
+    canvas.controls[clear_button].on_press = Some(|_control, _session, data| {
+        data.selected = None;
+        clear();
+    });
+
+    OverlayData {
+        name: "History".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.45 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: Vec3::NEG_Z * 0.7,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,214 @@
+use std::sync::Mutex;
+
+use glam::vec3;
+use once_cell::sync::Lazy;
+
+use crate::{
+    gui::{color_parse, Canvas, Control},
+    overlay::{OverlayData, RelativeTo},
+    toast, AppSession, TASKS,
+};
+
+const KEY_SIZE: f32 = 60.;
+const PADDING: f32 = 5.;
+const DISPLAY_HEIGHT: f32 = 70.;
+
+const WIDTH: f32 = KEY_SIZE * 4. + PADDING * 2.;
+const HEIGHT: f32 = DISPLAY_HEIGHT + KEY_SIZE * 4. + PADDING;
+
+const MAX_LENGTH: usize = 12;
+
+#[derive(Clone, Copy)]
+enum NumKey {
+    Char(char),
+    Backspace,
+    Cancel,
+    Submit,
+}
+
+const KEYS: [[(&str, NumKey); 4]; 4] = [
+    [
+        ("7", NumKey::Char('7')),
+        ("8", NumKey::Char('8')),
+        ("9", NumKey::Char('9')),
+        ("⌫", NumKey::Backspace),
+    ],
+    [
+        ("4", NumKey::Char('4')),
+        ("5", NumKey::Char('5')),
+        ("6", NumKey::Char('6')),
+        ("-", NumKey::Char('-')),
+    ],
+    [
+        ("1", NumKey::Char('1')),
+        ("2", NumKey::Char('2')),
+        ("3", NumKey::Char('3')),
+        (".", NumKey::Char('.')),
+    ],
+    [
+        ("Esc", NumKey::Cancel),
+        ("0", NumKey::Char('0')),
+        ("", NumKey::Char('0')),
+        ("OK", NumKey::Submit),
+    ],
+];
+
+// Value being typed for the control with the given focus id
+struct NumEntry {
+    owner: usize,
+    text: String,
+    min: f32,
+    max: f32,
+}
+
+static ENTRY: Lazy<Mutex<Option<NumEntry>>> = Lazy::new(|| Mutex::new(None));
+static RESULT: Mutex<Option<(usize, f32)>> = Mutex::new(None);
+
+// Shows the numpad for a control, starting out with its current value
+pub fn open(owner: usize, value: f32, min: f32, max: f32) {
+    let text = format!("{}", (value * 1000.).round() / 1000.);
+    if let Ok(mut entry) = ENTRY.lock() {
+        *entry = Some(NumEntry {
+            owner,
+            text,
+            min,
+            max,
+        });
+    }
+    set_visible(true);
+}
+
+// The value entered for the given control, once OK was pressed
+pub fn take_result(owner: usize) -> Option<f32> {
+    let mut result = RESULT.lock().ok()?;
+    match *result {
+        Some((o, value)) if o == owner => {
+            *result = None;
+            Some(value)
+        }
+        _ => None,
+    }
+}
+
+fn set_visible(visible: bool) {
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(move |_sk, _app, o| {
+            for overlay in o.iter_mut() {
+                if &*overlay.name == "Numpad" {
+                    overlay.want_visible = visible;
+                }
+            }
+        }));
+    }
+}
+
+fn key_press(control: &mut Control<(), NumKey>, _session: &AppSession, _data: &mut ()) {
+    let Some(key) = control.state else {
+        return;
+    };
+    let Ok(mut entry) = ENTRY.lock() else {
+        return;
+    };
+    let Some(current) = entry.as_mut() else {
+        return;
+    };
+
+    match key {
+        NumKey::Char(c) => {
+            if current.text.len() < MAX_LENGTH {
+                current.text.push(c);
+            }
+        }
+        NumKey::Backspace => {
+            current.text.pop();
+        }
+        NumKey::Cancel => {
+            *entry = None;
+            set_visible(false);
+        }
+        NumKey::Submit => {
+            let Ok(value) = current.text.parse::<f32>() else {
+                toast::warn(&format!("\"{}\" is not a number", current.text));
+                return;
+            };
+            if let Ok(mut result) = RESULT.lock() {
+                *result = Some((current.owner, value.clamp(current.min, current.max)));
+            }
+            *entry = None;
+            set_visible(false);
+        }
+    }
+}
+
+pub fn create_numpad(session: &AppSession) -> OverlayData {
+    let mut canvas: Canvas<(), NumKey> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        (),
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.keyboard_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.font_size = theme.font_size;
+    canvas.fg_color = color_parse(&theme.text_color);
+    let display = canvas.label(PADDING * 2., 38., WIDTH - PADDING * 4., 30., "".into());
+    canvas.controls[display].on_update = Some(|control, _data| {
+        if let Ok(entry) = ENTRY.lock() {
+            control.set_text(entry.as_ref().map(|e| e.text.as_str()).unwrap_or(""));
+        }
+    });
+
+    canvas.font_size = 12;
+    canvas.fg_color = color_parse(&theme.toggle_text_color);
+    let range = canvas.label(PADDING * 2., 60., WIDTH - PADDING * 4., 14., "".into());
+    canvas.controls[range].on_update = Some(|control, _data| {
+        if let Ok(entry) = ENTRY.lock() {
+            let text = entry
+                .as_ref()
+                .map(|e| format!("{} to {}", e.min, e.max))
+                .unwrap_or_default();
+            control.set_text(&text);
+        }
+    });
+
+    canvas.font_size = theme.key_font_size;
+    canvas.fg_color = color_parse(&theme.key_text_color);
+    for (row, keys) in KEYS.iter().enumerate() {
+        for (col, (label, key)) in keys.iter().enumerate() {
+            // the empty key is the right half of a wide zero
+            if label.is_empty() {
+                continue;
+            }
+            let wide = col + 1 < keys.len() && keys[col + 1].0.is_empty();
+            let w = if wide { KEY_SIZE * 2. } else { KEY_SIZE };
+
+            canvas.bg_color = match key {
+                NumKey::Submit => color_parse(&theme.keyboard_button_color),
+                _ => color_parse(&theme.key_color),
+            };
+            let idx = canvas.key_button(
+                PADDING + col as f32 * KEY_SIZE + PADDING / 2.,
+                DISPLAY_HEIGHT + row as f32 * KEY_SIZE + PADDING / 2.,
+                w - PADDING,
+                KEY_SIZE - PADDING,
+                &vec![label.to_string()],
+            );
+            canvas.controls[idx].state = Some(*key);
+            canvas.controls[idx].on_press = Some(key_press);
+        }
+    }
+
+    OverlayData {
+        name: "Numpad".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.2 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: vec3(0., -0.2, -0.5),
+        ..Default::default()
+    }
+}
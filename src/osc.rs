@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    net::UdpSocket,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use rosc::{decoder, encoder, OscMessage, OscPacket, OscType};
+use tokio::runtime::Runtime;
+
+use crate::{overlay::OverlayData, TASKS};
+
+// Addresses understood by the listener:
+//   /wlx/overlay/<name>/visible <bool|int>   show or hide an overlay
+//   /wlx/overlay/<name>/toggle               toggle an overlay
+//   /wlx/watch/text <string|int|float>       text shown on the watch
+//   /wlx/watch/metric <int|float>            number shown on the watch, e.g. heart rate
+// The sender reports /wlx/overlay/<name>/visible <bool> whenever an overlay is shown or hidden.
+
+const OVERLAY_PREFIX: &str = "/wlx/overlay/";
+const WATCH_TEXT_ADDR: &str = "/wlx/watch/text";
+const WATCH_METRIC_ADDR: &str = "/wlx/watch/metric";
+
+// A metric that stops coming in is hidden rather than shown as current
+const METRIC_TIMEOUT: Duration = Duration::from_secs(30);
+
+static WATCH_TEXT: Lazy<Mutex<Arc<str>>> = Lazy::new(|| Mutex::new("".into()));
+static WATCH_METRIC: Mutex<Option<(f32, Instant)>> = Mutex::new(None);
+
+pub fn get_watch_text() -> Arc<str> {
+    WATCH_TEXT
+        .lock()
+        .map(|t| t.clone())
+        .unwrap_or_else(|_| "".into())
+}
+
+// The last metric received, unless it's gone stale
+pub fn get_watch_metric() -> Option<f32> {
+    let metric = *WATCH_METRIC.lock().ok()?;
+    metric
+        .filter(|(_, received)| received.elapsed() < METRIC_TIMEOUT)
+        .map(|(value, _)| value)
+}
+
+pub fn start_listener(rt: &Runtime, address: String, port: u16) {
+    rt.spawn(async move {
+        let socket = match tokio::net::UdpSocket::bind((address.as_str(), port)).await {
+            Ok(socket) => socket,
+            Err(e) => {
+                error!("Failed to listen for OSC on {}:{}: {}", address, port, e);
+                return;
+            }
+        };
+        info!("Listening for OSC on {}:{}", address, port);
+
+        let mut buf = [0u8; rosc::decoder::MTU];
+        loop {
+            let len = match socket.recv(&mut buf).await {
+                Ok(len) => len,
+                Err(e) => {
+                    error!("Stopped listening for OSC: {}", e);
+                    return;
+                }
+            };
+            match decoder::decode_udp(&buf[..len]) {
+                Ok((_, packet)) => handle_packet(packet),
+                Err(e) => warn!("Invalid OSC packet: {:?}", e),
+            }
+        }
+    });
+}
+
+fn handle_packet(packet: OscPacket) {
+    match packet {
+        OscPacket::Message(msg) => handle_message(msg),
+        OscPacket::Bundle(bundle) => bundle.content.into_iter().for_each(handle_packet),
+    }
+}
+
+fn handle_message(msg: OscMessage) {
+    if msg.addr == WATCH_TEXT_ADDR {
+        let text = match msg.args.first() {
+            Some(OscType::String(s)) => s.clone(),
+            Some(OscType::Int(i)) => i.to_string(),
+            Some(OscType::Float(f)) => format!("{:.1}", f),
+            _ => String::new(),
+        };
+        if let Ok(mut watch_text) = WATCH_TEXT.lock() {
+            *watch_text = text.into();
+        }
+        return;
+    }
+
+    if msg.addr == WATCH_METRIC_ADDR {
+        let value = match msg.args.first() {
+            Some(OscType::Int(i)) => *i as f32,
+            Some(OscType::Float(f)) => *f,
+            Some(OscType::Double(d)) => *d as f32,
+            _ => return,
+        };
+        if let Ok(mut metric) = WATCH_METRIC.lock() {
+            *metric = Some((value, Instant::now()));
+        }
+        return;
+    }
+
+    let Some(rest) = msg.addr.strip_prefix(OVERLAY_PREFIX) else {
+        return;
+    };
+    let Some((name, action)) = rest.rsplit_once('/') else {
+        return;
+    };
+    let visible = match (action, msg.args.first()) {
+        ("visible", Some(OscType::Bool(b))) => Some(*b),
+        ("visible", Some(OscType::Int(i))) => Some(*i != 0),
+        ("toggle", _) => None,
+        _ => return,
+    };
+
+    let name = name.to_string();
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(move |_sk, _app, overlays| {
+            if let Some(overlay) = overlays.iter_mut().find(|o| *o.name == *name) {
+                overlay.want_visible = visible.unwrap_or(!overlay.want_visible);
+            }
+        }));
+    }
+}
+
+// Sends overlay events to the configured OSC address
+pub struct OscSender {
+    socket: UdpSocket,
+    last_visible: HashMap<Arc<str>, bool>,
+}
+
+impl OscSender {
+    pub fn new(address: &str) -> Option<OscSender> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .and_then(|socket| socket.connect(address).map(|_| socket))
+            .map_err(|e| error!("Failed to set up OSC sender to {}: {}", address, e))
+            .ok()?;
+        socket.set_nonblocking(true).ok()?;
+        Some(OscSender {
+            socket,
+            last_visible: HashMap::new(),
+        })
+    }
+
+    pub fn update(&mut self, overlays: &[OverlayData]) {
+        for overlay in overlays.iter().filter(|o| !o.name.is_empty()) {
+            let last = self
+                .last_visible
+                .insert(overlay.name.clone(), overlay.visible);
+            if last != Some(overlay.visible) {
+                self.send(OscMessage {
+                    addr: format!("{}{}/visible", OVERLAY_PREFIX, overlay.name),
+                    args: vec![OscType::Bool(overlay.visible)],
+                });
+            }
+        }
+    }
+
+    fn send(&self, msg: OscMessage) {
+        match encoder::encode(&OscPacket::Message(msg)) {
+            Ok(buf) => {
+                // nobody listening is not worth reporting
+                let _ = self.socket.send(&buf);
+            }
+            Err(e) => error!("Failed to encode OSC message: {:?}", e),
+        }
+    }
+}
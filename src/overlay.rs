@@ -1,14 +1,22 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use glam::{vec2, vec3, Affine3A, Mat3A, Quat, Vec3, Vec3A};
 use log::info;
 use stereokit::{
     sys::color32, Color128, Material, Mesh, RenderLayer, SkDraw, StereoKitDraw,
-    StereoKitMultiThread, Tex, TextureFormat, TextureType, Vert,
+    StereoKitMultiThread, Tex, Vert,
 };
 
 use crate::{
+    config::{ColorSpace, CvdFilter, StereoMode},
+    desktop::frame,
+    gl::GlRenderer,
     interactions::{DummyInteractionHandler, InteractionHandler},
+    power,
+    scripting::{self, ScriptEvent},
     AppSession, AppState,
 };
 
@@ -24,6 +32,13 @@ pub const COLOR_FALLBACK: Color128 = Color128 {
     b: 1.,
     a: 1.,
 };
+// Brightens the overlay, for the screenshot flash
+pub const COLOR_FLASH: Color128 = Color128 {
+    r: 3.,
+    g: 3.,
+    b: 3.,
+    a: 1.,
+};
 pub const COLOR_TRANSPARENT: Color128 = Color128 {
     r: 0.,
     g: 0.,
@@ -49,14 +64,40 @@ pub struct OverlayData {
     pub backend: Box<dyn OverlayBackend>,
     pub primary_pointer: Option<usize>,
     pub gfx: Option<OverlayGraphics>,
+    // Restored from the previous session, used instead of the spawn point on first show
+    pub saved_transform: Option<Affine3A>,
+    pub flash_until: Option<Instant>,
+    // Sample from a mipmapped copy of the texture, for screens full of small text
+    pub mipmaps: bool,
+    // How to read the backend's texture, linear values need encoding for the panel shader
+    pub color_space: ColorSpace,
+    // Draw reference gradients over the content, see color_test in the config
+    pub color_test: bool,
+    // Color vision correction, see cvd_filter in the config
+    pub cvd_filter: CvdFilter,
+    // Shows each eye its half of stereo content, see outputs in the config
+    pub stereo: StereoMode,
+    // When the backend last rendered, for max_fps and the power saving frame limit
+    pub last_render: Option<Instant>,
+    // Comes back where it was last hidden instead of in front of the head, for screens
+    pub remember_transform: bool,
 }
 
 pub trait OverlayBackend: OverlayRenderer + InteractionHandler {}
 
 pub struct OverlayGraphics {
     pub tex: Tex,
+    // GL name of tex, the backend may import a dmabuf into it
+    pub handle: u32,
+    // Copy of tex that the material samples, if the overlay needs mipmaps, color conversion
+    // or the color test
+    pub out_tex: Option<Tex>,
+    // What out_tex's mip chain was last built from: filter, linear and color test
+    pub mips_for: Option<(CvdFilter, bool, bool)>,
     pub mesh: Mesh,
     pub mat: Material,
+    // what mat was made for
+    pub stereo: StereoMode,
 }
 
 pub trait OverlayRenderer {
@@ -64,6 +105,8 @@ pub trait OverlayRenderer {
     fn pause(&mut self, app: &mut AppState);
     fn resume(&mut self, app: &mut AppState);
     fn render(&mut self, sk: &SkDraw, tex: &Tex, app: &mut AppState);
+    // Hand any textures of your own back to app.tex_pool; init() is called again before reuse
+    fn release(&mut self, _app: &mut AppState) {}
 }
 
 impl OverlayData {
@@ -75,69 +118,120 @@ impl OverlayData {
         info!("{}: Show", &self.name);
 
         self.visible = true;
+        scripting::emit(ScriptEvent::OverlayShown(self.name.clone()));
 
         if self.gfx.is_none() {
-            let tex = sk.tex_gen_color(
-                COLOR_FALLBACK,
-                self.size.0,
-                self.size.1,
-                TextureType::IMAGE_NO_MIPS,
-                TextureFormat::RGBA32,
-            );
-
-            let mesh = sk.mesh_create();
-
-            let scr_w = self.size.0 as f32;
-            let scr_h = self.size.1 as f32;
+            self.create_gfx(sk, app);
+        } else {
+            self.backend.resume(app);
+        }
 
-            let half_w: f32;
-            let half_h: f32;
+        if let Some(transform) = self.saved_transform.take() {
+            self.transform = transform;
+        } else {
+            self.reset(app);
+        }
+    }
 
-            if scr_w >= scr_h {
-                half_w = 1.;
-                half_h = scr_h / scr_w;
-            } else {
-                half_w = scr_w / scr_h;
-                half_h = 1.;
-            }
+    // Sets up the textures, mesh and material, then has the backend draw into them
+    pub fn create_gfx(&mut self, sk: &SkDraw, app: &mut AppState) {
+        let tex = app
+            .tex_pool
+            .take(sk, &mut app.gl, self.size.0, self.size.1, false);
+        let handle = unsafe { sk.tex_get_surface(&tex) as usize as u32 };
 
-            self.interaction_transform = Affine3A::from_scale_rotation_translation(
-                vec3(0.5 / -half_w, 0.5 / -half_h, 0.),
-                Quat::IDENTITY,
-                vec3(0.5, 0.5, 0.),
-            );
+        let mesh = sk.mesh_create();
 
-            let norm = vec3(0., 0., -1.);
-            let col = color32::new_rgb(255, 255, 255);
+        let scr_w = self.size.0 as f32;
+        let scr_h = self.size.1 as f32;
 
-            let x0 = 0f32;
-            let x1 = 1f32;
-            let y0 = 0f32;
-            let y1 = 1f32;
+        let half_w: f32;
+        let half_h: f32;
 
-            #[rustfmt::skip]
-            let verts = vec![
-                Vert { pos: vec3(-half_w, -half_h, 0.), uv: vec2(x1, y1), norm, col },
-                Vert { pos: vec3(-half_w, half_h, 0.), uv: vec2(x1, y0), norm, col },
-                Vert { pos: vec3(half_w, -half_h, 0.), uv: vec2(x0, y1), norm, col },
-                Vert { pos: vec3(half_w, half_h, 0.), uv: vec2(x0, y0), norm, col },
-            ];
+        if scr_w >= scr_h {
+            half_w = 1.;
+            half_h = scr_h / scr_w;
+        } else {
+            half_w = scr_w / scr_h;
+            half_h = 1.;
+        }
 
-            let inds = vec![0, 3, 2, 3, 0, 1];
-            sk.mesh_set_verts(&mesh, &verts, true);
-            sk.mesh_set_inds(&mesh, &inds);
+        self.interaction_transform = Affine3A::from_scale_rotation_translation(
+            vec3(0.5 / -half_w, 0.5 / -half_h, 0.),
+            Quat::IDENTITY,
+            vec3(0.5, 0.5, 0.),
+        );
 
-            let mat = sk.material_create(&app.panel_shader);
-            sk.material_set_texture(&mat, "diffuse", &tex);
+        let norm = vec3(0., 0., -1.);
+        let col = color32::new_rgb(255, 255, 255);
+
+        let x0 = 0f32;
+        let x1 = 1f32;
+        let y0 = 0f32;
+        let y1 = 1f32;
+
+        #[rustfmt::skip]
+        let verts = vec![
+            Vert { pos: vec3(-half_w, -half_h, 0.), uv: vec2(x1, y1), norm, col },
+            Vert { pos: vec3(-half_w, half_h, 0.), uv: vec2(x1, y0), norm, col },
+            Vert { pos: vec3(half_w, -half_h, 0.), uv: vec2(x0, y1), norm, col },
+            Vert { pos: vec3(half_w, half_h, 0.), uv: vec2(x0, y0), norm, col },
+        ];
+
+        let inds = vec![0, 3, 2, 3, 0, 1];
+        sk.mesh_set_verts(&mesh, &verts, true);
+        sk.mesh_set_inds(&mesh, &inds);
+
+        let out_tex = self.needs_copy().then(|| {
+            app.tex_pool
+                .take(sk, &mut app.gl, self.size.0, self.size.1, self.mipmaps)
+        });
+
+        let mat = create_material(sk, app, self.stereo, out_tex.as_ref().unwrap_or(&tex));
+
+        self.gfx = Some(OverlayGraphics {
+            tex,
+            handle,
+            out_tex,
+            mips_for: None,
+            mat,
+            mesh,
+            stereo: self.stereo,
+        });
+
+        self.backend.init(sk, app);
+    }
 
-            self.gfx = Some(OverlayGraphics { tex, mat, mesh });
+    // Whether the material samples a processed copy of the backend's texture
+    fn needs_copy(&self) -> bool {
+        self.mipmaps
+            || self.linear_content()
+            || self.color_test
+            || self.cvd_filter != CvdFilter::None
+    }
 
-            self.backend.init(sk, app);
-        } else {
-            self.backend.resume(app);
+    // Auto goes by the buffers the capture last imported
+    fn linear_content(&self) -> bool {
+        match self.color_space {
+            ColorSpace::Srgb => false,
+            ColorSpace::Linear => true,
+            ColorSpace::Auto => self
+                .gfx
+                .as_ref()
+                .is_some_and(|g| frame::holds_linear(g.handle)),
         }
+    }
 
-        self.reset(app);
+    // Gives the textures back to the pool, the next show() sets them up again
+    pub fn release_gfx(&mut self, app: &mut AppState) {
+        self.backend.release(app);
+        if let Some(gfx) = self.gfx.take() {
+            let (w, h) = self.size;
+            app.tex_pool.give_captured(gfx.tex, gfx.handle, w, h);
+            if let Some(out_tex) = gfx.out_tex {
+                app.tex_pool.give(out_tex, w, h, self.mipmaps);
+            }
+        }
     }
 
     pub fn hide(&mut self, app: &mut AppState) {
@@ -147,7 +241,11 @@ impl OverlayData {
 
         info!("{}: Hide", &self.name);
 
+        if self.remember_transform && matches!(self.relative_to, RelativeTo::None) {
+            self.saved_transform = Some(self.transform);
+        }
         self.visible = false;
+        scripting::emit(ScriptEvent::OverlayHidden(self.name.clone()));
         self.backend.pause(app);
     }
 
@@ -162,13 +260,74 @@ impl OverlayData {
             return;
         }
 
+        let color = match self.flash_until {
+            Some(until) if until > Instant::now() => COLOR_FLASH,
+            _ => self.color,
+        };
+
+        // While saving power or over the overlay's own limit, keep drawing the last content
+        // but update it less often
+        let config = &app.session.config;
+        let own_interval = config
+            .max_fps
+            .get(&*self.name)
+            .filter(|fps| **fps > 0.)
+            .map(|fps| Duration::from_secs_f32(1. / fps));
+        let interval = match (power::frame_interval(config), own_interval) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        let throttled = match (interval, self.last_render) {
+            (Some(interval), Some(last)) => last.elapsed() < interval,
+            _ => false,
+        };
+
+        let needs_copy = self.needs_copy();
+        let linear = self.linear_content();
         if let Some(gfx) = self.gfx.as_mut() {
-            self.backend.render(sk, &gfx.tex, app);
+            // a config reload can turn a filter on while shown
+            if needs_copy && gfx.out_tex.is_none() {
+                let out_tex =
+                    app.tex_pool
+                        .take(sk, &mut app.gl, self.size.0, self.size.1, self.mipmaps);
+                sk.material_set_texture(&gfx.mat, "diffuse", &out_tex);
+                gfx.out_tex = Some(out_tex);
+                gfx.mips_for = None;
+            }
+            // a profile switch can change the stereo mode while shown
+            if gfx.stereo != self.stereo {
+                let diffuse = gfx.out_tex.as_ref().unwrap_or(&gfx.tex);
+                gfx.mat = create_material(sk, app, self.stereo, diffuse);
+                gfx.stereo = self.stereo;
+            }
+            if !throttled {
+                self.last_render = Some(Instant::now());
+                self.backend.render(sk, &gfx.tex, app);
+                if let Some(out_tex) = gfx.out_tex.as_ref() {
+                    let src = unsafe { sk.tex_get_surface(&gfx.tex) as usize as u32 };
+                    app.gl.begin_sk(sk, out_tex);
+                    app.gl.blit_daltonized(src, self.cvd_filter, linear);
+                    if self.color_test {
+                        draw_color_test(&mut app.gl, self.size.0 as _, self.size.1 as _);
+                    }
+                    app.gl.end();
+                    // rebuilding the chain is the costly part, skip it while the screen is still
+                    let mips_for = Some((self.cvd_filter, linear, self.color_test));
+                    if self.mipmaps
+                        && (frame::take_new_frame(gfx.handle) || gfx.mips_for != mips_for)
+                    {
+                        gfx.mips_for = mips_for;
+                        let dst = unsafe { sk.tex_get_surface(out_tex) as usize as u32 };
+                        app.gl
+                            .generate_mipmaps(dst, app.session.config.screen_anisotropy);
+                    }
+                }
+            }
             sk.mesh_draw(
                 &gfx.mesh,
                 &gfx.mat,
                 self.transform,
-                self.color,
+                color,
                 RenderLayer::LAYER0,
             );
         }
@@ -259,6 +418,9 @@ impl OverlayRenderer for SplitOverlayBackend {
     fn render(&mut self, sk: &SkDraw, tex: &Tex, app: &mut AppState) {
         self.renderer.render(sk, tex, app);
     }
+    fn release(&mut self, app: &mut AppState) {
+        self.renderer.release(app);
+    }
 }
 impl InteractionHandler for SplitOverlayBackend {
     fn on_left(&mut self, hand: usize) {
@@ -278,6 +440,9 @@ impl InteractionHandler for SplitOverlayBackend {
     ) {
         self.interaction.on_pointer(session, hit, pressed);
     }
+    fn on_nav(&mut self, session: &AppSession, hand: usize, nav: crate::interactions::Nav) {
+        self.interaction.on_nav(session, hand, nav);
+    }
 }
 
 impl Default for OverlayData {
@@ -300,6 +465,50 @@ impl Default for OverlayData {
             gfx: None,
             backend: Box::<SplitOverlayBackend>::default(),
             primary_pointer: None,
+            saved_transform: None,
+            flash_until: None,
+            mipmaps: false,
+            color_space: ColorSpace::Srgb,
+            color_test: false,
+            cvd_filter: CvdFilter::None,
+            stereo: StereoMode::None,
+            last_render: None,
+            remember_transform: false,
+        }
+    }
+}
+
+// Panel material showing diffuse. For stereo content the stereo shader gives each eye its half.
+fn create_material(sk: &SkDraw, app: &AppState, stereo: StereoMode, diffuse: &Tex) -> Material {
+    let mat = if stereo == StereoMode::None {
+        sk.material_create(&app.panel_shader)
+    } else {
+        let mat = sk.material_create(&app.panel_stereo_shader);
+        sk.material_set_vector2(&mat, "uv_scale", stereo.uv_scale());
+        sk.material_set_vector2(&mat, "uv_offset", stereo.right_eye_offset());
+        mat
+    };
+    sk.material_set_texture(&mat, "diffuse", diffuse);
+    mat
+}
+
+// Gray, red, green and blue ramps across the top of the texture, in sRGB steps
+fn draw_color_test(gl: &mut GlRenderer, width: f32, height: f32) {
+    const STEPS: usize = 32;
+    let row_h = (height / 16.).max(8.);
+    let step_w = width / STEPS as f32;
+    let channels = [Vec3::ONE, Vec3::X, Vec3::Y, Vec3::Z];
+    for (row, channel) in channels.iter().enumerate() {
+        for step in 0..STEPS {
+            let value = step as f32 / (STEPS - 1) as f32;
+            gl.draw_color(
+                *channel * value,
+                1.,
+                step as f32 * step_w,
+                row as f32 * row_h,
+                step_w.ceil(),
+                row_h,
+            );
         }
     }
 }
@@ -0,0 +1,326 @@
+use std::{
+    fs,
+    path::Path,
+    process::Child,
+    sync::{Arc, Mutex},
+};
+
+use glam::Vec3;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::report_error,
+    config_io,
+    gui::{color_parse, is_html_color, Canvas, Control},
+    input::INPUT,
+    keyboard::{key_events_for_macro, ExecCommand},
+    overlay::{OverlayData, RelativeTo},
+    AppSession, TASKS,
+};
+
+// Panels are drawn at the same density as the built-in ones: 400 pixels to 0.3 meters
+const PIXELS_PER_METER: f32 = 400. / 0.3;
+const MAX_SIZE: f32 = 4096.;
+
+fn def_panel_width() -> f32 {
+    400.
+}
+
+fn def_panel_height() -> f32 {
+    300.
+}
+
+// A custom overlay, described by a file in panels/ under the config root
+#[derive(Deserialize, Serialize, Clone)]
+pub struct PanelConfig {
+    pub name: String,
+    #[serde(default = "def_panel_width")]
+    pub width: f32,
+    #[serde(default = "def_panel_height")]
+    pub height: f32,
+    // shown at startup
+    #[serde(default)]
+    pub visible: bool,
+    // put away and brought back along with the screens
+    #[serde(default)]
+    pub show_hide: bool,
+    // panel background, the theme's if not set
+    #[serde(default)]
+    pub color: Option<String>,
+    // fontconfig family for all the text, the configured font if not set
+    #[serde(default)]
+    pub font: Option<String>,
+    #[serde(default)]
+    pub controls: Vec<PanelControl>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PanelControlKind {
+    Panel,
+    Label,
+    LabelCentered,
+    Button,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct PanelControl {
+    #[serde(rename = "type")]
+    pub kind: PanelControlKind,
+    // x, y, width, height in pixels, from the top left
+    pub rect: [f32; 4],
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub tooltip: Option<String>,
+    // background and text, the theme's if not set
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub text_color: Option<String>,
+    #[serde(default)]
+    pub font_size: Option<isize>,
+    // the panel's if not set
+    #[serde(default)]
+    pub font: Option<String>,
+    // for buttons, run when pressed
+    #[serde(default)]
+    pub action: Option<PanelAction>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PanelAction {
+    // same as keyboard exec_commands
+    Exec(ExecCommand),
+    // same as keyboard macros: KEY, KEY DOWN or KEY UP
+    SendKeys(Vec<String>),
+    ShowOverlay(String),
+    HideOverlay(String),
+    ToggleOverlay(String),
+}
+
+impl PanelConfig {
+    fn post_load(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("name can't be empty".into());
+        }
+        let valid_size = 1.0..=MAX_SIZE;
+        if !valid_size.contains(&self.width) || !valid_size.contains(&self.height) {
+            return Err(format!(
+                "width and height need to be between 1 and {}",
+                MAX_SIZE
+            ));
+        }
+        if let Some(color) = self.color.as_ref().filter(|c| !is_html_color(c)) {
+            return Err(format!("color {} needs to be like #rrggbb", color));
+        }
+        for (i, control) in self.controls.iter().enumerate() {
+            let colors = [control.color.as_ref(), control.text_color.as_ref()];
+            if let Some(color) = colors.into_iter().flatten().find(|c| !is_html_color(c)) {
+                return Err(format!(
+                    "controls[{}]: color {} needs to be like #rrggbb",
+                    i, color
+                ));
+            }
+            match &control.action {
+                Some(_) if control.kind != PanelControlKind::Button => {
+                    return Err(format!("controls[{}]: only buttons can have actions", i));
+                }
+                Some(PanelAction::Exec(exec)) if exec.is_empty() => {
+                    return Err(format!("controls[{}]: exec has no command", i));
+                }
+                Some(PanelAction::SendKeys(verbs)) if key_events_for_macro(verbs).is_empty() => {
+                    return Err(format!("controls[{}]: send_keys has no valid keys", i));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// Loaded once at startup, kept to rebuild the panels with a new theme
+static PANELS: Mutex<Vec<PanelConfig>> = Mutex::new(Vec::new());
+
+// Reads panels/*.yaml from the config root; broken files are reported and skipped, as are
+// panels named like one of the existing overlays
+pub fn load_panels(session: &AppSession, existing: &[OverlayData]) -> Vec<OverlayData> {
+    let dir = config_io::get_config_file_path("panels");
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return vec![];
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|ext| ext == "yaml" || ext == "yml")
+        })
+        .collect();
+    paths.sort();
+
+    let mut configs: Vec<PanelConfig> = vec![];
+    for path in paths {
+        match load_panel(&path) {
+            Ok(config) if configs.iter().any(|c| c.name == config.name) => report_error(&format!(
+                "{}: there's already a panel named {}",
+                path.to_string_lossy(),
+                config.name
+            )),
+            Ok(config) if existing.iter().any(|o| *o.name == *config.name) => {
+                report_error(&format!(
+                    "{}: {} is already the name of a screen or built-in overlay",
+                    path.to_string_lossy(),
+                    config.name
+                ))
+            }
+            Ok(config) => {
+                info!(
+                    "Loaded panel {} from {}",
+                    config.name,
+                    path.to_string_lossy()
+                );
+                configs.push(config);
+            }
+            Err(e) => report_error(&format!("{}: {}", path.to_string_lossy(), e)),
+        }
+    }
+
+    let overlays = configs
+        .iter()
+        .map(|config| create_panel(session, config))
+        .collect();
+    if let Ok(mut panels) = PANELS.lock() {
+        *panels = configs;
+    }
+    overlays
+}
+
+fn load_panel(path: &Path) -> Result<PanelConfig, String> {
+    let yaml = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let config: PanelConfig = serde_yaml::from_str(&yaml).map_err(|e| e.to_string())?;
+    config.post_load()?;
+    Ok(config)
+}
+
+// The custom panel by that name, built anew
+pub fn rebuild_panel(session: &AppSession, name: &str) -> Option<OverlayData> {
+    let panels = PANELS.lock().ok()?;
+    let config = panels.iter().find(|c| c.name == name)?;
+    Some(create_panel(session, config))
+}
+
+#[derive(Default)]
+struct PanelData {
+    processes: Vec<Child>,
+}
+
+fn create_panel(session: &AppSession, config: &PanelConfig) -> OverlayData {
+    let mut canvas: Canvas<PanelData, PanelAction> = Canvas::new(
+        config.width as _,
+        config.height as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        PanelData::default(),
+    );
+    let theme = &session.theme;
+
+    let bg = config.color.as_deref().unwrap_or(&theme.watch_bg_color);
+    canvas.bg_color = color_parse(bg);
+    canvas.panel(0., 0., config.width, config.height);
+
+    for control in config.controls.iter() {
+        let (bg, fg) = match control.kind {
+            PanelControlKind::Button => (&theme.button_color, &theme.button_text_color),
+            _ => (&theme.watch_bg_color, &theme.text_color),
+        };
+        canvas.bg_color = color_parse(control.color.as_deref().unwrap_or(bg));
+        canvas.fg_color = color_parse(control.text_color.as_deref().unwrap_or(fg));
+        canvas.font_size = control.font_size.unwrap_or(theme.font_size);
+        canvas.font = control
+            .font
+            .as_deref()
+            .or(config.font.as_deref())
+            .map(Into::into);
+
+        let [x, y, w, h] = control.rect;
+        let text: Arc<str> = control.text.as_str().into();
+        let idx = match control.kind {
+            PanelControlKind::Panel => canvas.panel(x, y, w, h),
+            PanelControlKind::Label => canvas.label(x, y, w, h, text),
+            PanelControlKind::LabelCentered => canvas.label_centered(x, y, w, h, text),
+            PanelControlKind::Button => canvas.button(x, y, w, h, text),
+        };
+        let c = &mut canvas.controls[idx];
+        if let Some(tooltip) = control.tooltip.as_ref() {
+            c.set_tooltip(tooltip);
+        }
+        if let Some(action) = control.action.clone() {
+            c.state = Some(action);
+            c.on_press = Some(button_press);
+        }
+    }
+
+    OverlayData {
+        name: config.name.as_str().into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: config.width / PIXELS_PER_METER * session.config.ui_scale,
+        want_visible: config.visible,
+        show_hide: config.show_hide,
+        grabbable: true,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: Vec3::NEG_Z * 0.6,
+        ..Default::default()
+    }
+}
+
+fn button_press(
+    control: &mut Control<PanelData, PanelAction>,
+    session: &AppSession,
+    data: &mut PanelData,
+) {
+    let Some(action) = control.state.as_ref() else {
+        return;
+    };
+    match action {
+        PanelAction::Exec(exec) => {
+            // Reap previous processes
+            data.processes
+                .retain_mut(|child| !matches!(child.try_wait(), Ok(Some(_))));
+
+            let Some((program, mut command)) = exec.command(&session.config.terminal_command)
+            else {
+                return;
+            };
+            match command.spawn() {
+                Ok(child) => data.processes.push(child),
+                Err(e) => error!("Failed to run {}: {}", program, e),
+            }
+        }
+        PanelAction::SendKeys(verbs) => {
+            if let Ok(input) = INPUT.lock() {
+                for (vk, press) in key_events_for_macro(verbs) {
+                    input.send_key(vk as _, press);
+                }
+            }
+        }
+        PanelAction::ShowOverlay(name) => set_visible(name.clone(), Some(true)),
+        PanelAction::HideOverlay(name) => set_visible(name.clone(), Some(false)),
+        PanelAction::ToggleOverlay(name) => set_visible(name.clone(), None),
+    }
+}
+
+// None toggles
+fn set_visible(name: String, visible: Option<bool>) {
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(move |_sk, _app, o| {
+            for overlay in o.iter_mut() {
+                if *overlay.name == *name {
+                    overlay.want_visible = visible.unwrap_or(!overlay.want_visible);
+                }
+            }
+        }));
+    }
+}
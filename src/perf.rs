@@ -0,0 +1,345 @@
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use glam::vec3;
+use gles31::{
+    glBeginQuery, glDeleteQueries, glEndQuery, glGenQueries, glGetError, glGetQueryObjectuiv,
+    GL_NO_ERROR, GL_QUERY_RESULT, GL_QUERY_RESULT_AVAILABLE,
+};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+
+use crate::{
+    gl::check::{self, check_gl},
+    gui::{color_parse, Canvas, TextOverflow},
+    latency,
+    overlay::{OverlayData, RelativeTo},
+    AppSession,
+};
+
+// EXT_disjoint_timer_query
+const GL_TIME_ELAPSED_EXT: u32 = 0x88BF;
+
+// How often the averages are logged and shown on the HUD
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+const HUD_INTERVAL: Duration = Duration::from_secs(1);
+
+const WIDTH: f32 = 300.;
+const LINE_HEIGHT: f32 = 28.;
+
+#[derive(Clone, Copy)]
+pub enum Section {
+    // texture uploads of captured frames
+    Capture = 0,
+    // canvas text and control redraws
+    Canvas = 1,
+    // the whole overlay loop, CPU only since it contains the others
+    Overlays = 2,
+}
+
+const SECTION_NAMES: [&str; 3] = ["capture", "canvas", "overlays"];
+
+// the sections, then the GL error count
+const PERF_LINES: usize = SECTION_NAMES.len() + 1;
+
+#[derive(Default)]
+struct SectionStats {
+    cpu: Duration,
+    gpu_ns: u64,
+    gpu_samples: u32,
+    // queries that were ended but whose result isn't in yet
+    pending: Vec<u32>,
+    free: Vec<u32>,
+}
+
+struct PerfStats {
+    sections: [SectionStats; 3],
+    gpu_supported: bool,
+    frames: u32,
+    window_start: Instant,
+    hud_start: Instant,
+    hud_frames: u32,
+    hud_cpu: [Duration; 3],
+    hud_gpu: [(u64, u32); 3],
+    hud_text: Arc<str>,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static PERF: Lazy<Mutex<PerfStats>> = Lazy::new(|| {
+    Mutex::new(PerfStats {
+        sections: Default::default(),
+        gpu_supported: true,
+        frames: 0,
+        window_start: Instant::now(),
+        hud_start: Instant::now(),
+        hud_frames: 0,
+        hud_cpu: Default::default(),
+        hud_gpu: Default::default(),
+        hud_text: "".into(),
+    })
+});
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// Measures until dropped. GPU time is only measured for sections that don't nest.
+// Must be used on the render thread.
+pub struct SectionTimer {
+    section: Section,
+    start: Instant,
+    query: Option<u32>,
+}
+
+pub fn time(section: Section) -> Option<SectionTimer> {
+    if !is_enabled() {
+        return None;
+    }
+
+    let mut query = None;
+    if !matches!(section, Section::Overlays) {
+        if let Ok(mut perf) = PERF.lock() {
+            query = perf.begin_query(section);
+        }
+    }
+
+    Some(SectionTimer {
+        section,
+        start: Instant::now(),
+        query,
+    })
+}
+
+impl Drop for SectionTimer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let Ok(mut perf) = PERF.lock() else {
+            return;
+        };
+        let stats = &mut perf.sections[self.section as usize];
+        stats.cpu += elapsed;
+        if let Some(query) = self.query {
+            unsafe {
+                glEndQuery(GL_TIME_ELAPSED_EXT);
+                check_gl();
+            }
+            stats.pending.push(query);
+        }
+    }
+}
+
+impl PerfStats {
+    fn begin_query(&mut self, section: Section) -> Option<u32> {
+        if !self.gpu_supported {
+            return None;
+        }
+        let stats = &mut self.sections[section as usize];
+        let query = stats.free.pop().unwrap_or_else(|| {
+            let mut query = 0u32;
+            unsafe {
+                glGenQueries(1, &mut query);
+                check_gl();
+            }
+            query
+        });
+
+        unsafe {
+            glBeginQuery(GL_TIME_ELAPSED_EXT, query);
+            if glGetError() != GL_NO_ERROR {
+                warn!("GPU timer queries are not supported, only measuring CPU time");
+                glDeleteQueries(1, &query);
+                self.gpu_supported = false;
+                return None;
+            }
+        }
+        Some(query)
+    }
+
+    // Collects the results that are ready, without waiting for the rest
+    fn collect_queries(&mut self) {
+        for stats in self.sections.iter_mut() {
+            let mut i = 0;
+            while i < stats.pending.len() {
+                let query = stats.pending[i];
+                let mut available = 0u32;
+                unsafe {
+                    glGetQueryObjectuiv(query, GL_QUERY_RESULT_AVAILABLE, &mut available);
+                    check_gl();
+                }
+                if available == 0 {
+                    i += 1;
+                    continue;
+                }
+
+                let mut ns = 0u32;
+                unsafe {
+                    glGetQueryObjectuiv(query, GL_QUERY_RESULT, &mut ns);
+                    check_gl();
+                }
+                stats.gpu_ns += ns as u64;
+                stats.gpu_samples += 1;
+                stats.free.push(stats.pending.swap_remove(i));
+            }
+        }
+    }
+
+    fn summary(cpu: &[Duration; 3], gpu: &[(u64, u32); 3], frames: u32) -> String {
+        let frames = frames.max(1);
+        let mut text = String::new();
+        for (i, name) in SECTION_NAMES.iter().enumerate() {
+            let cpu_ms = cpu[i].as_secs_f64() * 1000. / frames as f64;
+            let _ = write!(text, "{}: cpu {:.2}ms", name, cpu_ms);
+            let (gpu_ns, samples) = gpu[i];
+            if samples > 0 {
+                let _ = write!(text, " gpu {:.2}ms", gpu_ns as f64 / 1e6 / frames as f64);
+            }
+            text.push('\n');
+        }
+        let _ = writeln!(text, "gl errors: {}", check::error_count());
+        text
+    }
+}
+
+// Once per frame: gathers finished GPU timings, logs the averages and refreshes the HUD text
+pub fn end_frame() {
+    if !is_enabled() {
+        return;
+    }
+    let Ok(mut perf) = PERF.lock() else {
+        return;
+    };
+    perf.collect_queries();
+    perf.frames += 1;
+    perf.hud_frames += 1;
+
+    if perf.hud_start.elapsed() > HUD_INTERVAL {
+        let mut cpu = [Duration::ZERO; 3];
+        let mut gpu = [(0, 0); 3];
+        for (i, stats) in perf.sections.iter().enumerate() {
+            cpu[i] = stats.cpu - perf.hud_cpu[i];
+            gpu[i] = (
+                stats.gpu_ns - perf.hud_gpu[i].0,
+                stats.gpu_samples - perf.hud_gpu[i].1,
+            );
+        }
+        let text = PerfStats::summary(&cpu, &gpu, perf.hud_frames);
+        perf.hud_text = text.trim_end().into();
+        for i in 0..perf.sections.len() {
+            perf.hud_cpu[i] = perf.sections[i].cpu;
+            perf.hud_gpu[i] = (perf.sections[i].gpu_ns, perf.sections[i].gpu_samples);
+        }
+        perf.hud_frames = 0;
+        perf.hud_start = Instant::now();
+    }
+
+    if perf.window_start.elapsed() > REPORT_INTERVAL {
+        let mut cpu = [Duration::ZERO; 3];
+        let mut gpu = [(0, 0); 3];
+        for (i, stats) in perf.sections.iter_mut().enumerate() {
+            cpu[i] = std::mem::take(&mut stats.cpu);
+            gpu[i] = (
+                std::mem::take(&mut stats.gpu_ns),
+                std::mem::take(&mut stats.gpu_samples),
+            );
+        }
+        let text = PerfStats::summary(&cpu, &gpu, perf.frames);
+        info!(
+            "Per frame, over {} frames: {}",
+            perf.frames,
+            text.trim_end().replace('\n', ", ")
+        );
+
+        perf.frames = 0;
+        perf.window_start = Instant::now();
+        perf.hud_cpu = Default::default();
+        perf.hud_gpu = Default::default();
+    }
+}
+
+// The queries were lost with the GL context, they're made anew as needed
+pub fn context_lost() {
+    let Ok(mut perf) = PERF.lock() else {
+        return;
+    };
+    for stats in perf.sections.iter_mut() {
+        stats.pending.clear();
+        stats.free.clear();
+    }
+}
+
+fn hud_text() -> Arc<str> {
+    PERF.lock()
+        .map(|perf| perf.hud_text.clone())
+        .unwrap_or_else(|_| "".into())
+}
+
+// Small panel at the edge of view with the averages of the last second, and the click
+// latency percentiles when latency_stats is on
+pub fn create_perf_hud(session: &AppSession) -> OverlayData {
+    // lines of the perf text come first, then those of the latency text
+    let mut lines = vec![];
+    if is_enabled() {
+        lines.extend(0..PERF_LINES);
+    }
+    if latency::is_enabled() {
+        lines.extend(PERF_LINES..PERF_LINES + 2);
+    }
+    let height = LINE_HEIGHT * (lines.len() as f32 + 1.) - 12.;
+
+    let mut canvas: Canvas<(), usize> = Canvas::new(
+        WIDTH as _,
+        height as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        (),
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, height);
+
+    canvas.font_size = 14;
+    canvas.fg_color = color_parse(&theme.text_color);
+    for (i, line) in lines.into_iter().enumerate() {
+        let y = LINE_HEIGHT + i as f32 * LINE_HEIGHT;
+        let label = canvas.label(8., y, WIDTH - 16., 24., "".into());
+        canvas.controls[label].set_overflow(TextOverflow::Ellipsis);
+        canvas.controls[label].state = Some(line);
+        canvas.controls[label].on_update = Some(|control, _data| {
+            let Some(line) = control.state else {
+                return;
+            };
+            let text = if line < PERF_LINES {
+                hud_text().lines().nth(line).unwrap_or("").to_string()
+            } else {
+                let text = latency::hud_text();
+                let line = line - PERF_LINES;
+                text.lines().nth(line).unwrap_or("").to_string()
+            };
+            control.set_text(&text);
+        });
+    }
+
+    OverlayData {
+        name: "Perf".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.15 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        want_visible: true,
+        relative_to: RelativeTo::Head,
+        spawn_point: vec3(-0.2, 0.15, -0.6),
+        grabbable: false,
+        ..Default::default()
+    }
+}
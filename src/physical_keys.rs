@@ -0,0 +1,102 @@
+use std::{
+    fs::{self, File},
+    mem::transmute,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use input_linux::{EvdevHandle, EventKind, Key};
+use log::info;
+use once_cell::sync::Lazy;
+
+use crate::keyboard::{KeyModifier, KEYS_TO_MODS, LOCKS};
+
+// Keyboards may be plugged in at any time
+const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+// Our own uinput device shows up among the others
+const OWN_DEVICE_PREFIX: &[u8] = b"WlxOverlay";
+
+struct PhysicalKeyboards {
+    devices: Vec<(PathBuf, EvdevHandle<File>)>,
+    next_scan: Instant,
+}
+
+static KEYBOARDS: Lazy<Mutex<PhysicalKeyboards>> = Lazy::new(|| {
+    Mutex::new(PhysicalKeyboards {
+        devices: Vec::new(),
+        next_scan: Instant::now(),
+    })
+});
+
+// Shift, Ctrl, Alt and Super as currently held down on any physical keyboard
+pub fn held_modifiers() -> KeyModifier {
+    let Ok(mut keyboards) = KEYBOARDS.lock() else {
+        return 0;
+    };
+    if keyboards.next_scan < Instant::now() {
+        keyboards.next_scan = Instant::now() + RESCAN_INTERVAL;
+        keyboards.scan();
+    }
+
+    let mut modifiers = 0;
+    keyboards.devices.retain(|(path, handle)| {
+        let Ok(state) = handle.key_state() else {
+            info!("Keyboard {} is gone", path.to_string_lossy());
+            return false;
+        };
+        for (vk, modifier) in KEYS_TO_MODS.iter() {
+            if modifier & LOCKS != 0 {
+                continue;
+            }
+            let key: Key = unsafe { transmute(*vk as u16 - 8) };
+            if state.get(key) {
+                modifiers |= modifier;
+            }
+        }
+        true
+    });
+    modifiers
+}
+
+impl PhysicalKeyboards {
+    fn scan(&mut self) {
+        let Ok(entries) = fs::read_dir("/dev/input") else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_event_node = path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with("event"));
+            if !is_event_node || self.devices.iter().any(|(p, _)| *p == path) {
+                continue;
+            }
+            // usually a permission problem, same as with uinput
+            let Ok(file) = File::open(&path) else {
+                continue;
+            };
+            let handle = EvdevHandle::new(file);
+            if !is_keyboard(&handle) {
+                continue;
+            }
+            info!("Mirroring modifiers of keyboard {}", path.to_string_lossy());
+            self.devices.push((path, handle));
+        }
+    }
+}
+
+// Has letter keys, and isn't us
+fn is_keyboard(handle: &EvdevHandle<File>) -> bool {
+    let is_own = handle
+        .device_name()
+        .is_ok_and(|name| name.starts_with(OWN_DEVICE_PREFIX));
+    let has_keys = handle
+        .event_bits()
+        .is_ok_and(|bits| bits.get(EventKind::Key));
+    let has_letters = handle
+        .key_bits()
+        .is_ok_and(|bits| bits.get(Key::A) && bits.get(Key::Z));
+    !is_own && has_keys && has_letters
+}
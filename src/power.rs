@@ -0,0 +1,109 @@
+use std::{
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::info;
+use once_cell::sync::Lazy;
+
+use crate::{
+    config::GeneralConfig,
+    toast::{self, NotifyLevel},
+};
+
+// How often /sys/class/power_supply is read
+const BATTERY_INTERVAL: Duration = Duration::from_secs(10);
+
+struct PowerState {
+    last_interaction: Instant,
+    on_battery: bool,
+    battery_checked: Option<Instant>,
+}
+
+static POWER: Lazy<Mutex<PowerState>> = Lazy::new(|| {
+    Mutex::new(PowerState {
+        last_interaction: Instant::now(),
+        on_battery: false,
+        battery_checked: None,
+    })
+});
+
+static SAVING: AtomicBool = AtomicBool::new(false);
+
+// Called whenever a pointer hovers or clicks an overlay
+pub fn mark_interaction() {
+    if let Ok(mut power) = POWER.lock() {
+        power.last_interaction = Instant::now();
+    }
+}
+
+pub fn is_saving() -> bool {
+    SAVING.load(Ordering::Relaxed)
+}
+
+// Minimum time between two renders of an overlay's content, while saving power
+pub fn frame_interval(config: &GeneralConfig) -> Option<Duration> {
+    is_saving().then(|| Duration::from_secs_f32(1. / config.power_save_fps))
+}
+
+// Re-checks the idle time and the power supply, once per frame
+pub fn update(config: &GeneralConfig) {
+    let Ok(mut power) = POWER.lock() else {
+        return;
+    };
+    if !config.power_save {
+        SAVING.store(false, Ordering::Relaxed);
+        return;
+    }
+
+    if config.power_save_on_battery
+        && power
+            .battery_checked
+            .map_or(true, |t| t.elapsed() > BATTERY_INTERVAL)
+    {
+        let on_battery = on_battery();
+        if on_battery && !power.on_battery {
+            toast::notify(
+                NotifyLevel::Warn,
+                "Running on battery, captures are slowed down to save power",
+            );
+        }
+        power.on_battery = on_battery;
+        power.battery_checked = Some(Instant::now());
+    }
+
+    let idle = power.last_interaction.elapsed().as_secs() >= config.power_save_idle_secs as u64;
+    let saving = idle || (config.power_save_on_battery && power.on_battery);
+    if SAVING.swap(saving, Ordering::Relaxed) != saving {
+        info!(
+            "Power saving {}",
+            if saving { "enabled" } else { "disabled" }
+        );
+    }
+}
+
+// True if there is a mains supply and none of them is online
+fn on_battery() -> bool {
+    let Ok(entries) = fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let mut any_mains = false;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(kind) = fs::read_to_string(path.join("type")) else {
+            continue;
+        };
+        if kind.trim() != "Mains" {
+            continue;
+        }
+        any_mains = true;
+        if fs::read_to_string(path.join("online")).is_ok_and(|o| o.trim() == "1") {
+            return false;
+        }
+    }
+    any_mains
+}
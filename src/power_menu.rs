@@ -0,0 +1,160 @@
+use std::{
+    process::{Child, Command},
+    time::{Duration, Instant},
+};
+
+use glam::Vec3;
+use log::info;
+
+use crate::{
+    config::{report_error, PowerMenuConfig},
+    gui::{color_parse, Canvas, Control},
+    overlay::{OverlayData, RelativeTo},
+    AppSession,
+};
+
+const WIDTH: f32 = 400.;
+const HEIGHT: f32 = 150.;
+
+const LABELS: [&str; 4] = ["Lock", "Suspend", "Log out", "Shut down"];
+
+// A hold that went on this much longer than needed without going off was cut short, like by
+// the panel being hidden, and isn't run when it comes back
+const STALE_HOLD: Duration = Duration::from_millis(500);
+
+struct PowerData {
+    config: PowerMenuConfig,
+    // reaped as they exit, the session may outlive suspend and lock
+    children: Vec<Child>,
+}
+
+impl PowerData {
+    fn command(&self, action: usize) -> &[String] {
+        match action {
+            0 => &self.config.lock,
+            1 => &self.config.suspend,
+            2 => &self.config.logout,
+            _ => &self.config.shutdown,
+        }
+    }
+
+    fn run(&mut self, action: usize) {
+        let Some((program, args)) = self.command(action).split_first() else {
+            report_error(&format!("No command set for {}", LABELS[action]));
+            return;
+        };
+        info!("Power menu: {} {:?}", program, args);
+        match Command::new(program).args(args).spawn() {
+            Ok(child) => self.children.push(child),
+            Err(e) => report_error(&format!("Failed to run {}: {}", program, e)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct PowerButton {
+    action: usize,
+    pressed_at: Option<Instant>,
+    // ran during this hold, so letting go doesn't need to do anything
+    done: bool,
+}
+
+// Lock, suspend, log out and shut down, each only once held for power_menu.hold_ms
+pub fn create_power_menu(session: &AppSession) -> OverlayData {
+    let data = PowerData {
+        config: session.config.power_menu.clone(),
+        children: vec![],
+    };
+    let mut canvas: Canvas<PowerData, PowerButton> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        data,
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.font_size = theme.font_size;
+    canvas.fg_color = color_parse(&theme.text_color);
+    canvas.label(
+        10.,
+        30.,
+        WIDTH - 20.,
+        30.,
+        "Hold a button to confirm".into(),
+    );
+
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+    for (action, label) in LABELS.iter().enumerate() {
+        let x = 10. + (action % 2) as f32 * (WIDTH - 10.) / 2.;
+        let y = 45. + (action / 2) as f32 * 50.;
+        let button = canvas.button(x, y, (WIDTH - 30.) / 2., 40., (*label).into());
+        let button = &mut canvas.controls[button];
+        button.state = Some(PowerButton {
+            action,
+            ..Default::default()
+        });
+        button.on_press = Some(|control, _session, _data| {
+            if let Some(state) = control.state.as_mut() {
+                state.pressed_at = Some(Instant::now());
+                state.done = false;
+            }
+        });
+        button.on_release = Some(|control, _data| {
+            if let Some(state) = control.state.as_mut() {
+                state.pressed_at = None;
+                let label = LABELS[state.action];
+                control.set_text(label);
+            }
+        });
+        button.on_update = Some(hold_update);
+    }
+
+    OverlayData {
+        name: "Power".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.3 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: Vec3::NEG_Z * 0.6,
+        ..Default::default()
+    }
+}
+
+// Counts down on the button while it's held, then runs its command once
+fn hold_update(control: &mut Control<PowerData, PowerButton>, data: &mut PowerData) {
+    data.children
+        .retain_mut(|child| !matches!(child.try_wait(), Ok(Some(_))));
+
+    let Some(state) = control.state.as_mut() else {
+        return;
+    };
+    let Some(pressed_at) = state.pressed_at else {
+        return;
+    };
+    if state.done {
+        return;
+    }
+    let hold = Duration::from_millis(data.config.hold_ms as u64);
+    let held = pressed_at.elapsed();
+    if held > hold + STALE_HOLD {
+        state.pressed_at = None;
+        let label = LABELS[state.action];
+        control.set_text(label);
+        return;
+    }
+    if held < hold {
+        let left = (hold - held).as_secs_f32();
+        let text = format!("{} in {:.1}", LABELS[state.action], left);
+        control.set_text(&text);
+        return;
+    }
+    state.done = true;
+    let action = state.action;
+    control.set_text(LABELS[action]);
+    data.run(action);
+}
@@ -0,0 +1,167 @@
+use std::f32::consts::PI;
+
+use glam::Vec3;
+
+use crate::{
+    gui::{color_parse, Canvas, Control},
+    overlay::{OverlayData, RelativeTo},
+    AppSession, AppState, TASKS,
+};
+
+const SIZE: f32 = 400.;
+const RING_RADIUS: f32 = 150.;
+const BUTTON_W: f32 = 96.;
+const BUTTON_H: f32 = 60.;
+
+// Distance in front of the controller where the menu appears
+pub const RADIAL_DISTANCE: f32 = 0.3;
+
+#[derive(Clone, Copy)]
+enum RadialAction {
+    Screens,
+    Keyboard,
+    Recenter,
+    Lock,
+    Taskbar,
+    Log,
+    History,
+    Magnifier,
+    Close,
+}
+
+const RING_ACTIONS: [(RadialAction, &str); 8] = [
+    (RadialAction::Screens, "Screens"),
+    (RadialAction::Keyboard, "Kbd"),
+    (RadialAction::Recenter, "Recenter"),
+    (RadialAction::Lock, "Lock"),
+    (RadialAction::Taskbar, "Windows"),
+    (RadialAction::Log, "Log"),
+    (RadialAction::History, "Notifs"),
+    (RadialAction::Magnifier, "Zoom"),
+];
+
+pub fn create_radial_menu(session: &AppSession) -> OverlayData {
+    let mut canvas = Canvas::new(
+        SIZE as _,
+        SIZE as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        (),
+    );
+    let theme = &session.theme;
+
+    canvas.font_size = theme.font_size;
+    canvas.fg_color = color_parse(&theme.button_text_color);
+    canvas.bg_color = color_parse(&theme.button_color);
+
+    for (i, (action, label)) in RING_ACTIONS.iter().enumerate() {
+        let angle = i as f32 * 2. * PI / RING_ACTIONS.len() as f32 - PI / 2.;
+        let x = SIZE / 2. + RING_RADIUS * angle.cos() - BUTTON_W / 2.;
+        let y = SIZE / 2. + RING_RADIUS * angle.sin() - BUTTON_H / 2.;
+        let idx = canvas.button(x, y, BUTTON_W, BUTTON_H, (*label).into());
+        canvas.controls[idx].state = Some(*action);
+        canvas.controls[idx].on_release = Some(radial_release);
+    }
+
+    canvas.bg_color = color_parse(&theme.keyboard_button_color);
+    let half = BUTTON_H / 2.;
+    let idx = canvas.button(
+        SIZE / 2. - half,
+        SIZE / 2. - half,
+        BUTTON_H,
+        BUTTON_H,
+        "X".into(),
+    );
+    canvas.controls[idx].state = Some(RadialAction::Close);
+    canvas.controls[idx].on_release = Some(radial_release);
+
+    OverlayData {
+        name: "Radial".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.25 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: Vec3::NEG_Z * RADIAL_DISTANCE,
+        ..Default::default()
+    }
+}
+
+fn radial_release(control: &mut Control<(), RadialAction>, _data: &mut ()) {
+    let Some(action) = control.state else {
+        return;
+    };
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(move |_sk, app, o| {
+            action.run(app, o);
+            for overlay in o.iter_mut() {
+                if &*overlay.name == "Radial" {
+                    overlay.want_visible = false;
+                }
+            }
+        }));
+    }
+}
+
+impl RadialAction {
+    fn run(self, app: &mut AppState, overlays: &mut [OverlayData]) {
+        match self {
+            RadialAction::Screens => {
+                let any_visible = app.screens.iter().any(|(i, _)| overlays[*i].want_visible);
+                for (i, _) in app.screens.iter() {
+                    overlays[*i].want_visible = !any_visible;
+                }
+            }
+            RadialAction::Keyboard
+            | RadialAction::Taskbar
+            | RadialAction::Log
+            | RadialAction::History
+            | RadialAction::Magnifier => {
+                let name = match self {
+                    RadialAction::Keyboard => "Kbd",
+                    RadialAction::Log => "Log",
+                    RadialAction::History => "History",
+                    RadialAction::Magnifier => "Magnifier",
+                    _ => "Taskbar",
+                };
+                for overlay in overlays.iter_mut() {
+                    if *overlay.name == *name {
+                        overlay.want_visible = !overlay.want_visible;
+                    }
+                }
+            }
+            RadialAction::Recenter => recenter_overlays(app, overlays),
+            RadialAction::Lock => {
+                for overlay in overlays.iter_mut() {
+                    if overlay.show_hide {
+                        overlay.grabbable = !overlay.grabbable;
+                    }
+                }
+            }
+            RadialAction::Close => {}
+        }
+    }
+}
+
+// Everything visible back in front of the head, and the watch back on its wrist
+pub fn reset_all_overlays(app: &mut AppState, overlays: &mut [OverlayData]) {
+    let session = &app.session;
+    let (hand, point, rotation) = (session.watch_hand, session.watch_pos, session.watch_rot);
+    for overlay in overlays.iter_mut() {
+        if &*overlay.name == "Watch" {
+            overlay.relative_to = RelativeTo::Hand(hand);
+            overlay.spawn_point = point;
+            overlay.spawn_rotation = rotation;
+        } else if overlay.visible && matches!(overlay.relative_to, RelativeTo::None) {
+            overlay.reset(app);
+        }
+    }
+}
+
+// Brings the visible screens and keyboard back in front of the head
+pub fn recenter_overlays(app: &mut AppState, overlays: &mut [OverlayData]) {
+    for overlay in overlays.iter_mut() {
+        if overlay.visible && overlay.show_hide && matches!(overlay.relative_to, RelativeTo::None) {
+            overlay.reset(app);
+        }
+    }
+}
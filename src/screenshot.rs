@@ -0,0 +1,140 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use stereokit::{SkDraw, StereoKitMultiThread};
+
+use crate::{
+    config,
+    overlay::OverlayData,
+    toast::{self, NotifyLevel},
+    AppState,
+};
+
+// How long a screen lights up after its screenshot was taken
+pub const FLASH_DURATION: Duration = Duration::from_millis(150);
+
+// XDG_PICTURES_DIR as xdg-user-dirs writes it: a line like XDG_PICTURES_DIR="$HOME/Pictures"
+fn parse_user_dirs(contents: &str, home: &str) -> Option<PathBuf> {
+    contents.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("XDG_PICTURES_DIR=")?;
+        let value = value.trim_matches('"');
+        let path = match value.strip_prefix("$HOME") {
+            Some(rest) => format!("{}{}", home, rest),
+            None if value.starts_with('/') => value.to_string(),
+            None => return None,
+        };
+        Some(PathBuf::from(path))
+    })
+}
+
+fn get_pictures_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("XDG_PICTURES_DIR") {
+        return PathBuf::from(dir);
+    }
+    let home = std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".config"));
+    let user_dirs = std::fs::read_to_string(config_dir.join("user-dirs.dirs"))
+        .ok()
+        .and_then(|contents| parse_user_dirs(&contents, &home.to_string_lossy()));
+    user_dirs.unwrap_or_else(|| home.join("Pictures"))
+}
+
+// Two screenshots within the same millisecond get a counter. The file is made here rather than
+// on the writer thread so the next one sees it taken.
+fn create_unique(dir: &Path, name: &str) -> io::Result<(PathBuf, File)> {
+    let stamp = Local::now().format("%Y%m%d-%H%M%S%.3f");
+    let mut path = dir.join(format!("wlx-{}-{}.png", name, stamp));
+    let mut n = 1;
+    loop {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                n += 1;
+                path = dir.join(format!("wlx-{}-{}-{}.png", name, stamp, n));
+            }
+            result => return result.map(|file| (path, file)),
+        }
+    }
+}
+
+// Reads back the overlay's current frame and saves it as a PNG in the background
+pub fn take_screenshot(
+    sk: &SkDraw,
+    app: &mut AppState,
+    overlay: &mut OverlayData,
+) -> Result<PathBuf, String> {
+    let Some(gfx) = overlay.gfx.as_ref() else {
+        return Err(format!("{} has not been shown yet", overlay.name));
+    };
+    let width = sk.tex_get_width(&gfx.tex) as u32;
+    let height = sk.tex_get_height(&gfx.tex) as u32;
+    let texture = unsafe { sk.tex_get_surface(&gfx.tex) as usize as u32 };
+    let pixels = app.gl.read_pixels(texture, width, height);
+
+    let dir = get_pictures_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let (path, file) = create_unique(&dir, &overlay.name).map_err(|e| e.to_string())?;
+
+    let out = path.clone();
+    thread::spawn(move || {
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let result = encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&pixels))
+            .map_err(|e| e.to_string());
+        match result {
+            Ok(()) => {
+                let msg = format!("Saved screenshot to {}", out.to_string_lossy());
+                toast::notify(NotifyLevel::Info, &msg);
+            }
+            Err(e) => config::report_error(&format!("Failed to save screenshot: {}", e)),
+        }
+    });
+
+    overlay.flash_until = Some(Instant::now() + FLASH_DURATION);
+    Ok(path)
+}
+
+// Screenshots the named overlay, or every visible screen when no name is given
+pub fn screenshot_overlays(
+    sk: &SkDraw,
+    app: &mut AppState,
+    overlays: &mut [OverlayData],
+    name: Option<&str>,
+) -> Result<Vec<PathBuf>, String> {
+    let targets: Vec<usize> = match name {
+        Some(name) => overlays
+            .iter()
+            .position(|o| *o.name == *name)
+            .into_iter()
+            .collect(),
+        None => app
+            .screens
+            .iter()
+            .map(|(idx, _)| *idx)
+            .filter(|idx| overlays[*idx].visible)
+            .collect(),
+    };
+    if targets.is_empty() {
+        return Err(match name {
+            Some(name) => format!("No overlay named {}", name),
+            None => "No screen is visible".to_string(),
+        });
+    }
+
+    targets
+        .into_iter()
+        .map(|idx| take_screenshot(sk, app, &mut overlays[idx]))
+        .collect()
+}
@@ -0,0 +1,340 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fs,
+    process::{Child, Command},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{error, info};
+use mlua::{Function, HookTriggers, Lua, RegistryKey, Table, Variadic};
+
+use crate::{
+    config::report_error,
+    config_io,
+    input::INPUT,
+    keyboard::key_events_for_macro,
+    overlay::OverlayData,
+    toast::{self, NotifyLevel},
+};
+
+// Registry table of event name -> list of handler functions
+const HANDLERS: &str = "wlx_handlers";
+
+// Scripts run on the render thread, so each run of a script, handler or timer is stopped
+// once it has gone through this many instructions, counted in steps of HOOK_INTERVAL
+const MAX_INSTRUCTIONS: u32 = 10_000_000;
+const HOOK_INTERVAL: u32 = 1000;
+
+const EVENTS: [&str; 4] = [
+    "overlay_shown",
+    "overlay_hidden",
+    "key_pressed",
+    "notification",
+];
+
+pub enum ScriptEvent {
+    OverlayShown(Arc<str>),
+    OverlayHidden(Arc<str>),
+    // name of the virtual key, as in keyboard layouts
+    KeyPressed(String),
+    Notification {
+        app: String,
+        summary: String,
+        body: String,
+    },
+}
+
+impl ScriptEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            ScriptEvent::OverlayShown(_) => "overlay_shown",
+            ScriptEvent::OverlayHidden(_) => "overlay_hidden",
+            ScriptEvent::KeyPressed(_) => "key_pressed",
+            ScriptEvent::Notification { .. } => "notification",
+        }
+    }
+
+    fn args(&self) -> Variadic<String> {
+        match self {
+            ScriptEvent::OverlayShown(name) | ScriptEvent::OverlayHidden(name) => {
+                Variadic::from_iter([name.to_string()])
+            }
+            ScriptEvent::KeyPressed(key) => Variadic::from_iter([key.clone()]),
+            ScriptEvent::Notification { app, summary, body } => {
+                Variadic::from_iter([app.clone(), summary.clone(), body.clone()])
+            }
+        }
+    }
+}
+
+// Only queued while scripts are loaded, so nothing piles up without them
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static QUEUED_EVENTS: Mutex<VecDeque<ScriptEvent>> = Mutex::new(VecDeque::new());
+
+// Safe to call from any thread; handlers run on the next frame
+pub fn emit(event: ScriptEvent) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(mut events) = QUEUED_EVENTS.lock() {
+        events.push_back(event);
+    }
+}
+
+// What the scripts asked for, applied once their handlers return
+#[derive(Default)]
+struct ScriptQueue {
+    // overlay name, None toggles
+    visibility: Vec<(String, Option<bool>)>,
+    timers: Vec<(Instant, RegistryKey)>,
+    processes: Vec<Child>,
+}
+
+// Lives on the render thread, which runs every handler
+pub struct Scripts {
+    lua: Lua,
+    queue: Rc<RefCell<ScriptQueue>>,
+    // hook steps left for the current run
+    budget: Arc<AtomicU32>,
+}
+
+impl Scripts {
+    // Runs scripts/*.lua from the config root; None if there are none
+    pub fn load() -> Option<Scripts> {
+        let dir = config_io::get_config_file_path("scripts");
+        let mut paths: Vec<_> = fs::read_dir(dir)
+            .ok()?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "lua"))
+            .collect();
+        if paths.is_empty() {
+            return None;
+        }
+        paths.sort();
+
+        let scripts = Scripts {
+            lua: Lua::new(),
+            queue: Rc::new(RefCell::new(ScriptQueue::default())),
+            budget: Arc::new(AtomicU32::new(0)),
+        };
+        scripts.limit_instructions();
+        if let Err(e) = scripts.register_api() {
+            report_error(&format!("Failed to set up scripting: {}", e));
+            return None;
+        }
+
+        for path in paths {
+            let name = path.to_string_lossy().to_string();
+            let result = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|code| {
+                    scripts.start_run();
+                    let chunk = scripts.lua.load(&code).set_name(&name);
+                    chunk.exec().map_err(|e| e.to_string())
+                });
+            match result {
+                Ok(()) => info!("Loaded script {}", name),
+                Err(e) => report_error(&format!("{}: {}", name, e)),
+            }
+        }
+        ENABLED.store(true, Ordering::Relaxed);
+        Some(scripts)
+    }
+
+    // Stops a run that has used up its budget with an error. Once out, the hook keeps failing
+    // until the next run, so pcall in a loop doesn't get around it.
+    fn limit_instructions(&self) {
+        let budget = self.budget.clone();
+        let triggers = HookTriggers::new().every_nth_instruction(HOOK_INTERVAL);
+        self.lua.set_hook(triggers, move |_lua, _debug| {
+            budget
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                    left.checked_sub(1)
+                })
+                .map(|_| ())
+                .map_err(|_| {
+                    mlua::Error::RuntimeError(format!(
+                        "stopped after {} instructions",
+                        MAX_INSTRUCTIONS
+                    ))
+                })
+        });
+    }
+
+    fn start_run(&self) {
+        self.budget
+            .store(MAX_INSTRUCTIONS / HOOK_INTERVAL, Ordering::Relaxed);
+    }
+
+    // The `wlx` table scripts talk to us through
+    fn register_api(&self) -> mlua::Result<()> {
+        let lua = &self.lua;
+        lua.set_named_registry_value(HANDLERS, lua.create_table()?)?;
+        let wlx = lua.create_table()?;
+
+        // wlx.on(event, function(...) end)
+        wlx.set(
+            "on",
+            lua.create_function(|lua, (event, f): (String, Function)| {
+                if !EVENTS.contains(&event.as_str()) {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "unknown event {}, expected one of {}",
+                        event,
+                        EVENTS.join(", ")
+                    )));
+                }
+                let handlers: Table = lua.named_registry_value(HANDLERS)?;
+                let list = match handlers.get::<_, Option<Table>>(event.as_str())? {
+                    Some(list) => list,
+                    None => {
+                        let list = lua.create_table()?;
+                        handlers.set(event.as_str(), list.clone())?;
+                        list
+                    }
+                };
+                list.raw_set(list.raw_len() + 1, f)
+            })?,
+        )?;
+
+        // wlx.after(milliseconds, function() end)
+        let queue = self.queue.clone();
+        wlx.set(
+            "after",
+            lua.create_function(move |lua, (ms, f): (u64, Function)| {
+                let key = lua.create_registry_value(f)?;
+                let at = Instant::now() + Duration::from_millis(ms);
+                queue.borrow_mut().timers.push((at, key));
+                Ok(())
+            })?,
+        )?;
+
+        // wlx.show(name), wlx.hide(name), wlx.toggle(name)
+        for (fn_name, visible) in [
+            ("show", Some(true)),
+            ("hide", Some(false)),
+            ("toggle", None),
+        ] {
+            let queue = self.queue.clone();
+            wlx.set(
+                fn_name,
+                lua.create_function(move |_, name: String| {
+                    queue.borrow_mut().visibility.push((name, visible));
+                    Ok(())
+                })?,
+            )?;
+        }
+
+        // wlx.send_keys({"LCtrl DOWN", "C", "LCtrl UP"}), same as keyboard macros
+        wlx.set(
+            "send_keys",
+            lua.create_function(|_, verbs: Vec<String>| {
+                if let Ok(input) = INPUT.lock() {
+                    for (vk, press) in key_events_for_macro(&verbs) {
+                        input.send_key(vk as _, press);
+                    }
+                }
+                Ok(())
+            })?,
+        )?;
+
+        // wlx.exec(program, {args})
+        let queue = self.queue.clone();
+        wlx.set(
+            "exec",
+            lua.create_function(move |_, (program, args): (String, Option<Vec<String>>)| {
+                match Command::new(&program)
+                    .args(args.unwrap_or_default())
+                    .spawn()
+                {
+                    Ok(child) => queue.borrow_mut().processes.push(child),
+                    Err(e) => error!("Script failed to run {}: {}", program, e),
+                }
+                Ok(())
+            })?,
+        )?;
+
+        // wlx.notify(text) pops it up next to the watch, wlx.log(text) only logs it
+        wlx.set(
+            "notify",
+            lua.create_function(|_, text: String| {
+                toast::notify(NotifyLevel::Info, &text);
+                Ok(())
+            })?,
+        )?;
+        wlx.set(
+            "log",
+            lua.create_function(|_, text: String| {
+                info!("script: {}", text);
+                Ok(())
+            })?,
+        )?;
+
+        lua.globals().set("wlx", wlx)
+    }
+
+    // Once per frame: runs the handlers of queued events and due timers, then applies
+    // what they asked for
+    pub fn update(&mut self, overlays: &mut [OverlayData]) {
+        let events: Vec<_> = QUEUED_EVENTS
+            .lock()
+            .map(|mut events| events.drain(..).collect())
+            .unwrap_or_default();
+        for event in events {
+            self.dispatch(&event);
+        }
+
+        let due: Vec<_> = {
+            let mut queue = self.queue.borrow_mut();
+            let now = Instant::now();
+            let (due, waiting) = queue.timers.drain(..).partition(|(at, _)| *at <= now);
+            queue.timers = waiting;
+            due
+        };
+        for (_, key) in due {
+            self.start_run();
+            let result = self
+                .lua
+                .registry_value::<Function>(&key)
+                .and_then(|f| f.call::<_, ()>(()));
+            if let Err(e) = result {
+                error!("Script timer failed: {}", e);
+            }
+            let _ = self.lua.remove_registry_value(key);
+        }
+
+        let mut queue = self.queue.borrow_mut();
+        for (name, visible) in queue.visibility.drain(..) {
+            for overlay in overlays.iter_mut() {
+                if *overlay.name == *name {
+                    overlay.want_visible = visible.unwrap_or(!overlay.want_visible);
+                }
+            }
+        }
+        // Reap finished processes
+        queue
+            .processes
+            .retain_mut(|child| !matches!(child.try_wait(), Ok(Some(_))));
+    }
+
+    fn dispatch(&self, event: &ScriptEvent) {
+        let handlers = self
+            .lua
+            .named_registry_value::<Table>(HANDLERS)
+            .and_then(|handlers| handlers.get::<_, Option<Table>>(event.name()));
+        let Ok(Some(list)) = handlers else {
+            return;
+        };
+        for f in list.sequence_values::<Function>() {
+            self.start_run();
+            if let Err(e) = f.and_then(|f| f.call::<_, ()>(event.args())) {
+                error!("Script handler for {} failed: {}", event.name(), e);
+            }
+        }
+    }
+}
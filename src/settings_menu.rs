@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use glam::Vec3;
+
+use crate::{
+    bed_mode,
+    config::report_error,
+    config_io,
+    gui::{color_parse, theme::next_builtin_theme, Canvas},
+    overlay::{OverlayData, RelativeTo},
+    AppSession, TASKS,
+};
+
+const WIDTH: f32 = 400.;
+const HEIGHT: f32 = 110.;
+
+// Overlays opened from this page, by button
+const PANELS: [(&str, &str, &str); 2] = [
+    ("Colors", "Colors", "Pick pointer and theme colors"),
+    ("Power", "Power", "Lock, suspend, log out or shut down"),
+];
+
+fn get_theme_path() -> PathBuf {
+    let mut path = config_io::get_conf_d_path();
+    path.push("theme.yaml");
+    path
+}
+
+// The config watcher picks the file up and applies it
+fn save_theme(theme: &str) {
+    let mut conf = serde_yaml::Mapping::new();
+    conf.insert("theme".into(), theme.into());
+    let result = serde_yaml::to_string(&conf)
+        .map_err(|e| e.to_string())
+        .and_then(|yaml| std::fs::write(get_theme_path(), yaml).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        report_error(&format!("Failed to save theme: {}", e));
+    }
+}
+
+// Settings sub-page of the watch, opened with its ☰ button
+pub fn create_settings_menu(session: &AppSession) -> OverlayData {
+    let mut canvas: Canvas<(), &'static str> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        (),
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+    canvas.font_size = theme.font_size;
+
+    let button_w = (WIDTH - 30.) / 2.;
+    let theme_button = canvas.button(10., 10., button_w, 40., "Theme".into());
+    canvas.controls[theme_button].set_tooltip("Switch to the next built-in theme");
+    canvas.controls[theme_button].on_press = Some(|_control, session, _data| {
+        save_theme(next_builtin_theme(&session.config.theme));
+    });
+
+    let bed = canvas.button(20. + button_w, 10., button_w, 40., "Bed mode".into());
+    canvas.controls[bed].set_tooltip("Bed mode: overlays follow your head while lying down");
+    canvas.controls[bed].on_press = Some(|_control, _session, _data| {
+        bed_mode::toggle();
+    });
+
+    let panel_w = (WIDTH - 10. * (PANELS.len() + 1) as f32) / PANELS.len() as f32;
+    for (i, (label, overlay, tooltip)) in PANELS.iter().enumerate() {
+        let x = 10. + i as f32 * (panel_w + 10.);
+        let button = canvas.button(x, 60., panel_w, 40., (*label).into());
+        let button = &mut canvas.controls[button];
+        button.set_tooltip(tooltip);
+        button.state = Some(*overlay);
+        button.on_press = Some(|control, _session, _data| {
+            let Some(name) = control.state else {
+                return;
+            };
+            if let Ok(mut tasks) = TASKS.lock() {
+                tasks.push_back(Box::new(move |_sk, _app, o| {
+                    for overlay in o {
+                        if &*overlay.name == name {
+                            overlay.want_visible = !overlay.want_visible;
+                        }
+                    }
+                }));
+            }
+        });
+    }
+
+    OverlayData {
+        name: "Settings".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.3 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: Vec3::NEG_Z * 0.6,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,186 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use futures_util::StreamExt;
+use log::{info, warn};
+use tokio::runtime::Runtime;
+use zbus::{
+    dbus_proxy,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+    Connection,
+};
+
+use crate::{
+    config::{GlobalShortcutConfig, ShortcutAction},
+    overlay::OverlayData,
+    TASKS,
+};
+
+const SHORTCUT_ID: &str = "toggle";
+
+#[dbus_proxy(
+    interface = "org.freedesktop.portal.GlobalShortcuts",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait GlobalShortcuts {
+    fn create_session(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<OwnedObjectPath>;
+
+    fn bind_shortcuts(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        shortcuts: &[(&str, HashMap<&str, Value<'_>>)],
+        parent_window: &str,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedObjectPath>;
+
+    #[dbus_proxy(signal)]
+    fn activated(
+        &self,
+        session_handle: ObjectPath<'_>,
+        shortcut_id: &str,
+        timestamp: u64,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.portal.Request",
+    default_service = "org.freedesktop.portal.Desktop"
+)]
+trait Request {
+    #[dbus_proxy(signal)]
+    fn response(&self, response: u32, results: HashMap<&str, OwnedValue>) -> zbus::Result<()>;
+}
+
+// Overlays hidden by the shortcut, shown again the next time it's pressed
+static HIDDEN: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// Binds the shortcut through xdg-desktop-portal; the desktop may ask the user to confirm
+pub fn start(rt: &Runtime, config: GlobalShortcutConfig) {
+    rt.spawn(async move {
+        if let Err(e) = listen(&config).await {
+            warn!("Global shortcut not available: {}", e);
+        }
+    });
+}
+
+async fn listen(config: &GlobalShortcutConfig) -> zbus::Result<()> {
+    let conn = Connection::session().await?;
+    let proxy = GlobalShortcutsProxy::new(&conn).await?;
+
+    let mut options = HashMap::new();
+    options.insert("handle_token", Value::from("wlx_session"));
+    options.insert("session_handle_token", Value::from("wlx"));
+    let results = portal_request(&conn, "wlx_session", proxy.create_session(options)).await?;
+    let session_handle = results
+        .get("session_handle")
+        .and_then(|v| String::try_from(v.clone()).ok())
+        .ok_or_else(|| zbus::Error::Failure("no session handle".into()))?;
+    let session_handle = ObjectPath::try_from(session_handle)?;
+
+    let description = match config.action {
+        ShortcutAction::ToggleOverlays => "Show or hide the VR overlay",
+        ShortcutAction::ToggleKeyboard => "Show or hide the VR keyboard",
+    };
+    let mut shortcut = HashMap::new();
+    shortcut.insert("description", Value::from(description));
+    shortcut.insert("preferred_trigger", Value::from(config.trigger.as_str()));
+    let mut options = HashMap::new();
+    options.insert("handle_token", Value::from("wlx_bind"));
+    portal_request(
+        &conn,
+        "wlx_bind",
+        proxy.bind_shortcuts(&session_handle, &[(SHORTCUT_ID, shortcut)], "", options),
+    )
+    .await?;
+    info!(
+        "Global shortcut bound, preferred trigger {}",
+        config.trigger
+    );
+
+    let action = config.action;
+    let mut activations = proxy.receive_activated().await?;
+    while let Some(signal) = activations.next().await {
+        let Ok(args) = signal.args() else {
+            continue;
+        };
+        if args.shortcut_id != SHORTCUT_ID {
+            continue;
+        }
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(move |_sk, _app, o| run(action, o)));
+        }
+    }
+    Ok(())
+}
+
+// Portal calls answer through a Request object, which needs to be listened to before the call
+async fn portal_request(
+    conn: &Connection,
+    token: &str,
+    call: impl std::future::Future<Output = zbus::Result<OwnedObjectPath>>,
+) -> zbus::Result<HashMap<String, OwnedValue>> {
+    let sender = conn
+        .unique_name()
+        .map(|n| n.trim_start_matches(':').replace('.', "_"))
+        .unwrap_or_default();
+    let path = format!(
+        "/org/freedesktop/portal/desktop/request/{}/{}",
+        sender, token
+    );
+    let request = RequestProxy::builder(conn).path(path)?.build().await?;
+    let mut responses = request.receive_response().await?;
+
+    call.await?;
+
+    let signal = responses
+        .next()
+        .await
+        .ok_or_else(|| zbus::Error::Failure("no response".into()))?;
+    let args = signal.args()?;
+    if args.response != 0 {
+        return Err(zbus::Error::Failure("request denied".into()));
+    }
+    Ok(args
+        .results
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect())
+}
+
+fn run(action: ShortcutAction, overlays: &mut [OverlayData]) {
+    match action {
+        ShortcutAction::ToggleKeyboard => {
+            for overlay in overlays.iter_mut() {
+                if &*overlay.name == "Kbd" {
+                    overlay.want_visible = !overlay.want_visible;
+                }
+            }
+        }
+        ShortcutAction::ToggleOverlays => {
+            let Ok(mut hidden) = HIDDEN.lock() else {
+                return;
+            };
+            let shown: Vec<String> = overlays
+                .iter()
+                .filter(|o| o.show_hide && o.want_visible)
+                .map(|o| o.name.to_string())
+                .collect();
+            if shown.is_empty() {
+                for overlay in overlays.iter_mut() {
+                    if hidden.iter().any(|n| *n == *overlay.name) {
+                        overlay.want_visible = true;
+                    }
+                }
+                hidden.clear();
+            } else {
+                for overlay in overlays.iter_mut() {
+                    if overlay.show_hide {
+                        overlay.want_visible = false;
+                    }
+                }
+                *hidden = shown;
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::{collections::HashMap, error::Error, path::PathBuf};
+
+use glam::{Affine3A, Quat, Vec3};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config_io,
+    overlay::{OverlayData, RelativeTo},
+    AppSession,
+};
+
+// Machine-written session state, kept apart from the user-authored config
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct SessionState {
+    #[serde(default)]
+    pub overlays: HashMap<String, OverlayState>,
+    #[serde(default)]
+    pub watch_pos: Option<[f32; 3]>,
+    #[serde(default)]
+    pub watch_rot: Option<[f32; 4]>,
+    // Last world transform of each screen by output name, also for outputs not plugged in
+    #[serde(default)]
+    pub screens: HashMap<String, [f32; 12]>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct OverlayState {
+    pub visible: bool,
+    pub scale: f32,
+    // World transform, only for overlays that are not attached to the head or a hand
+    #[serde(default)]
+    pub transform: Option<[f32; 12]>,
+}
+
+fn get_state_path() -> PathBuf {
+    config_io::get_config_file_path("state.yaml")
+}
+
+impl SessionState {
+    pub fn load() -> Result<SessionState, Box<dyn Error>> {
+        let yaml = std::fs::read_to_string(get_state_path())?;
+        Ok(serde_yaml::from_str(&yaml)?)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let yaml = serde_yaml::to_string(self)?;
+        std::fs::write(get_state_path(), yaml)?;
+        Ok(())
+    }
+
+    pub fn capture(overlays: &[OverlayData]) -> SessionState {
+        let mut state = SessionState::default();
+        for overlay in overlays.iter() {
+            // the radial menu, toasts, edit HUD, magnifier, numpad, dropdown and screen picker
+            // are shown on demand, the perf HUD by config and the setup wizard only on the
+            // first run
+            if overlay.name.is_empty()
+                || matches!(
+                    &*overlay.name,
+                    "Radial"
+                        | "Toast"
+                        | "Edit"
+                        | "Magnifier"
+                        | "Numpad"
+                        | "Dropdown"
+                        | "Picker"
+                        | "Perf"
+                        | "Wizard"
+                )
+            {
+                continue;
+            }
+            if &*overlay.name == "Watch" {
+                state.watch_pos = Some(overlay.spawn_point.to_array());
+                state.watch_rot = Some(overlay.spawn_rotation.to_array());
+            }
+
+            let transform = match overlay.relative_to {
+                RelativeTo::None if overlay.visible => Some(overlay.transform.to_cols_array()),
+                _ => None,
+            };
+            if overlay.remember_transform {
+                let last = transform.or(overlay.saved_transform.map(|t| t.to_cols_array()));
+                if let Some(last) = last {
+                    state.screens.insert(overlay.name.to_string(), last);
+                }
+            }
+            state.overlays.insert(
+                overlay.name.to_string(),
+                OverlayState {
+                    visible: overlay.want_visible,
+                    scale: overlay.scale,
+                    transform,
+                },
+            );
+        }
+        state
+    }
+
+    pub fn apply_session(&self, session: &mut AppSession) {
+        if let Some(pos) = self.watch_pos {
+            session.watch_pos = Vec3::from_array(pos);
+        }
+        if let Some(rot) = self.watch_rot {
+            session.watch_rot = Quat::from_array(rot);
+        }
+    }
+
+    pub fn apply_overlays(&self, overlays: &mut [OverlayData]) {
+        for overlay in overlays.iter_mut() {
+            // a transform from the outputs config comes first
+            if overlay.remember_transform && overlay.saved_transform.is_none() {
+                overlay.saved_transform = self.screen_transform(&overlay.name);
+            }
+            let Some(saved) = self.overlays.get(&*overlay.name) else {
+                continue;
+            };
+            overlay.want_visible = saved.visible;
+            overlay.scale = saved.scale;
+            if let Some(t) = saved.transform {
+                overlay.saved_transform = Some(Affine3A::from_cols_array(&t));
+            }
+        }
+    }
+
+    pub fn screen_transform(&self, name: &str) -> Option<Affine3A> {
+        self.screens.get(name).map(Affine3A::from_cols_array)
+    }
+}
+
+pub fn save_state(overlays: &[OverlayData]) {
+    let mut state = SessionState::capture(overlays);
+    // screens of another monitor set keep their places for next time
+    if let Ok(old) = SessionState::load() {
+        for (name, transform) in old.screens {
+            state.screens.entry(name).or_insert(transform);
+        }
+    }
+    if let Err(e) = state.save() {
+        error!("Failed to save state: {}", e);
+    }
+}
@@ -0,0 +1,121 @@
+use glam::Vec3;
+use smithay_client_toolkit::reexports::protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1;
+
+use crate::{
+    desktop::toplevel::{toplevel_action, ToplevelAction, TOPLEVELS},
+    gui::{color_parse, Canvas},
+    overlay::{OverlayData, RelativeTo},
+    AppSession,
+};
+
+const WIDTH: f32 = 500.;
+const HEIGHT: f32 = 400.;
+const ROW_HEIGHT: f32 = 36.;
+const BUTTON_H: f32 = 40.;
+
+#[derive(Default)]
+struct TaskbarData {
+    // windows of the rows as last built, a row picks from these rather than the live list
+    handles: Vec<ZwlrForeignToplevelHandleV1>,
+    selected: Option<ZwlrForeignToplevelHandleV1>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TaskbarControl {
+    // generation of the window list the items were built from
+    List(u64),
+    Action(ToplevelAction),
+}
+
+// Open windows, with focus / minimize / close for the selected one
+pub fn create_taskbar(session: &AppSession) -> OverlayData {
+    let mut canvas: Canvas<TaskbarData, TaskbarControl> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        TaskbarData::default(),
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.font_size = theme.font_size;
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+
+    let list_h = HEIGHT - BUTTON_H - 30.;
+    let list = canvas.list(10., 10., WIDTH - 20., list_h, ROW_HEIGHT);
+    canvas.controls[list].on_update = Some(|control, data| {
+        let Ok(toplevels) = TOPLEVELS.lock() else {
+            return;
+        };
+        let state = Some(TaskbarControl::List(toplevels.generation));
+        if control.state == state {
+            return;
+        }
+        let items = toplevels
+            .toplevels
+            .iter()
+            .map(|t| {
+                let marker = if t.activated {
+                    "▶ "
+                } else if t.minimized {
+                    "_ "
+                } else {
+                    ""
+                };
+                format!("{}{}: {}", marker, t.app_id, t.title).into()
+            })
+            .collect();
+        control.set_items(items);
+        control.state = state;
+        data.handles = toplevels
+            .toplevels
+            .iter()
+            .map(|t| t.handle.clone())
+            .collect();
+
+        // forget a selection whose window has gone away
+        if let Some(selected) = data.selected.as_ref() {
+            if !data.handles.contains(selected) {
+                data.selected = None;
+            }
+        }
+    });
+    canvas.controls[list].on_select = Some(|_control, row, data| {
+        data.selected = data.handles.get(row).cloned();
+    });
+
+    let buttons = [
+        ("Focus", ToplevelAction::Activate),
+        ("Minimize", ToplevelAction::Minimize),
+        ("Close", ToplevelAction::Close),
+    ];
+    let button_w = (WIDTH - 20.) / buttons.len() as f32;
+    let y = HEIGHT - BUTTON_H - 10.;
+    for (i, (label, action)) in buttons.into_iter().enumerate() {
+        let x = 10. + i as f32 * button_w;
+        let idx = canvas.button(x + 2., y, button_w - 4., BUTTON_H, label.into());
+        canvas.controls[idx].state = Some(TaskbarControl::Action(action));
+        canvas.controls[idx].on_press = Some(|control, _session, data| {
+            let (Some(handle), Some(TaskbarControl::Action(action))) =
+                (data.selected.as_ref(), control.state)
+            else {
+                return;
+            };
+            toplevel_action(handle, action);
+        });
+    }
+
+    OverlayData {
+        name: "Taskbar".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.35 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: Vec3::NEG_Z * 0.6,
+        ..Default::default()
+    }
+}
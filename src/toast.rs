@@ -0,0 +1,162 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use glam::{vec3, Vec3};
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+
+use crate::{
+    gui::{color_parse, Canvas, TextOverflow},
+    notifications,
+    overlay::{OverlayData, RelativeTo},
+    schedule_after, AppSession,
+};
+
+const INFO_DURATION: Duration = Duration::from_secs(3);
+const WARN_DURATION: Duration = Duration::from_secs(6);
+const WIDTH: f32 = 500.;
+const HEIGHT: f32 = 110.;
+
+// Above the watch face, in the watch's own space
+const WATCH_OFFSET: Vec3 = Vec3::new(0., 0.035, 0.);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NotifyLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+impl NotifyLevel {
+    fn duration(self) -> Duration {
+        match self {
+            NotifyLevel::Info => INFO_DURATION,
+            _ => WARN_DURATION,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ToastQueue {
+    pending: VecDeque<(NotifyLevel, Arc<str>)>,
+    current: Option<(NotifyLevel, Arc<str>)>,
+    // toasts shown so far, so an expiry only clears the one it was scheduled for
+    shown: u64,
+}
+
+static TOASTS: Lazy<Mutex<ToastQueue>> = Lazy::new(|| Mutex::new(ToastQueue::default()));
+
+// Logs the message and queues a popup next to the watch. Safe to call from any thread, even
+// before the overlays exist.
+pub fn notify(level: NotifyLevel, text: &str) {
+    match level {
+        NotifyLevel::Info => info!("{}", text),
+        NotifyLevel::Warn => warn!("{}", text),
+        NotifyLevel::Error => error!("{}", text),
+    }
+    notifications::record(level, text);
+    if let Ok(mut toasts) = TOASTS.lock() {
+        toasts.pending.push_back((level, text.into()));
+    }
+}
+
+pub fn warn(text: &str) {
+    notify(NotifyLevel::Warn, text);
+}
+
+fn current() -> Option<(NotifyLevel, Arc<str>)> {
+    let toasts = TOASTS.lock().ok()?;
+    toasts.current.clone()
+}
+
+fn expire(shown: u64) {
+    if let Ok(mut toasts) = TOASTS.lock() {
+        if toasts.shown == shown {
+            toasts.current = None;
+        }
+    }
+}
+
+// Moves on to the next toast once the current one expires and shows or hides the overlay to match
+pub fn update(overlays: &mut [OverlayData]) {
+    let Ok(mut toasts) = TOASTS.lock() else {
+        return;
+    };
+    if toasts.current.is_none() {
+        if let Some((level, text)) = toasts.pending.pop_front() {
+            toasts.shown += 1;
+            let shown = toasts.shown;
+            schedule_after(
+                level.duration(),
+                Box::new(move |_sk, _app, _o| expire(shown)),
+            );
+            toasts.current = Some((level, text));
+        }
+    }
+
+    let visible = toasts.current.is_some();
+    for overlay in overlays.iter_mut() {
+        if &*overlay.name == "Toast" {
+            overlay.want_visible = visible;
+        }
+    }
+}
+
+pub fn create_toast(session: &AppSession) -> OverlayData {
+    let mut canvas = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        (),
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    // One heading per level, each in its own color; only the current one has text
+    canvas.font_size = theme.font_size;
+    let headings = [
+        (NotifyLevel::Warn, "Warning", vec3(1., 0.8, 0.2)),
+        (NotifyLevel::Error, "Error", vec3(1., 0.35, 0.3)),
+    ];
+    for (level, heading, color) in headings {
+        canvas.fg_color = color;
+        let label = canvas.label(10., 30., WIDTH - 20., 30., "".into());
+        canvas.controls[label].state = Some((level, heading));
+        canvas.controls[label].on_update = Some(|control, _data| {
+            let Some((level, heading)) = control.state else {
+                return;
+            };
+            match current() {
+                Some((current, _)) if current == level => control.set_text(heading),
+                _ => control.set_text(""),
+            }
+        });
+    }
+
+    canvas.fg_color = color_parse(&theme.text_color);
+    let label = canvas.label(10., 60., WIDTH - 20., HEIGHT - 60., "".into());
+    canvas.controls[label].set_overflow(TextOverflow::Wrap);
+    canvas.controls[label].on_update = Some(|control, _data| {
+        if let Some((_, text)) = current() {
+            control.set_text(&text);
+        }
+    });
+
+    OverlayData {
+        name: "Toast".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.1 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::Hand(session.watch_hand),
+        spawn_point: session.watch_pos + session.watch_rot * WATCH_OFFSET,
+        spawn_rotation: session.watch_rot,
+        grabbable: false,
+        ..Default::default()
+    }
+}
@@ -1,34 +1,75 @@
-use std::{sync::Arc, time::Instant};
+use std::{path::PathBuf, sync::Arc, time::Instant};
 
 use chrono::Local;
-use glam::{Quat, Vec3};
+use glam::{vec2, vec3, Quat, Vec3};
 
 use crate::{
-    gui::{color_parse, Canvas},
+    audio::create_audio_panel,
+    colors::create_colors_panel,
+    config::{self, MetricConfig},
+    config_io,
+    desktop::{capture::pw_capture, gamma, output_power, picker::create_picker},
+    dropdown::create_dropdown,
+    edit_mode::{self, create_edit_hud},
+    gui::{
+        color_parse,
+        layout::{FlexLayout, Rect},
+        Canvas, TextOverflow, TextSpan,
+    },
+    keyboard::create_keyboard,
+    logview::create_log_viewer,
+    magnifier::create_magnifier,
+    notifications::create_history,
+    numpad::create_numpad,
+    osc,
     overlay::{OverlayData, RelativeTo},
-    AppSession, TASKS,
+    panels::rebuild_panel,
+    perf::create_perf_hud,
+    power,
+    power_menu::create_power_menu,
+    radial::create_radial_menu,
+    screenshot::screenshot_overlays,
+    settings_menu::create_settings_menu,
+    taskbar::create_taskbar,
+    toast::create_toast,
+    weather,
+    wizard::create_wizard,
+    wm::{self, panel::create_wm_panel},
+    AppSession, AppState, TASKS,
 };
 
 pub const WATCH_DEFAULT_POS: Vec3 = Vec3::new(0., 0., 0.15);
 pub const WATCH_DEFAULT_ROT: Quat = Quat::from_xyzw(0.7071066, 0., 0.7071066, 0.0007963);
 
 pub fn create_watch(session: &AppSession, screens: Vec<(usize, Arc<str>)>) -> OverlayData {
-    let mut canvas = Canvas::new(400, 200, ());
+    let data = WatchData {
+        metric: session.config.osc_metric.clone(),
+    };
+    let mut canvas = Canvas::new(
+        400,
+        200,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        data,
+    );
     let empty_str: Arc<str> = Arc::from("");
 
+    let theme = &session.theme;
+
     // Background
-    canvas.bg_color = color_parse("#353535");
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
     canvas.panel(0., 0., 400., 200.);
 
     // Time display
-    canvas.font_size = 46;
+    canvas.fg_color = color_parse(&theme.text_color);
+    canvas.font_size = theme.clock_font_size;
     let clock = canvas.label(19., 100., 200., 50., empty_str.clone());
     canvas.controls[clock].on_update = Some(|control, _data| {
         let date = Local::now();
         control.set_text(&format!("{}", &date.format("%H:%M")));
     });
 
-    canvas.font_size = 14;
+    canvas.font_size = theme.font_size;
     let date = canvas.label(20., 125., 200., 50., empty_str.clone());
     canvas.controls[date].on_update = Some(|control, _data| {
         let date = Local::now();
@@ -41,13 +82,111 @@ pub fn create_watch(session: &AppSession, screens: Vec<(usize, Arc<str>)>) -> Ov
         control.set_text(&format!("{}", &date.format("%A")));
     });
 
-    // Volume controls
-    canvas.bg_color = color_parse("#222222");
-    canvas.fg_color = color_parse("#AAAAAA");
-    canvas.font_size = 14;
+    // Text sent over OSC
+    let osc_text = canvas.label(19., 62., 195., 30., "".into());
+    canvas.controls[osc_text].set_overflow(TextOverflow::Ellipsis);
+    canvas.controls[osc_text].on_update = Some(|control, _data| {
+        control.set_text(&osc::get_watch_text());
+    });
+
+    // Shown while any screen is being captured through Pipewire, with the screens' names
+    canvas.font_size = 12;
+    canvas.fg_color = vec3(1., 0.25, 0.25);
+    let capturing = canvas.label(220., 52., 100., 14., "".into());
+    canvas.controls[capturing].set_overflow(TextOverflow::Ellipsis);
+    canvas.controls[capturing].on_update = Some(|control, _data| {
+        let streams = pw_capture::active_streams();
+        if streams.is_empty() {
+            control.set_text("");
+        } else {
+            control.set_text(&format!("● {}", streams.join(", ")));
+        }
+    });
+    canvas.font_size = theme.font_size;
+    canvas.fg_color = color_parse(&theme.text_color);
+
+    // Current conditions, if a location is configured
+    let weather = canvas.label(220., 80., 50., 24., "".into());
+    canvas.controls[weather].set_overflow(TextOverflow::Ellipsis);
+    canvas.controls[weather].on_update = Some(|control, _data| {
+        control.set_text(&weather::get_weather_text());
+    });
+
+    // Number from OSC, colored when out of the configured range
+    let metric = canvas.label(272., 80., 50., 24., "".into());
+    canvas.controls[metric].set_overflow(TextOverflow::Ellipsis);
+    canvas.controls[metric].on_update = Some(|control, data| {
+        let Some(config) = data.metric.as_ref() else {
+            return;
+        };
+        let Some(value) = osc::get_watch_metric() else {
+            control.set_text("");
+            return;
+        };
+        let text = if value.fract() == 0. {
+            format!("{} {:.0}", config.label, value)
+        } else {
+            format!("{} {:.1}", config.label, value)
+        };
+        if control.get_text() == text {
+            return;
+        }
+        let color = if config.low.is_some_and(|low| value < low) {
+            Some(color_parse(&config.low_color))
+        } else if config.high.is_some_and(|high| value > high) {
+            Some(color_parse(&config.high_color))
+        } else {
+            None
+        };
+        control.set_spans(vec![TextSpan::new(&text, color, None)]);
+    });
+
+    // Focused window, from the window manager's IPC
+    let title = canvas.label(160., 100., 160., 30., "".into());
+    canvas.controls[title].set_overflow(TextOverflow::Ellipsis);
+    canvas.controls[title].on_update = Some(|control, _data| {
+        control.set_text(wm::focused_title().as_deref().unwrap_or(""));
+    });
+
+    // Shown while captures and canvases are rate-limited to save power
+    let eco = canvas.label(160., 128., 55., 30., "".into());
+    canvas.controls[eco].on_update = Some(|control, _data| {
+        control.set_text(if power::is_saving() { "ECO" } else { "" });
+    });
 
-    canvas.bg_color = color_parse("#303030");
-    canvas.fg_color = color_parse("#353535");
+    // Physical monitors, for long sessions in the headset
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+
+    let monitors = canvas.button(220., 108., 46., 26., "Mon".into());
+    canvas.controls[monitors]
+        .set_tooltip("Turn the real monitors off or on; screens may stop updating while off");
+    canvas.controls[monitors].on_press = Some(|_control, _session, _data| {
+        if let Err(e) = output_power::set_displays_off(!output_power::displays_off()) {
+            config::report_error(&e);
+        }
+    });
+
+    let edit = canvas.button(272., 108., 50., 26., "Edit".into());
+    canvas.controls[edit]
+        .set_tooltip("Edit mode: move and resize overlays without clicking through to the desktop");
+    canvas.controls[edit].on_press = Some(|_control, _session, _data| {
+        edit_mode::toggle();
+    });
+
+    // Dims the real monitors through their gamma ramps
+    let brightness = canvas.slider(220., 140., 100., 16., 0.1..=1., 0.05);
+    canvas.controls[brightness].set_value(gamma::brightness());
+    canvas.controls[brightness].set_tooltip("Monitor brightness");
+    canvas.controls[brightness].on_change = Some(|control, _data| {
+        if let Err(e) = gamma::set_brightness(control.get_value()) {
+            config::report_error(&e);
+        }
+    });
+
+    // Volume controls
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
 
     let vol_up = canvas.button(327., 116., 46., 32., "+".into());
     canvas.controls[vol_up].on_press = Some(|_control, _session, _data| {
@@ -59,23 +198,152 @@ pub fn create_watch(session: &AppSession, screens: Vec<(usize, Arc<str>)>) -> Ov
         println!("Volume down!"); //TODO
     });
 
-    canvas.bg_color = color_parse("#303030");
-    canvas.fg_color = color_parse("#353535");
+    let shot = canvas.button(327., 86., 46., 28., "Shot".into());
+    canvas.controls[shot].set_tooltip("Screenshot visible screens to ~/Pictures");
+    canvas.controls[shot].on_press = Some(|_control, _session, _data| {
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(|sk, app, o| {
+                if let Err(e) = screenshot_overlays(sk, app, o, None) {
+                    config::report_error(&e);
+                }
+            }));
+        }
+    });
 
-    let settings = canvas.button(2., 162., 36., 36., "☰".into());
-    canvas.controls[settings].on_press = Some(|_control, _session, _data| {
-        println!("Settings!"); //TODO
+    // Profile selector, cycles through profiles/*.yaml and back to the plain config
+    let profile = canvas.button(19., 8., 110., 32., "".into());
+    canvas.controls[profile].set_overflow(TextOverflow::Ellipsis);
+    canvas.controls[profile].set_tooltip("Switch profile");
+    canvas.controls[profile].on_update = Some(|control, _data| {
+        let profile = config::get_profile();
+        control.set_text(profile.as_deref().unwrap_or("default"));
+    });
+    canvas.controls[profile].on_press = Some(|_control, _session, _data| {
+        let profiles = config::list_profiles();
+        let next = match config::get_profile() {
+            Some(current) => profiles
+                .iter()
+                .skip_while(|p| **p != current)
+                .nth(1)
+                .cloned(),
+            None => profiles.first().cloned(),
+        };
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(move |_sk, app, o| {
+                config::switch_profile(app, o, next);
+            }));
+        }
+    });
+
+    // Makes the current screen arrangement the default
+    let save = canvas.button(135., 8., 70., 32., "Save".into());
+    canvas.controls[save].set_tooltip("Save screen layout as default");
+    canvas.controls[save].on_press = Some(|_control, _session, _data| {
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(|_sk, app, o| {
+                config::save_layout(app, o);
+            }));
+        }
+    });
+
+    // Playspace controls, need Monado
+    let recenter = canvas.button(211., 8., 95., 32., "Recenter".into());
+    canvas.controls[recenter].set_tooltip("Recenter playspace");
+    canvas.controls[recenter].on_press = Some(|_control, _session, _data| {
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(|_sk, app, _o| match app.monado.as_mut() {
+                Some(monado) => {
+                    if let Err(e) = monado.recenter() {
+                        config::report_error(&e);
+                    }
+                }
+                None => config::report_error("Recenter needs Monado"),
+            }));
+        }
     });
 
-    canvas.fg_color = color_parse("#CCBBAA");
-    canvas.bg_color = color_parse("#406050");
-    // Bottom row
+    let drag = canvas.button(312., 8., 70., 32., "Drag".into());
+    canvas.controls[drag].set_tooltip("Toggle playspace drag: grip empty space to move");
+    canvas.controls[drag].on_press = Some(|_control, _session, _data| {
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(|_sk, app, _o| match app.monado.as_mut() {
+                Some(monado) => monado.drag_enabled = !monado.drag_enabled,
+                None => config::report_error("Playspace drag needs Monado"),
+            }));
+        }
+    });
+
+    // Bottom row: settings, sound output, workspaces if there's a window manager to talk to,
+    // keyboard and one button per screen
+    let show_wm = wm::is_available();
     let num_buttons = screens.len() + 1;
-    let button_width = 360. / num_buttons as f32;
-    let mut button_x = 40.;
+    let mut weights = vec![40., 40.];
+    if show_wm {
+        weights.push(40.);
+    }
+    let remaining = 400. - weights.iter().sum::<f32>();
+    weights.extend(std::iter::repeat(remaining / num_buttons as f32).take(num_buttons));
+    let mut bottom_row = FlexLayout::row()
+        .with_padding(vec2(theme.button_padding, 0.))
+        .layout(Rect::new(0., 162., 400., 36.), &weights)
+        .into_iter();
+
+    let r = bottom_row.next().unwrap();
+    let settings = canvas.button(r.x, r.y, r.w, r.h, "☰".into());
+    canvas.controls[settings].set_font(&session.config.symbol_font);
+    canvas.controls[settings].set_tooltip("Settings: theme, colors, bed mode and power");
+    canvas.controls[settings].on_press = Some(|_control, _session, _data| {
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(|_sk, _app, o| {
+                for overlay in o {
+                    if &*overlay.name == "Settings" {
+                        overlay.want_visible = !overlay.want_visible;
+                    }
+                }
+            }));
+        }
+    });
+
+    let r = bottom_row.next().unwrap();
+    let sound = canvas.button(r.x, r.y, r.w, r.h, "♪".into());
+    canvas.controls[sound].set_font(&session.config.symbol_font);
+    canvas.controls[sound].set_tooltip("Pick the output for UI sounds");
+    canvas.controls[sound].on_press = Some(|_control, _session, _data| {
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(|_sk, _app, o| {
+                for overlay in o {
+                    if &*overlay.name == "Audio" {
+                        overlay.want_visible = !overlay.want_visible;
+                    }
+                }
+            }));
+        }
+    });
+
+    canvas.fg_color = color_parse(&theme.toggle_text_color);
+    canvas.bg_color = color_parse(&theme.keyboard_button_color);
+
+    if show_wm {
+        let r = bottom_row.next().unwrap();
+        let workspaces = canvas.button(r.x, r.y, r.w, r.h, "WS".into());
+        canvas.controls[workspaces].set_tooltip("Show/hide workspaces and windows");
+        canvas.controls[workspaces].on_press = Some(|_control, _session, _data| {
+            if let Ok(mut tasks) = TASKS.lock() {
+                tasks.push_back(Box::new(|_sk, _app, o| {
+                    for overlay in o {
+                        if &*overlay.name == "Workspaces" {
+                            overlay.want_visible = !overlay.want_visible;
+                        }
+                    }
+                }));
+            }
+        });
+    }
 
-    let i = canvas.button(button_x + 2., 162., button_width - 4., 36., "Kbd".into());
+    let r = bottom_row.next().unwrap();
+    let i = canvas.button(r.x, r.y, r.w, r.h, "Kbd".into());
     let keyboard = &mut canvas.controls[i];
+    keyboard.set_tooltip("Show/hide keyboard, hold to reset position");
     keyboard.state = Some(WatchButtonState {
         pressed_at: Instant::now(),
         scr_idx: 0,
@@ -114,13 +382,14 @@ pub fn create_watch(session: &AppSession, screens: Vec<(usize, Arc<str>)>) -> Ov
             }
         }
     });
-    button_x += button_width;
 
-    canvas.bg_color = color_parse("#405060");
+    canvas.bg_color = color_parse(&theme.screen_button_color);
 
-    for (scr_idx, scr_name) in screens.into_iter() {
-        let i = canvas.button(button_x + 2., 162., button_width - 4., 36., scr_name);
+    for ((scr_idx, scr_name), r) in screens.into_iter().zip(bottom_row) {
+        let i = canvas.button(r.x, r.y, r.w, r.h, scr_name);
         let button = &mut canvas.controls[i];
+        button.set_tooltip("Show/hide screen, hold to reset position");
+        button.set_overflow(TextOverflow::Ellipsis);
         button.state = Some(WatchButtonState {
             pressed_at: Instant::now(),
             scr_idx,
@@ -145,21 +414,21 @@ pub fn create_watch(session: &AppSession, screens: Vec<(usize, Arc<str>)>) -> Ov
                         }));
                     } else {
                         tasks.push_back(Box::new(move |_sk, app, o| {
+                            o[scr_idx].saved_transform = None;
                             o[scr_idx].reset(app);
                         }));
                     }
                 }
             }
         });
-        button_x += button_width;
     }
 
     let relative_to = RelativeTo::Hand(session.watch_hand);
 
     OverlayData {
         name: "Watch".into(),
-        size: (400, 200),
-        width: 0.065 * session.config.watch_scale,
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.065 * session.config.watch_scale * session.config.ui_scale,
         backend: Box::new(canvas),
         want_visible: true,
         relative_to,
@@ -169,6 +438,95 @@ pub fn create_watch(session: &AppSession, screens: Vec<(usize, Arc<str>)>) -> Ov
     }
 }
 
+fn get_watch_path() -> PathBuf {
+    let mut path = config_io::get_conf_d_path();
+    path.push("watch.yaml");
+    path
+}
+
+// Saves where the watch was put in edit mode; the config watcher picks the file up and applies it
+pub fn save_watch_placement(hand: usize, pos: Vec3, rot: Quat) {
+    let mut watch = serde_yaml::Mapping::new();
+    let hand = if hand == 0 { "right" } else { "left" };
+    watch.insert("watch_hand".into(), hand.into());
+    let result = serde_yaml::to_value(pos.to_array())
+        .and_then(|pos| {
+            watch.insert("watch_pos".into(), pos);
+            serde_yaml::to_value(rot.to_array())
+        })
+        .and_then(|rot| {
+            watch.insert("watch_rot".into(), rot);
+            serde_yaml::to_string(&watch)
+        })
+        .map_err(|e| e.to_string())
+        .and_then(|yaml| std::fs::write(get_watch_path(), yaml).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        config::report_error(&format!("Failed to save watch placement: {}", e));
+    }
+}
+
+// Moves the watch to the same spot on the other wrist
+pub fn mirror_watch(session: &mut AppSession) {
+    session.watch_pos.x = -session.watch_pos.x;
+    let rot = session.watch_rot;
+    session.watch_rot = Quat::from_xyzw(rot.x, -rot.y, -rot.z, rot.w);
+}
+
+// Recreates the overlays built from the session's config and theme
+pub fn rebuild_ui(app: &mut AppState, overlays: &mut [OverlayData]) {
+    for overlay in overlays.iter_mut() {
+        let rebuilt = match &*overlay.name {
+            "Kbd" => create_keyboard(&app.session),
+            "Watch" => create_watch(&app.session, app.screens.clone()),
+            "Radial" => create_radial_menu(&app.session),
+            "Toast" => create_toast(&app.session),
+            "Log" => create_log_viewer(&app.session),
+            "History" => create_history(&app.session),
+            "Edit" => create_edit_hud(&app.session),
+            "Magnifier" => create_magnifier(&app.session),
+            "Numpad" => create_numpad(&app.session),
+            "Dropdown" => create_dropdown(&app.session),
+            "Audio" => create_audio_panel(&app.session),
+            "Power" => create_power_menu(&app.session),
+            "Settings" => create_settings_menu(&app.session),
+            "Colors" => create_colors_panel(&app.session),
+            "Picker" => create_picker(&app.session),
+            "Wizard" => create_wizard(&app.session, &app.screens),
+            "Workspaces" => create_wm_panel(&app.session),
+            "Taskbar" => create_taskbar(&app.session),
+            "Perf" => create_perf_hud(&app.session),
+            name => match rebuild_panel(&app.session, name) {
+                Some(panel) => panel,
+                None => continue,
+            },
+        };
+        overlay.release_gfx(app);
+        let old = std::mem::replace(overlay, rebuilt);
+        keep_placement(&old, overlay);
+    }
+}
+
+// A rebuild is for new looks, so the overlay stays where it was put. Hand-attached ones take
+// their new spawn point, that is where a changed watch placement comes in.
+fn keep_placement(old: &OverlayData, overlay: &mut OverlayData) {
+    overlay.want_visible = old.want_visible;
+    // shown again on the next frame, from where it was
+    overlay.saved_transform = if old.visible {
+        Some(old.transform)
+    } else {
+        old.saved_transform
+    };
+    overlay.transform = old.transform;
+    if !matches!(overlay.relative_to, RelativeTo::Hand(_)) {
+        overlay.spawn_point = old.spawn_point;
+        overlay.spawn_rotation = old.spawn_rotation;
+    }
+}
+
+struct WatchData {
+    metric: Option<MetricConfig>,
+}
+
 struct WatchButtonState {
     pressed_at: Instant,
     scr_idx: usize,
@@ -0,0 +1,99 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use tokio::runtime::Runtime;
+
+use crate::config::WeatherConfig;
+
+// Providers don't update more often than this anyway
+const MIN_INTERVAL_MINS: u32 = 5;
+
+static WEATHER_TEXT: Lazy<Mutex<Arc<str>>> = Lazy::new(|| Mutex::new("".into()));
+
+// Open-Meteo's response, which compatible providers share
+#[derive(Deserialize)]
+struct Forecast {
+    current_weather: CurrentWeather,
+}
+
+#[derive(Deserialize)]
+struct CurrentWeather {
+    temperature: f32,
+    weathercode: u32,
+}
+
+// Condition glyph and temperature, empty until the first successful fetch
+pub fn get_weather_text() -> Arc<str> {
+    WEATHER_TEXT
+        .lock()
+        .map(|t| t.clone())
+        .unwrap_or_else(|_| "".into())
+}
+
+pub fn start(rt: &Runtime, config: WeatherConfig) {
+    rt.spawn(async move {
+        let client = reqwest::Client::new();
+        let interval = Duration::from_secs(config.interval_mins.max(MIN_INTERVAL_MINS) as u64 * 60);
+        info!("Fetching weather from {}", config.provider_url);
+
+        loop {
+            match fetch(&client, &config).await {
+                Ok(text) => {
+                    if let Ok(mut weather) = WEATHER_TEXT.lock() {
+                        *weather = text.into();
+                    }
+                }
+                // the last known conditions stay up
+                Err(e) => warn!("Failed to fetch weather: {}", e),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+async fn fetch(client: &reqwest::Client, config: &WeatherConfig) -> Result<String, reqwest::Error> {
+    let unit = if config.fahrenheit {
+        "fahrenheit"
+    } else {
+        "celsius"
+    };
+    let forecast: Forecast = client
+        .get(&config.provider_url)
+        .query(&[
+            ("latitude", config.latitude.to_string()),
+            ("longitude", config.longitude.to_string()),
+            ("current_weather", "true".to_string()),
+            ("temperature_unit", unit.to_string()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let current = forecast.current_weather;
+    Ok(format!(
+        "{} {:.0}°",
+        condition_glyph(current.weathercode),
+        current.temperature
+    ))
+}
+
+// WMO weather interpretation codes, as used by Open-Meteo
+fn condition_glyph(code: u32) -> &'static str {
+    match code {
+        0 => "☀",
+        1 | 2 => "⛅",
+        3 => "☁",
+        45 | 48 => "≋",
+        51..=67 | 80..=82 => "☂",
+        71..=77 | 85 | 86 => "❄",
+        95..=99 => "⚡",
+        _ => "?",
+    }
+}
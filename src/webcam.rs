@@ -0,0 +1,256 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use jpeg_decoder::PixelFormat;
+use log::{info, warn};
+use stereokit::{SkDraw, StereoKitMultiThread, Tex};
+use v4l::{
+    buffer::Type, io::traits::CaptureStream, prelude::MmapStream, video::Capture, Device, FourCC,
+    Fraction,
+};
+
+use crate::{
+    config::WebcamConfig,
+    desktop::frame::{texture_load_memptr, FrameFormat, MemPtrFrame},
+    gl::egl::DRM_FORMAT_ABGR8888,
+    interactions::DummyInteractionHandler,
+    overlay::{OverlayData, OverlayRenderer, SplitOverlayBackend},
+    AppSession, AppState, TASKS,
+};
+
+// Devices may be busy or unplugged for a while
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+const NUM_BUFFERS: u32 = 4;
+
+// A device that stops sending frames is opened again, rather than blocking the thread for good
+const FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct WebcamFrame {
+    w: u32,
+    h: u32,
+    rgba: Vec<u8>,
+    dirty: bool,
+}
+
+// Captures from a V4L2 device on its own thread while the overlay is shown
+pub struct WebcamRenderer {
+    name: Arc<str>,
+    config: WebcamConfig,
+    frame: Arc<Mutex<WebcamFrame>>,
+    // bumped to stop the current capture thread
+    generation: Arc<AtomicU32>,
+}
+
+impl WebcamRenderer {
+    fn start(&mut self) {
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        let name = self.name.clone();
+        let config = self.config.clone();
+        let frame = self.frame.clone();
+        let current = self.generation.clone();
+        thread::spawn(move || {
+            let mut warned = false;
+            while current.load(Ordering::Relaxed) == generation {
+                if let Err(e) = capture(&name, &config, &frame, &current, generation) {
+                    if !warned {
+                        warn!("{}: {}", config.device, e);
+                        warned = true;
+                    }
+                }
+                thread::sleep(RETRY_INTERVAL);
+            }
+        });
+    }
+
+    fn stop(&mut self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Reads frames until the generation changes or the device fails
+fn capture(
+    name: &Arc<str>,
+    config: &WebcamConfig,
+    frame: &Mutex<WebcamFrame>,
+    current: &AtomicU32,
+    generation: u32,
+) -> std::io::Result<()> {
+    let dev = Device::with_path(&config.device)?;
+
+    let mut format = dev.format()?;
+    format.width = config.width;
+    format.height = config.height;
+    format.fourcc = FourCC::new(b"MJPG");
+    let mut format = dev.set_format(&format)?;
+    if format.fourcc != FourCC::new(b"MJPG") {
+        format.fourcc = FourCC::new(b"YUYV");
+        format = dev.set_format(&format)?;
+    }
+    let mut params = dev.params()?;
+    params.interval = Fraction::new(1, config.fps.max(1));
+    let _ = dev.set_params(&params);
+    info!(
+        "{}: Capturing {}x{} {}",
+        config.device, format.width, format.height, format.fourcc
+    );
+    // the device picks the closest size it has, the overlay is made to match
+    if (format.width, format.height) != (config.width, config.height) {
+        resize_overlay(name.clone(), format.width, format.height);
+    }
+
+    let mut stream = MmapStream::with_buffers(&dev, Type::VideoCapture, NUM_BUFFERS)?;
+    stream.set_timeout(FRAME_TIMEOUT);
+    while current.load(Ordering::Relaxed) == generation {
+        let (buf, meta) = stream.next()?;
+        let data = &buf[..(meta.bytesused as usize).min(buf.len())];
+        let decoded = match &format.fourcc.repr {
+            b"MJPG" => decode_mjpeg(data),
+            b"YUYV" => decode_yuyv(data, format.width, format.height),
+            _ => None,
+        };
+        // a corrupt frame now and then is normal for MJPEG
+        let Some((w, h, rgba)) = decoded else {
+            continue;
+        };
+        if let Ok(mut frame) = frame.lock() {
+            *frame = WebcamFrame {
+                w,
+                h,
+                rgba,
+                dirty: true,
+            };
+        }
+    }
+    Ok(())
+}
+
+// Sets the overlay up again at the size the device went with. This stops the capture, the
+// one started on the next show finds the size already matching.
+fn resize_overlay(name: Arc<str>, width: u32, height: u32) {
+    let Ok(mut tasks) = TASKS.lock() else {
+        return;
+    };
+    tasks.push_back(Box::new(move |_sk, app, overlays| {
+        let Some(overlay) = overlays.iter_mut().find(|o| o.name == name) else {
+            return;
+        };
+        let size = (width as i32, height as i32);
+        if overlay.size == size {
+            return;
+        }
+        overlay.hide(app);
+        overlay.release_gfx(app);
+        overlay.size = size;
+    }));
+}
+
+fn decode_mjpeg(data: &[u8]) -> Option<(u32, u32, Vec<u8>)> {
+    let mut decoder = jpeg_decoder::Decoder::new(data);
+    let pixels = decoder.decode().ok()?;
+    let info = decoder.info()?;
+    let rgba = match info.pixel_format {
+        PixelFormat::RGB24 => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        PixelFormat::L8 => pixels.iter().flat_map(|l| [*l, *l, *l, 255]).collect(),
+        _ => return None,
+    };
+    Some((info.width as _, info.height as _, rgba))
+}
+
+// Two pixels per four bytes: Y0 U Y1 V, BT.601
+fn decode_yuyv(data: &[u8], w: u32, h: u32) -> Option<(u32, u32, Vec<u8>)> {
+    if data.len() < (w * h * 2) as usize {
+        return None;
+    }
+    let mut rgba = Vec::with_capacity((w * h * 4) as usize);
+    for px in data.chunks_exact(4).take((w * h / 2) as usize) {
+        let u = px[1] as f32 - 128.;
+        let v = px[3] as f32 - 128.;
+        for y in [px[0], px[2]] {
+            let y = y as f32;
+            rgba.extend_from_slice(&[
+                (y + 1.402 * v).clamp(0., 255.) as u8,
+                (y - 0.344 * u - 0.714 * v).clamp(0., 255.) as u8,
+                (y + 1.772 * u).clamp(0., 255.) as u8,
+                255,
+            ]);
+        }
+    }
+    Some((w, h, rgba))
+}
+
+impl OverlayRenderer for WebcamRenderer {
+    fn init(&mut self, _sk: &SkDraw, _app: &mut AppState) {
+        self.start();
+    }
+    fn pause(&mut self, _app: &mut AppState) {
+        self.stop();
+    }
+    fn resume(&mut self, _app: &mut AppState) {
+        self.start();
+    }
+    fn render(&mut self, sk: &SkDraw, tex: &Tex, _app: &mut AppState) {
+        let Ok(mut frame) = self.frame.lock() else {
+            return;
+        };
+        if !frame.dirty {
+            return;
+        }
+        frame.dirty = false;
+
+        let handle = unsafe { sk.tex_get_surface(tex) as usize as u32 };
+        texture_load_memptr(
+            handle,
+            &MemPtrFrame {
+                fmt: FrameFormat {
+                    w: frame.w,
+                    h: frame.h,
+                    format: DRM_FORMAT_ABGR8888,
+                    ..Default::default()
+                },
+                ptr: frame.rgba.as_ptr() as usize,
+            },
+        );
+    }
+    fn release(&mut self, _app: &mut AppState) {
+        self.stop();
+    }
+}
+
+pub fn create_webcam(session: &AppSession, idx: usize, config: &WebcamConfig) -> OverlayData {
+    let name = config
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("Webcam {}", idx + 1));
+    let name: Arc<str> = name.into();
+    let renderer = WebcamRenderer {
+        name: name.clone(),
+        config: config.clone(),
+        frame: Arc::new(Mutex::new(WebcamFrame::default())),
+        generation: Arc::new(AtomicU32::new(0)),
+    };
+
+    OverlayData {
+        name,
+        size: (config.width as _, config.height as _),
+        scale: session.config.desktop_view_scale,
+        show_hide: true,
+        grabbable: true,
+        want_visible: config.visible,
+        backend: Box::new(SplitOverlayBackend {
+            renderer: Box::new(renderer),
+            interaction: Box::new(DummyInteractionHandler),
+        }),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use glam::vec3;
+
+use crate::{
+    config::{report_error, screen_selected},
+    config_io,
+    gui::{color_parse, Canvas, TextOverflow},
+    interactions::InputState,
+    overlay::{OverlayData, RelativeTo},
+    toast::{self, NotifyLevel},
+    AppSession, TASKS,
+};
+
+const WIDTH: f32 = 600.;
+const HEIGHT: f32 = 430.;
+
+const MAX_SCREENS: usize = 6;
+
+// Trigger and grip of both pointers, as of the last frame
+static LIVE: Mutex<[(f32, f32); 2]> = Mutex::new([(0., 0.); 2]);
+
+struct WizardData {
+    screens: Vec<Arc<str>>,
+    shown: Vec<bool>,
+    watch_hand: &'static str,
+    trigger_threshold: f32,
+    grab_threshold: f32,
+}
+
+fn get_setup_path() -> PathBuf {
+    let mut path = config_io::get_conf_d_path();
+    path.push("setup.yaml");
+    path
+}
+
+// No config.yaml, and the wizard was neither saved nor skipped before
+pub fn is_first_run() -> bool {
+    !config_io::get_config_file_path("config.yaml").exists() && !get_setup_path().exists()
+}
+
+pub fn update(input: &InputState) {
+    if let Ok(mut live) = LIVE.lock() {
+        for (i, values) in live.iter_mut().enumerate() {
+            *values = input.trigger_grip(i);
+        }
+    }
+}
+
+fn live_max() -> (f32, f32) {
+    LIVE.lock()
+        .map(|live| (live[0].0.max(live[1].0), live[0].1.max(live[1].1)))
+        .unwrap_or_default()
+}
+
+// Walks through the screens to show, the watch hand and the click and grab thresholds,
+// then writes them to conf.d/setup.yaml
+pub fn create_wizard(session: &AppSession, screens: &[(usize, Arc<str>)]) -> OverlayData {
+    let config = &session.config;
+    let screens: Vec<Arc<str>> = screens
+        .iter()
+        .take(MAX_SCREENS)
+        .map(|(_, name)| name.clone())
+        .collect();
+    let shown = screens
+        .iter()
+        .map(|name| {
+            config
+                .outputs
+                .get(&**name)
+                .and_then(|o| o.visible)
+                .unwrap_or_else(|| screen_selected(&session.show_screens, name))
+        })
+        .collect();
+    let data = WizardData {
+        screens,
+        shown,
+        watch_hand: if config.watch_hand == "right" {
+            "right"
+        } else {
+            "left"
+        },
+        trigger_threshold: config.trigger_threshold,
+        grab_threshold: config.grab_threshold,
+    };
+
+    let mut canvas: Canvas<WizardData, usize> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        config.ui_scale,
+        config.canvas_supersample,
+        data,
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.font_size = theme.font_size;
+    canvas.fg_color = color_parse(&theme.text_color);
+    canvas.label(
+        20.,
+        40.,
+        WIDTH - 40.,
+        30.,
+        "Welcome! Let's set up the basics.".into(),
+    );
+
+    // 1. Screens
+    canvas.fg_color = color_parse(&theme.toggle_text_color);
+    canvas.label(
+        20.,
+        85.,
+        WIDTH - 40.,
+        30.,
+        "1. Screens to show at startup".into(),
+    );
+
+    for i in 0..canvas.data.screens.len() {
+        let x = 20. + (i % 3) as f32 * 190.;
+        let y = 100. + (i / 3) as f32 * 40.;
+
+        canvas.bg_color = color_parse(&theme.button_color);
+        canvas.fg_color = color_parse(&theme.button_text_color);
+        let toggle = canvas.toggle(x, y, 50., 30., canvas.data.shown[i]);
+        canvas.controls[toggle].state = Some(i);
+        canvas.controls[toggle].on_change = Some(|control, data| {
+            if let Some(i) = control.state {
+                data.shown[i] = control.is_on();
+            }
+        });
+
+        canvas.fg_color = color_parse(&theme.text_color);
+        let name = canvas.data.screens[i].clone();
+        let label = canvas.label(x + 58., y + 22., 120., 30., name);
+        canvas.controls[label].set_overflow(TextOverflow::Ellipsis);
+    }
+
+    // 2. Watch hand
+    canvas.fg_color = color_parse(&theme.toggle_text_color);
+    canvas.label(
+        20.,
+        210.,
+        WIDTH - 40.,
+        30.,
+        "2. Wrist to wear the watch on".into(),
+    );
+
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+    let left = canvas.button(20., 222., 90., 32., "Left".into());
+    canvas.controls[left].test_highlight = Some(|_control, data| data.watch_hand == "left");
+    canvas.controls[left].on_press = Some(|_control, _session, data| {
+        data.watch_hand = "left";
+    });
+    let right = canvas.button(120., 222., 90., 32., "Right".into());
+    canvas.controls[right].test_highlight = Some(|_control, data| data.watch_hand == "right");
+    canvas.controls[right].on_press = Some(|_control, _session, data| {
+        data.watch_hand = "right";
+    });
+
+    // 3. Thresholds, applied right away so they can be tried out
+    canvas.fg_color = color_parse(&theme.toggle_text_color);
+    canvas.label(
+        20.,
+        295.,
+        WIDTH - 40.,
+        30.,
+        "3. How far to pull the trigger and grip".into(),
+    );
+
+    canvas.fg_color = color_parse(&theme.text_color);
+    canvas.label(20., 330., 70., 30., "Click".into());
+    canvas.label(20., 362., 70., 30., "Grab".into());
+
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+    let trigger = canvas.slider(90., 316., 220., 16., 0.1..=1., 0.05);
+    canvas.controls[trigger].set_value(canvas.data.trigger_threshold);
+    canvas.controls[trigger].on_change = Some(|control, data| {
+        data.trigger_threshold = control.get_value();
+        let value = data.trigger_threshold;
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(move |_sk, app, _o| {
+                app.session.config.trigger_threshold = value;
+            }));
+        }
+    });
+    let grab = canvas.slider(90., 348., 220., 16., 0.1..=1., 0.05);
+    canvas.controls[grab].set_value(canvas.data.grab_threshold);
+    canvas.controls[grab].on_change = Some(|control, data| {
+        data.grab_threshold = control.get_value();
+        let value = data.grab_threshold;
+        if let Ok(mut tasks) = TASKS.lock() {
+            tasks.push_back(Box::new(move |_sk, app, _o| {
+                app.session.config.grab_threshold = value;
+            }));
+        }
+    });
+
+    canvas.fg_color = color_parse(&theme.text_color);
+    let trigger_live = canvas.label(330., 330., 250., 30., "".into());
+    canvas.controls[trigger_live].on_update = Some(|control, data| {
+        let (trigger, _) = live_max();
+        let state = if trigger >= data.trigger_threshold {
+            "click!"
+        } else {
+            ""
+        };
+        control.set_text(&format!(
+            "{:.2} / {:.2} {}",
+            trigger, data.trigger_threshold, state
+        ));
+    });
+    let grab_live = canvas.label(330., 362., 250., 30., "".into());
+    canvas.controls[grab_live].on_update = Some(|control, data| {
+        let (_, grip) = live_max();
+        let state = if grip >= data.grab_threshold {
+            "grab!"
+        } else {
+            ""
+        };
+        control.set_text(&format!(
+            "{:.2} / {:.2} {}",
+            grip, data.grab_threshold, state
+        ));
+    });
+
+    // Skip leaves a setup.yaml without settings, so the wizard doesn't come back
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+    let skip = canvas.button(380., 384., 90., 36., "Skip".into());
+    canvas.controls[skip].on_press = Some(|_control, _session, _data| {
+        if let Err(e) = std::fs::write(get_setup_path(), "# Setup wizard skipped\n") {
+            report_error(&format!("Failed to save setup: {}", e));
+        }
+        close_wizard();
+    });
+
+    canvas.bg_color = color_parse(&theme.keyboard_button_color);
+    let save = canvas.button(480., 384., 100., 36., "Save".into());
+    canvas.controls[save].on_press = Some(|_control, _session, data| {
+        save_setup(data);
+    });
+
+    OverlayData {
+        name: "Wizard".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.5 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: vec3(0., 0., -0.7),
+        ..Default::default()
+    }
+}
+
+fn close_wizard() {
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(|_sk, _app, o| {
+            for overlay in o.iter_mut() {
+                if &*overlay.name == "Wizard" {
+                    overlay.want_visible = false;
+                }
+            }
+        }));
+    }
+}
+
+// The config watcher picks the file up and applies it. Screens are saved as
+// outputs.<name>.visible, so patterns in show_screens keep working for outputs plugged in later.
+fn save_setup(data: &WizardData) {
+    let shown: Vec<(Arc<str>, bool)> = data
+        .screens
+        .iter()
+        .cloned()
+        .zip(data.shown.iter().copied())
+        .collect();
+
+    let mut outputs = serde_yaml::Mapping::new();
+    for (name, visible) in shown.iter() {
+        let mut output = serde_yaml::Mapping::new();
+        output.insert("visible".into(), (*visible).into());
+        outputs.insert(name.to_string().into(), output.into());
+    }
+    let mut setup = serde_yaml::Mapping::new();
+    setup.insert("outputs".into(), outputs.into());
+    setup.insert("watch_hand".into(), data.watch_hand.into());
+    setup.insert(
+        "trigger_threshold".into(),
+        (data.trigger_threshold as f64).into(),
+    );
+    setup.insert("grab_threshold".into(), (data.grab_threshold as f64).into());
+
+    let path = get_setup_path();
+    let result = serde_yaml::to_string(&setup)
+        .map_err(|e| e.to_string())
+        .and_then(|yaml| std::fs::write(&path, yaml).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        return report_error(&format!("Failed to save setup: {}", e));
+    }
+
+    if let Ok(mut tasks) = TASKS.lock() {
+        tasks.push_back(Box::new(move |_sk, app, o| {
+            for (idx, name) in app.screens.iter() {
+                if let Some((_, visible)) = shown.iter().find(|(n, _)| n == name) {
+                    o[*idx].want_visible = *visible;
+                }
+            }
+            toast::notify(
+                NotifyLevel::Info,
+                &format!("Setup saved to {}", path.to_string_lossy()),
+            );
+        }));
+    }
+    close_wizard();
+}
@@ -0,0 +1,135 @@
+use std::{
+    error::Error,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use super::{Window, WmIpc, Workspace};
+
+// Talks to Hyprland's request socket, one connection per request
+pub struct HyprlandIpc {
+    socket: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct HyprWorkspace {
+    id: i64,
+    name: String,
+    monitor: String,
+}
+
+#[derive(Deserialize)]
+struct HyprWorkspaceRef {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct HyprClient {
+    address: String,
+    title: String,
+    class: String,
+    workspace: HyprWorkspaceRef,
+}
+
+#[derive(Deserialize)]
+struct HyprActiveWindow {
+    #[serde(default)]
+    title: Option<String>,
+}
+
+impl HyprlandIpc {
+    pub fn from_env() -> Option<HyprlandIpc> {
+        let signature = std::env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+
+        // newer versions keep the socket under XDG_RUNTIME_DIR, older ones in /tmp
+        let mut candidates = vec![];
+        if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
+            candidates.push(PathBuf::from(runtime_dir).join("hypr"));
+        }
+        candidates.push(PathBuf::from("/tmp/hypr"));
+
+        candidates
+            .into_iter()
+            .map(|dir| dir.join(&signature).join(".socket.sock"))
+            .find(|path| path.exists())
+            .map(|socket| HyprlandIpc { socket })
+    }
+
+    fn request(&self, request: &str) -> Result<String, Box<dyn Error>> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        stream.write_all(request.as_bytes())?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        Ok(response)
+    }
+
+    fn dispatch(&self, dispatcher: &str) -> Result<(), Box<dyn Error>> {
+        let response = self.request(&format!("dispatch {}", dispatcher))?;
+        if response.trim() != "ok" {
+            return Err(response.into());
+        }
+        Ok(())
+    }
+}
+
+impl WmIpc for HyprlandIpc {
+    fn name(&self) -> &'static str {
+        "Hyprland"
+    }
+
+    fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    fn workspaces(&mut self) -> Result<Vec<Workspace>, Box<dyn Error>> {
+        let mut workspaces: Vec<HyprWorkspace> =
+            serde_json::from_str(&self.request("j/workspaces")?)?;
+        // special (scratchpad) workspaces have negative ids, list them after the numbered ones
+        workspaces.sort_by_key(|ws| (ws.id < 0, ws.id.abs()));
+        Ok(workspaces
+            .into_iter()
+            .map(|ws| Workspace {
+                id: ws.id,
+                name: ws.name,
+                output: ws.monitor,
+            })
+            .collect())
+    }
+
+    fn windows(&mut self) -> Result<Vec<Window>, Box<dyn Error>> {
+        let clients: Vec<HyprClient> = serde_json::from_str(&self.request("j/clients")?)?;
+        Ok(clients
+            .into_iter()
+            .map(|client| Window {
+                id: client.address,
+                title: client.title,
+                app: client.class,
+                workspace: client.workspace.name,
+            })
+            .collect())
+    }
+
+    fn focused_title(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        let active: HyprActiveWindow = serde_json::from_str(&self.request("j/activewindow")?)?;
+        Ok(active.title)
+    }
+
+    fn switch_workspace(&mut self, workspace: &Workspace) -> Result<(), Box<dyn Error>> {
+        // "workspace -98" is refused, a special workspace is toggled by its name instead
+        if workspace.id < 0 {
+            let name = workspace
+                .name
+                .strip_prefix("special:")
+                .unwrap_or(&workspace.name);
+            return self.dispatch(&format!("togglespecialworkspace {}", name));
+        }
+        self.dispatch(&format!("workspace {}", workspace.id))
+    }
+
+    fn focus_window(&mut self, window: &Window) -> Result<(), Box<dyn Error>> {
+        self.dispatch(&format!("focuswindow address:{}", window.id))
+    }
+}
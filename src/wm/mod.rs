@@ -0,0 +1,171 @@
+use std::{
+    error::Error,
+    path::Path,
+    sync::{
+        mpsc::{channel, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::{error, info};
+use once_cell::sync::Lazy;
+
+pub mod hyprland;
+pub mod panel;
+pub mod sway;
+
+// How often workspaces and windows are re-read from the compositor
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// While reads keep failing, the wait doubles up to this
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, PartialEq)]
+pub struct Workspace {
+    pub id: i64,
+    pub name: String,
+    pub output: String,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Window {
+    pub id: String,
+    pub title: String,
+    pub app: String,
+    pub workspace: String,
+}
+
+pub enum WmCommand {
+    SwitchWorkspace(Workspace),
+    FocusWindow(Window),
+}
+
+// Window manager specific IPC, picked by what the environment points to
+pub trait WmIpc: Send {
+    fn name(&self) -> &'static str;
+    fn socket(&self) -> &Path;
+    fn workspaces(&mut self) -> Result<Vec<Workspace>, Box<dyn Error>>;
+    fn windows(&mut self) -> Result<Vec<Window>, Box<dyn Error>>;
+    fn focused_title(&mut self) -> Result<Option<String>, Box<dyn Error>>;
+    fn switch_workspace(&mut self, workspace: &Workspace) -> Result<(), Box<dyn Error>>;
+    fn focus_window(&mut self, window: &Window) -> Result<(), Box<dyn Error>>;
+}
+
+// Last state read from the compositor; generation changes whenever it does
+#[derive(Clone, Default)]
+pub struct WmSnapshot {
+    pub generation: u64,
+    pub workspaces: Vec<Workspace>,
+    pub windows: Vec<Window>,
+    pub focused_title: Option<Arc<str>>,
+}
+
+static SNAPSHOT: Lazy<Mutex<WmSnapshot>> = Lazy::new(|| Mutex::new(WmSnapshot::default()));
+static COMMANDS: Mutex<Option<Sender<WmCommand>>> = Mutex::new(None);
+
+// Sway (or i3) when SWAYSOCK / I3SOCK is set, otherwise Hyprland
+fn connect() -> Option<Box<dyn WmIpc>> {
+    if sway::SwayIpc::socket_path().is_some() {
+        return sway::SwayIpc::from_env().map(|ipc| Box::new(ipc) as Box<dyn WmIpc>);
+    }
+    hyprland::HyprlandIpc::from_env().map(|ipc| Box::new(ipc) as Box<dyn WmIpc>)
+}
+
+pub fn is_available() -> bool {
+    sway::SwayIpc::socket_path().is_some() || hyprland::HyprlandIpc::from_env().is_some()
+}
+
+pub fn generation() -> u64 {
+    SNAPSHOT.lock().map(|s| s.generation).unwrap_or(0)
+}
+
+pub fn focused_title() -> Option<Arc<str>> {
+    SNAPSHOT.lock().ok()?.focused_title.clone()
+}
+
+pub fn snapshot() -> WmSnapshot {
+    SNAPSHOT.lock().map(|s| s.clone()).unwrap_or_default()
+}
+
+pub fn send(command: WmCommand) {
+    if let Ok(commands) = COMMANDS.lock() {
+        if let Some(commands) = commands.as_ref() {
+            let _ = commands.send(command);
+        }
+    }
+}
+
+// Polls the window manager on its own thread, running commands as they come in. Called again
+// after the compositor comes back, the old thread then stops once its channel is replaced, or
+// once the compositor's socket is gone.
+pub fn start() {
+    let Some(mut ipc) = connect() else {
+        return;
+    };
+    info!("Using {} IPC", ipc.name());
+
+    let (tx, rx) = channel();
+    if let Ok(mut commands) = COMMANDS.lock() {
+        *commands = Some(tx);
+    }
+
+    let mut interval = POLL_INTERVAL;
+    thread::spawn(move || loop {
+        if !ipc.socket().exists() {
+            info!("{} IPC socket is gone, stopping", ipc.name());
+            return;
+        }
+
+        interval = if refresh(ipc.as_mut()) {
+            POLL_INTERVAL
+        } else {
+            (interval * 2).min(MAX_POLL_INTERVAL)
+        };
+
+        match rx.recv_timeout(interval) {
+            Ok(command) => {
+                let result = match &command {
+                    WmCommand::SwitchWorkspace(ws) => ipc.switch_workspace(ws),
+                    WmCommand::FocusWindow(win) => ipc.focus_window(win),
+                };
+                if let Err(e) = result {
+                    error!("{}: {}", ipc.name(), e);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    });
+}
+
+type WmState = (Vec<Workspace>, Vec<Window>, Option<String>);
+
+fn read_state(ipc: &mut dyn WmIpc) -> Result<WmState, Box<dyn Error>> {
+    Ok((ipc.workspaces()?, ipc.windows()?, ipc.focused_title()?))
+}
+
+// Returns false when the compositor could not be read
+fn refresh(ipc: &mut dyn WmIpc) -> bool {
+    let (workspaces, windows, title) = match read_state(ipc) {
+        Ok(state) => state,
+        Err(e) => {
+            error!("{}: {}", ipc.name(), e);
+            return false;
+        }
+    };
+
+    let Ok(mut snapshot) = SNAPSHOT.lock() else {
+        return true;
+    };
+    let changed = snapshot.focused_title.as_deref() != title.as_deref()
+        || snapshot.workspaces != workspaces
+        || snapshot.windows != windows;
+    if changed {
+        snapshot.generation += 1;
+        snapshot.workspaces = workspaces;
+        snapshot.windows = windows;
+        snapshot.focused_title = title.map(Arc::from);
+    }
+    true
+}
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use glam::Vec3;
+
+use crate::{
+    gui::{color_parse, Canvas, Control},
+    overlay::{OverlayData, RelativeTo},
+    AppSession,
+};
+
+use super::{generation, send, snapshot, WmCommand, WmSnapshot};
+
+const WIDTH: f32 = 600.;
+const HEIGHT: f32 = 360.;
+const ROW_HEIGHT: f32 = 32.;
+
+// Lists workspaces and windows; selecting one switches to it.
+// Each list keeps the snapshot generation its items were built from.
+pub fn create_wm_panel(session: &AppSession) -> OverlayData {
+    let mut canvas: Canvas<(), u64> = Canvas::new(
+        WIDTH as _,
+        HEIGHT as _,
+        session.config.ui_scale,
+        session.config.canvas_supersample,
+        (),
+    );
+    let theme = &session.theme;
+
+    canvas.bg_color = color_parse(&theme.watch_bg_color);
+    canvas.panel(0., 0., WIDTH, HEIGHT);
+
+    canvas.font_size = theme.font_size;
+    canvas.fg_color = color_parse(&theme.text_color);
+    canvas.label(10., 30., 200., 30., "Workspaces".into());
+    canvas.label(220., 30., 370., 30., "Windows".into());
+
+    canvas.bg_color = color_parse(&theme.button_color);
+    canvas.fg_color = color_parse(&theme.button_text_color);
+
+    let workspaces = canvas.list(10., 45., 200., HEIGHT - 55., ROW_HEIGHT);
+    canvas.controls[workspaces].on_update = Some(|control, _data| {
+        update_items(control, |s| {
+            s.workspaces
+                .iter()
+                .map(|ws| format!("{}: {}", ws.output, ws.name).into())
+                .collect()
+        });
+    });
+    canvas.controls[workspaces].on_select = Some(|control, row, _data| {
+        let snapshot = snapshot();
+        if control.state != Some(snapshot.generation) {
+            return;
+        }
+        if let Some(ws) = snapshot.workspaces.get(row) {
+            send(WmCommand::SwitchWorkspace(ws.clone()));
+        }
+    });
+
+    let windows = canvas.list(220., 45., 370., HEIGHT - 55., ROW_HEIGHT);
+    canvas.controls[windows].on_update = Some(|control, _data| {
+        update_items(control, |s| {
+            s.windows
+                .iter()
+                .map(|win| format!("{} · {}: {}", win.workspace, win.app, win.title).into())
+                .collect()
+        });
+    });
+    canvas.controls[windows].on_select = Some(|control, row, _data| {
+        let snapshot = snapshot();
+        if control.state != Some(snapshot.generation) {
+            return;
+        }
+        if let Some(win) = snapshot.windows.get(row) {
+            send(WmCommand::FocusWindow(win.clone()));
+        }
+    });
+
+    OverlayData {
+        name: "Workspaces".into(),
+        size: (canvas.width as _, canvas.height as _),
+        width: 0.4 * session.config.ui_scale,
+        backend: Box::new(canvas),
+        relative_to: RelativeTo::None,
+        spawn_point: Vec3::NEG_Z * 0.6,
+        ..Default::default()
+    }
+}
+
+fn update_items(control: &mut Control<(), u64>, items: fn(&WmSnapshot) -> Vec<Arc<str>>) {
+    if control.state == Some(generation()) {
+        return;
+    }
+    let snapshot = snapshot();
+    control.set_items(items(&snapshot));
+    control.state = Some(snapshot.generation);
+}
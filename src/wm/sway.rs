@@ -0,0 +1,202 @@
+use std::{
+    error::Error,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{Window, WmIpc, Workspace};
+
+const MAGIC: &[u8] = b"i3-ipc";
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const GET_TREE: u32 = 4;
+
+// After the socket breaks, e.g. on "swaymsg reload", reconnect waiting this long and doubling
+const RECONNECT_DELAY: Duration = Duration::from_millis(250);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(8);
+const MAX_RECONNECT_ATTEMPTS: u32 = 6;
+
+// Sway and i3 share the same protocol: magic, length, type, then a JSON payload
+pub struct SwayIpc {
+    stream: UnixStream,
+    socket: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct SwayWorkspace {
+    num: i64,
+    name: String,
+    output: String,
+}
+
+#[derive(Deserialize)]
+struct CommandResult {
+    success: bool,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl SwayIpc {
+    pub fn socket_path() -> Option<PathBuf> {
+        std::env::var_os("SWAYSOCK")
+            .or_else(|| std::env::var_os("I3SOCK"))
+            .map(PathBuf::from)
+    }
+
+    pub fn from_env() -> Option<SwayIpc> {
+        let socket = Self::socket_path()?;
+        let stream = UnixStream::connect(&socket).ok()?;
+        Some(SwayIpc { stream, socket })
+    }
+
+    // Sends the message, and on a broken socket reconnects and sends it once more
+    fn request(&mut self, msg_type: u32, payload: &str) -> Result<String, Box<dyn Error>> {
+        match self.try_request(msg_type, payload) {
+            Err(e) if e.is::<std::io::Error>() => {
+                warn!("Sway IPC connection lost: {}", e);
+                self.reconnect()?;
+                self.try_request(msg_type, payload)
+            }
+            result => result,
+        }
+    }
+
+    fn reconnect(&mut self) -> Result<(), Box<dyn Error>> {
+        let path = Self::socket_path().ok_or("SWAYSOCK is no longer set")?;
+        let mut delay = RECONNECT_DELAY;
+        for _ in 0..MAX_RECONNECT_ATTEMPTS {
+            thread::sleep(delay);
+            if let Ok(stream) = UnixStream::connect(&path) {
+                info!("Sway IPC reconnected");
+                self.stream = stream;
+                return Ok(());
+            }
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+        Err("Could not reconnect to the Sway IPC socket".into())
+    }
+
+    fn try_request(&mut self, msg_type: u32, payload: &str) -> Result<String, Box<dyn Error>> {
+        let mut msg = MAGIC.to_vec();
+        msg.extend((payload.len() as u32).to_ne_bytes());
+        msg.extend(msg_type.to_ne_bytes());
+        msg.extend(payload.as_bytes());
+        self.stream.write_all(&msg)?;
+
+        let mut header = [0u8; 14];
+        self.stream.read_exact(&mut header)?;
+        if &header[..6] != MAGIC {
+            return Err("Invalid reply header".into());
+        }
+        let len = u32::from_ne_bytes(header[6..10].try_into()?) as usize;
+        let mut reply = vec![0u8; len];
+        self.stream.read_exact(&mut reply)?;
+        Ok(String::from_utf8(reply)?)
+    }
+
+    fn run_command(&mut self, command: &str) -> Result<(), Box<dyn Error>> {
+        let results: Vec<CommandResult> =
+            serde_json::from_str(&self.request(RUN_COMMAND, command)?)?;
+        match results.into_iter().find(|r| !r.success) {
+            Some(failed) => Err(failed.error.unwrap_or_default().into()),
+            None => Ok(()),
+        }
+    }
+
+    fn tree(&mut self) -> Result<Value, Box<dyn Error>> {
+        Ok(serde_json::from_str(&self.request(GET_TREE, "")?)?)
+    }
+}
+
+// Windows are the leaves of the tree that have an app_id (Wayland) or window class (X11)
+fn collect_windows(node: &Value, workspace: &str, out: &mut Vec<Window>) {
+    let workspace = match node["type"].as_str() {
+        Some("workspace") => node["name"].as_str().unwrap_or(workspace),
+        _ => workspace,
+    };
+
+    let app = node["app_id"]
+        .as_str()
+        .or_else(|| node["window_properties"]["class"].as_str());
+    if let (Some(app), Some(id)) = (app, node["id"].as_i64()) {
+        out.push(Window {
+            id: id.to_string(),
+            title: node["name"].as_str().unwrap_or_default().to_string(),
+            app: app.to_string(),
+            workspace: workspace.to_string(),
+        });
+    }
+
+    for key in ["nodes", "floating_nodes"] {
+        if let Some(children) = node[key].as_array() {
+            for child in children {
+                collect_windows(child, workspace, out);
+            }
+        }
+    }
+}
+
+fn find_focused(node: &Value) -> Option<&Value> {
+    if node["focused"].as_bool() == Some(true) {
+        return Some(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[*key].as_array())
+        .flatten()
+        .find_map(find_focused)
+}
+
+impl WmIpc for SwayIpc {
+    fn name(&self) -> &'static str {
+        "Sway"
+    }
+
+    fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    fn workspaces(&mut self) -> Result<Vec<Workspace>, Box<dyn Error>> {
+        let workspaces: Vec<SwayWorkspace> =
+            serde_json::from_str(&self.request(GET_WORKSPACES, "")?)?;
+        Ok(workspaces
+            .into_iter()
+            .map(|ws| Workspace {
+                id: ws.num,
+                name: ws.name,
+                output: ws.output,
+            })
+            .collect())
+    }
+
+    fn windows(&mut self) -> Result<Vec<Window>, Box<dyn Error>> {
+        let mut windows = vec![];
+        collect_windows(&self.tree()?, "", &mut windows);
+        Ok(windows)
+    }
+
+    fn focused_title(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        let tree = self.tree()?;
+        // a focused workspace or output has no window title to show
+        Ok(find_focused(&tree)
+            .filter(|node| matches!(node["type"].as_str(), Some("con" | "floating_con")))
+            .and_then(|node| node["name"].as_str())
+            .map(str::to_string))
+    }
+
+    fn switch_workspace(&mut self, workspace: &Workspace) -> Result<(), Box<dyn Error>> {
+        let name = workspace.name.replace('"', "\\\"");
+        self.run_command(&format!("workspace \"{}\"", name))
+    }
+
+    fn focus_window(&mut self, window: &Window) -> Result<(), Box<dyn Error>> {
+        self.run_command(&format!("[con_id={}] focus", window.id))
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    ffi::{c_char, c_int, c_uint, c_ulong, c_void},
+    ptr,
+};
+
+use glam::Vec2;
+use libloading::Library;
+use log::info;
+
+use crate::{
+    input::{InputProvider, MOUSE_BACK, MOUSE_FORWARD, MOUSE_LEFT, MOUSE_MIDDLE, MOUSE_RIGHT},
+    keyboard::{CAPS_LOCK, MODS_TO_KEYS, NUM_LOCK},
+};
+
+// Input through the XTest extension, loaded at runtime. X takes pointer positions in
+// root window coordinates and X keycodes, so multi-monitor layouts map as they are.
+
+type Display = c_void;
+
+type OpenDisplayFn = unsafe extern "C" fn(*const c_char) -> *mut Display;
+type CloseDisplayFn = unsafe extern "C" fn(*mut Display) -> c_int;
+type FlushFn = unsafe extern "C" fn(*mut Display) -> c_int;
+type FakeMotionFn = unsafe extern "C" fn(*mut Display, c_int, c_int, c_int, c_ulong) -> c_int;
+type FakeButtonFn = unsafe extern "C" fn(*mut Display, c_uint, c_int, c_ulong) -> c_int;
+type FakeKeyFn = unsafe extern "C" fn(*mut Display, c_uint, c_int, c_ulong) -> c_int;
+type GetIndicatorStateFn = unsafe extern "C" fn(*mut Display, c_uint, *mut c_uint) -> c_int;
+
+const BUTTON_WHEEL_UP: c_uint = 4;
+const BUTTON_WHEEL_DOWN: c_uint = 5;
+
+const XKB_USE_CORE_KBD: c_uint = 0x0100;
+// indicator bits of the stock keymaps
+const INDICATOR_CAPS_LOCK: c_uint = 0x1;
+const INDICATOR_NUM_LOCK: c_uint = 0x2;
+
+pub struct XTestProvider {
+    display: *mut Display,
+    close_display: CloseDisplayFn,
+    flush: FlushFn,
+    fake_motion: FakeMotionFn,
+    fake_button: FakeButtonFn,
+    fake_key: FakeKeyFn,
+    get_indicator_state: Option<GetIndicatorStateFn>,
+    // the function pointers above are only valid while these are loaded
+    _xlib: Library,
+    _xtst: Library,
+    mouse_moved: bool,
+    cur_modifiers: u8,
+    // X keycodes and buttons that are down
+    held_keys: RefCell<BTreeSet<c_uint>>,
+    held_buttons: RefCell<BTreeSet<c_uint>>,
+}
+
+// The display is only ever used from behind the INPUT mutex
+unsafe impl Send for XTestProvider {}
+
+impl XTestProvider {
+    // None if there is no X server or it lacks XTest
+    pub fn try_new() -> Option<Self> {
+        std::env::var_os("DISPLAY")?;
+        unsafe {
+            let xlib = Library::new("libX11.so.6")
+                .map_err(|e| info!("libX11 not available: {}", e))
+                .ok()?;
+            let xtst = Library::new("libXtst.so.6")
+                .map_err(|e| info!("libXtst not available: {}", e))
+                .ok()?;
+
+            let open_display: OpenDisplayFn = *xlib.get(b"XOpenDisplay\0").ok()?;
+            let close_display: CloseDisplayFn = *xlib.get(b"XCloseDisplay\0").ok()?;
+            let flush: FlushFn = *xlib.get(b"XFlush\0").ok()?;
+            let fake_motion: FakeMotionFn = *xtst.get(b"XTestFakeMotionEvent\0").ok()?;
+            let fake_button: FakeButtonFn = *xtst.get(b"XTestFakeButtonEvent\0").ok()?;
+            let fake_key: FakeKeyFn = *xtst.get(b"XTestFakeKeyEvent\0").ok()?;
+            let get_indicator_state: Option<GetIndicatorStateFn> =
+                xlib.get(b"XkbGetIndicatorState\0").ok().map(|f| *f);
+
+            let display = open_display(ptr::null());
+            if display.is_null() {
+                return None;
+            }
+
+            Some(XTestProvider {
+                display,
+                close_display,
+                flush,
+                fake_motion,
+                fake_button,
+                fake_key,
+                get_indicator_state,
+                _xlib: xlib,
+                _xtst: xtst,
+                mouse_moved: false,
+                cur_modifiers: 0,
+                held_keys: RefCell::new(BTreeSet::new()),
+                held_buttons: RefCell::new(BTreeSet::new()),
+            })
+        }
+    }
+}
+
+impl Drop for XTestProvider {
+    fn drop(&mut self) {
+        self.release_all();
+        unsafe { (self.close_display)(self.display) };
+    }
+}
+
+impl InputProvider for XTestProvider {
+    fn mouse_move(&mut self, pos: Vec2) {
+        if self.mouse_moved {
+            return;
+        }
+        self.mouse_moved = true;
+
+        unsafe {
+            // -1: the screen the pointer is on
+            (self.fake_motion)(self.display, -1, pos.x as _, pos.y as _, 0);
+            (self.flush)(self.display);
+        }
+    }
+    fn send_button(&self, button: u16, down: bool) {
+        let button = match button {
+            MOUSE_LEFT => 1,
+            MOUSE_MIDDLE => 2,
+            MOUSE_RIGHT => 3,
+            MOUSE_BACK => 8,
+            MOUSE_FORWARD => 9,
+            _ => return,
+        };
+        track(&self.held_buttons, button, down);
+        unsafe {
+            (self.fake_button)(self.display, button, down as _, 0);
+            (self.flush)(self.display);
+        }
+    }
+    fn wheel(&self, delta: i32) {
+        let button = if delta > 0 {
+            BUTTON_WHEEL_UP
+        } else {
+            BUTTON_WHEEL_DOWN
+        };
+        unsafe {
+            for _ in 0..delta.unsigned_abs() {
+                (self.fake_button)(self.display, button, 1, 0);
+                (self.fake_button)(self.display, button, 0, 0);
+            }
+            (self.flush)(self.display);
+        }
+    }
+    fn set_modifiers(&mut self, modifiers: u8) {
+        let changed = self.cur_modifiers ^ modifiers;
+        for i in 0..7 {
+            let m = 1 << i;
+            if changed & m != 0 {
+                let vk = MODS_TO_KEYS.get(m).unwrap()[0] as u16;
+                self.send_key(vk, modifiers & m != 0);
+            }
+        }
+        self.cur_modifiers = modifiers;
+    }
+    // key: already an X keycode
+    fn send_key(&self, key: u16, down: bool) {
+        track(&self.held_keys, key as _, down);
+        unsafe {
+            (self.fake_key)(self.display, key as _, down as _, 0);
+            (self.flush)(self.display);
+        }
+    }
+    fn set_desktop_extent(&mut self, _extent: Vec2) {}
+    fn on_new_frame(&mut self) {
+        self.mouse_moved = false;
+    }
+    fn release_all(&mut self) {
+        let keys = std::mem::take(&mut *self.held_keys.borrow_mut());
+        let buttons = std::mem::take(&mut *self.held_buttons.borrow_mut());
+        unsafe {
+            for key in keys {
+                (self.fake_key)(self.display, key, 0, 0);
+            }
+            for button in buttons {
+                (self.fake_button)(self.display, button, 0, 0);
+            }
+            (self.flush)(self.display);
+        }
+        self.cur_modifiers = 0;
+    }
+    fn lock_state(&mut self) -> Option<u8> {
+        let get_indicator_state = self.get_indicator_state?;
+        let mut state: c_uint = 0;
+        if unsafe { get_indicator_state(self.display, XKB_USE_CORE_KBD, &mut state) } != 0 {
+            return None;
+        }
+        let mut locks = 0;
+        if state & INDICATOR_CAPS_LOCK != 0 {
+            locks |= CAPS_LOCK;
+        }
+        if state & INDICATOR_NUM_LOCK != 0 {
+            locks |= NUM_LOCK;
+        }
+        Some(locks)
+    }
+}
+
+fn track(held: &RefCell<BTreeSet<c_uint>>, code: c_uint, down: bool) {
+    let mut held = held.borrow_mut();
+    if down {
+        held.insert(code);
+    } else {
+        held.remove(&code);
+    }
+}